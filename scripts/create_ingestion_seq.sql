@@ -0,0 +1,27 @@
+/**
+ * Optional script creating the ingestion_seq triggers, needed by record_ingestion_seq, to apply
+ * after create_schema.sql. The triggers assign the seq to every row written to the account and
+ * transaction tables, by any connection, so apply it only when the feature is used.
+ */
+
+-- The ingestion offsets assigned to the seq column of the account and transaction tables.
+-- Each row takes the next value of the sequence without any lock, so the concurrent writes
+-- are not serialized, and a seq may commit after a higher one.
+CREATE SEQUENCE ingestion_seq;
+
+CREATE INDEX account_seq ON account (seq);
+
+CREATE INDEX transaction_seq ON transaction (seq);
+
+CREATE FUNCTION assign_ingestion_seq() RETURNS trigger AS $assign_ingestion_seq$
+    BEGIN
+        NEW.seq := nextval('ingestion_seq');
+        RETURN NEW;
+    END;
+$assign_ingestion_seq$ LANGUAGE plpgsql;
+
+CREATE TRIGGER account_ingestion_seq_trigger BEFORE INSERT OR UPDATE ON account
+    FOR EACH ROW EXECUTE PROCEDURE assign_ingestion_seq();
+
+CREATE TRIGGER transaction_ingestion_seq_trigger BEFORE INSERT OR UPDATE ON transaction
+    FOR EACH ROW EXECUTE PROCEDURE assign_ingestion_seq();
@@ -0,0 +1,62 @@
+/**
+ * Optional script creating the account_count_by_owner triggers, needed by maintain_owner_counts,
+ * to apply after create_schema.sql. The triggers count the accounts written to the account table
+ * by any connection, so apply it only when the feature is used.
+ */
+
+-- The number of accounts owned by each program, split in shards so that the concurrent writers
+-- updating the accounts of a popular owner, such as the token program, do not all wait on its
+-- single count row. Each connection adds its deltas to the shard of its backend. The closed
+-- accounts, with no lamports, are not counted.
+CREATE TABLE account_count_by_owner_shard (
+    owner BYTEA NOT NULL,
+    shard SMALLINT NOT NULL,
+    account_count BIGINT NOT NULL,
+    PRIMARY KEY (owner, shard)
+);
+
+-- The number of accounts owned by each program, summed over the shards.
+CREATE VIEW account_count_by_owner AS
+    SELECT owner, sum(account_count)::BIGINT AS account_count
+    FROM account_count_by_owner_shard GROUP BY owner;
+
+-- The deltas are applied per statement in the owner order, so that the concurrent bulk
+-- inserts sharing a shard lock the count rows in the same order.
+CREATE FUNCTION count_accounts_by_owner() RETURNS trigger AS $count_accounts_by_owner$
+    DECLARE
+        backend_shard SMALLINT := pg_backend_pid() % 16;
+    BEGIN
+        IF TG_OP = 'INSERT' THEN
+            INSERT INTO account_count_by_owner_shard AS counts (owner, shard, account_count)
+                SELECT owner, backend_shard, count(*) FROM new_accounts
+                WHERE lamports > 0 AND owner IS NOT NULL GROUP BY owner ORDER BY owner
+                ON CONFLICT (owner, shard) DO UPDATE SET account_count = counts.account_count + excluded.account_count;
+        ELSIF TG_OP = 'UPDATE' THEN
+            INSERT INTO account_count_by_owner_shard AS counts (owner, shard, account_count)
+                SELECT owner, backend_shard, sum(delta) FROM (
+                    SELECT owner, -1 AS delta FROM old_accounts WHERE lamports > 0 AND owner IS NOT NULL
+                    UNION ALL
+                    SELECT owner, 1 AS delta FROM new_accounts WHERE lamports > 0 AND owner IS NOT NULL
+                ) deltas GROUP BY owner HAVING sum(delta) <> 0 ORDER BY owner
+                ON CONFLICT (owner, shard) DO UPDATE SET account_count = counts.account_count + excluded.account_count;
+        ELSE
+            INSERT INTO account_count_by_owner_shard AS counts (owner, shard, account_count)
+                SELECT owner, backend_shard, -count(*) FROM old_accounts
+                WHERE lamports > 0 AND owner IS NOT NULL GROUP BY owner ORDER BY owner
+                ON CONFLICT (owner, shard) DO UPDATE SET account_count = counts.account_count + excluded.account_count;
+        END IF;
+        RETURN NULL;
+    END;
+$count_accounts_by_owner$ LANGUAGE plpgsql;
+
+CREATE TRIGGER account_count_by_owner_insert_trigger AFTER INSERT ON account
+    REFERENCING NEW TABLE AS new_accounts
+    FOR EACH STATEMENT EXECUTE PROCEDURE count_accounts_by_owner();
+
+CREATE TRIGGER account_count_by_owner_update_trigger AFTER UPDATE ON account
+    REFERENCING OLD TABLE AS old_accounts NEW TABLE AS new_accounts
+    FOR EACH STATEMENT EXECUTE PROCEDURE count_accounts_by_owner();
+
+CREATE TRIGGER account_count_by_owner_delete_trigger AFTER DELETE ON account
+    REFERENCING OLD TABLE AS old_accounts
+    FOR EACH STATEMENT EXECUTE PROCEDURE count_accounts_by_owner();
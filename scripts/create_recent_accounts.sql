@@ -0,0 +1,53 @@
+/**
+ * Optional script creating the recent_account table and its account_recent triggers, needed by
+ * maintain_recent_account_table, to apply after create_schema.sql. The triggers capture the
+ * transition table of every statement writing the account table, so apply it only when the
+ * feature is used.
+ */
+
+-- The table storing the accounts updated within the last recent_account_window_slots, copied
+-- from the account table by the account_recent triggers for the connections which set the
+-- geyser_plugin.maintain_recent_accounts session setting, and pruned by the plugin as the slots
+-- are rooted.
+CREATE TABLE recent_account (
+    pubkey BYTEA PRIMARY KEY,
+    owner BYTEA,
+    lamports BIGINT NOT NULL,
+    slot BIGINT NOT NULL,
+    executable BOOL NOT NULL,
+    rent_epoch BIGINT NOT NULL,
+    data BYTEA,
+    write_version BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL
+);
+
+CREATE INDEX recent_account_owner ON recent_account (owner);
+
+CREATE INDEX recent_account_slot ON recent_account (slot);
+
+-- The rows are copied per statement in the pubkey order, so that the concurrent bulk inserts
+-- lock the recent rows in the same order.
+CREATE FUNCTION copy_recent_accounts() RETURNS trigger AS $copy_recent_accounts$
+    BEGIN
+        IF current_setting('geyser_plugin.maintain_recent_accounts', true) IS DISTINCT FROM 'on' THEN
+            RETURN NULL;
+        END IF;
+        INSERT INTO recent_account AS recent (pubkey, owner, lamports, slot, executable, rent_epoch, data, write_version, updated_on)
+            SELECT pubkey, owner, lamports, slot, executable, rent_epoch, data, write_version, updated_on
+            FROM new_accounts ORDER BY pubkey
+            ON CONFLICT (pubkey) DO UPDATE SET owner=excluded.owner, lamports=excluded.lamports,
+                slot=excluded.slot, executable=excluded.executable, rent_epoch=excluded.rent_epoch,
+                data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on
+            WHERE recent.slot < excluded.slot OR (
+                recent.slot = excluded.slot AND recent.write_version < excluded.write_version);
+        RETURN NULL;
+    END;
+$copy_recent_accounts$ LANGUAGE plpgsql;
+
+CREATE TRIGGER account_recent_insert_trigger AFTER INSERT ON account
+    REFERENCING NEW TABLE AS new_accounts
+    FOR EACH STATEMENT EXECUTE PROCEDURE copy_recent_accounts();
+
+CREATE TRIGGER account_recent_update_trigger AFTER UPDATE ON account
+    REFERENCING NEW TABLE AS new_accounts
+    FOR EACH STATEMENT EXECUTE PROCEDURE copy_recent_accounts();
@@ -13,21 +13,113 @@ CREATE TABLE account (
     rent_epoch BIGINT NOT NULL,
     data BYTEA,
     write_version BIGINT NOT NULL,
-    updated_on TIMESTAMP NOT NULL
+    updated_on TIMESTAMP NOT NULL,
+    pre_lamports BIGINT, -- the lamports before the update when track_lamport_deltas is set
+    data_len INTEGER, -- the full length of the data when store_data_prefix_len is set
+    search_text TSVECTOR, -- the strings of the data when extract_text_search is set
+    write_lag_ms BIGINT, -- the lag from the slot to the write when record_write_lag is set
+    data_chunk_count INTEGER, -- the chunks of the data when account_data_chunk_threshold is set
+    slot_status VARCHAR(16), -- the status of the slot when record_account_slot_status is set
+    seq BIGINT, -- the ingestion offset of the write when record_ingestion_seq is set
+    account_type VARCHAR(16), -- the type of the account when classify_account_type is set
+    rent_exempt BOOL -- whether the account is rent-exempt when compute_rent_exempt is set
 );
 
 CREATE INDEX account_owner ON account (owner);
 
+CREATE INDEX account_search_text ON account USING GIN (search_text);
+
 CREATE INDEX account_slot ON account (slot);
 
+CREATE INDEX account_account_type ON account (account_type);
+
+-- The table linking the upgradeable program accounts to their programdata accounts
+CREATE TABLE program_link (
+    program_pubkey BYTEA PRIMARY KEY,
+    programdata_pubkey BYTEA NOT NULL,
+    slot BIGINT NOT NULL
+);
+
+CREATE INDEX program_link_programdata ON program_link (programdata_pubkey);
+
+-- The table recording the accounts closed, when their lamports went to zero, with the
+-- owner and the data length before the close, when track_account_closes is set
+CREATE TABLE account_close (
+    pubkey BYTEA NOT NULL,
+    closed_at_slot BIGINT NOT NULL,
+    last_owner BYTEA,
+    last_data_len INTEGER,
+    updated_on TIMESTAMP NOT NULL,
+    PRIMARY KEY (pubkey, closed_at_slot)
+);
+
+CREATE INDEX account_close_slot ON account_close (closed_at_slot);
+
+-- The table storing the data of the accounts larger than the account_data_chunk_threshold,
+-- in chunks reassembled by the chunk_index, when account_data_chunk_threshold is set
+CREATE TABLE account_data_chunk (
+    pubkey BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    write_version BIGINT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    chunk_bytes BYTEA NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    PRIMARY KEY (pubkey, slot, write_version, chunk_index)
+);
+
 -- The table storing slot information
 CREATE TABLE slot (
     slot BIGINT PRIMARY KEY,
     parent BIGINT,
     status VARCHAR(16) NOT NULL,
+    updated_on TIMESTAMP NOT NULL,
+    num_transactions BIGINT,
+    num_successful BIGINT,
+    num_failed BIGINT,
+    dead BOOL NOT NULL DEFAULT false
+);
+
+-- The table storing the times at which the plugin observed the slot statuses,
+-- this is the plugin observed latency rather than the network's.
+CREATE TABLE slot_timing (
+    slot BIGINT PRIMARY KEY,
+    processed_on TIMESTAMP,
+    confirmed_on TIMESTAMP,
+    rooted_on TIMESTAMP,
+    processed_to_confirmed_ms BIGINT GENERATED ALWAYS AS
+        ((EXTRACT(EPOCH FROM (confirmed_on - processed_on)) * 1000)::BIGINT) STORED,
+    confirmed_to_rooted_ms BIGINT GENERATED ALWAYS AS
+        ((EXTRACT(EPOCH FROM (rooted_on - confirmed_on)) * 1000)::BIGINT) STORED,
+    processed_to_rooted_ms BIGINT GENERATED ALWAYS AS
+        ((EXTRACT(EPOCH FROM (rooted_on - processed_on)) * 1000)::BIGINT) STORED
+);
+
+-- The highest rooted slot below which all the notifications are written, when
+-- track_slot_checkpoint is set. The table holds a single row.
+CREATE TABLE checkpoint (
+    id SMALLINT PRIMARY KEY DEFAULT 0 CHECK (id = 0),
+    highest_contiguous_rooted_slot BIGINT NOT NULL,
     updated_on TIMESTAMP NOT NULL
 );
 
+-- The liveness of the plugin, updated every heartbeat_interval_secs when it is set, with the
+-- highest slot notified, also when no data is written. The table holds a single row.
+CREATE TABLE heartbeat (
+    id SMALLINT PRIMARY KEY DEFAULT 0 CHECK (id = 0),
+    beat_on TIMESTAMP NOT NULL,
+    highest_slot BIGINT
+);
+
+-- The accounts bulk inserts committed, numbered by the monotonically increasing batch_seq,
+-- when log_batches is set. A gap in the batch_seq is a batch which failed or is not yet logged.
+CREATE TABLE batch_log (
+    batch_seq BIGINT PRIMARY KEY,
+    min_slot BIGINT NOT NULL,
+    max_slot BIGINT NOT NULL,
+    row_count INTEGER NOT NULL,
+    committed_at TIMESTAMP NOT NULL
+);
+
 -- Types for Transactions
 
 Create TYPE "TransactionErrorCode" AS ENUM (
@@ -164,9 +256,105 @@ CREATE TABLE transaction (
     meta "TransactionStatusMeta",
     write_version BIGINT,
     updated_on TIMESTAMP NOT NULL,
+    seq BIGINT, -- the ingestion offset of the write when record_ingestion_seq is set
+    -- The codec of the compressed columns when compress_transaction_data is set, 1: zstd, 2: lz4
+    data_compression SMALLINT,
+    -- The legacy_message or v0_loaded_message, and the meta, in the binary format of their
+    -- types, compressed
+    compressed_message BYTEA,
+    compressed_meta BYTEA,
     CONSTRAINT transaction_pk PRIMARY KEY (slot, signature)
 );
 
+CREATE INDEX transaction_signature ON transaction (signature);
+
+-- The table storing all the signatures of each transaction, for looking up the
+-- multi-signature transactions by any of their signatures.
+CREATE TABLE transaction_signatures (
+    signature BYTEA NOT NULL,
+    slot BIGINT NOT NULL,
+    -- The first signature, identifying the transaction in the transaction table
+    transaction_signature BYTEA NOT NULL,
+    signature_index SMALLINT NOT NULL,
+    CONSTRAINT transaction_signatures_pk PRIMARY KEY (signature, slot)
+);
+
+-- The table recording the plugin version and configuration of each load of
+-- the plugin
+CREATE TABLE plugin_metadata (
+    id BIGSERIAL PRIMARY KEY,
+    plugin_version VARCHAR(32) NOT NULL,
+    started_on TIMESTAMP NOT NULL,
+    -- The SHA-256 hash of the configuration, in base58
+    config_hash VARCHAR(44) NOT NULL
+);
+
+-- The table storing the configuration of the plugin each time it is loaded, when
+-- audit_plugin_config is set, with the credentials redacted
+CREATE TABLE plugin_config_audit (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_on TIMESTAMP NOT NULL,
+    plugin_version VARCHAR(32) NOT NULL,
+    config_hash VARCHAR(44) NOT NULL,
+    config JSONB NOT NULL
+);
+
+-- The table storing the fields of the configuration changed since it was last recorded into
+-- plugin_config_audit, when record_config_changelog is set
+CREATE TABLE config_changelog (
+    id BIGSERIAL PRIMARY KEY,
+    recorded_on TIMESTAMP NOT NULL,
+    -- The plugin_config_audit rows of the configuration and of the one it changed
+    config_audit_id BIGINT NOT NULL,
+    previous_config_audit_id BIGINT NOT NULL,
+    -- The path of the field in the config of plugin_config_audit, such as config.batch_size
+    field VARCHAR(256) NOT NULL,
+    -- The values of the field, NULL when absent
+    old_value JSONB,
+    new_value JSONB
+);
+
+CREATE INDEX config_changelog_config_audit_id ON config_changelog (config_audit_id);
+
+-- The table storing the checksums of the SQL of the statements prepared by the plugin, when
+-- record_statement_checksums is set, for warning about the statements changed across the
+-- versions of the plugin
+CREATE TABLE statement_checksums (
+    config_hash VARCHAR(44) NOT NULL,
+    statement_name VARCHAR(128) NOT NULL,
+    plugin_version VARCHAR(32) NOT NULL,
+    -- The SHA-256 hash of the SQL text, in base58
+    checksum VARCHAR(44) NOT NULL,
+    recorded_on TIMESTAMP NOT NULL,
+    CONSTRAINT statement_checksums_pk PRIMARY KEY (config_hash, statement_name, plugin_version)
+);
+
+-- The table storing the accounts each transaction could have written, its
+-- writable accounts, or its fee payer when it failed, with the programs it invoked
+CREATE TABLE transaction_account_write (
+    slot BIGINT NOT NULL,
+    pubkey BYTEA NOT NULL,
+    signature BYTEA NOT NULL,
+    program_ids BYTEA[],
+    CONSTRAINT transaction_account_write_pk PRIMARY KEY (slot, pubkey, signature)
+);
+
+-- The table storing the instructions of the program decoded by the instruction_idl,
+-- inner_index 0 being the instruction itself and 1 onwards its inner instructions
+CREATE TABLE transaction_instruction (
+    slot BIGINT NOT NULL,
+    signature BYTEA NOT NULL,
+    instruction_index SMALLINT NOT NULL,
+    inner_index SMALLINT NOT NULL,
+    program_id BYTEA NOT NULL,
+    instruction_name VARCHAR(256),
+    args JSONB,
+    data BYTEA,
+    CONSTRAINT transaction_instruction_pk PRIMARY KEY (slot, signature, instruction_index, inner_index)
+);
+
+CREATE INDEX transaction_instruction_name ON transaction_instruction (instruction_name, slot);
+
 -- The table storing block metadata
 CREATE TABLE block (
     slot BIGINT PRIMARY KEY,
@@ -174,9 +362,13 @@ CREATE TABLE block (
     rewards "Reward"[],
     block_time BIGINT,
     block_height BIGINT,
-    updated_on TIMESTAMP NOT NULL
+    updated_on TIMESTAMP NOT NULL,
+    -- The block_time truncated to the block_time_bucket_secs, when stored
+    block_time_bucket BIGINT
 );
 
+CREATE INDEX block_time_bucket ON block (block_time_bucket);
+
 -- The table storing spl token owner to account indexes
 CREATE TABLE spl_token_owner_index (
     owner_key BYTEA NOT NULL,
@@ -197,6 +389,72 @@ CREATE TABLE spl_token_mint_index (
 CREATE INDEX spl_token_mint_index_mint_key ON spl_token_mint_index (mint_key);
 CREATE UNIQUE INDEX spl_token_mint_index_mint_pair ON spl_token_mint_index (mint_key, account_key);
 
+-- The table storing spl token account to delegate indexes, with a NULL delegate_key when the
+-- account has no delegate
+CREATE TABLE spl_token_delegate_index (
+    account_key BYTEA PRIMARY KEY,
+    delegate_key BYTEA,
+    slot BIGINT NOT NULL
+);
+
+CREATE INDEX spl_token_delegate_index_delegate_key ON spl_token_delegate_index (delegate_key);
+
+-- The table storing spl token account states: uninitialized, initialized or frozen, and
+-- close authorities
+CREATE TABLE spl_token_state_index (
+    account_key BYTEA PRIMARY KEY,
+    state VARCHAR(16) NOT NULL,
+    close_authority BYTEA, -- NULL when the account has no close authority
+    slot BIGINT NOT NULL
+);
+
+CREATE INDEX spl_token_state_index_state ON spl_token_state_index (state);
+CREATE INDEX spl_token_state_index_close_authority ON spl_token_state_index (close_authority);
+
+-- The table storing stake account indexes
+CREATE TABLE stake_account_index (
+    stake_pubkey BYTEA PRIMARY KEY,
+    withdrawer BYTEA NOT NULL,
+    staker BYTEA NOT NULL,
+    voter BYTEA, -- NULL when the stake is not delegated
+    delegated_stake BIGINT,
+    activation_epoch BIGINT,
+    slot BIGINT NOT NULL
+);
+
+CREATE INDEX stake_account_index_withdrawer ON stake_account_index (withdrawer);
+CREATE INDEX stake_account_index_staker ON stake_account_index (staker);
+CREATE INDEX stake_account_index_voter ON stake_account_index (voter);
+
+-- The table storing the Metaplex token metadata indexes, the strings stripped of their
+-- NUL padding. Their lengths are bounded by the program rather than by the columns.
+CREATE TABLE metaplex_metadata_index (
+    metadata_pubkey BYTEA PRIMARY KEY,
+    mint BYTEA NOT NULL,
+    update_authority BYTEA NOT NULL,
+    name VARCHAR NOT NULL,
+    symbol VARCHAR NOT NULL,
+    uri VARCHAR NOT NULL,
+    slot BIGINT NOT NULL
+);
+
+CREATE INDEX metaplex_metadata_index_mint ON metaplex_metadata_index (mint);
+CREATE INDEX metaplex_metadata_index_update_authority ON metaplex_metadata_index (update_authority);
+
+-- The account updates written in the staging_table_mode, partitioned by the slot. The
+-- partitions are created by the plugin, merged into the account table and dropped.
+CREATE TABLE account_staging (
+    pubkey BYTEA NOT NULL,
+    owner BYTEA,
+    lamports BIGINT NOT NULL,
+    slot BIGINT NOT NULL,
+    executable BOOL NOT NULL,
+    rent_epoch BIGINT NOT NULL,
+    data BYTEA,
+    write_version BIGINT NOT NULL,
+    updated_on TIMESTAMP NOT NULL
+) PARTITION BY LIST (slot);
+
 /**
  * The following is for keeping historical data for accounts and is not required for plugin to work.
  */
@@ -210,7 +468,15 @@ CREATE TABLE account_audit (
     rent_epoch BIGINT NOT NULL,
     data BYTEA,
     write_version BIGINT NOT NULL,
-    updated_on TIMESTAMP NOT NULL
+    updated_on TIMESTAMP NOT NULL,
+    pre_lamports BIGINT,
+    data_len INTEGER,
+    search_text TSVECTOR,
+    write_lag_ms BIGINT,
+    data_chunk_count INTEGER,
+    slot_status VARCHAR(16),
+    account_type VARCHAR(16),
+    rent_exempt BOOL
 );
 
 CREATE INDEX account_audit_account_key ON  account_audit (pubkey, write_version);
@@ -219,9 +485,12 @@ CREATE INDEX account_audit_pubkey_slot ON account_audit (pubkey, slot);
 
 CREATE FUNCTION audit_account_update() RETURNS trigger AS $audit_account_update$
     BEGIN
-		INSERT INTO account_audit (pubkey, owner, lamports, slot, executable, rent_epoch, data, write_version, updated_on)
+        IF current_setting('geyser_plugin.skip_account_audit', true) = 'on' THEN
+            RETURN NEW;
+        END IF;
+		INSERT INTO account_audit (pubkey, owner, lamports, slot, executable, rent_epoch, data, write_version, updated_on, pre_lamports, data_len, search_text, write_lag_ms, data_chunk_count, slot_status, account_type, rent_exempt)
             VALUES (OLD.pubkey, OLD.owner, OLD.lamports, OLD.slot,
-                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on);
+                    OLD.executable, OLD.rent_epoch, OLD.data, OLD.write_version, OLD.updated_on, OLD.pre_lamports, OLD.data_len, OLD.search_text, OLD.write_lag_ms, OLD.data_chunk_count, OLD.slot_status, OLD.account_type, OLD.rent_exempt);
         RETURN NEW;
     END;
 
@@ -229,3 +498,15 @@ $audit_account_update$ LANGUAGE plpgsql;
 
 CREATE TRIGGER account_update_trigger AFTER UPDATE OR DELETE ON account
     FOR EACH ROW EXECUTE PROCEDURE audit_account_update();
+
+-- The account updates joined to the transactions of the same slot which could
+-- have written them
+CREATE VIEW account_update_transaction AS
+    SELECT u.pubkey, u.slot, u.owner, u.lamports, u.write_version,
+        w.signature, w.program_ids
+    FROM (
+        SELECT pubkey, slot, owner, lamports, write_version FROM account
+        UNION ALL
+        SELECT pubkey, slot, owner, lamports, write_version FROM account_audit
+    ) u
+    JOIN transaction_account_write w ON w.slot = u.slot AND w.pubkey = u.pubkey;
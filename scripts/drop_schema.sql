@@ -2,15 +2,53 @@
  * Script for cleaning up the schema for PostgreSQL used for the AccountsDb plugin.
  */
 
+-- The objects of the optional scripts, when applied.
+DROP TRIGGER IF EXISTS account_ingestion_seq_trigger ON account;
+DROP TRIGGER IF EXISTS transaction_ingestion_seq_trigger ON transaction;
+DROP FUNCTION IF EXISTS assign_ingestion_seq;
+DROP SEQUENCE IF EXISTS ingestion_seq;
+DROP INDEX IF EXISTS account_seq;
+DROP INDEX IF EXISTS transaction_seq;
+DROP TRIGGER IF EXISTS account_count_by_owner_insert_trigger ON account;
+DROP TRIGGER IF EXISTS account_count_by_owner_update_trigger ON account;
+DROP TRIGGER IF EXISTS account_count_by_owner_delete_trigger ON account;
+DROP FUNCTION IF EXISTS count_accounts_by_owner;
+DROP VIEW IF EXISTS account_count_by_owner;
+DROP TABLE IF EXISTS account_count_by_owner_shard;
+DROP TRIGGER IF EXISTS account_recent_insert_trigger ON account;
+DROP TRIGGER IF EXISTS account_recent_update_trigger ON account;
+DROP FUNCTION IF EXISTS copy_recent_accounts;
+DROP TABLE IF EXISTS recent_account;
+
+DROP VIEW account_update_transaction;
 DROP TRIGGER account_update_trigger ON account;
 DROP FUNCTION audit_account_update;
 DROP TABLE account_audit;
 DROP TABLE account CASCADE;
+DROP TABLE account_staging;
 DROP TABLE slot;
+DROP TABLE slot_timing;
+DROP TABLE checkpoint;
+DROP TABLE heartbeat;
+DROP TABLE batch_log;
 DROP TABLE transaction;
+DROP TABLE transaction_signatures;
+DROP TABLE transaction_account_write;
+DROP TABLE transaction_instruction;
 DROP TABLE block;
 DROP TABLE spl_token_owner_index;
 DROP TABLE spl_token_mint_index;
+DROP TABLE spl_token_delegate_index;
+DROP TABLE spl_token_state_index;
+DROP TABLE stake_account_index;
+DROP TABLE metaplex_metadata_index;
+DROP TABLE program_link;
+DROP TABLE account_close;
+DROP TABLE account_data_chunk;
+DROP TABLE plugin_metadata;
+DROP TABLE plugin_config_audit;
+DROP TABLE config_changelog;
+DROP TABLE statement_checksums;
 
 DROP TYPE "TransactionError" CASCADE;
 DROP TYPE "TransactionErrorCode" CASCADE;
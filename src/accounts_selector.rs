@@ -1,10 +1,33 @@
-use {log::*, std::collections::HashSet};
+use {
+    crate::{inline_spl_token, inline_spl_token_2022},
+    log::*,
+    solana_sdk::{native_loader, sysvar},
+    std::{collections::HashSet, sync::Arc},
+};
+
+/// Symbolic owner selecting all the accounts owned by the SPL Token program.
+const SPL_TOKEN_OWNER_SYMBOL: &str = "$spl_token";
+/// Symbolic owner selecting all the accounts owned by the SPL Token 2022 program.
+const SPL_TOKEN_2022_OWNER_SYMBOL: &str = "$spl_token_2022";
 
 #[derive(Debug)]
 pub(crate) struct AccountsSelector {
     pub accounts: HashSet<Vec<u8>>,
     pub owners: HashSet<Vec<u8>>,
     pub select_all_accounts: bool,
+    /// The accounts and owners rejected whatever the selection, the native loader and the
+    /// sysvars when exclude_builtin_accounts is set.
+    pub rejected: HashSet<Vec<u8>>,
+    /// The accounts with fewer lamports are rejected whatever the selection, the dust accounts
+    /// when min_lamports is set.
+    pub min_lamports: u64,
+    /// Whether the accounts not executable are rejected whatever the selection, when
+    /// executable_only is set.
+    pub executable_only: bool,
+    /// The entries of an accounts_selector array, in their order, with the tables their
+    /// accounts are routed to. An account is selected when an entry selects it, and routed
+    /// by the first of them.
+    pub entries: Vec<(AccountsSelector, Option<Arc<str>>)>,
 }
 
 impl AccountsSelector {
@@ -13,6 +36,10 @@ impl AccountsSelector {
             accounts: HashSet::default(),
             owners: HashSet::default(),
             select_all_accounts: true,
+            rejected: HashSet::default(),
+            min_lamports: 0,
+            executable_only: false,
+            entries: Vec::default(),
         }
     }
 
@@ -28,30 +55,123 @@ impl AccountsSelector {
                 accounts: HashSet::default(),
                 owners: HashSet::default(),
                 select_all_accounts,
+                rejected: HashSet::default(),
+                min_lamports: 0,
+                executable_only: false,
+                entries: Vec::default(),
             };
         }
         let accounts = accounts
             .iter()
             .map(|key| bs58::decode(key).into_vec().unwrap())
             .collect();
-        let owners = owners
-            .iter()
-            .map(|key| bs58::decode(key).into_vec().unwrap())
-            .collect();
+        let owners = owners.iter().map(|key| Self::decode_owner(key)).collect();
         AccountsSelector {
             accounts,
             owners,
             select_all_accounts,
+            rejected: HashSet::default(),
+            min_lamports: 0,
+            executable_only: false,
+            entries: Vec::default(),
+        }
+    }
+
+    /// The selector of the entries, each with its optional target table.
+    pub fn from_entries(entries: Vec<(AccountsSelector, Option<Arc<str>>)>) -> Self {
+        AccountsSelector {
+            accounts: HashSet::default(),
+            owners: HashSet::default(),
+            select_all_accounts: false,
+            rejected: HashSet::default(),
+            min_lamports: 0,
+            executable_only: false,
+            entries,
         }
     }
 
-    pub fn is_account_selected(&self, account: &[u8], owner: &[u8]) -> bool {
+    /// Reject the accounts owned by the native loader or the sysvar program, and the
+    /// well-known sysvars, whatever the selection.
+    pub fn exclude_builtin_accounts(mut self) -> Self {
+        self.rejected.insert(native_loader::id().as_ref().to_vec());
+        self.rejected.insert(sysvar::id().as_ref().to_vec());
+        self.rejected
+            .extend(sysvar::ALL_IDS.iter().map(|id| id.as_ref().to_vec()));
+        self
+    }
+
+    /// Reject the accounts with fewer lamports than min_lamports, whatever the selection.
+    pub fn with_min_lamports(mut self, min_lamports: u64) -> Self {
+        self.min_lamports = min_lamports;
+        self
+    }
+
+    /// Reject the accounts which are not executable, whatever the selection.
+    pub fn with_executable_only(mut self) -> Self {
+        self.executable_only = true;
+        self
+    }
+
+    /// Decode the Base58 owner key, expanding the symbolic program names.
+    fn decode_owner(key: &str) -> Vec<u8> {
+        match key {
+            SPL_TOKEN_OWNER_SYMBOL => inline_spl_token::id().as_ref().to_vec(),
+            SPL_TOKEN_2022_OWNER_SYMBOL => inline_spl_token_2022::id().as_ref().to_vec(),
+            _ => bs58::decode(key).into_vec().unwrap(),
+        }
+    }
+
+    pub fn is_account_selected(
+        &self,
+        account: &[u8],
+        owner: &[u8],
+        lamports: u64,
+        executable: bool,
+    ) -> bool {
+        if !self.entries.is_empty() {
+            return self
+                .entries
+                .iter()
+                .any(|(entry, _)| entry.is_account_selected(account, owner, lamports, executable));
+        }
+        if lamports < self.min_lamports
+            || (self.executable_only && !executable)
+            || self.rejected.contains(account)
+            || self.rejected.contains(owner)
+        {
+            return false;
+        }
         self.select_all_accounts || self.accounts.contains(account) || self.owners.contains(owner)
     }
 
+    /// The table the selected account is routed to by the first entry selecting it, None for
+    /// the account table.
+    pub fn target_table(
+        &self,
+        account: &[u8],
+        owner: &[u8],
+        lamports: u64,
+        executable: bool,
+    ) -> Option<Arc<str>> {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry.is_account_selected(account, owner, lamports, executable))
+            .and_then(|(_, target_table)| target_table.clone())
+    }
+
+    /// The tables the accounts are routed to.
+    pub fn target_tables(&self) -> impl Iterator<Item = &str> {
+        self.entries
+            .iter()
+            .filter_map(|(_, target_table)| target_table.as_deref())
+    }
+
     /// Check if any account is of interested at all
     pub fn is_enabled(&self) -> bool {
-        self.select_all_accounts || !self.accounts.is_empty() || !self.owners.is_empty()
+        self.entries.iter().any(|(entry, _)| entry.is_enabled())
+            || self.select_all_accounts
+            || !self.accounts.is_empty()
+            || !self.owners.is_empty()
     }
 }
 
@@ -71,4 +191,122 @@ pub(crate) mod tests {
             &["9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string()],
         );
     }
+
+    #[test]
+    fn test_symbolic_token_owners() {
+        let selector = AccountsSelector::new(
+            &[],
+            &[
+                SPL_TOKEN_OWNER_SYMBOL.to_string(),
+                SPL_TOKEN_2022_OWNER_SYMBOL.to_string(),
+            ],
+        );
+
+        let account = solana_sdk::pubkey::Pubkey::new_unique();
+        assert!(selector.is_account_selected(
+            account.as_ref(),
+            inline_spl_token::id().as_ref(),
+            1,
+            false
+        ));
+        assert!(selector.is_account_selected(
+            account.as_ref(),
+            inline_spl_token_2022::id().as_ref(),
+            1,
+            false
+        ));
+        assert!(!selector.is_account_selected(account.as_ref(), account.as_ref(), 1, false));
+    }
+
+    #[test]
+    fn test_exclude_builtin_accounts() {
+        let selector = AccountsSelector::new(&["*".to_string()], &[]).exclude_builtin_accounts();
+
+        let account = solana_sdk::pubkey::Pubkey::new_unique();
+        let owner = solana_sdk::pubkey::Pubkey::new_unique();
+        assert!(selector.is_account_selected(account.as_ref(), owner.as_ref(), 1, false));
+        assert!(!selector.is_account_selected(
+            account.as_ref(),
+            native_loader::id().as_ref(),
+            1,
+            false
+        ));
+        assert!(!selector.is_account_selected(
+            sysvar::clock::id().as_ref(),
+            sysvar::id().as_ref(),
+            1,
+            false
+        ));
+        assert!(!selector.is_account_selected(
+            sysvar::rent::id().as_ref(),
+            owner.as_ref(),
+            1,
+            false
+        ));
+    }
+
+    #[test]
+    fn test_min_lamports() {
+        let selector = AccountsSelector::new(&["*".to_string()], &[]).with_min_lamports(1000);
+
+        let account = solana_sdk::pubkey::Pubkey::new_unique();
+        assert!(selector.is_account_selected(account.as_ref(), account.as_ref(), 1000, false));
+        assert!(!selector.is_account_selected(account.as_ref(), account.as_ref(), 999, false));
+        assert!(!selector.is_account_selected(account.as_ref(), account.as_ref(), 0, false));
+    }
+
+    #[test]
+    fn test_executable_only() {
+        let program_owner = solana_sdk::bpf_loader_upgradeable::id();
+        let selector =
+            AccountsSelector::new(&[], &[program_owner.to_string()]).with_executable_only();
+
+        let account = solana_sdk::pubkey::Pubkey::new_unique();
+        assert!(selector.is_account_selected(account.as_ref(), program_owner.as_ref(), 1, true));
+        // The programdata and buffer accounts of the same owner are not executable.
+        assert!(!selector.is_account_selected(account.as_ref(), program_owner.as_ref(), 1, false));
+        assert!(!selector.is_account_selected(account.as_ref(), account.as_ref(), 1, true));
+    }
+
+    #[test]
+    fn test_selector_entries() {
+        let program = solana_sdk::pubkey::Pubkey::new_unique();
+        let selector = AccountsSelector::from_entries(vec![
+            (
+                AccountsSelector::new(&[], &[SPL_TOKEN_OWNER_SYMBOL.to_string()]),
+                Some(Arc::from("token_account")),
+            ),
+            (
+                AccountsSelector::new(&[], &[program.to_string()]),
+                Some(Arc::from("program_account")),
+            ),
+            (AccountsSelector::new(&["*".to_string()], &[]), None),
+        ]);
+
+        let account = solana_sdk::pubkey::Pubkey::new_unique();
+        let token_owner = inline_spl_token::id();
+        assert!(selector.is_enabled());
+        assert!(selector.is_account_selected(account.as_ref(), account.as_ref(), 1, false));
+        assert_eq!(
+            selector
+                .target_table(account.as_ref(), token_owner.as_ref(), 1, false)
+                .as_deref(),
+            Some("token_account")
+        );
+        assert_eq!(
+            selector
+                .target_table(account.as_ref(), program.as_ref(), 1, false)
+                .as_deref(),
+            Some("program_account")
+        );
+        // The first entry selecting the account routes it.
+        assert_eq!(
+            selector.target_table(account.as_ref(), account.as_ref(), 1, false),
+            None
+        );
+        assert_eq!(
+            selector.target_tables().collect::<Vec<_>>(),
+            vec!["token_account", "program_account"]
+        );
+    }
 }
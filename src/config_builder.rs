@@ -0,0 +1,255 @@
+/// A builder of the plugin configuration, for constructing it programmatically, such as in
+/// the integration tests or custom harnesses, instead of writing the JSON by hand.
+use {
+    crate::{
+        geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+        postgres_client::SYNCHRONOUS_COMMIT_VALUES,
+    },
+    serde_json::json,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct GeyserPluginPostgresConfigBuilder {
+    config: GeyserPluginPostgresConfig,
+    libpath: Option<String>,
+    /// The "accounts" and "owners" of the accounts_selector.
+    accounts_selector: Option<(Vec<String>, Vec<String>)>,
+    /// The "mentions" of the transaction_selector.
+    transaction_selector: Option<Vec<String>>,
+}
+
+impl GeyserPluginPostgresConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The path of the plugin library, only used in the JSON configuration.
+    pub fn libpath(mut self, libpath: &str) -> Self {
+        self.libpath = Some(libpath.to_string());
+        self
+    }
+
+    pub fn host(mut self, host: &str) -> Self {
+        self.config.host = Some(host.to_string());
+        self
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.config.user = Some(user.to_string());
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = Some(port);
+        self
+    }
+
+    pub fn connection_str(mut self, connection_str: &str) -> Self {
+        self.config.connection_str = Some(connection_str.to_string());
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.config.threads = Some(threads);
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.config.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn panic_on_db_errors(mut self, panic_on_db_errors: bool) -> Self {
+        self.config.panic_on_db_errors = Some(panic_on_db_errors);
+        self
+    }
+
+    pub fn store_account_historical_data(mut self, store_account_historical_data: bool) -> Self {
+        self.config.store_account_historical_data = Some(store_account_historical_data);
+        self
+    }
+
+    /// Connect with SSL, using the server's certificate, and the client's certificate and
+    /// private PEM key files.
+    pub fn ssl(mut self, server_ca: &str, client_cert: &str, client_key: &str) -> Self {
+        self.config.use_ssl = Some(true);
+        self.config.server_ca = Some(server_ca.to_string());
+        self.config.client_cert = Some(client_cert.to_string());
+        self.config.client_key = Some(client_key.to_string());
+        self
+    }
+
+    pub fn index_token_owner(mut self, index_token_owner: bool) -> Self {
+        self.config.index_token_owner = Some(index_token_owner);
+        self
+    }
+
+    pub fn index_token_mint(mut self, index_token_mint: bool) -> Self {
+        self.config.index_token_mint = Some(index_token_mint);
+        self
+    }
+
+    pub fn store_transaction_signatures(mut self, store_transaction_signatures: bool) -> Self {
+        self.config.store_transaction_signatures = Some(store_transaction_signatures);
+        self
+    }
+
+    pub fn synchronous_commit(mut self, synchronous_commit: &str) -> Self {
+        self.config.synchronous_commit = Some(synchronous_commit.to_string());
+        self
+    }
+
+    /// Select the accounts by their pubkeys and owners, "*" selects all the accounts.
+    pub fn accounts_selector(mut self, accounts: &[&str], owners: &[&str]) -> Self {
+        let to_strings = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        self.accounts_selector = Some((to_strings(accounts), to_strings(owners)));
+        self
+    }
+
+    /// Select the transactions mentioning the accounts, "*" selects all the transactions and
+    /// "all_votes" all the vote transactions.
+    pub fn transaction_selector(mut self, mentions: &[&str]) -> Self {
+        self.transaction_selector =
+            Some(mentions.iter().map(|mention| mention.to_string()).collect());
+        self
+    }
+
+    /// Set any of the other options of the configuration.
+    pub fn with(mut self, update: impl FnOnce(&mut GeyserPluginPostgresConfig)) -> Self {
+        update(&mut self.config);
+        self
+    }
+
+    fn validate(&self) -> Result<(), GeyserPluginError> {
+        let config = &self.config;
+        let msg = if config.connection_str.is_none()
+            && (config.host.is_none() || config.user.is_none())
+        {
+            "\"connection_str\", or \"host\" and \"user\" must be specified".to_string()
+        } else if matches!(config.use_ssl, Some(true))
+            && (config.server_ca.is_none()
+                || config.client_cert.is_none()
+                || config.client_key.is_none())
+        {
+            "\"server_ca\", \"client_cert\" and \"client_key\" must be specified when \"use_ssl\" is set"
+                .to_string()
+        } else if matches!(config.threads, Some(0)) {
+            "\"threads\" must be at least 1".to_string()
+        } else if matches!(config.batch_size, Some(0)) {
+            "\"batch_size\" must be at least 1".to_string()
+        } else if matches!(
+            &config.synchronous_commit,
+            Some(synchronous_commit) if !SYNCHRONOUS_COMMIT_VALUES.contains(&synchronous_commit.as_str())
+        ) {
+            format!(
+                "\"synchronous_commit\" must be one of {:?}, got: {:?}",
+                SYNCHRONOUS_COMMIT_VALUES, config.synchronous_commit
+            )
+        } else {
+            return Ok(());
+        };
+        Err(GeyserPluginError::Custom(Box::new(
+            GeyserPluginPostgresError::ConfigurationError { msg },
+        )))
+    }
+
+    /// Build the validated configuration.
+    pub fn build(&self) -> Result<GeyserPluginPostgresConfig, GeyserPluginError> {
+        self.validate()?;
+        Ok(self.config.clone())
+    }
+
+    /// Build the validated configuration in the JSON format of the plugin's config file,
+    /// along with the libpath and the selectors.
+    pub fn build_json(&self) -> Result<serde_json::Value, GeyserPluginError> {
+        let mut value = serde_json::to_value(self.build()?).unwrap();
+        let fields = value.as_object_mut().unwrap();
+        fields.retain(|_, field| !field.is_null());
+        if let Some(libpath) = &self.libpath {
+            fields.insert("libpath".to_string(), json!(libpath));
+        }
+        if let Some((accounts, owners)) = &self.accounts_selector {
+            fields.insert(
+                "accounts_selector".to_string(),
+                json!({ "accounts": accounts, "owners": owners }),
+            );
+        }
+        if let Some(mentions) = &self.transaction_selector {
+            fields.insert(
+                "transaction_selector".to_string(),
+                json!({ "mentions": mentions }),
+            );
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_config() {
+        let config = GeyserPluginPostgresConfigBuilder::new()
+            .host("localhost")
+            .user("solana")
+            .threads(4)
+            .batch_size(20)
+            .with(|config| config.track_slot_timing = Some(true))
+            .build()
+            .unwrap();
+        let expected: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "host": "localhost",
+            "user": "solana",
+            "threads": 4,
+            "batch_size": 20,
+            "track_slot_timing": true,
+        }))
+        .unwrap();
+        assert_eq!(config, expected);
+
+        let builder = GeyserPluginPostgresConfigBuilder::new();
+        assert!(builder.build().is_err());
+        assert!(builder.clone().user("solana").build().is_err());
+        let builder = builder.connection_str("host=localhost user=solana");
+        assert!(builder.build().is_ok());
+        assert!(builder.clone().batch_size(0).build().is_err());
+        assert!(builder
+            .clone()
+            .synchronous_commit("remote")
+            .build()
+            .is_err());
+        assert!(builder
+            .with(|config| config.use_ssl = Some(true))
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_build_json() {
+        let value = GeyserPluginPostgresConfigBuilder::new()
+            .libpath("/tmp/libsolana_geyser_plugin_postgres.so")
+            .connection_str("host=localhost user=solana")
+            .accounts_selector(&["*"], &[])
+            .transaction_selector(&["all_votes"])
+            .build_json()
+            .unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "libpath": "/tmp/libsolana_geyser_plugin_postgres.so",
+                "connection_str": "host=localhost user=solana",
+                "accounts_selector": { "accounts": ["*"], "owners": [] },
+                "transaction_selector": { "mentions": ["all_votes"] },
+            })
+        );
+
+        // The plugin reads back the same configuration.
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            config.connection_str.as_deref(),
+            Some("host=localhost user=solana")
+        );
+    }
+}
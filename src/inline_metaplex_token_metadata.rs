@@ -0,0 +1,180 @@
+/// Partial Metaplex Token Metadata program declarations inlined to avoid depending on the
+/// program crate. Only the fields used for the metadata index are unpacked.
+use solana_sdk::pubkey::{Pubkey, PUBKEY_BYTES};
+
+solana_sdk::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/*
+    /// The metadata account definition -- serialized with borsh, the strings are a u32
+    /// length followed by their bytes, padded with NUL characters by the program to
+    /// MAX_NAME_LENGTH (32), MAX_SYMBOL_LENGTH (10) and MAX_URI_LENGTH (200).
+    Metadata {
+        key: Key, // u8, MetadataV1 = 4
+        update_authority: Pubkey,
+        mint: Pubkey,
+        data: Data {
+            name: String,
+            symbol: String,
+            uri: String,
+            seller_fee_basis_points: u16,
+            creators: Option<Vec<Creator>>,
+        },
+        primary_sale_happened: bool,
+        is_mutable: bool,
+        // The fields added by the later versions of the program, absent from the accounts
+        // written by the earlier ones.
+        edition_nonce: Option<u8>,
+        token_standard: Option<TokenStandard>,
+        collection: Option<Collection>,
+        uses: Option<Uses>,
+        ...
+    }
+*/
+const KEY_METADATA_V1: u8 = 4;
+pub const METADATA_UPDATE_AUTHORITY_OFFSET: usize = 1;
+pub const METADATA_MINT_OFFSET: usize = 33;
+pub const METADATA_NAME_OFFSET: usize = 65;
+
+/// The fields of a metadata account which are indexed. The strings are stripped of their
+/// NUL padding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MetadataAccount<'a> {
+    pub update_authority: &'a Pubkey,
+    pub mint: &'a Pubkey,
+    pub name: &'a str,
+    pub symbol: &'a str,
+    pub uri: &'a str,
+}
+
+pub struct Account;
+impl Account {
+    // Call after account length has already been verified
+    fn unpack_pubkey_unchecked(account_data: &[u8], offset: usize) -> &Pubkey {
+        bytemuck::from_bytes(&account_data[offset..offset + PUBKEY_BYTES])
+    }
+
+    /// Unpack the borsh string at the offset, returns it and the offset past it. None when its
+    /// length runs past the data, or it is not UTF-8 or has a NUL character before its padding.
+    fn unpack_string(account_data: &[u8], offset: usize) -> Option<(&str, usize)> {
+        let len_bytes = account_data.get(offset..offset.checked_add(4)?)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().unwrap()) as usize;
+        let start = offset + 4;
+        let end = start.checked_add(len)?;
+        let value = std::str::from_utf8(account_data.get(start..end)?).ok()?;
+        let value = value.trim_end_matches('\0');
+        (!value.contains('\0')).then(|| (value, end))
+    }
+
+    /// Unpack the metadata account, returns None for the other accounts of the program, such
+    /// as the editions, and for the malformed metadata accounts. The fields unpacked precede
+    /// those added by the later versions of the program, so all the versions are unpacked.
+    pub fn unpack_account(account_data: &[u8]) -> Option<MetadataAccount<'_>> {
+        if account_data.len() < METADATA_NAME_OFFSET || account_data[0] != KEY_METADATA_V1 {
+            return None;
+        }
+
+        let (name, offset) = Self::unpack_string(account_data, METADATA_NAME_OFFSET)?;
+        let (symbol, offset) = Self::unpack_string(account_data, offset)?;
+        let (uri, _) = Self::unpack_string(account_data, offset)?;
+        Some(MetadataAccount {
+            update_authority: Self::unpack_pubkey_unchecked(
+                account_data,
+                METADATA_UPDATE_AUTHORITY_OFFSET,
+            ),
+            mint: Self::unpack_pubkey_unchecked(account_data, METADATA_MINT_OFFSET),
+            name,
+            symbol,
+            uri,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Serialize a metadata account the way the program does, the strings padded to their
+    /// maximum lengths, followed by the fields of the version.
+    pub(crate) fn build_metadata_account_data(
+        update_authority: &Pubkey,
+        mint: &Pubkey,
+        name: &str,
+        symbol: &str,
+        uri: &str,
+        trailing_fields: &[u8],
+    ) -> Vec<u8> {
+        let mut data = vec![KEY_METADATA_V1];
+        data.extend_from_slice(update_authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for (value, max_len) in [(name, 32), (symbol, 10), (uri, 200)] {
+            let mut bytes = value.as_bytes().to_vec();
+            bytes.resize(max_len.max(bytes.len()), 0);
+            data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+        data.extend_from_slice(trailing_fields);
+        data
+    }
+
+    #[test]
+    fn test_metaplex_token_metadata_program_id() {
+        assert_eq!(
+            id().to_string(),
+            "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+        );
+    }
+
+    #[test]
+    fn test_unpack_metadata_account() {
+        let update_authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let expected = MetadataAccount {
+            update_authority: &update_authority,
+            mint: &mint,
+            name: "Degen Ape #1",
+            symbol: "DAPE",
+            uri: "https://arweave.net/abc",
+        };
+
+        // The earliest layout ends after is_mutable, the later ones have more fields and the
+        // accounts are allocated with a zero padding.
+        let v1_fields = [0xf4, 0x01, 0, 1, 1];
+        let mut later_fields = v1_fields.to_vec();
+        later_fields.extend_from_slice(&[1, 255, 1, 0, 0]);
+        later_fields.resize(later_fields.len() + 100, 0);
+        for trailing_fields in [&v1_fields[..], &later_fields[..]] {
+            let account_data = build_metadata_account_data(
+                &update_authority,
+                &mint,
+                expected.name,
+                expected.symbol,
+                expected.uri,
+                trailing_fields,
+            );
+            assert_eq!(Account::unpack_account(&account_data), Some(expected));
+        }
+
+        // A string running past the data, with a NUL character or not in UTF-8.
+        let account_data =
+            build_metadata_account_data(&update_authority, &mint, "name", "SYM", "uri", &[]);
+        assert!(Account::unpack_account(&account_data[..account_data.len() - 1]).is_none());
+        let mut account_data = account_data.clone();
+        account_data[METADATA_NAME_OFFSET..METADATA_NAME_OFFSET + 4]
+            .copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Account::unpack_account(&account_data).is_none());
+        let account_data =
+            build_metadata_account_data(&update_authority, &mint, "\u{0}name", "SYM", "uri", &[]);
+        assert!(Account::unpack_account(&account_data).is_none());
+        let mut account_data =
+            build_metadata_account_data(&update_authority, &mint, "name", "SYM", "uri", &[]);
+        account_data[METADATA_NAME_OFFSET + 4] = 0xff;
+        assert!(Account::unpack_account(&account_data).is_none());
+
+        // An edition account of the program.
+        let mut account_data =
+            build_metadata_account_data(&update_authority, &mint, "name", "SYM", "uri", &[]);
+        account_data[0] = 6;
+        assert!(Account::unpack_account(&account_data).is_none());
+        assert!(Account::unpack_account(&[KEY_METADATA_V1; 10]).is_none());
+    }
+}
@@ -9,8 +9,8 @@ pub(crate) mod new_token_program {
 }
 
 /*
-    /// The SPL token definition -- we care about only the mint and owner fields for now.
-    /// at offset 0 and 32 respectively.
+    /// The SPL token definition -- we care about the mint and owner fields at offset 0 and 32
+    /// respectively, and the delegate, state and close_authority fields at offset 72, 108 and 129.
     spl_token::state::Account {
         mint: Pubkey,
         owner: Pubkey,
@@ -24,6 +24,11 @@ pub(crate) mod new_token_program {
 */
 pub const SPL_TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
 pub const SPL_TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+pub const SPL_TOKEN_ACCOUNT_DELEGATE_OFFSET: usize = 72;
+pub const SPL_TOKEN_ACCOUNT_STATE_OFFSET: usize = 108;
+pub const SPL_TOKEN_ACCOUNT_CLOSE_AUTHORITY_OFFSET: usize = 129;
+/// The length of the tag of a COption.
+const COPTION_TAG_LENGTH: usize = 4;
 const SPL_TOKEN_ACCOUNT_LENGTH: usize = 165;
 
 pub(crate) trait GenericTokenAccount {
@@ -44,6 +49,17 @@ pub(crate) trait GenericTokenAccount {
         bytemuck::from_bytes(&account_data[offset..offset + PUBKEY_BYTES])
     }
 
+    // Call after account length has already been verified
+    fn unpack_coption_pubkey_unchecked(account_data: &[u8], offset: usize) -> Option<&Pubkey> {
+        match account_data[offset..offset + COPTION_TAG_LENGTH] {
+            [1, 0, 0, 0] => Some(Self::unpack_pubkey_unchecked(
+                account_data,
+                offset + COPTION_TAG_LENGTH,
+            )),
+            _ => None,
+        }
+    }
+
     fn unpack_account_owner(account_data: &[u8]) -> Option<&Pubkey> {
         if Self::valid_account_data(account_data) {
             Some(Self::unpack_account_owner_unchecked(account_data))
@@ -59,6 +75,34 @@ pub(crate) trait GenericTokenAccount {
             None
         }
     }
+
+    fn unpack_account_delegate(account_data: &[u8]) -> Option<&Pubkey> {
+        if Self::valid_account_data(account_data) {
+            Self::unpack_coption_pubkey_unchecked(account_data, SPL_TOKEN_ACCOUNT_DELEGATE_OFFSET)
+        } else {
+            None
+        }
+    }
+
+    /// The AccountState: 0 uninitialized, 1 initialized, 2 frozen.
+    fn unpack_account_state(account_data: &[u8]) -> Option<u8> {
+        if Self::valid_account_data(account_data) {
+            Some(account_data[SPL_TOKEN_ACCOUNT_STATE_OFFSET])
+        } else {
+            None
+        }
+    }
+
+    fn unpack_account_close_authority(account_data: &[u8]) -> Option<&Pubkey> {
+        if Self::valid_account_data(account_data) {
+            Self::unpack_coption_pubkey_unchecked(
+                account_data,
+                SPL_TOKEN_ACCOUNT_CLOSE_AUTHORITY_OFFSET,
+            )
+        } else {
+            None
+        }
+    }
 }
 
 pub struct Account;
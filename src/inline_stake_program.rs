@@ -0,0 +1,179 @@
+/// Partial Stake program declarations inlined to avoid deserializing the full `StakeState`
+/// Only the fields used for the stake account index are unpacked.
+use solana_sdk::pubkey::{Pubkey, PUBKEY_BYTES};
+
+solana_sdk::declare_id!("Stake11111111111111111111111111111111111111");
+
+/*
+    /// The stake account definition -- serialized with bincode, the enum discriminant
+    /// is a u32 followed by the variant fields.
+    solana_sdk::stake::state::StakeState {
+        Uninitialized,
+        Initialized(Meta),
+        Stake(Meta, Stake),
+        RewardsPool,
+    }
+
+    Meta {
+        rent_exempt_reserve: u64,
+        authorized: Authorized { staker: Pubkey, withdrawer: Pubkey },
+        lockup: Lockup { unix_timestamp: i64, epoch: u64, custodian: Pubkey },
+    }
+
+    Stake {
+        delegation: Delegation {
+            voter_pubkey: Pubkey,
+            stake: u64,
+            activation_epoch: u64,
+            deactivation_epoch: u64,
+            warmup_cooldown_rate: f64,
+        },
+        credits_observed: u64,
+    }
+*/
+const STAKE_STATE_INITIALIZED: u32 = 1;
+const STAKE_STATE_STAKE: u32 = 2;
+pub const STAKE_ACCOUNT_STAKER_OFFSET: usize = 12;
+pub const STAKE_ACCOUNT_WITHDRAWER_OFFSET: usize = 44;
+pub const STAKE_ACCOUNT_VOTER_OFFSET: usize = 124;
+pub const STAKE_ACCOUNT_DELEGATED_STAKE_OFFSET: usize = 156;
+pub const STAKE_ACCOUNT_ACTIVATION_EPOCH_OFFSET: usize = 164;
+const STAKE_ACCOUNT_LENGTH: usize = 200;
+
+/// The delegation of a stake account, only present once the stake is delegated.
+#[derive(Debug, PartialEq)]
+pub struct StakeDelegation<'a> {
+    pub voter: &'a Pubkey,
+    pub delegated_stake: u64,
+    pub activation_epoch: u64,
+}
+
+/// The fields of a stake account which are indexed.
+#[derive(Debug, PartialEq)]
+pub struct StakeAccount<'a> {
+    pub staker: &'a Pubkey,
+    pub withdrawer: &'a Pubkey,
+    pub delegation: Option<StakeDelegation<'a>>,
+}
+
+pub struct Account;
+impl Account {
+    pub fn get_packed_len() -> usize {
+        STAKE_ACCOUNT_LENGTH
+    }
+
+    fn state(account_data: &[u8]) -> u32 {
+        u32::from_le_bytes(account_data[0..4].try_into().unwrap())
+    }
+
+    // Call after account length has already been verified
+    fn unpack_pubkey_unchecked(account_data: &[u8], offset: usize) -> &Pubkey {
+        bytemuck::from_bytes(&account_data[offset..offset + PUBKEY_BYTES])
+    }
+
+    // Call after account length has already been verified
+    fn unpack_u64_unchecked(account_data: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(account_data[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// Unpack the stake account, returns None for the uninitialized and rewards pool accounts.
+    pub fn unpack_account(account_data: &[u8]) -> Option<StakeAccount<'_>> {
+        if account_data.len() != STAKE_ACCOUNT_LENGTH {
+            return None;
+        }
+
+        let delegation = match Self::state(account_data) {
+            STAKE_STATE_INITIALIZED => None,
+            STAKE_STATE_STAKE => Some(StakeDelegation {
+                voter: Self::unpack_pubkey_unchecked(account_data, STAKE_ACCOUNT_VOTER_OFFSET),
+                delegated_stake: Self::unpack_u64_unchecked(
+                    account_data,
+                    STAKE_ACCOUNT_DELEGATED_STAKE_OFFSET,
+                ),
+                activation_epoch: Self::unpack_u64_unchecked(
+                    account_data,
+                    STAKE_ACCOUNT_ACTIVATION_EPOCH_OFFSET,
+                ),
+            }),
+            _ => return None,
+        };
+
+        Some(StakeAccount {
+            staker: Self::unpack_pubkey_unchecked(account_data, STAKE_ACCOUNT_STAKER_OFFSET),
+            withdrawer: Self::unpack_pubkey_unchecked(
+                account_data,
+                STAKE_ACCOUNT_WITHDRAWER_OFFSET,
+            ),
+            delegation,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        solana_sdk::stake::{
+            self,
+            state::{Authorized, Delegation, Lockup, Meta, Stake, StakeState},
+        },
+    };
+
+    #[test]
+    fn test_stake_program_id() {
+        assert_eq!(id(), stake::program::id());
+    }
+
+    /// Serialize the stake state into an account sized the way the Stake program allocates it.
+    fn build_stake_account_data(stake_state: &StakeState) -> Vec<u8> {
+        let data = bincode::serialize(stake_state).unwrap();
+        let mut account_data = vec![0u8; std::mem::size_of::<StakeState>()];
+        account_data[..data.len()].copy_from_slice(&data);
+        account_data
+    }
+
+    #[test]
+    fn test_unpack_stake_account() {
+        let staker = Pubkey::new_unique();
+        let withdrawer = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+        let meta = Meta {
+            rent_exempt_reserve: 42,
+            authorized: Authorized { staker, withdrawer },
+            lockup: Lockup::default(),
+        };
+
+        let account_data = build_stake_account_data(&StakeState::Initialized(meta));
+        assert_eq!(account_data.len(), Account::get_packed_len());
+        assert_eq!(
+            Account::unpack_account(&account_data),
+            Some(StakeAccount {
+                staker: &staker,
+                withdrawer: &withdrawer,
+                delegation: None,
+            })
+        );
+
+        let stake = Stake {
+            delegation: Delegation::new(&voter, 1_000_000, 7, 0.25),
+            credits_observed: 3,
+        };
+        let account_data = build_stake_account_data(&StakeState::Stake(meta, stake));
+        assert_eq!(
+            Account::unpack_account(&account_data),
+            Some(StakeAccount {
+                staker: &staker,
+                withdrawer: &withdrawer,
+                delegation: Some(StakeDelegation {
+                    voter: &voter,
+                    delegated_stake: 1_000_000,
+                    activation_epoch: 7,
+                }),
+            })
+        );
+
+        let account_data = build_stake_account_data(&StakeState::RewardsPool);
+        assert_eq!(Account::unpack_account(&account_data), None);
+        assert_eq!(Account::unpack_account(&[0u8; 10]), None);
+    }
+}
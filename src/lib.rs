@@ -1,6 +1,12 @@
 pub mod accounts_selector;
+pub mod config_builder;
 pub mod geyser_plugin_postgres;
+pub mod inline_metaplex_token_metadata;
 pub mod inline_spl_token;
 pub mod inline_spl_token_2022;
+pub mod inline_stake_program;
+pub mod notification_sink;
+pub mod parquet_sink;
 pub mod postgres_client;
+pub mod stdout_sink;
 pub mod transaction_selector;
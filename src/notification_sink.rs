@@ -0,0 +1,44 @@
+/// The sink of the selected notifications, assembled into the plugin's database types.
+///
+/// The plugin selects the accounts and transactions and assembles them, and the sink stores
+/// them. The ParallelPostgresClient is the sink loaded from the configuration, writing to the
+/// PostgreSQL database, or the ParquetSink writing to Parquet files, or the StdoutSink printing
+/// JSON lines. Another sink, such as one writing to a queue, is given to the plugin with
+/// GeyserPluginPostgres::with_sink, reusing the selection and the assembly.
+use {
+    crate::postgres_client::{DbAccountInfo, DbBlockInfo, LogTransactionRequest},
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    std::thread,
+};
+
+pub trait NotificationSink: Send + Sync {
+    /// Stop the sink, waiting for the notifications received to be stored.
+    fn join(&mut self) -> thread::Result<()> {
+        Ok(())
+    }
+
+    fn update_account(
+        &mut self,
+        account: DbAccountInfo,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError>;
+
+    fn update_slot_status(
+        &mut self,
+        slot: u64,
+        parent: Option<u64>,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError>;
+
+    fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError>;
+
+    fn log_transaction(
+        &mut self,
+        transaction_log_info: LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError>;
+
+    fn update_block_metadata(&mut self, block_info: DbBlockInfo) -> Result<(), GeyserPluginError>;
+
+    /// Count a transaction notified for the slot, whether or not it is selected.
+    fn tally_transaction(&mut self, _slot: u64, _is_successful: bool) {}
+}
@@ -0,0 +1,429 @@
+/// A sink writing the selected accounts and transactions to rolling Parquet files for bulk
+/// analytics, instead of the PostgreSQL database, when parquet_output_dir is set.
+///
+/// The account updates and the transactions are buffered into the account and transaction
+/// subdirectories of parquet_output_dir, and each file is written once parquet_rows_per_file
+/// rows are buffered, at the end of startup and when the plugin is unloaded. A file is named
+/// by the range of the slots of its rows, such as 00000000000000000100-00000000000000000103.parquet,
+/// zero-padded so the files list in the slot order, and is written under a .tmp name then
+/// renamed, so that readers only see complete files. The files are written on the notifying
+/// thread; the rows buffered when the validator exits without unloading the plugin are lost.
+use {
+    crate::{
+        geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+        notification_sink::NotificationSink,
+        postgres_client::{DbAccountInfo, DbBlockInfo, DbTransaction, LogTransactionRequest},
+    },
+    log::*,
+    parquet::{
+        basic::Compression,
+        data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type},
+        errors::ParquetError,
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    },
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    std::{
+        fs::{self, File},
+        path::{Path, PathBuf},
+        sync::Arc,
+        thread,
+    },
+};
+
+const DEFAULT_PARQUET_ROWS_PER_FILE: usize = 100_000;
+
+/// The schema of the account files, a row per account update.
+const ACCOUNT_SCHEMA: &str = "
+message account {
+    REQUIRED BYTE_ARRAY pubkey;
+    REQUIRED BYTE_ARRAY owner;
+    REQUIRED INT64 lamports;
+    REQUIRED INT64 slot;
+    REQUIRED BOOLEAN executable;
+    REQUIRED INT64 rent_epoch;
+    REQUIRED BYTE_ARRAY data;
+    REQUIRED INT64 write_version (INTEGER(64, false));
+}";
+
+/// The schema of the transaction files, a row per transaction. The message and the status
+/// meta, stored as composite types in the transaction table, are reduced to their flat fields.
+const TRANSACTION_SCHEMA: &str = "
+message transaction {
+    REQUIRED INT64 slot;
+    REQUIRED BYTE_ARRAY signature;
+    REQUIRED BOOLEAN is_vote;
+    REQUIRED INT32 message_type (INTEGER(16, true));
+    REQUIRED BYTE_ARRAY message_hash;
+    REQUIRED BOOLEAN is_successful;
+    REQUIRED INT64 fee;
+    REQUIRED INT64 write_version;
+}";
+
+/// The values of a column of the rows of a file, in the order of the schema.
+enum ColumnValues {
+    Boolean(Vec<bool>),
+    Int32(Vec<i32>),
+    Int64(Vec<i64>),
+    ByteArray(Vec<ByteArray>),
+}
+
+/// The rows of a kind of Parquet files.
+trait ParquetRow: Send + Sync {
+    /// The name of the subdirectory of the files.
+    const TABLE: &'static str;
+    const SCHEMA: &'static str;
+
+    fn slot(&self) -> i64;
+
+    fn columns(rows: &[Self]) -> Vec<ColumnValues>
+    where
+        Self: Sized;
+
+    fn write_error(msg: String) -> GeyserPluginError;
+}
+
+impl ParquetRow for DbAccountInfo {
+    const TABLE: &'static str = "account";
+    const SCHEMA: &'static str = ACCOUNT_SCHEMA;
+
+    fn slot(&self) -> i64 {
+        self.slot
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ColumnValues> {
+        vec![
+            ColumnValues::ByteArray(rows.iter().map(|row| row.pubkey.clone().into()).collect()),
+            ColumnValues::ByteArray(rows.iter().map(|row| row.owner.clone().into()).collect()),
+            ColumnValues::Int64(rows.iter().map(|row| row.lamports).collect()),
+            ColumnValues::Int64(rows.iter().map(|row| row.slot).collect()),
+            ColumnValues::Boolean(rows.iter().map(|row| row.executable).collect()),
+            ColumnValues::Int64(rows.iter().map(|row| row.rent_epoch).collect()),
+            ColumnValues::ByteArray(rows.iter().map(|row| row.data.clone().into()).collect()),
+            // The unsigned write_version is stored in the bits of the INT64.
+            ColumnValues::Int64(rows.iter().map(|row| row.write_version.0 as i64).collect()),
+        ]
+    }
+
+    fn write_error(msg: String) -> GeyserPluginError {
+        GeyserPluginError::AccountsUpdateError { msg }
+    }
+}
+
+impl ParquetRow for DbTransaction {
+    const TABLE: &'static str = "transaction";
+    const SCHEMA: &'static str = TRANSACTION_SCHEMA;
+
+    fn slot(&self) -> i64 {
+        self.slot
+    }
+
+    fn columns(rows: &[Self]) -> Vec<ColumnValues> {
+        vec![
+            ColumnValues::Int64(rows.iter().map(|row| row.slot).collect()),
+            ColumnValues::ByteArray(
+                rows.iter()
+                    .map(|row| row.signature.clone().into())
+                    .collect(),
+            ),
+            ColumnValues::Boolean(rows.iter().map(|row| row.is_vote).collect()),
+            ColumnValues::Int32(rows.iter().map(|row| row.message_type as i32).collect()),
+            ColumnValues::ByteArray(
+                rows.iter()
+                    .map(|row| row.message_hash.clone().into())
+                    .collect(),
+            ),
+            ColumnValues::Boolean(rows.iter().map(|row| row.meta.error.is_none()).collect()),
+            ColumnValues::Int64(rows.iter().map(|row| row.meta.fee).collect()),
+            ColumnValues::Int64(rows.iter().map(|row| row.write_version).collect()),
+        ]
+    }
+
+    fn write_error(msg: String) -> GeyserPluginError {
+        GeyserPluginError::TransactionUpdateError { msg }
+    }
+}
+
+/// The rows buffered for the next file of a kind.
+struct RollingFile<R: ParquetRow> {
+    directory: PathBuf,
+    rows_per_file: usize,
+    rows: Vec<R>,
+}
+
+impl<R: ParquetRow> RollingFile<R> {
+    fn new(output_dir: &Path, rows_per_file: usize) -> Result<Self, GeyserPluginError> {
+        let directory = output_dir.join(R::TABLE);
+        if let Err(err) = fs::create_dir_all(&directory) {
+            let msg = format!(
+                "Failed to create the Parquet output directory {:?}. Error: {:?}",
+                directory, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        Ok(Self {
+            directory,
+            rows_per_file,
+            rows: Vec::new(),
+        })
+    }
+
+    /// Buffer the row, writing the file once it is full.
+    fn push(&mut self, row: R) -> Result<(), GeyserPluginError> {
+        self.rows.push(row);
+        if self.rows.len() >= self.rows_per_file {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The path of the next file, named by the slot range of the rows.
+    fn next_path(&self) -> PathBuf {
+        let first_slot = self.rows.iter().map(R::slot).min().unwrap_or_default();
+        let last_slot = self.rows.iter().map(R::slot).max().unwrap_or_default();
+        let name = format!("{:020}-{:020}", first_slot, last_slot);
+        let mut path = self.directory.join(format!("{}.parquet", name));
+        let mut sequence = 1;
+        while path.exists() {
+            path = self
+                .directory
+                .join(format!("{}-{}.parquet", name, sequence));
+            sequence += 1;
+        }
+        path
+    }
+
+    fn write_file(&self, path: &Path) -> Result<(), ParquetError> {
+        let schema = Arc::new(parse_message_type(R::SCHEMA)?);
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+        );
+        let file = File::create(path)?;
+        let mut writer = SerializedFileWriter::new(file, schema, properties)?;
+        let mut row_group = writer.next_row_group()?;
+        let mut columns = R::columns(&self.rows).into_iter();
+        while let Some(mut column) = row_group.next_column()? {
+            match columns.next() {
+                Some(ColumnValues::Boolean(values)) => {
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&values, None, None)?;
+                }
+                Some(ColumnValues::Int32(values)) => {
+                    column
+                        .typed::<Int32Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                Some(ColumnValues::Int64(values)) => {
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&values, None, None)?;
+                }
+                Some(ColumnValues::ByteArray(values)) => {
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, None, None)?;
+                }
+                None => {
+                    return Err(ParquetError::General(format!(
+                        "The {} schema has more columns than the rows",
+                        R::TABLE
+                    )))
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+
+    /// Write the rows buffered into a file.
+    fn flush(&mut self) -> Result<(), GeyserPluginError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let path = self.next_path();
+        let tmp_path = path.with_extension("parquet.tmp");
+        let result = self
+            .write_file(&tmp_path)
+            .and_then(|()| Ok(fs::rename(&tmp_path, &path)?));
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to write the {} Parquet file {:?}. Error: {:?}",
+                R::TABLE,
+                path,
+                err
+            );
+            error!("{}", msg);
+            return Err(R::write_error(msg));
+        }
+        info!("Wrote {} rows to {:?}", self.rows.len(), path);
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+/// The sink writing the accounts and the transactions to Parquet files.
+pub struct ParquetSink {
+    accounts: RollingFile<DbAccountInfo>,
+    transactions: RollingFile<DbTransaction>,
+}
+
+impl ParquetSink {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let configuration_error = |msg: String| {
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConfigurationError {
+                msg,
+            }))
+        };
+        let output_dir = match &config.parquet_output_dir {
+            Some(output_dir) => Path::new(output_dir),
+            None => {
+                return Err(configuration_error(
+                    "\"parquet_output_dir\" is required for the Parquet files".to_string(),
+                ))
+            }
+        };
+        let rows_per_file = config
+            .parquet_rows_per_file
+            .unwrap_or(DEFAULT_PARQUET_ROWS_PER_FILE);
+        if rows_per_file == 0 {
+            return Err(configuration_error(
+                "\"parquet_rows_per_file\" must be greater than 0".to_string(),
+            ));
+        }
+        info!("Writing the Parquet files into {:?}", output_dir);
+        Ok(Self {
+            accounts: RollingFile::new(output_dir, rows_per_file)?,
+            transactions: RollingFile::new(output_dir, rows_per_file)?,
+        })
+    }
+
+    /// Write the rows buffered into files.
+    fn flush(&mut self) -> Result<(), GeyserPluginError> {
+        self.accounts.flush()?;
+        self.transactions.flush()
+    }
+}
+
+impl NotificationSink for ParquetSink {
+    fn join(&mut self) -> thread::Result<()> {
+        if let Err(err) = self.flush() {
+            error!("Failed to write the Parquet files at unload: ({})", err);
+        }
+        Ok(())
+    }
+
+    fn update_account(
+        &mut self,
+        account: DbAccountInfo,
+        _is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        self.accounts.push(account)
+    }
+
+    fn update_slot_status(
+        &mut self,
+        _slot: u64,
+        _parent: Option<u64>,
+        _status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        Ok(())
+    }
+
+    fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
+        self.flush()
+    }
+
+    fn log_transaction(
+        &mut self,
+        transaction_log_info: LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError> {
+        self.transactions
+            .push(transaction_log_info.transaction_info)
+    }
+
+    fn update_block_metadata(&mut self, _block_info: DbBlockInfo) -> Result<(), GeyserPluginError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::tests::build_account,
+        parquet::file::reader::{FileReader, SerializedFileReader},
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn file_rows(directory: &Path) -> Vec<(String, i64)> {
+        let mut files: Vec<(String, i64)> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+                (
+                    path.file_name().unwrap().to_str().unwrap().to_string(),
+                    reader.metadata().file_metadata().num_rows(),
+                )
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_parquet_sink() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "parquet_output_dir": output_dir.path(),
+            "parquet_rows_per_file": 2,
+        }))
+        .unwrap();
+        let mut sink = ParquetSink::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        for slot in [3, 1, 4, 4, 5] {
+            sink.update_account(build_account(&owner, vec![1; 8], slot), false)
+                .unwrap();
+        }
+        sink.join().unwrap();
+        // A second file of the same slot range does not overwrite the first.
+        for slot in [3, 1] {
+            sink.update_account(build_account(&owner, vec![], slot), false)
+                .unwrap();
+        }
+
+        assert_eq!(
+            file_rows(&output_dir.path().join("account")),
+            vec![
+                (
+                    "00000000000000000001-00000000000000000003-1.parquet".to_string(),
+                    2
+                ),
+                (
+                    "00000000000000000001-00000000000000000003.parquet".to_string(),
+                    2
+                ),
+                (
+                    "00000000000000000004-00000000000000000004.parquet".to_string(),
+                    2
+                ),
+                (
+                    "00000000000000000005-00000000000000000005.parquet".to_string(),
+                    1
+                ),
+            ]
+        );
+        assert!(file_rows(&output_dir.path().join("transaction")).is_empty());
+    }
+}
@@ -1,33 +1,134 @@
 #![allow(clippy::integer_arithmetic)]
 
+mod postgres_client_account_close;
+mod postgres_client_account_columns;
 mod postgres_client_account_index;
+mod postgres_client_account_rebuild;
+mod postgres_client_account_routing;
+mod postgres_client_account_slot_status;
+mod postgres_client_account_type;
+mod postgres_client_adaptive_batch;
+mod postgres_client_audit_budget;
+mod postgres_client_audit_compaction;
+mod postgres_client_audit_retention;
+mod postgres_client_batch_log;
+mod postgres_client_batch_metrics;
+mod postgres_client_batch_order;
+mod postgres_client_batch_split;
+mod postgres_client_batch_tuning;
 mod postgres_client_block_metadata;
+mod postgres_client_checkpoint;
+mod postgres_client_circuit_breaker;
+mod postgres_client_compression;
+mod postgres_client_config_audit;
+mod postgres_client_config_changelog;
+mod postgres_client_connection_check;
+mod postgres_client_control_socket;
+mod postgres_client_data_chunks;
+mod postgres_client_data_size;
+mod postgres_client_dead_slots;
+mod postgres_client_existence_bloom;
+mod postgres_client_existing_accounts;
+mod postgres_client_fillfactor;
+mod postgres_client_heartbeat;
+mod postgres_client_inflight_limit;
+mod postgres_client_ingestion_control;
+mod postgres_client_ingestion_seq;
+mod postgres_client_instruction_idl;
+mod postgres_client_lamport_delta;
+mod postgres_client_lamport_fast_path;
+mod postgres_client_latency;
+mod postgres_client_metadata_db;
+mod postgres_client_metaplex_index;
+mod postgres_client_owner_counts;
+mod postgres_client_pg_service;
+mod postgres_client_placeholder_blocks;
+mod postgres_client_plugin_metadata;
+mod postgres_client_program_link;
+mod postgres_client_proxy;
+mod postgres_client_rate_limit;
+mod postgres_client_recent_accounts;
+mod postgres_client_rent_exempt;
+mod postgres_client_reprepare;
+mod postgres_client_row_isolation;
+mod postgres_client_schema_check;
+mod postgres_client_serialization;
+mod postgres_client_slot_index;
+mod postgres_client_slot_tally;
+mod postgres_client_slot_timing;
+mod postgres_client_staging;
+mod postgres_client_stake_index;
+mod postgres_client_startup_audit;
+mod postgres_client_statement_cache;
+mod postgres_client_statement_checksums;
+mod postgres_client_table_names;
+mod postgres_client_text_search;
+mod postgres_client_token_field_index;
 mod postgres_client_transaction;
+mod postgres_client_transaction_accounts;
+mod postgres_client_unprepared;
+mod postgres_client_write_buffer;
+mod postgres_client_write_lag;
+mod postgres_client_write_version;
 
 /// A concurrent implementation for writing accounts into the PostgreSQL in parallel.
 use {
     crate::{
-        geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+        geyser_plugin_postgres::{
+            AccountDataEncoding, DataCompression, GeyserPluginPostgresConfig,
+            GeyserPluginPostgresError,
+        },
+        notification_sink::NotificationSink,
         postgres_client::postgres_client_account_index::TokenSecondaryIndexEntry,
     },
-    chrono::Utc,
-    crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender},
+    chrono::{naive::NaiveDateTime, Utc},
+    crossbeam_channel::{bounded, Receiver, RecvTimeoutError, SendError, Sender},
     log::*,
     openssl::ssl::{SslConnector, SslFiletype, SslMethod},
-    postgres::{Client, NoTls, Statement},
-    postgres_client_block_metadata::DbBlockInfo,
-    postgres_client_transaction::LogTransactionRequest,
+    postgres::{Client, NoTls},
+    postgres_client_account_columns::AccountColumns,
+    postgres_client_account_slot_status::SlotStatuses,
+    postgres_client_adaptive_batch::AdaptiveBatching,
+    postgres_client_audit_retention::AuditRetention,
+    postgres_client_batch_log::BatchSequence,
+    postgres_client_batch_metrics::{accounts_batch_bytes, report_batch_commit},
+    postgres_client_batch_order::report_deadlock,
+    postgres_client_batch_split::AccountBatchSplitter,
+    postgres_client_batch_tuning::BatchSizeTuner,
+    postgres_client_checkpoint::SlotCheckpoint,
+    postgres_client_circuit_breaker::{CircuitBreaker, WriteAdmission},
+    postgres_client_control_socket::{ControlSocket, ControlTargets},
+    postgres_client_data_size::DataSizeHistogram,
+    postgres_client_existence_bloom::ExistenceBloom,
+    postgres_client_heartbeat::Heartbeat,
+    postgres_client_inflight_limit::InflightLimit,
+    postgres_client_ingestion_control::IngestionControl,
+    postgres_client_instruction_idl::InstructionIdl,
+    postgres_client_lamport_fast_path::LamportFastPath,
+    postgres_client_latency::LatencyHistogram,
+    postgres_client_rate_limit::AccountRateLimiter,
+    postgres_client_reprepare::StatementReprepare,
+    postgres_client_serialization::TransactionParamTypes,
+    postgres_client_slot_tally::SlotTransactionTally,
+    postgres_client_staging::StagingMerger,
+    postgres_client_statement_cache::PreparedStatementCache,
+    postgres_client_transaction::TransactionColumns,
+    postgres_client_unprepared::{PluginStatement, TypeRegistry},
+    postgres_client_write_buffer::{BufferedWrite, WriteBuffer},
+    postgres_client_write_lag::SlotClock,
     postgres_openssl::MakeTlsConnector,
+    rand::Rng,
     solana_geyser_plugin_interface::geyser_plugin_interface::{
-        GeyserPluginError, ReplicaAccountInfo, ReplicaBlockInfo, SlotStatus,
+        GeyserPluginError, ReplicaAccountInfo, SlotStatus,
     },
     solana_measure::measure::Measure,
     solana_metrics::*,
     solana_sdk::timing::AtomicInterval,
     std::{
-        collections::HashSet,
+        collections::{BTreeMap, HashMap, HashSet},
+        iter,
         sync::{
-            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+            atomic::{AtomicBool, AtomicUsize, Ordering},
             Arc, Mutex,
         },
         thread::{self, sleep, Builder, JoinHandle},
@@ -36,6 +137,12 @@ use {
     tokio_postgres::types,
 };
 
+pub use {
+    postgres_client_block_metadata::DbBlockInfo,
+    postgres_client_transaction::{DbTransaction, LogTransactionRequest},
+    postgres_client_write_version::DbWriteVersion,
+};
+
 /// The maximum asynchronous requests allowed in the channel to avoid excessive
 /// memory usage. The downside -- calls after this threshold is reached can get blocked.
 const MAX_ASYNC_REQUESTS: usize = 40960;
@@ -45,37 +152,123 @@ const DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE: usize = 10;
 const ACCOUNT_COLUMN_COUNT: usize = 9;
 const DEFAULT_PANIC_ON_DB_ERROR: bool = false;
 const DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA: bool = false;
+/// The interval to wait for the in-flight work to drain when the in-flight limit is reached.
+const INFLIGHT_BACKPRESSURE_WAIT_MS: u64 = 10;
+/// The interval to wait for the circuit breaker to admit the writes while it is open.
+const CIRCUIT_BREAKER_WAIT_MS: u64 = 100;
+const DEFAULT_MAX_PREPARED_STATEMENTS: usize = 256;
+const DEFAULT_ACCOUNT_DATA_ENCODING: AccountDataEncoding = AccountDataEncoding::Bytea;
+pub(crate) const SYNCHRONOUS_COMMIT_VALUES: [&str; 3] = ["on", "off", "local"];
 
 struct PostgresSqlClientWrapper {
     client: Client,
-    update_account_stmt: Statement,
-    bulk_account_insert_stmt: Statement,
-    update_slot_with_parent_stmt: Statement,
-    update_slot_without_parent_stmt: Statement,
-    update_transaction_log_stmt: Statement,
-    update_block_metadata_stmt: Statement,
-    insert_account_audit_stmt: Option<Statement>,
-    insert_token_owner_index_stmt: Option<Statement>,
-    insert_token_mint_index_stmt: Option<Statement>,
-    bulk_insert_token_owner_index_stmt: Option<Statement>,
-    bulk_insert_token_mint_index_stmt: Option<Statement>,
+    update_account_stmt: PluginStatement,
+    bulk_account_insert_stmt: PluginStatement,
+    update_slot_with_parent_stmt: PluginStatement,
+    update_slot_without_parent_stmt: PluginStatement,
+    update_transaction_log_stmt: PluginStatement,
+    insert_transaction_signatures_stmt: Option<PluginStatement>,
+    insert_transaction_account_writes_stmt: Option<PluginStatement>,
+    insert_transaction_instructions_stmt: Option<PluginStatement>,
+    update_block_metadata_stmt: PluginStatement,
+    update_slot_transaction_tally_stmt: Option<PluginStatement>,
+    insert_account_audit_stmt: Option<PluginStatement>,
+    insert_token_owner_index_stmt: Option<PluginStatement>,
+    insert_token_mint_index_stmt: Option<PluginStatement>,
+    bulk_insert_token_owner_index_stmt: Option<PluginStatement>,
+    bulk_insert_token_mint_index_stmt: Option<PluginStatement>,
+    upsert_stake_account_index_stmt: Option<PluginStatement>,
+    upsert_metaplex_metadata_index_stmt: Option<PluginStatement>,
+    upsert_token_delegate_index_stmt: Option<PluginStatement>,
+    upsert_token_state_index_stmt: Option<PluginStatement>,
+    upsert_slot_timing_stmt: Option<PluginStatement>,
+    update_dead_slots_stmt: Option<PluginStatement>,
+    insert_placeholder_block_stmt: Option<PluginStatement>,
+    upsert_program_link_stmt: Option<PluginStatement>,
+    insert_account_close_stmt: Option<PluginStatement>,
+    select_existing_accounts_stmt: Option<PluginStatement>,
+    upsert_checkpoint_stmt: Option<PluginStatement>,
+    insert_batch_log_stmt: Option<PluginStatement>,
+    insert_account_staging_stmt: Option<PluginStatement>,
+    insert_account_data_chunk_stmt: Option<PluginStatement>,
+    update_account_slot_status_stmt: Option<PluginStatement>,
+    statement_cache: PreparedStatementCache,
+    /// The composite and enum types of the connection, read when first needed by a statement
+    /// not prepared.
+    types: Mutex<Option<TypeRegistry>>,
 }
 
 pub struct SimplePostgresClient {
     batch_size: usize,
+    index_batch_size: usize,
     slots_at_startup: HashSet<u64>,
     pending_account_updates: Vec<DbAccountInfo>,
+    /// The splitting of the accounts batches too large for a single statement.
+    batch_splitter: AccountBatchSplitter,
+    /// The columns of the account tables written.
+    account_columns: AccountColumns,
+    /// The columns of the transaction table written.
+    transaction_columns: TransactionColumns,
+    /// The encoder of the transactions compressed, when compress_transaction_data is set.
+    transaction_encoder: Option<TransactionParamTypes>,
     index_token_owner: bool,
     index_token_mint: bool,
     pending_token_owner_index: Vec<TokenSecondaryIndexEntry>,
     pending_token_mint_index: Vec<TokenSecondaryIndexEntry>,
+    /// The latency histograms of the accounts bulk inserts and single upserts.
+    bulk_insert_latency: LatencyHistogram,
+    upsert_latency: LatencyHistogram,
+    /// The periodic re-preparation of the statements, when enabled.
+    statement_reprepare: Option<StatementReprepare>,
+    /// Whether the historical data of the account updates is skipped, until the end of startup.
+    skip_account_audit: bool,
+    /// The slots whose staging tables this client created, in the staging table mode.
+    staged_slots: HashSet<u64>,
+    /// Whether the staging tables are created UNLOGGED.
+    staging_unlogged: bool,
+    /// The granularity of the block_time_bucket column in seconds, when it is stored.
+    block_time_bucket_secs: Option<i64>,
+    /// The switching of the account updates after startup to the bulk inserts, when enabled.
+    adaptive_batching: Option<AdaptiveBatching>,
+    /// The tuning of the accounts batch size by the commit latency, when enabled.
+    batch_size_tuner: Option<BatchSizeTuner>,
+    /// Whether the rows of the bulk inserts are sorted by their keys.
+    sort_batch_rows: bool,
+    /// Whether the rows of the failed accounts bulk inserts are inserted one by one.
+    isolate_failing_rows: bool,
+    /// The batch_seq of the committed batches shared by the workers, when log_batches is set.
+    batch_sequence: Option<Arc<BatchSequence>>,
+    /// The IDL decoding the instructions of the program, when configured.
+    instruction_idl: Option<InstructionIdl>,
+    /// The names of the tables overridden by table_names.
+    table_names: Option<BTreeMap<String, String>>,
+    /// The clock of the slots shared by the workers, when record_write_lag is set.
+    slot_clock: Option<Arc<SlotClock>>,
+    /// The statuses of the slots shared by the workers, when record_account_slot_status is set.
+    slot_statuses: Option<Arc<SlotStatuses>>,
+    /// The bloom filter of the stored pubkeys shared by the workers, until the end of startup.
+    existence_bloom: Option<Arc<ExistenceBloom>>,
+    /// The retention of the account_audit rows by owner shared by the workers, when configured.
+    audit_retention: Option<Arc<AuditRetention>>,
+    /// The slots of the recent_account table after the end of startup, when it is maintained.
+    recent_account_window_slots: Option<u64>,
+    /// The versions of the accounts written, for the balance-only updates, when enabled.
+    lamport_fast_path: Option<LamportFastPath>,
     client: Mutex<PostgresSqlClientWrapper>,
 }
 
 struct PostgresClientWorker {
     client: SimplePostgresClient,
+    /// The client writing the slots, blocks and transactions, when they go to another database
+    /// than the accounts.
+    metadata_client: Option<SimplePostgresClient>,
     /// Indicating if accounts notification during startup is done.
     is_startup_done: bool,
+    /// The sequences of the account updates handled which may still be buffered, for the
+    /// checkpoint.
+    held_sequences: Vec<u64>,
+    /// The generation of the last flush requested by the control socket and handled.
+    flushed_generation: u64,
 }
 
 impl Eq for DbAccountInfo {}
@@ -89,7 +282,9 @@ pub struct DbAccountInfo {
     pub rent_epoch: i64,
     pub data: Vec<u8>,
     pub slot: i64,
-    pub write_version: i64,
+    pub write_version: DbWriteVersion,
+    /// The table the account is routed to by the accounts_selector, None for the account table.
+    pub target_table: Option<Arc<str>>,
 }
 
 pub(crate) fn abort() -> ! {
@@ -106,18 +301,31 @@ pub(crate) fn abort() -> ! {
 }
 
 impl DbAccountInfo {
-    fn new<T: ReadableAccountInfo>(account: &T, slot: u64) -> DbAccountInfo {
+    /// Fails when the slot is beyond the BIGINT slot column, instead of wrapping it around to
+    /// a negative slot.
+    pub fn new<T: ReadableAccountInfo>(
+        account: &T,
+        slot: u64,
+    ) -> Result<DbAccountInfo, GeyserPluginError> {
+        let slot = i64::try_from(slot).map_err(|_| GeyserPluginError::AccountsUpdateError {
+            msg: format!(
+                "The slot {} of the account {} is beyond the BIGINT slot column",
+                slot,
+                bs58::encode(account.pubkey()).into_string()
+            ),
+        })?;
         let data = account.data().to_vec();
-        Self {
+        Ok(Self {
             pubkey: account.pubkey().to_vec(),
             lamports: account.lamports() as i64,
             owner: account.owner().to_vec(),
             executable: account.executable(),
             rent_epoch: account.rent_epoch() as i64,
             data,
-            slot: slot as i64,
+            slot,
             write_version: account.write_version(),
-        }
+            target_table: None,
+        })
     }
 }
 
@@ -128,7 +336,7 @@ pub trait ReadableAccountInfo: Sized {
     fn executable(&self) -> bool;
     fn rent_epoch(&self) -> i64;
     fn data(&self) -> &[u8];
-    fn write_version(&self) -> i64;
+    fn write_version(&self) -> DbWriteVersion;
 }
 
 impl ReadableAccountInfo for DbAccountInfo {
@@ -156,7 +364,7 @@ impl ReadableAccountInfo for DbAccountInfo {
         &self.data
     }
 
-    fn write_version(&self) -> i64 {
+    fn write_version(&self) -> DbWriteVersion {
         self.write_version
     }
 }
@@ -186,8 +394,8 @@ impl<'a> ReadableAccountInfo for ReplicaAccountInfo<'a> {
         self.data
     }
 
-    fn write_version(&self) -> i64 {
-        self.write_version as i64
+    fn write_version(&self) -> DbWriteVersion {
+        DbWriteVersion(self.write_version)
     }
 }
 
@@ -226,13 +434,19 @@ impl SimplePostgresClient {
     pub fn connect_to_db(config: &GeyserPluginPostgresConfig) -> Result<Client, GeyserPluginError> {
         let port = config.port.unwrap_or(DEFAULT_POSTGRES_PORT);
 
-        let connection_str = if let Some(connection_str) = &config.connection_str {
+        let connection_str = if let Some(connection_str) = config
+            .accounts_connection_str
+            .as_ref()
+            .or(config.connection_str.as_ref())
+        {
             connection_str.clone()
+        } else if let Some(service) = &config.pg_service {
+            Self::pg_service_connection_str(config, service)?
         } else {
             if config.host.is_none() || config.user.is_none() {
                 let msg = format!(
-                    "\"connection_str\": {:?}, or \"host\": {:?} \"user\": {:?} must be specified",
-                    config.connection_str, config.host, config.user
+                    "\"connection_str\": {:?}, \"pg_service\": {:?}, or \"host\": {:?} \"user\": {:?} must be specified",
+                    config.connection_str, config.pg_service, config.host, config.user
                 );
                 return Err(GeyserPluginError::Custom(Box::new(
                     GeyserPluginPostgresError::ConfigurationError { msg },
@@ -246,6 +460,11 @@ impl SimplePostgresClient {
             )
         };
 
+        let proxied_config = match &config.proxy_url {
+            Some(proxy_url) => Some(Self::proxied_config(&connection_str, proxy_url)?),
+            None => None,
+        };
+
         let result = if let Some(true) = config.use_ssl {
             if config.server_ca.is_none() {
                 let msg = "\"server_ca\" must be specified when \"use_ssl\" is set".to_string();
@@ -302,9 +521,15 @@ impl SimplePostgresClient {
                 connect_config.set_verify_hostname(false);
                 Ok(())
             });
-            Client::connect(&connection_str, connector)
+            match &proxied_config {
+                Some(proxied_config) => proxied_config.connect(connector),
+                None => Client::connect(&connection_str, connector),
+            }
         } else {
-            Client::connect(&connection_str, NoTls)
+            match &proxied_config {
+                Some(proxied_config) => proxied_config.connect(NoTls),
+                None => Client::connect(&connection_str, NoTls),
+            }
         };
 
         match result {
@@ -318,31 +543,107 @@ impl SimplePostgresClient {
                     GeyserPluginPostgresError::DataStoreConnectionError { msg },
                 )))
             }
-            Ok(client) => Ok(client),
+            Ok(mut client) => {
+                Self::log_connection_addresses(&mut client);
+                Self::set_synchronous_commit(client, config)
+            }
         }
     }
 
-    fn build_bulk_account_insert_statement(
+    /// Get the local and remote socket addresses of the connection, as seen by the server,
+    /// and the process id of its server backend. The addresses are None for the connections
+    /// through a Unix-domain socket.
+    fn get_connection_addresses(
         client: &mut Client,
+    ) -> Result<(Option<String>, Option<String>, i32), postgres::Error> {
+        let row = client.query_one(
+            "SELECT host(inet_client_addr()), inet_client_port(), \
+            host(inet_server_addr()), inet_server_port(), pg_backend_pid()",
+            &[],
+        )?;
+        let format_address = |host: Option<String>, port: Option<i32>| match (host, port) {
+            (Some(host), Some(port)) if host.contains(':') => Some(format!("[{}]:{}", host, port)),
+            (Some(host), Some(port)) => Some(format!("{}:{}", host, port)),
+            _ => None,
+        };
+        Ok((
+            format_address(row.get(0), row.get(1)),
+            format_address(row.get(2), row.get(3)),
+            row.get(4),
+        ))
+    }
+
+    /// Log the socket addresses of the connection once it is established, for correlating
+    /// the plugin's connections with the firewall and flow logs.
+    fn log_connection_addresses(client: &mut Client) {
+        match Self::get_connection_addresses(client) {
+            Ok((local_addr, remote_addr, backend_pid)) => info!(
+                "Connected to the PostgreSQL database: local_addr={} remote_addr={} backend_pid={}",
+                local_addr.as_deref().unwrap_or("unix-socket"),
+                remote_addr.as_deref().unwrap_or("unix-socket"),
+                backend_pid
+            ),
+            Err(err) => warn!(
+                "Failed to get the socket addresses of the PostgreSQL database connection. Error: {:?}",
+                err
+            ),
+        }
+    }
+
+    /// Apply the configured synchronous_commit setting to the session.
+    fn set_synchronous_commit(
+        mut client: Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let batch_size = config
-            .batch_size
-            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
-        let mut stmt = String::from("INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) VALUES");
+    ) -> Result<Client, GeyserPluginError> {
+        let synchronous_commit = match &config.synchronous_commit {
+            Some(synchronous_commit) => synchronous_commit,
+            None => return Ok(client),
+        };
+
+        if !SYNCHRONOUS_COMMIT_VALUES.contains(&synchronous_commit.as_str()) {
+            let msg = format!(
+                "\"synchronous_commit\" must be one of {:?}, got: {:?}",
+                SYNCHRONOUS_COMMIT_VALUES, synchronous_commit
+            );
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+
+        if let Err(err) =
+            client.batch_execute(&format!("SET synchronous_commit = {}", synchronous_commit))
+        {
+            let msg = format!(
+                "Failed to set synchronous_commit to {}. Error: {:?}",
+                synchronous_commit, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(client)
+    }
+
+    /// Build the SQL text of the accounts bulk insert statement with `batch_size` rows.
+    fn build_bulk_account_insert_sql(
+        account_table: &str,
+        batch_size: usize,
+        account_data_encoding: AccountDataEncoding,
+        empty_data_as_null: bool,
+        track_lamport_deltas: bool,
+        account_columns: &AccountColumns,
+    ) -> String {
+        let mut stmt = format!(
+            "INSERT INTO {} AS acct ({}) VALUES",
+            account_table,
+            account_columns.columns_sql()
+        );
         for j in 0..batch_size {
-            let row = j * ACCOUNT_COLUMN_COUNT;
-            let val_str = format!(
-                "(${}, ${}, ${}, ${}, ${}, ${}, ${}, ${}, ${})",
-                row + 1,
-                row + 2,
-                row + 3,
-                row + 4,
-                row + 5,
-                row + 6,
-                row + 7,
-                row + 8,
-                row + 9,
+            let val_str = account_columns.values_sql(
+                j * account_columns.len() + 1,
+                account_data_encoding,
+                empty_data_as_null,
             );
 
             if j == 0 {
@@ -352,14 +653,78 @@ impl SimplePostgresClient {
             }
         }
 
-        let handle_conflict = "ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, executable=excluded.executable, rent_epoch=excluded.rent_epoch, \
-            data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on WHERE acct.slot < excluded.slot OR (\
-            acct.slot = excluded.slot AND acct.write_version < excluded.write_version)";
+        let handle_conflict = format!(
+            "ON CONFLICT (pubkey) DO UPDATE SET {}{} WHERE acct.slot < excluded.slot OR (\
+            acct.slot = excluded.slot AND acct.write_version < excluded.write_version)",
+            account_columns.update_set_sql(),
+            Self::pre_lamports_update_sql(track_lamport_deltas)
+        );
+
+        format!("{} {}", stmt, handle_conflict)
+    }
+
+    fn get_account_data_encoding(config: &GeyserPluginPostgresConfig) -> AccountDataEncoding {
+        config
+            .account_data_encoding
+            .unwrap_or(DEFAULT_ACCOUNT_DATA_ENCODING)
+    }
+
+    fn get_empty_data_as_null(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.empty_data_as_null, Some(true))
+    }
+
+    /// The SQL expression storing the account data bound to the parameter in the encoding.
+    /// The parameter is explicitly typed bytea, which the driver always sends in the binary
+    /// format, so the large account data is never text encoded on the wire. The text
+    /// encodings are applied by the server. With empty_data_as_null, zero-length data is
+    /// stored as NULL.
+    fn encode_account_data_sql(
+        account_data_encoding: AccountDataEncoding,
+        empty_data_as_null: bool,
+        data_prefix_len: Option<usize>,
+        param: usize,
+    ) -> String {
+        let data = match data_prefix_len {
+            Some(data_prefix_len) => format!(
+                "SUBSTRING(${}::BYTEA FROM 1 FOR {})",
+                param, data_prefix_len
+            ),
+            None => format!("${}::BYTEA", param),
+        };
+        let data = if empty_data_as_null {
+            format!("NULLIF({}, ''::BYTEA)", data)
+        } else {
+            data
+        };
+        match account_data_encoding {
+            AccountDataEncoding::Bytea => data,
+            AccountDataEncoding::Hex => format!("encode({}, 'hex')", data),
+            // PostgreSQL wraps the base64 output every 76 characters.
+            AccountDataEncoding::Base64 => {
+                format!("translate(encode({}, 'base64'), E'\\n', '')", data)
+            }
+        }
+    }
 
-        stmt = format!("{} {}", stmt, handle_conflict);
+    fn build_bulk_account_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let batch_size = config
+            .batch_size
+            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
+        let stmt = Self::build_bulk_account_insert_sql(
+            Self::table_name(&config.table_names, "account"),
+            batch_size,
+            Self::get_account_data_encoding(config),
+            Self::get_empty_data_as_null(config),
+            Self::get_track_lamport_deltas(config),
+            &AccountColumns::new(config)?,
+        );
 
         info!("{}", stmt);
-        let bulk_stmt = client.prepare(&stmt);
+        let bulk_stmt =
+            PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match bulk_stmt {
             Err(err) => {
@@ -377,14 +742,25 @@ impl SimplePostgresClient {
     fn build_single_account_upsert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
-        ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, executable=excluded.executable, rent_epoch=excluded.rent_epoch, \
-        data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on  WHERE acct.slot < excluded.slot OR (\
-        acct.slot = excluded.slot AND acct.write_version < excluded.write_version)";
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let account_columns = AccountColumns::new(config)?;
+        let values = account_columns.values_sql(
+            1,
+            Self::get_account_data_encoding(config),
+            Self::get_empty_data_as_null(config),
+        );
+        let stmt = format!(
+            "INSERT INTO {} AS acct ({}) VALUES {} \
+        ON CONFLICT (pubkey) DO UPDATE SET {}{} WHERE acct.slot < excluded.slot OR (\
+        acct.slot = excluded.slot AND acct.write_version < excluded.write_version)",
+            Self::table_name(&config.table_names, "account"),
+            account_columns.columns_sql(),
+            values,
+            account_columns.update_set_sql(),
+            Self::pre_lamports_update_sql(Self::get_track_lamport_deltas(config))
+        );
 
-        let stmt = client.prepare(stmt);
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -403,8 +779,9 @@ impl SimplePostgresClient {
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
         stmt: &str,
-    ) -> Result<Statement, GeyserPluginError> {
-        let statement = client.prepare(stmt);
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let statement =
+            PluginStatement::prepare(client, stmt, Self::use_prepared_statements(config));
 
         match statement {
             Err(err) => {
@@ -422,11 +799,21 @@ impl SimplePostgresClient {
     fn build_account_audit_insert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO account_audit (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)";
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let account_columns = AccountColumns::new(config)?;
+        let values = account_columns.values_sql(
+            1,
+            Self::get_account_data_encoding(config),
+            Self::get_empty_data_as_null(config),
+        );
+        let stmt = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            Self::table_name(&config.table_names, "account_audit"),
+            account_columns.columns_sql(),
+            values
+        );
 
-        let stmt = client.prepare(stmt);
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -444,12 +831,13 @@ impl SimplePostgresClient {
     fn build_slot_upsert_statement_with_parent(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO slot (slot, parent, status, updated_on) \
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!("INSERT INTO {} (slot, parent, status, updated_on) \
         VALUES ($1, $2, $3, $4) \
-        ON CONFLICT (slot) DO UPDATE SET parent=excluded.parent, status=excluded.status, updated_on=excluded.updated_on";
+        ON CONFLICT (slot) DO UPDATE SET parent=excluded.parent, status=excluded.status, updated_on=excluded.updated_on",
+            Self::table_name(&config.table_names, "slot"));
 
-        let stmt = client.prepare(stmt);
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -467,12 +855,15 @@ impl SimplePostgresClient {
     fn build_slot_upsert_statement_without_parent(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO slot (slot, status, updated_on) \
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (slot, status, updated_on) \
         VALUES ($1, $2, $3) \
-        ON CONFLICT (slot) DO UPDATE SET status=excluded.status, updated_on=excluded.updated_on";
+        ON CONFLICT (slot) DO UPDATE SET status=excluded.status, updated_on=excluded.updated_on",
+            Self::table_name(&config.table_names, "slot")
+        );
 
-        let stmt = client.prepare(stmt);
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -490,26 +881,19 @@ impl SimplePostgresClient {
     /// Internal function for inserting an account into account_audit table.
     fn insert_account_audit(
         account: &DbAccountInfo,
-        statement: &Statement,
+        statement: &PluginStatement,
         client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        account_columns: &AccountColumns,
+        slot_clock: &Option<Arc<SlotClock>>,
+        slot_statuses: &Option<Arc<SlotStatuses>>,
     ) -> Result<(), GeyserPluginError> {
-        let lamports = account.lamports() as i64;
-        let rent_epoch = account.rent_epoch() as i64;
         let updated_on = Utc::now().naive_utc();
-        let result = client.execute(
-            statement,
-            &[
-                &account.pubkey(),
-                &account.slot,
-                &account.owner(),
-                &lamports,
-                &account.executable(),
-                &rent_epoch,
-                &account.data(),
-                &account.write_version(),
-                &updated_on,
-            ],
-        );
+        let derived =
+            account_columns.derived_values(account, &updated_on, slot_clock, slot_statuses);
+        let mut values = Vec::with_capacity(account_columns.len());
+        account_columns.push_values(account, &derived, &updated_on, &mut values);
+        let result = statement.execute(client, types, &values);
 
         if let Err(err) = result {
             let msg = format!(
@@ -523,31 +907,35 @@ impl SimplePostgresClient {
     }
 
     /// Internal function for updating or inserting a single account
+    #[allow(clippy::too_many_arguments)]
     fn upsert_account_internal(
         account: &DbAccountInfo,
-        statement: &Statement,
+        statement: &PluginStatement,
         client: &mut Client,
-        insert_account_audit_stmt: &Option<Statement>,
-        insert_token_owner_index_stmt: &Option<Statement>,
-        insert_token_mint_index_stmt: &Option<Statement>,
+        types: &Mutex<Option<TypeRegistry>>,
+        insert_account_audit_stmt: &Option<PluginStatement>,
+        insert_token_owner_index_stmt: &Option<PluginStatement>,
+        insert_token_mint_index_stmt: &Option<PluginStatement>,
+        upsert_stake_account_index_stmt: &Option<PluginStatement>,
+        upsert_metaplex_metadata_index_stmt: &Option<PluginStatement>,
+        upsert_token_delegate_index_stmt: &Option<PluginStatement>,
+        upsert_token_state_index_stmt: &Option<PluginStatement>,
+        upsert_program_link_stmt: &Option<PluginStatement>,
+        insert_account_close_stmt: &Option<PluginStatement>,
+        account_columns: &AccountColumns,
+        slot_clock: &Option<Arc<SlotClock>>,
+        slot_statuses: &Option<Arc<SlotStatuses>>,
     ) -> Result<(), GeyserPluginError> {
-        let lamports = account.lamports() as i64;
-        let rent_epoch = account.rent_epoch() as i64;
+        if let Some(insert_account_close_stmt) = insert_account_close_stmt {
+            Self::insert_account_close(client, types, insert_account_close_stmt, account)?;
+        }
+
         let updated_on = Utc::now().naive_utc();
-        let result = client.execute(
-            statement,
-            &[
-                &account.pubkey(),
-                &account.slot,
-                &account.owner(),
-                &lamports,
-                &account.executable(),
-                &rent_epoch,
-                &account.data(),
-                &account.write_version(),
-                &updated_on,
-            ],
-        );
+        let derived =
+            account_columns.derived_values(account, &updated_on, slot_clock, slot_statuses);
+        let mut values = Vec::with_capacity(account_columns.len());
+        account_columns.push_values(account, &derived, &updated_on, &mut values);
+        let result = statement.execute(client, types, &values);
 
         if let Err(err) = result {
             let msg = format!(
@@ -560,36 +948,116 @@ impl SimplePostgresClient {
             // If no records modified (inserted or updated), it is because the account is updated
             // at an older slot, insert the record directly into the account_audit table.
             let statement = insert_account_audit_stmt.as_ref().unwrap();
-            Self::insert_account_audit(account, statement, client)?;
+            Self::insert_account_audit(
+                account,
+                statement,
+                client,
+                types,
+                account_columns,
+                slot_clock,
+                slot_statuses,
+            )?;
         }
 
         if let Some(insert_token_owner_index_stmt) = insert_token_owner_index_stmt {
-            Self::update_token_owner_index(client, insert_token_owner_index_stmt, account)?;
+            Self::update_token_owner_index(client, types, insert_token_owner_index_stmt, account)?;
         }
 
         if let Some(insert_token_mint_index_stmt) = insert_token_mint_index_stmt {
-            Self::update_token_mint_index(client, insert_token_mint_index_stmt, account)?;
+            Self::update_token_mint_index(client, types, insert_token_mint_index_stmt, account)?;
+        }
+
+        if let Some(upsert_stake_account_index_stmt) = upsert_stake_account_index_stmt {
+            Self::update_stake_account_index(
+                client,
+                types,
+                upsert_stake_account_index_stmt,
+                account,
+            )?;
+        }
+
+        if let Some(upsert_metaplex_metadata_index_stmt) = upsert_metaplex_metadata_index_stmt {
+            Self::update_metaplex_metadata_index(
+                client,
+                types,
+                upsert_metaplex_metadata_index_stmt,
+                account,
+            )?;
+        }
+
+        if let Some(upsert_token_delegate_index_stmt) = upsert_token_delegate_index_stmt {
+            Self::update_token_delegate_index(
+                client,
+                types,
+                upsert_token_delegate_index_stmt,
+                account,
+            )?;
+        }
+
+        if let Some(upsert_token_state_index_stmt) = upsert_token_state_index_stmt {
+            Self::update_token_state_index(client, types, upsert_token_state_index_stmt, account)?;
+        }
+
+        if let Some(upsert_program_link_stmt) = upsert_program_link_stmt {
+            Self::update_program_link(client, types, upsert_program_link_stmt, account)?;
         }
 
         Ok(())
     }
 
-    /// Update or insert a single account
+    /// Update or insert a single account, or insert it into the staging table of its slot in
+    /// the staging table mode.
     fn upsert_account(&mut self, account: &DbAccountInfo) -> Result<(), GeyserPluginError> {
+        if self.update_account_lamports(account)? {
+            return Ok(());
+        }
+        if self
+            .client
+            .get_mut()
+            .unwrap()
+            .insert_account_staging_stmt
+            .is_some()
+        {
+            self.ensure_staging_table(account.slot)?;
+        }
         let client = self.client.get_mut().unwrap();
         let insert_account_audit_stmt = &client.insert_account_audit_stmt;
-        let statement = &client.update_account_stmt;
+        let statement = client
+            .insert_account_staging_stmt
+            .as_ref()
+            .unwrap_or(&client.update_account_stmt);
         let insert_token_owner_index_stmt = &client.insert_token_owner_index_stmt;
         let insert_token_mint_index_stmt = &client.insert_token_mint_index_stmt;
+        let upsert_stake_account_index_stmt = &client.upsert_stake_account_index_stmt;
+        let upsert_metaplex_metadata_index_stmt = &client.upsert_metaplex_metadata_index_stmt;
+        let upsert_token_delegate_index_stmt = &client.upsert_token_delegate_index_stmt;
+        let upsert_token_state_index_stmt = &client.upsert_token_state_index_stmt;
+        let upsert_program_link_stmt = &client.upsert_program_link_stmt;
+        let insert_account_close_stmt = &client.insert_account_close_stmt;
+        let types = &client.types;
         let client = &mut client.client;
+        let mut measure = Measure::start("geyser-plugin-postgres-upsert-account");
         Self::upsert_account_internal(
             account,
             statement,
             client,
+            types,
             insert_account_audit_stmt,
             insert_token_owner_index_stmt,
             insert_token_mint_index_stmt,
+            upsert_stake_account_index_stmt,
+            upsert_metaplex_metadata_index_stmt,
+            upsert_token_delegate_index_stmt,
+            upsert_token_state_index_stmt,
+            upsert_program_link_stmt,
+            insert_account_close_stmt,
+            &self.account_columns,
+            &self.slot_clock,
+            &self.slot_statuses,
         )?;
+        measure.stop();
+        self.upsert_latency.record(measure.as_us());
+        self.record_account_written(account);
 
         Ok(())
     }
@@ -600,6 +1068,10 @@ impl SimplePostgresClient {
         account: DbAccountInfo,
     ) -> Result<(), GeyserPluginError> {
         self.queue_secondary_indexes(&account);
+        self.upsert_stake_account_index(&account)?;
+        self.upsert_metaplex_metadata_index(&account)?;
+        self.upsert_token_field_indexes(&account)?;
+        self.upsert_program_link(&account)?;
         self.pending_account_updates.push(account);
 
         self.bulk_insert_accounts()?;
@@ -608,24 +1080,58 @@ impl SimplePostgresClient {
     }
 
     fn bulk_insert_accounts(&mut self) -> Result<(), GeyserPluginError> {
-        if self.pending_account_updates.len() == self.batch_size {
+        let batch_size = self.effective_batch_size();
+        if self.pending_account_updates.len() >= batch_size {
+            self.sort_pending_account_updates();
+            if self
+                .client
+                .get_mut()
+                .unwrap()
+                .select_existing_accounts_stmt
+                .is_some()
+                && !self.slots_at_startup.is_empty()
+            {
+                return self.bulk_insert_new_accounts();
+            }
+            // The batches of a tuned size other than batch_size use the cached statements.
+            if let Some(sub_batches) = self
+                .batch_splitter
+                .split(&self.pending_account_updates)
+                .or_else(|| {
+                    (batch_size != self.batch_size)
+                        .then(|| iter::once(0..self.pending_account_updates.len()).collect())
+                })
+            {
+                let mut measure = Measure::start("geyser-plugin-postgres-update-account");
+                let result = self.bulk_insert_account_sub_batches(&sub_batches);
+                measure.stop();
+                self.pending_account_updates.clear();
+                if result.is_ok() {
+                    self.record_batch_commit_latency(measure.as_us());
+                }
+                return result;
+            }
+
             let mut measure = Measure::start("geyser-plugin-postgres-prepare-values");
 
             let mut values: Vec<&(dyn types::ToSql + Sync)> =
-                Vec::with_capacity(self.batch_size * ACCOUNT_COLUMN_COUNT);
+                Vec::with_capacity(self.batch_size * self.account_columns.len());
             let updated_on = Utc::now().naive_utc();
-            for j in 0..self.batch_size {
-                let account = &self.pending_account_updates[j];
-
-                values.push(&account.pubkey);
-                values.push(&account.slot);
-                values.push(&account.owner);
-                values.push(&account.lamports);
-                values.push(&account.executable);
-                values.push(&account.rent_epoch);
-                values.push(&account.data);
-                values.push(&account.write_version);
-                values.push(&updated_on);
+            let derived_values: Vec<_> = self
+                .pending_account_updates
+                .iter()
+                .map(|account| {
+                    self.account_columns.derived_values(
+                        account,
+                        &updated_on,
+                        &self.slot_clock,
+                        &self.slot_statuses,
+                    )
+                })
+                .collect();
+            for (account, derived) in self.pending_account_updates.iter().zip(&derived_values) {
+                self.account_columns
+                    .push_values(account, derived, &updated_on, &mut values);
             }
             measure.stop();
             inc_new_counter_debug!(
@@ -637,22 +1143,45 @@ impl SimplePostgresClient {
 
             let mut measure = Measure::start("geyser-plugin-postgres-update-account");
             let client = self.client.get_mut().unwrap();
-            let result = client
-                .client
-                .query(&client.bulk_account_insert_stmt, &values);
-
-            self.pending_account_updates.clear();
+            debug_assert_eq!(values.len(), client.bulk_account_insert_stmt.param_count());
+            let result =
+                client
+                    .bulk_account_insert_stmt
+                    .query(&mut client.client, &client.types, &values);
+            measure.stop();
+            report_batch_commit(
+                "account",
+                self.batch_size,
+                accounts_batch_bytes(&self.pending_account_updates),
+                measure.as_us(),
+                result.is_ok(),
+            );
+            let logged = if result.is_ok() {
+                Self::log_batch(client, &self.batch_sequence, &self.pending_account_updates)
+            } else {
+                Ok(())
+            };
 
             if let Err(err) = result {
+                report_deadlock(&err);
                 let msg = format!(
                     "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
                     err
                 );
                 error!("{}", msg);
-                return Err(GeyserPluginError::AccountsUpdateError { msg });
+                let result = if self.isolate_failing_rows {
+                    self.insert_failed_batch_rows(0..self.pending_account_updates.len())
+                } else {
+                    Err(GeyserPluginError::AccountsUpdateError { msg })
+                };
+                self.pending_account_updates.clear();
+                return result;
             }
+            self.pending_account_updates.clear();
+            logged?;
 
-            measure.stop();
+            self.bulk_insert_latency.record(measure.as_us());
+            self.record_batch_commit_latency(measure.as_us());
             inc_new_counter_debug!(
                 "geyser-plugin-postgres-update-account-us",
                 measure.as_us() as usize,
@@ -665,22 +1194,37 @@ impl SimplePostgresClient {
                 10000,
                 10000
             );
+            self.count_batch_for_reprepare()?;
         }
         Ok(())
     }
 
     /// Flush any left over accounts in batch which are not processed in the last batch
     fn flush_buffered_writes(&mut self) -> Result<(), GeyserPluginError> {
+        self.flush_buffered_indexes()?;
+        self.remove_existing_accounts()?;
         if self.pending_account_updates.is_empty() {
             return Ok(());
         }
 
         let client = self.client.get_mut().unwrap();
-        let insert_account_audit_stmt = &client.insert_account_audit_stmt;
+        let insert_account_audit_stmt = if self.skip_account_audit {
+            &None
+        } else {
+            &client.insert_account_audit_stmt
+        };
         let statement = &client.update_account_stmt;
         let insert_token_owner_index_stmt = &client.insert_token_owner_index_stmt;
         let insert_token_mint_index_stmt = &client.insert_token_mint_index_stmt;
+        let upsert_stake_account_index_stmt = &client.upsert_stake_account_index_stmt;
+        let upsert_metaplex_metadata_index_stmt = &client.upsert_metaplex_metadata_index_stmt;
+        let upsert_token_delegate_index_stmt = &client.upsert_token_delegate_index_stmt;
+        let upsert_token_state_index_stmt = &client.upsert_token_state_index_stmt;
+        let upsert_program_link_stmt = &client.upsert_program_link_stmt;
+        // The accounts left from startup are not closes.
+        let insert_account_close_stmt = &None;
         let insert_slot_stmt = &client.update_slot_without_parent_stmt;
+        let types = &client.types;
         let client = &mut client.client;
 
         for account in self.pending_account_updates.drain(..) {
@@ -688,9 +1232,19 @@ impl SimplePostgresClient {
                 &account,
                 statement,
                 client,
+                types,
                 insert_account_audit_stmt,
                 insert_token_owner_index_stmt,
                 insert_token_mint_index_stmt,
+                upsert_stake_account_index_stmt,
+                upsert_metaplex_metadata_index_stmt,
+                upsert_token_delegate_index_stmt,
+                upsert_token_state_index_stmt,
+                upsert_program_link_stmt,
+                insert_account_close_stmt,
+                &self.account_columns,
+                &self.slot_clock,
+                &self.slot_statuses,
             )?;
         }
 
@@ -702,6 +1256,7 @@ impl SimplePostgresClient {
                 None,
                 SlotStatus::Rooted,
                 client,
+                types,
                 insert_slot_stmt,
             )?;
         }
@@ -714,7 +1269,6 @@ impl SimplePostgresClient {
         );
 
         self.slots_at_startup.clear();
-        self.clear_buffered_indexes();
         Ok(())
     }
 
@@ -723,7 +1277,8 @@ impl SimplePostgresClient {
         parent: Option<u64>,
         status: SlotStatus,
         client: &mut Client,
-        statement: &Statement,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
     ) -> Result<(), GeyserPluginError> {
         let slot = slot as i64; // postgres only supports i64
         let parent = parent.map(|parent| parent as i64);
@@ -731,8 +1286,10 @@ impl SimplePostgresClient {
         let status_str = status.as_str();
 
         let result = match parent {
-            Some(parent) => client.execute(statement, &[&slot, &parent, &status_str, &updated_on]),
-            None => client.execute(statement, &[&slot, &status_str, &updated_on]),
+            Some(parent) => {
+                statement.execute(client, types, &[&slot, &parent, &status_str, &updated_on])
+            }
+            None => statement.execute(client, types, &[&slot, &status_str, &updated_on]),
         };
 
         match result {
@@ -754,7 +1311,9 @@ impl SimplePostgresClient {
 
     pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
         info!("Creating SimplePostgresClient...");
+        Self::check_table_names(config)?;
         let mut client = Self::connect_to_db(config)?;
+        Self::check_write_version_type(&mut client, config)?;
         let bulk_account_insert_stmt =
             Self::build_bulk_account_insert_statement(&mut client, config)?;
         let update_account_stmt = Self::build_single_account_upsert_statement(&mut client, config)?;
@@ -765,17 +1324,67 @@ impl SimplePostgresClient {
             Self::build_slot_upsert_statement_without_parent(&mut client, config)?;
         let update_transaction_log_stmt =
             Self::build_transaction_info_upsert_statement(&mut client, config)?;
+        let transaction_encoder = match config.compress_transaction_data {
+            Some(DataCompression::Zstd | DataCompression::Lz4) => {
+                Some(TransactionParamTypes::load(&mut client, config)?)
+            }
+            _ => None,
+        };
         let update_block_metadata_stmt =
             Self::build_block_metadata_upsert_statement(&mut client, config)?;
+        let insert_transaction_signatures_stmt =
+            if let Some(true) = config.store_transaction_signatures {
+                Some(Self::build_transaction_signatures_insert_statement(
+                    &mut client,
+                    config,
+                )?)
+            } else {
+                None
+            };
+        let insert_transaction_account_writes_stmt =
+            if let Some(true) = config.index_transaction_accounts {
+                Some(Self::build_transaction_account_writes_insert_statement(
+                    &mut client,
+                    config,
+                )?)
+            } else {
+                None
+            };
+        let instruction_idl = InstructionIdl::new(config)?;
+        let insert_transaction_instructions_stmt = if instruction_idl.is_some() {
+            Some(Self::build_transaction_instructions_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+        let update_slot_transaction_tally_stmt = if let Some(true) = config.tally_slot_transactions
+        {
+            Some(Self::build_slot_transaction_tally_update_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
 
         let batch_size = config
             .batch_size
             .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
 
+        let index_batch_size = Self::get_index_batch_size(config);
+        let batch_size_tuner = BatchSizeTuner::new(config, batch_size)?;
+
         let store_account_historical_data = config
             .store_account_historical_data
             .unwrap_or(DEFAULT_STORE_ACCOUNT_HISTORICAL_DATA);
 
+        let skip_account_audit = Self::skip_account_audit_during_startup(config);
+        if skip_account_audit {
+            Self::set_skip_account_audit(&mut client, true)?;
+        }
+
         let insert_account_audit_stmt = if store_account_historical_data {
             let stmt = Self::build_account_audit_insert_statement(&mut client, config)?;
             Some(stmt)
@@ -783,21 +1392,26 @@ impl SimplePostgresClient {
             None
         };
 
-        let bulk_insert_token_owner_index_stmt = if let Some(true) = config.index_token_owner {
+        // The statements and buffers of each token index are only created when it is enabled,
+        // so the table of the other index need not exist.
+        let index_token_owner = config.index_token_owner.unwrap_or(false);
+        let index_token_mint = config.index_token_mint.unwrap_or(false);
+
+        let bulk_insert_token_owner_index_stmt = if index_token_owner {
             let stmt = Self::build_bulk_token_owner_index_insert_statement(&mut client, config)?;
             Some(stmt)
         } else {
             None
         };
 
-        let bulk_insert_token_mint_index_stmt = if let Some(true) = config.index_token_mint {
+        let bulk_insert_token_mint_index_stmt = if index_token_mint {
             let stmt = Self::build_bulk_token_mint_index_insert_statement(&mut client, config)?;
             Some(stmt)
         } else {
             None
         };
 
-        let insert_token_owner_index_stmt = if let Some(true) = config.index_token_owner {
+        let insert_token_owner_index_stmt = if index_token_owner {
             Some(Self::build_single_token_owner_index_upsert_statement(
                 &mut client,
                 config,
@@ -806,7 +1420,7 @@ impl SimplePostgresClient {
             None
         };
 
-        let insert_token_mint_index_stmt = if let Some(true) = config.index_token_mint {
+        let insert_token_mint_index_stmt = if index_token_mint {
             Some(Self::build_single_token_mint_index_upsert_statement(
                 &mut client,
                 config,
@@ -815,10 +1429,150 @@ impl SimplePostgresClient {
             None
         };
 
+        let upsert_stake_account_index_stmt = if let Some(true) = config.index_stake_accounts {
+            Some(Self::build_stake_account_index_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let upsert_metaplex_metadata_index_stmt = if let Some(true) = config.index_metaplex_metadata
+        {
+            Some(Self::build_metaplex_metadata_index_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let upsert_token_delegate_index_stmt = if let Some(true) = config.index_token_delegate {
+            Some(Self::build_token_delegate_index_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let upsert_token_state_index_stmt = if let Some(true) = config.index_token_state {
+            Some(Self::build_token_state_index_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let upsert_program_link_stmt = if let Some(true) = config.index_programs {
+            Some(Self::build_program_link_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let insert_account_close_stmt = if Self::get_track_account_closes(config) {
+            Some(Self::build_account_close_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let select_existing_accounts_stmt =
+            if let Some(true) = config.skip_upsert_existing_accounts_at_startup {
+                Some(Self::build_existing_accounts_select_statement(
+                    &mut client,
+                    config,
+                )?)
+            } else {
+                None
+            };
+
+        let upsert_checkpoint_stmt = if let Some(true) = config.track_slot_checkpoint {
+            Some(Self::build_checkpoint_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let insert_batch_log_stmt = if let Some(true) = config.log_batches {
+            Some(Self::build_batch_log_insert_statement(&mut client, config)?)
+        } else {
+            None
+        };
+
+        let upsert_slot_timing_stmt = if let Some(true) = config.track_slot_timing {
+            Some(Self::build_slot_timing_upsert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let update_dead_slots_stmt = if let Some(true) = config.track_dead_slots {
+            Some(Self::build_dead_slots_update_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let insert_placeholder_block_stmt = if let Some(true) = config.insert_placeholder_blocks {
+            Some(Self::build_placeholder_block_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let insert_account_staging_stmt = if Self::is_staging_table_mode(config) {
+            Some(Self::build_account_staging_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let insert_account_data_chunk_stmt = if config.account_data_chunk_threshold.is_some() {
+            Some(Self::build_account_data_chunk_insert_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
+        let update_account_slot_status_stmt = if let Some(true) = config.record_account_slot_status
+        {
+            Some(Self::build_account_slot_status_update_statement(
+                &mut client,
+                config,
+            )?)
+        } else {
+            None
+        };
+
         info!("Created SimplePostgresClient.");
         Ok(Self {
             batch_size,
+            index_batch_size,
             pending_account_updates: Vec::with_capacity(batch_size),
+            batch_splitter: AccountBatchSplitter::new(config),
+            account_columns: AccountColumns::new(config)?,
+            transaction_columns: TransactionColumns::new(config),
+            transaction_encoder,
             client: Mutex::new(PostgresSqlClientWrapper {
                 client,
                 update_account_stmt,
@@ -826,17 +1580,75 @@ impl SimplePostgresClient {
                 update_slot_with_parent_stmt,
                 update_slot_without_parent_stmt,
                 update_transaction_log_stmt,
+                insert_transaction_signatures_stmt,
+                insert_transaction_account_writes_stmt,
+                insert_transaction_instructions_stmt,
                 update_block_metadata_stmt,
+                update_slot_transaction_tally_stmt,
                 insert_account_audit_stmt,
                 insert_token_owner_index_stmt,
                 insert_token_mint_index_stmt,
                 bulk_insert_token_owner_index_stmt,
                 bulk_insert_token_mint_index_stmt,
+                upsert_stake_account_index_stmt,
+                upsert_metaplex_metadata_index_stmt,
+                upsert_token_delegate_index_stmt,
+                upsert_token_state_index_stmt,
+                upsert_slot_timing_stmt,
+                update_dead_slots_stmt,
+                insert_placeholder_block_stmt,
+                upsert_program_link_stmt,
+                insert_account_close_stmt,
+                select_existing_accounts_stmt,
+                upsert_checkpoint_stmt,
+                insert_batch_log_stmt,
+                insert_account_staging_stmt,
+                insert_account_data_chunk_stmt,
+                update_account_slot_status_stmt,
+                statement_cache: PreparedStatementCache::new(
+                    config
+                        .max_prepared_statements
+                        .unwrap_or(DEFAULT_MAX_PREPARED_STATEMENTS),
+                    Self::use_prepared_statements(config),
+                ),
+                types: Mutex::default(),
             }),
-            index_token_owner: config.index_token_owner.unwrap_or_default(),
-            index_token_mint: config.index_token_mint.unwrap_or(false),
-            pending_token_owner_index: Vec::with_capacity(batch_size),
-            pending_token_mint_index: Vec::with_capacity(batch_size),
+            index_token_owner,
+            index_token_mint,
+            pending_token_owner_index: if index_token_owner {
+                Vec::with_capacity(index_batch_size)
+            } else {
+                Vec::default()
+            },
+            pending_token_mint_index: if index_token_mint {
+                Vec::with_capacity(index_batch_size)
+            } else {
+                Vec::default()
+            },
+            bulk_insert_latency: LatencyHistogram::new(
+                "geyser-plugin-postgres-bulk-insert-account-latency",
+            ),
+            upsert_latency: LatencyHistogram::new("geyser-plugin-postgres-upsert-account-latency"),
+            statement_reprepare: StatementReprepare::new(config),
+            skip_account_audit,
+            staged_slots: HashSet::default(),
+            staging_unlogged: matches!(config.staging_unlogged, Some(true)),
+            block_time_bucket_secs: Self::get_block_time_bucket_secs(config),
+            adaptive_batching: AdaptiveBatching::new(config),
+            batch_size_tuner,
+            sort_batch_rows: matches!(config.sort_batch_rows, Some(true)),
+            isolate_failing_rows: matches!(config.isolate_failing_rows, Some(true)),
+            batch_sequence: None,
+            instruction_idl,
+            table_names: config.table_names.clone(),
+            slot_clock: matches!(config.record_write_lag, Some(true))
+                .then(|| Arc::new(SlotClock::default())),
+            slot_statuses: matches!(config.record_account_slot_status, Some(true))
+                .then(|| Arc::new(SlotStatuses::default())),
+            existence_bloom: None,
+            audit_retention: None,
+            recent_account_window_slots: Self::get_recent_account_window_slots(config),
+            lamport_fast_path: LamportFastPath::new(config, &AccountColumns::new(config)?)?,
             slots_at_startup: HashSet::default(),
         })
     }
@@ -854,8 +1666,15 @@ impl PostgresClient for SimplePostgresClient {
             bs58::encode(account.owner()).into_string(),
             account.slot,
         );
+        self.insert_account_data_chunks(&account)?;
+        if let Some(target_table) = account.target_table.clone() {
+            if is_startup {
+                self.slots_at_startup.insert(account.slot as u64);
+            }
+            return self.upsert_routed_account(&account, &target_table, is_startup);
+        }
         if !is_startup {
-            return self.upsert_account(&account);
+            return self.update_account_adaptive(account);
         }
         self.slots_at_startup.insert(account.slot as u64);
         self.insert_accounts_in_batch(account)
@@ -869,18 +1688,15 @@ impl PostgresClient for SimplePostgresClient {
     ) -> Result<(), GeyserPluginError> {
         info!("Updating slot {:?} at with status {:?}", slot, status);
 
-        let client = self.client.get_mut().unwrap();
-
-        let statement = match parent {
-            Some(_) => &client.update_slot_with_parent_stmt,
-            None => &client.update_slot_without_parent_stmt,
-        };
-
-        Self::upsert_slot_status_internal(slot, parent, status, &mut client.client, statement)
+        self.update_slot_metadata(slot, parent, status)?;
+        self.update_slot_accounts(slot, status)
     }
 
     fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
-        self.flush_buffered_writes()
+        self.flush_buffered_writes()?;
+        self.existence_bloom = None;
+        self.start_recent_accounts()?;
+        self.resume_account_audit()
     }
 
     fn log_transaction(
@@ -907,6 +1723,10 @@ struct UpdateSlotRequest {
     slot: u64,
     parent: Option<u64>,
     slot_status: SlotStatus,
+    /// The time at which the plugin was notified of the slot status.
+    observed_on: NaiveDateTime,
+    /// The transactions tallied for the slot, set when the slot is rooted.
+    transaction_tally: Option<SlotTransactionTally>,
 }
 
 pub struct UpdateBlockMetadataRequest {
@@ -921,14 +1741,45 @@ enum DbWorkItem {
     UpdateBlockMetadata(Box<UpdateBlockMetadataRequest>),
 }
 
+/// A work item with the sequence of its dispatch, when the checkpoint is tracked.
+struct DbWork {
+    sequence: Option<u64>,
+    item: DbWorkItem,
+}
+
 impl PostgresClientWorker {
-    fn new(config: GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
-        let result = SimplePostgresClient::new(&config);
+    fn new(
+        config: GeyserPluginPostgresConfig,
+        batch_sequence: Option<Arc<BatchSequence>>,
+        slot_clock: Option<Arc<SlotClock>>,
+        slot_statuses: Option<Arc<SlotStatuses>>,
+        existence_bloom: Option<Arc<ExistenceBloom>>,
+        audit_retention: Option<Arc<AuditRetention>>,
+    ) -> Result<Self, GeyserPluginError> {
+        let metadata_client = match SimplePostgresClient::metadata_config(&config) {
+            Some(metadata_config) => {
+                let mut metadata_client = SimplePostgresClient::new(&metadata_config)?;
+                metadata_client.set_slot_clock(slot_clock.clone());
+                Some(metadata_client)
+            }
+            None => None,
+        };
+        let result = SimplePostgresClient::new(&config);
         match result {
-            Ok(client) => Ok(PostgresClientWorker {
-                client,
-                is_startup_done: false,
-            }),
+            Ok(mut client) => {
+                client.set_batch_sequence(batch_sequence);
+                client.set_slot_clock(slot_clock);
+                client.set_slot_statuses(slot_statuses);
+                client.set_existence_bloom(existence_bloom);
+                client.set_audit_retention(audit_retention);
+                Ok(PostgresClientWorker {
+                    client,
+                    metadata_client,
+                    is_startup_done: false,
+                    held_sequences: Vec::default(),
+                    flushed_generation: 0,
+                })
+            }
             Err(err) => {
                 error!("Error in creating SimplePostgresClient: {}", err);
                 Err(err)
@@ -936,15 +1787,157 @@ impl PostgresClientWorker {
         }
     }
 
+    /// The number of account updates buffered by the client but not yet written to the database.
+    fn pending_count(&self) -> usize {
+        self.client.pending_account_updates.len()
+    }
+
+    /// The client writing the slots, blocks and transactions.
+    fn metadata_client(&mut self) -> &mut SimplePostgresClient {
+        self.metadata_client.as_mut().unwrap_or(&mut self.client)
+    }
+
+    /// Write the work item, returns whether any of its writes failed.
+    fn handle_work_item(&mut self, work: DbWorkItem, panic_on_db_errors: bool) -> bool {
+        let mut failed = false;
+        match work {
+            DbWorkItem::UpdateAccount(request) => {
+                if let Err(err) = self
+                    .client
+                    .update_account(request.account, request.is_startup)
+                {
+                    error!("Failed to update account: ({})", err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                    failed = true;
+                }
+            }
+            DbWorkItem::UpdateSlot(request) => {
+                let result = match &mut self.metadata_client {
+                    Some(metadata_client) => metadata_client
+                        .update_slot_metadata(request.slot, request.parent, request.slot_status)
+                        .and_then(|_| {
+                            self.client
+                                .update_slot_accounts(request.slot, request.slot_status)
+                        }),
+                    None => self.client.update_slot_status(
+                        request.slot,
+                        request.parent,
+                        request.slot_status,
+                    ),
+                };
+                if let Err(err) = result {
+                    error!("Failed to update slot: ({})", err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                    failed = true;
+                }
+                if let Err(err) = self.metadata_client().update_slot_timing(
+                    request.slot,
+                    request.slot_status,
+                    request.observed_on,
+                ) {
+                    error!("Failed to update slot timing: ({})", err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                    failed = true;
+                }
+                if let Some(tally) = &request.transaction_tally {
+                    if let Err(err) = self
+                        .metadata_client()
+                        .update_slot_transaction_tally(request.slot, tally)
+                    {
+                        error!("Failed to update slot transaction tally: ({})", err);
+                        if panic_on_db_errors {
+                            abort();
+                        }
+                        failed = true;
+                    }
+                }
+            }
+            DbWorkItem::LogTransaction(transaction_log_info) => {
+                if let Err(err) = self
+                    .metadata_client()
+                    .log_transaction(*transaction_log_info)
+                {
+                    error!("Failed to update transaction: ({})", err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                    failed = true;
+                }
+            }
+            DbWorkItem::UpdateBlockMetadata(block_info) => {
+                if let Err(err) = self.metadata_client().update_block_metadata(*block_info) {
+                    error!("Failed to update block metadata: ({})", err);
+                    if panic_on_db_errors {
+                        abort();
+                    }
+                    failed = true;
+                }
+            }
+        }
+        failed
+    }
+
+    /// Whether the work item is to be written, waiting while the writes are paused by the
+    /// ingestion control or the circuit breaker is open.
+    fn admit_work(
+        ingestion_control: &Option<Arc<IngestionControl>>,
+        circuit_breaker: &Option<Arc<CircuitBreaker>>,
+        exit_worker: &AtomicBool,
+    ) -> bool {
+        if ingestion_control.is_none() && circuit_breaker.is_none() {
+            return true;
+        }
+        loop {
+            let admission = match ingestion_control.as_ref().map(|control| control.admit()) {
+                None | Some(WriteAdmission::Allow) => match circuit_breaker {
+                    Some(circuit_breaker) => circuit_breaker.admit(),
+                    None => WriteAdmission::Allow,
+                },
+                Some(admission) => admission,
+            };
+            match admission {
+                WriteAdmission::Allow => return true,
+                WriteAdmission::Drop => return false,
+                WriteAdmission::Wait => {
+                    if exit_worker.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    sleep(Duration::from_millis(CIRCUIT_BREAKER_WAIT_MS));
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn do_work(
         &mut self,
-        receiver: Receiver<DbWorkItem>,
+        receiver: Receiver<DbWork>,
         exit_worker: Arc<AtomicBool>,
         is_startup_done: Arc<AtomicBool>,
         startup_done_count: Arc<AtomicUsize>,
+        inflight_count: Arc<AtomicUsize>,
+        inflight_limit: Option<(Arc<InflightLimit>, usize)>,
+        circuit_breaker: Option<Arc<CircuitBreaker>>,
+        ingestion_control: Option<Arc<IngestionControl>>,
+        checkpoint: Option<Arc<SlotCheckpoint>>,
         panic_on_db_errors: bool,
     ) -> Result<(), GeyserPluginError> {
         while !exit_worker.load(Ordering::Relaxed) {
+            if let Some((inflight_limit, worker)) = &inflight_limit {
+                inflight_limit.hold(*worker, self.pending_count());
+            }
+            self.flush_on_request(
+                &ingestion_control,
+                &inflight_count,
+                &checkpoint,
+                panic_on_db_errors,
+            );
             let mut measure = Measure::start("geyser-plugin-postgres-worker-recv");
             let work = receiver.recv_timeout(Duration::from_millis(500));
             measure.stop();
@@ -954,59 +1947,97 @@ impl PostgresClientWorker {
                 100000,
                 100000
             );
+            let pending_before = self.pending_count();
             match work {
-                Ok(work) => match work {
-                    DbWorkItem::UpdateAccount(request) => {
-                        if let Err(err) = self
-                            .client
-                            .update_account(request.account, request.is_startup)
-                        {
-                            error!("Failed to update account: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
-                    }
-                    DbWorkItem::UpdateSlot(request) => {
-                        if let Err(err) = self.client.update_slot_status(
-                            request.slot,
-                            request.parent,
-                            request.slot_status,
-                        ) {
-                            error!("Failed to update slot: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
+                Ok(DbWork { sequence, item }) => {
+                    let is_account_update = matches!(item, DbWorkItem::UpdateAccount(_));
+                    if let Some((inflight_limit, worker)) = &inflight_limit {
+                        inflight_limit.receive(
+                            *worker,
+                            inflight_limit.units(&item),
+                            pending_before,
+                        );
                     }
-                    DbWorkItem::LogTransaction(transaction_log_info) => {
-                        if let Err(err) = self.client.log_transaction(*transaction_log_info) {
-                            error!("Failed to update transaction: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
+                    // A dropped work item is not written.
+                    let mut failed = true;
+                    if Self::admit_work(&ingestion_control, &circuit_breaker, &exit_worker) {
+                        let mut measure = Measure::start("geyser-plugin-postgres-worker-write");
+                        failed = self.handle_work_item(item, panic_on_db_errors);
+                        measure.stop();
+                        if let Some(circuit_breaker) = &circuit_breaker {
+                            // An account update buffered for a bulk insert is not written yet,
+                            // the flush writing it records the outcome.
+                            if is_account_update && self.pending_count() > pending_before {
+                                circuit_breaker.record_buffered();
+                            } else {
+                                circuit_breaker.record(failed, measure.as_us());
                             }
                         }
                     }
-                    DbWorkItem::UpdateBlockMetadata(block_info) => {
-                        if let Err(err) = self.client.update_block_metadata(*block_info) {
-                            error!("Failed to update block metadata: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
+                    if let Some(checkpoint) = &checkpoint {
+                        self.complete_work(
+                            checkpoint,
+                            sequence,
+                            is_account_update,
+                            failed,
+                            panic_on_db_errors,
+                        );
                     }
-                },
+                }
                 Err(err) => match err {
                     RecvTimeoutError::Timeout => {
                         if !self.is_startup_done && is_startup_done.load(Ordering::Relaxed) {
-                            if let Err(err) = self.client.notify_end_of_startup() {
+                            let result = self.client.notify_end_of_startup();
+                            if let Err(err) = &result {
                                 error!("Error in notifying end of startup: ({})", err);
                                 if panic_on_db_errors {
                                     abort();
                                 }
                             }
+                            if let Some(checkpoint) = &checkpoint {
+                                self.complete_work(
+                                    checkpoint,
+                                    None,
+                                    false,
+                                    result.is_err(),
+                                    panic_on_db_errors,
+                                );
+                            }
+                            inflight_count.fetch_sub(
+                                pending_before - self.pending_count(),
+                                Ordering::Relaxed,
+                            );
                             self.is_startup_done = true;
                             startup_done_count.fetch_add(1, Ordering::Relaxed);
+                        } else if self.is_startup_done {
+                            // Write the accounts buffered in the adaptive bulk mode while idle.
+                            let mut measure = Measure::start("geyser-plugin-postgres-worker-write");
+                            let result = self.client.flush_adaptive_batch();
+                            measure.stop();
+                            if let (Some(circuit_breaker), true) =
+                                (&circuit_breaker, pending_before > self.pending_count())
+                            {
+                                circuit_breaker.record(result.is_err(), measure.as_us());
+                            }
+                            if let Err(err) = &result {
+                                error!("Error in flushing the accounts batch: ({})", err);
+                                if panic_on_db_errors {
+                                    abort();
+                                }
+                            }
+                            if let Some(checkpoint) = &checkpoint {
+                                self.complete_work(
+                                    checkpoint,
+                                    None,
+                                    false,
+                                    result.is_err(),
+                                    panic_on_db_errors,
+                                );
+                            }
+                            inflight_count.fetch_sub(
+                                pending_before - self.pending_count(),
+                                Ordering::Relaxed,
+                            );
                         }
 
                         continue;
@@ -1020,8 +2051,12 @@ impl PostgresClientWorker {
                     }
                 },
             }
+
+            // Accounts buffered for a bulk insert stay in flight until the batch is written.
+            let completed = pending_before + 1 - self.pending_count();
+            inflight_count.fetch_sub(completed, Ordering::Relaxed);
         }
-        Ok(())
+        self.client.flush_adaptive_batch()
     }
 }
 pub struct ParallelPostgresClient {
@@ -1030,48 +2065,147 @@ pub struct ParallelPostgresClient {
     is_startup_done: Arc<AtomicBool>,
     startup_done_count: Arc<AtomicUsize>,
     initialized_worker_count: Arc<AtomicUsize>,
-    sender: Sender<DbWorkItem>,
+    sender: Sender<DbWork>,
+    /// The buffer of the work items queued for the workers.
+    write_buffer: WriteBuffer,
+    /// The limiter of the rate of the account updates, when enabled.
+    rate_limiter: Option<AccountRateLimiter>,
     last_report: AtomicInterval,
-    transaction_write_version: AtomicU64,
+    /// The number of work items dispatched but not yet written to the database.
+    inflight_count: Arc<AtomicUsize>,
+    /// The limit of the batches in flight per worker, when enabled.
+    inflight_limit: Option<Arc<InflightLimit>>,
+    /// The circuit breaker shared by the workers, when enabled.
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// The queue of the serialization threads, when enabled.
+    serialization_sender: Option<Sender<DbWork>>,
+    serializers: Vec<JoinHandle<()>>,
+    /// The merger of the staging tables, in the staging table mode.
+    staging_merger: Option<StagingMerger>,
+    /// The heartbeat, when heartbeat_interval_secs is set.
+    heartbeat: Option<Heartbeat>,
+    /// The transactions tallied per slot until it is rooted, when tally_slot_transactions is
+    /// set.
+    slot_transaction_tallies: Option<HashMap<u64, SlotTransactionTally>>,
+    /// The tracking of the work items written, when the checkpoint is tracked.
+    checkpoint: Option<Arc<SlotCheckpoint>>,
+    /// The control socket, when enabled.
+    control_socket: Option<ControlSocket>,
+    /// The distribution of the sizes of the account data queued.
+    data_size_histogram: DataSizeHistogram,
 }
 
 impl ParallelPostgresClient {
+    /// The delay before the worker connects to the database, staggering the connections of
+    /// the workers by connection_stagger_ms, with a random jitter of up to as much.
+    fn connection_stagger_delay(
+        config: &GeyserPluginPostgresConfig,
+        worker_index: usize,
+    ) -> Option<Duration> {
+        match config.connection_stagger_ms {
+            Some(stagger_ms) if stagger_ms > 0 && worker_index > 0 => {
+                let jitter_ms = rand::thread_rng().gen_range(0, stagger_ms);
+                Some(Duration::from_millis(
+                    worker_index as u64 * stagger_ms + jitter_ms,
+                ))
+            }
+            _ => None,
+        }
+    }
+
     pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
         info!("Creating ParallelPostgresClient...");
-        let (sender, receiver) = bounded(MAX_ASYNC_REQUESTS);
+        let rate_limiter = AccountRateLimiter::new(config)?;
+        let (write_buffer, sender, receiver) = WriteBuffer::new(config);
         let exit_worker = Arc::new(AtomicBool::new(false));
         let mut workers = Vec::default();
         let is_startup_done = Arc::new(AtomicBool::new(false));
         let startup_done_count = Arc::new(AtomicUsize::new(0));
-        let worker_count = config.threads.unwrap_or(DEFAULT_THREADS_COUNT);
+        let worker_count = config
+            .db_writer_threads
+            .or(config.threads)
+            .unwrap_or(DEFAULT_THREADS_COUNT);
         let initialized_worker_count = Arc::new(AtomicUsize::new(0));
+        let inflight_count = Arc::new(AtomicUsize::new(0));
+        let batch_size = config
+            .batch_size
+            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
+        let inflight_limit = InflightLimit::new(config, batch_size, worker_count).map(Arc::new);
+        let circuit_breaker = config
+            .circuit_breaker
+            .as_ref()
+            .map(|circuit_breaker| Arc::new(CircuitBreaker::new(circuit_breaker)));
+        let ingestion_control = IngestionControl::new(config).map(Arc::new);
+        let checkpoint = SlotCheckpoint::new(config).map(Arc::new);
+        let batch_sequence = BatchSequence::load(config)?;
+        let slot_clock =
+            matches!(config.record_write_lag, Some(true)).then(|| Arc::new(SlotClock::default()));
+        let slot_statuses = matches!(config.record_account_slot_status, Some(true))
+            .then(|| Arc::new(SlotStatuses::default()));
+        let existence_bloom = ExistenceBloom::load(config)?;
+        let audit_retention = AuditRetention::new(config)?;
         for i in 0..worker_count {
             let cloned_receiver = receiver.clone();
             let exit_clone = exit_worker.clone();
             let is_startup_done_clone = is_startup_done.clone();
             let startup_done_count_clone = startup_done_count.clone();
             let initialized_worker_count_clone = initialized_worker_count.clone();
+            let inflight_count_clone = inflight_count.clone();
+            let inflight_limit_clone = inflight_limit.clone();
+            let circuit_breaker_clone = circuit_breaker.clone();
+            let ingestion_control_clone = ingestion_control.clone();
+            let checkpoint_clone = checkpoint.clone();
+            let batch_sequence_clone = batch_sequence.clone();
+            let slot_clock_clone = slot_clock.clone();
+            let slot_statuses_clone = slot_statuses.clone();
+            let existence_bloom_clone = existence_bloom.clone();
+            let audit_retention_clone = audit_retention.clone();
+            let connection_stagger_delay = Self::connection_stagger_delay(config, i);
             let config = config.clone();
             let worker = Builder::new()
                 .name(format!("worker-{}", i))
                 .spawn(move || -> Result<(), GeyserPluginError> {
+                    if let Some(delay) = connection_stagger_delay {
+                        sleep(delay);
+                    }
                     let panic_on_db_errors = *config
                         .panic_on_db_errors
                         .as_ref()
                         .unwrap_or(&DEFAULT_PANIC_ON_DB_ERROR);
-                    let result = PostgresClientWorker::new(config);
-
-                    match result {
+                    // The first worker records the checksums of the statements it prepared.
+                    let statement_checksums_config = (i == 0
+                        && matches!(config.record_statement_checksums, Some(true)))
+                    .then(|| config.clone());
+                    let result = PostgresClientWorker::new(
+                        config,
+                        batch_sequence_clone,
+                        slot_clock_clone,
+                        slot_statuses_clone,
+                        existence_bloom_clone,
+                        audit_retention_clone,
+                    );
+
+                    let result = match result {
                         Ok(mut worker) => {
+                            if let Some(config) = &statement_checksums_config {
+                                // The errors are logged, the checksums are not required.
+                                let _ = worker.client.check_statement_checksums(config);
+                            }
                             initialized_worker_count_clone.fetch_add(1, Ordering::Relaxed);
                             worker.do_work(
                                 cloned_receiver,
                                 exit_clone,
                                 is_startup_done_clone,
                                 startup_done_count_clone,
+                                inflight_count_clone,
+                                inflight_limit_clone
+                                    .clone()
+                                    .map(|inflight_limit| (inflight_limit, i)),
+                                circuit_breaker_clone,
+                                ingestion_control_clone,
+                                checkpoint_clone,
                                 panic_on_db_errors,
-                            )?;
-                            Ok(())
+                            )
                         }
                         Err(err) => {
                             error!("Error when making connection to database: ({})", err);
@@ -1080,13 +2214,54 @@ impl ParallelPostgresClient {
                             }
                             Err(err)
                         }
+                    };
+                    if let Some(inflight_limit) = &inflight_limit_clone {
+                        inflight_limit.stop(i);
                     }
+                    result
                 })
                 .unwrap();
 
             workers.push(worker);
         }
 
+        let (serialization_sender, serializers) = match config.serialization_threads {
+            Some(serialization_threads) if serialization_threads > 0 => {
+                let (serialization_sender, serialization_receiver) = bounded(MAX_ASYNC_REQUESTS);
+                let serializers = postgres_client_serialization::spawn_serializers(
+                    config,
+                    serialization_threads,
+                    serialization_receiver,
+                    sender.clone(),
+                    exit_worker.clone(),
+                )?;
+                (Some(serialization_sender), serializers)
+            }
+            _ => (None, Vec::default()),
+        };
+
+        let staging_merger = if SimplePostgresClient::is_staging_table_mode(config) {
+            Some(StagingMerger::new(config)?)
+        } else {
+            None
+        };
+        let heartbeat = Heartbeat::new(config)?;
+
+        let control_socket = match (&config.control_socket, &ingestion_control) {
+            (Some(path), Some(ingestion_control)) => Some(ControlSocket::start(
+                path,
+                ControlTargets {
+                    ingestion_control: ingestion_control.clone(),
+                    worker_count,
+                    initialized_worker_count: initialized_worker_count.clone(),
+                    is_startup_done: is_startup_done.clone(),
+                    inflight_count: inflight_count.clone(),
+                    sender: sender.clone(),
+                },
+            )?),
+            _ => None,
+        };
+
         info!("Created ParallelPostgresClient.");
         Ok(Self {
             last_report: AtomicInterval::default(),
@@ -1096,12 +2271,105 @@ impl ParallelPostgresClient {
             startup_done_count,
             initialized_worker_count,
             sender,
-            transaction_write_version: AtomicU64::default(),
+            write_buffer,
+            rate_limiter,
+            inflight_count,
+            inflight_limit,
+            circuit_breaker,
+            serialization_sender,
+            serializers,
+            staging_merger,
+            heartbeat,
+            slot_transaction_tallies: matches!(config.tally_slot_transactions, Some(true))
+                .then(HashMap::default),
+            checkpoint,
+            control_socket,
+            data_size_histogram: DataSizeHistogram::default(),
         })
     }
 
-    pub fn join(&mut self) -> thread::Result<()> {
+    /// Send the work item to the workers, blocking while the in-flight limit is reached.
+    fn send_work_item(&self, wrk_item: DbWorkItem) -> Result<(), SendError<DbWork>> {
+        let inflight_units = match &self.inflight_limit {
+            Some(inflight_limit) => {
+                loop {
+                    match inflight_limit.is_reached() {
+                        Some(true) if !self.exit_worker.load(Ordering::Relaxed) => {
+                            sleep(Duration::from_millis(INFLIGHT_BACKPRESSURE_WAIT_MS))
+                        }
+                        Some(_) => break,
+                        // No worker is left to free the queue.
+                        None => {
+                            return Err(SendError(DbWork {
+                                sequence: None,
+                                item: wrk_item,
+                            }))
+                        }
+                    }
+                }
+                let inflight_units = inflight_limit.units(&wrk_item);
+                inflight_limit.queue(inflight_units);
+                inflight_units
+            }
+            None => 0,
+        };
+        let sequence = self.checkpoint.as_ref().map(|checkpoint| {
+            let rooted_slot = match &wrk_item {
+                DbWorkItem::UpdateSlot(request) if request.slot_status == SlotStatus::Rooted => {
+                    Some(request.slot)
+                }
+                _ => None,
+            };
+            checkpoint.dispatch(rooted_slot)
+        });
+        self.inflight_count.fetch_add(1, Ordering::Relaxed);
+        let work = DbWork {
+            sequence,
+            item: wrk_item,
+        };
+        // The transactions are encoded by the serialization threads first, when enabled.
+        let result = match (&work.item, &self.serialization_sender) {
+            (DbWorkItem::LogTransaction(_), Some(serialization_sender)) => serialization_sender
+                .send(work)
+                .map(|_| BufferedWrite::Queued),
+            _ => self.write_buffer.send(&self.sender, work),
+        };
+        match result {
+            Ok(BufferedWrite::Queued) => Ok(()),
+            // A dropped work item is not written.
+            Ok(BufferedWrite::Dropped) => {
+                self.inflight_count.fetch_sub(1, Ordering::Relaxed);
+                if let Some(inflight_limit) = &self.inflight_limit {
+                    inflight_limit.unqueue(inflight_units);
+                }
+                if let Some(checkpoint) = &self.checkpoint {
+                    checkpoint.stop(sequence);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                self.inflight_count.fetch_sub(1, Ordering::Relaxed);
+                if let Some(inflight_limit) = &self.inflight_limit {
+                    inflight_limit.unqueue(inflight_units);
+                }
+                if let Some(checkpoint) = &self.checkpoint {
+                    checkpoint.stop(sequence);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+impl NotificationSink for ParallelPostgresClient {
+    fn join(&mut self) -> thread::Result<()> {
         self.exit_worker.store(true, Ordering::Relaxed);
+        if let Some(control_socket) = &mut self.control_socket {
+            control_socket.join()?;
+        }
+        for serializer in self.serializers.drain(..) {
+            serializer.join()?;
+        }
         while !self.workers.is_empty() {
             let worker = self.workers.pop();
             if worker.is_none() {
@@ -1114,44 +2382,70 @@ impl ParallelPostgresClient {
             }
         }
 
+        if let Some(staging_merger) = &mut self.staging_merger {
+            staging_merger.join()?;
+        }
+        if let Some(heartbeat) = &mut self.heartbeat {
+            heartbeat.join()?;
+        }
+
         Ok(())
     }
 
-    pub fn update_account(
+    fn update_account(
         &mut self,
-        account: &ReplicaAccountInfo,
-        slot: u64,
+        account: DbAccountInfo,
         is_startup: bool,
     ) -> Result<(), GeyserPluginError> {
         if self.last_report.should_update(30000) {
             datapoint_debug!(
                 "postgres-plugin-stats",
                 ("message-queue-length", self.sender.len() as i64, i64),
+                (
+                    "serialization-queue-length",
+                    self.serialization_sender
+                        .as_ref()
+                        .map(|serialization_sender| serialization_sender.len())
+                        .unwrap_or_default() as i64,
+                    i64
+                ),
+                (
+                    "inflight-count",
+                    self.inflight_count.load(Ordering::Relaxed) as i64,
+                    i64
+                ),
+                (
+                    "circuit-breaker-state",
+                    self.circuit_breaker
+                        .as_ref()
+                        .map(|circuit_breaker| circuit_breaker.state_code())
+                        .unwrap_or_default(),
+                    i64
+                ),
             );
         }
-        let mut measure = Measure::start("geyser-plugin-posgres-create-work-item");
+        self.write_buffer.maybe_report(&self.sender);
+        // The snapshot restored at startup is not limited, so the startup is not slowed down
+        // nor left with gaps.
+        if let (Some(rate_limiter), false) = (&mut self.rate_limiter, is_startup) {
+            if !rate_limiter.admit() {
+                return Ok(());
+            }
+        }
+        self.data_size_histogram.record(account.data.len());
+        let pubkey = bs58::encode(&account.pubkey).into_string();
         let wrk_item = DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
-            account: DbAccountInfo::new(account, slot),
+            account,
             is_startup,
         }));
 
-        measure.stop();
-
-        inc_new_counter_debug!(
-            "geyser-plugin-posgres-create-work-item-us",
-            measure.as_us() as usize,
-            100000,
-            100000
-        );
-
         let mut measure = Measure::start("geyser-plugin-posgres-send-msg");
 
-        if let Err(err) = self.sender.send(wrk_item) {
+        if let Err(err) = self.send_work_item(wrk_item) {
             return Err(GeyserPluginError::AccountsUpdateError {
                 msg: format!(
                     "Failed to update the account {:?}, error: {:?}",
-                    bs58::encode(account.pubkey()).into_string(),
-                    err
+                    pubkey, err
                 ),
             });
         }
@@ -1167,20 +2461,26 @@ impl ParallelPostgresClient {
         Ok(())
     }
 
-    pub fn update_slot_status(
+    fn update_slot_status(
         &mut self,
         slot: u64,
         parent: Option<u64>,
         status: SlotStatus,
     ) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self
-            .sender
-            .send(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
-                slot,
-                parent,
-                slot_status: status,
-            })))
-        {
+        if let Some(heartbeat) = &self.heartbeat {
+            heartbeat.observe_slot(slot);
+        }
+        let transaction_tally = match status {
+            SlotStatus::Rooted => self.take_rooted_slot_tally(slot),
+            _ => None,
+        };
+        if let Err(err) = self.send_work_item(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot,
+            parent,
+            slot_status: status,
+            observed_on: Utc::now().naive_utc(),
+            transaction_tally,
+        }))) {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the slot {:?}, error: {:?}", slot, err),
             });
@@ -1188,26 +2488,22 @@ impl ParallelPostgresClient {
         Ok(())
     }
 
-    pub fn update_block_metadata(
-        &mut self,
-        block_info: &ReplicaBlockInfo,
-    ) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self.sender.send(DbWorkItem::UpdateBlockMetadata(Box::new(
-            UpdateBlockMetadataRequest {
-                block_info: DbBlockInfo::from(block_info),
-            },
+    fn update_block_metadata(&mut self, block_info: DbBlockInfo) -> Result<(), GeyserPluginError> {
+        let slot = block_info.slot;
+        if let Err(err) = self.send_work_item(DbWorkItem::UpdateBlockMetadata(Box::new(
+            UpdateBlockMetadataRequest { block_info },
         ))) {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!(
                     "Failed to update the block metadata at slot {:?}, error: {:?}",
-                    block_info.slot, err
+                    slot, err
                 ),
             });
         }
         Ok(())
     }
 
-    pub fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
+    fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
         info!("Notifying the end of startup");
         // Ensure all items in the queue has been received by the workers
         while !self.sender.is_empty() {
@@ -1230,6 +2526,31 @@ impl ParallelPostgresClient {
         info!("Done with notifying the end of startup");
         Ok(())
     }
+
+    fn log_transaction(
+        &mut self,
+        transaction_log_info: LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let wrk_item = DbWorkItem::LogTransaction(Box::new(transaction_log_info));
+        if let Err(err) = self.send_work_item(wrk_item) {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!("Failed to update the transaction, error: {:?}", err),
+            });
+        }
+        Ok(())
+    }
+
+    fn tally_transaction(&mut self, slot: u64, is_successful: bool) {
+        let tally = match &mut self.slot_transaction_tallies {
+            Some(tallies) => tallies.entry(slot).or_default(),
+            None => return,
+        };
+        if is_successful {
+            tally.num_successful += 1;
+        } else {
+            tally.num_failed += 1;
+        }
+    }
 }
 
 pub struct PostgresClientBuilder {}
@@ -1247,3 +2568,670 @@ impl PostgresClientBuilder {
         SimplePostgresClient::new(config)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{inline_spl_token, inline_stake_program},
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+        std::sync::atomic::AtomicUsize,
+    };
+
+    /// The transaction built for the tests of the sinks outside of the module.
+    pub(crate) use super::postgres_client_transaction::tests::build_test_log_transaction_request;
+
+    /// The PostgreSQL database used by the tests, the same one the integration test in
+    /// tests/test_postgres_plugin.rs expects. It can be overridden with POSTGRES_TEST_CONNECTION_STR.
+    const DEFAULT_TEST_CONNECTION_STR: &str =
+        "host=localhost user=solana password=solana port=5432";
+
+    static TEST_SCHEMA_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    /// A private schema in the test database, created from scripts/create_schema.sql so that
+    /// the statement builders are exercised against the real tables. Dropped with the schema.
+    pub(crate) struct TestDatabase {
+        schema: String,
+        connection_str: String,
+        pub client: Client,
+    }
+
+    impl TestDatabase {
+        /// Create the schema, returns None when the test database is not available.
+        pub fn new() -> Option<Self> {
+            let connection_str = std::env::var("POSTGRES_TEST_CONNECTION_STR")
+                .unwrap_or_else(|_| DEFAULT_TEST_CONNECTION_STR.to_string());
+            let mut client = match Client::connect(&connection_str, NoTls) {
+                Ok(client) => client,
+                Err(err) => {
+                    info!("Skipping the test, no PostgreSQL database: {:?}", err);
+                    return None;
+                }
+            };
+
+            let schema = format!(
+                "plugin_test_{}_{}",
+                std::process::id(),
+                TEST_SCHEMA_COUNT.fetch_add(1, Ordering::Relaxed)
+            );
+            client
+                .batch_execute(&format!(
+                    "DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema}; SET search_path TO {schema};",
+                    schema = schema
+                ))
+                .unwrap();
+            client
+                .batch_execute(include_str!("../scripts/create_schema.sql"))
+                .unwrap();
+
+            Some(Self {
+                connection_str: format!("{} options='-c search_path={}'", connection_str, schema),
+                schema,
+                client,
+            })
+        }
+
+        /// Build the plugin config connecting to the schema, `overrides` are merged on top.
+        pub fn config(&self, overrides: serde_json::Value) -> GeyserPluginPostgresConfig {
+            let mut config = json!({
+                "connection_str": self.connection_str,
+                "threads": 1,
+                "batch_size": 2,
+            });
+            for (key, value) in overrides.as_object().unwrap() {
+                config[key] = value.clone();
+            }
+            serde_json::from_value(config).unwrap()
+        }
+
+        pub fn count(&mut self, table: &str) -> i64 {
+            self.client
+                .query_one(format!("SELECT COUNT(*) FROM {}", table).as_str(), &[])
+                .unwrap()
+                .get(0)
+        }
+    }
+
+    impl Drop for TestDatabase {
+        fn drop(&mut self) {
+            let _ = self
+                .client
+                .batch_execute(&format!("DROP SCHEMA IF EXISTS {} CASCADE", self.schema));
+        }
+    }
+
+    pub(crate) fn build_account(owner: &Pubkey, data: Vec<u8>, slot: i64) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: Pubkey::new_unique().as_ref().to_vec(),
+            lamports: 1_000_000,
+            owner: owner.as_ref().to_vec(),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot,
+            write_version: DbWriteVersion(1),
+            target_table: None,
+        }
+    }
+
+    /// Check the statement binds the parameters $1 to $`param_count` exactly, each of them once.
+    pub(crate) fn check_bulk_statement_placeholders(stmt: &str, param_count: usize) {
+        let mut placeholders: Vec<usize> = stmt
+            .split('$')
+            .skip(1)
+            .map(|token| {
+                let digits: String = token.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().unwrap()
+            })
+            .collect();
+        placeholders.sort_unstable();
+        assert_eq!(placeholders, (1..=param_count).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_get_connection_addresses() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+
+        let mut client = SimplePostgresClient::connect_to_db(&db.config(json!({}))).unwrap();
+        let (local_addr, remote_addr, backend_pid) =
+            SimplePostgresClient::get_connection_addresses(&mut client).unwrap();
+        // The addresses are those of the TCP connection, none over a unix socket.
+        let test_config: postgres::Config = db.connection_str.parse().unwrap();
+        match test_config.get_hosts().first() {
+            Some(postgres::config::Host::Tcp(_)) => {
+                let port = test_config.get_ports().first().copied().unwrap_or(5432);
+                assert!(local_addr.is_some());
+                assert!(remote_addr.unwrap().ends_with(&format!(":{}", port)));
+            }
+            _ => {
+                assert!(local_addr.is_none());
+                assert!(remote_addr.is_none());
+            }
+        }
+        assert!(backend_pid > 0);
+    }
+
+    #[test]
+    fn test_synchronous_commit() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+
+        let mut client =
+            SimplePostgresClient::connect_to_db(&db.config(json!({ "synchronous_commit": "off" })))
+                .unwrap();
+        let synchronous_commit: String = client
+            .query_one("SHOW synchronous_commit", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(synchronous_commit, "off");
+
+        assert!(SimplePostgresClient::connect_to_db(
+            &db.config(json!({ "synchronous_commit": "off; DROP TABLE account" }))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_connection_stagger_delay() {
+        let config = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(
+            ParallelPostgresClient::connection_stagger_delay(&config, 3),
+            None
+        );
+
+        let config = serde_json::from_value(json!({ "connection_stagger_ms": 50 })).unwrap();
+        assert_eq!(
+            ParallelPostgresClient::connection_stagger_delay(&config, 0),
+            None
+        );
+        for worker_index in 1..10 {
+            let delay = ParallelPostgresClient::connection_stagger_delay(&config, worker_index)
+                .unwrap()
+                .as_millis() as u64;
+            let stagger_ms = worker_index as u64 * 50;
+            assert!((stagger_ms..stagger_ms + 50).contains(&delay));
+        }
+    }
+
+    #[test]
+    fn test_bulk_account_insert_placeholders() {
+        for batch_size in [1, 2, 10, 100] {
+            for account_data_encoding in [
+                AccountDataEncoding::Bytea,
+                AccountDataEncoding::Base64,
+                AccountDataEncoding::Hex,
+            ] {
+                for track_lamport_deltas in [false, true] {
+                    for empty_data_as_null in [false, true] {
+                        let stmt = SimplePostgresClient::build_bulk_account_insert_sql(
+                            "account",
+                            batch_size,
+                            account_data_encoding,
+                            empty_data_as_null,
+                            track_lamport_deltas,
+                            &AccountColumns::default(),
+                        );
+                        check_bulk_statement_placeholders(&stmt, batch_size * ACCOUNT_COLUMN_COUNT);
+                    }
+                }
+            }
+        }
+    }
+
+    fn build_token_account_data(mint: &Pubkey, owner: &Pubkey) -> Vec<u8> {
+        let mut data = vec![0u8; inline_spl_token::Account::get_packed_len()];
+        data[inline_spl_token::SPL_TOKEN_ACCOUNT_MINT_OFFSET
+            ..inline_spl_token::SPL_TOKEN_ACCOUNT_MINT_OFFSET + 32]
+            .copy_from_slice(mint.as_ref());
+        data[inline_spl_token::SPL_TOKEN_ACCOUNT_OWNER_OFFSET
+            ..inline_spl_token::SPL_TOKEN_ACCOUNT_OWNER_OFFSET + 32]
+            .copy_from_slice(owner.as_ref());
+        data
+    }
+
+    #[test]
+    fn test_upsert_account() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![1, 2, 3], 10);
+        client.update_account(account.clone(), false).unwrap();
+
+        // An update at an older slot must not overwrite the newer one.
+        let newer_data = vec![4, 5, 6];
+        account.data = newer_data.clone();
+        account.slot = 11;
+        client.update_account(account.clone(), false).unwrap();
+        account.data = vec![7, 8, 9];
+        account.slot = 9;
+        client.update_account(account.clone(), false).unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT slot, data, lamports, write_version FROM account WHERE pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 11);
+        assert_eq!(row.get::<_, Vec<u8>>(1), newer_data);
+        assert_eq!(row.get::<_, i64>(2), account.lamports);
+        assert_eq!(row.get::<_, i64>(3), account.write_version.0 as i64);
+    }
+
+    #[test]
+    fn test_reordered_account_writes() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "store_account_historical_data": true }));
+        // A retried batch and a fresh batch written concurrently by two workers.
+        let mut fresh_client = SimplePostgresClient::new(&config).unwrap();
+        let mut retry_client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let fresh: Vec<_> = (0..2)
+            .map(|_| {
+                let mut account = build_account(&owner, vec![2], 20);
+                account.write_version = DbWriteVersion(2);
+                account
+            })
+            .collect();
+        for account in &fresh {
+            fresh_client.update_account(account.clone(), true).unwrap();
+        }
+
+        // The retried batch lands after the fresh one, with an older write at the same slot
+        // and a write at an older slot.
+        let mut retried = fresh.clone();
+        retried[0].write_version = DbWriteVersion(1);
+        retried[1].slot = 19;
+        for account in &mut retried {
+            account.data = vec![1];
+        }
+        for account in &retried {
+            retry_client.update_account(account.clone(), true).unwrap();
+        }
+        for account in &retried {
+            retry_client.update_account(account.clone(), false).unwrap();
+        }
+
+        for account in &fresh {
+            let row = db
+                .client
+                .query_one(
+                    "SELECT slot, write_version, data FROM account WHERE pubkey = $1",
+                    &[&account.pubkey],
+                )
+                .unwrap();
+            assert_eq!(row.get::<_, i64>(0), 20);
+            assert_eq!(row.get::<_, i64>(1), 2);
+            assert_eq!(row.get::<_, Vec<u8>>(2), vec![2]);
+        }
+        // The stale single upserts are stored as the history only.
+        assert_eq!(db.count("account_audit"), 2);
+    }
+
+    #[test]
+    fn test_bulk_insert_accounts_at_startup() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        let owner = Pubkey::new_unique();
+        // Two full batches and one account left over for the flush at the end of startup.
+        for slot in 0..5 {
+            client
+                .update_account(build_account(&owner, vec![slot as u8; 10], slot), true)
+                .unwrap();
+        }
+        assert_eq!(db.count("account"), 4);
+
+        client.notify_end_of_startup().unwrap();
+        assert_eq!(db.count("account"), 5);
+        assert_eq!(db.count("slot"), 5);
+    }
+
+    #[test]
+    fn test_account_data_binary_parameter() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+
+        for account_data_encoding in ["bytea", "hex", "base64"] {
+            if account_data_encoding == "hex" {
+                db.client
+                    .batch_execute(
+                        "ALTER TABLE account ALTER COLUMN data TYPE TEXT; \
+                        ALTER TABLE account_audit ALTER COLUMN data TYPE TEXT;",
+                    )
+                    .unwrap();
+            }
+            let config = db.config(json!({
+                "batch_size": 2,
+                "account_data_encoding": account_data_encoding,
+            }));
+            let statements = [
+                SimplePostgresClient::build_bulk_account_insert_statement(&mut db.client, &config)
+                    .unwrap(),
+                SimplePostgresClient::build_single_account_upsert_statement(
+                    &mut db.client,
+                    &config,
+                )
+                .unwrap(),
+                SimplePostgresClient::build_account_audit_insert_statement(&mut db.client, &config)
+                    .unwrap(),
+            ];
+            for statement in statements {
+                for data_param in statement
+                    .param_types()
+                    .unwrap()
+                    .chunks(ACCOUNT_COLUMN_COUNT)
+                {
+                    assert_eq!(data_param[6], types::Type::BYTEA);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_account_data_encoding() {
+        for (account_data_encoding, expected) in [("hex", "0102ff"), ("base64", "AQL/")] {
+            let mut db = match TestDatabase::new() {
+                Some(db) => db,
+                None => return,
+            };
+            db.client
+                .batch_execute(
+                    "ALTER TABLE account ALTER COLUMN data TYPE TEXT; \
+                    ALTER TABLE account_audit ALTER COLUMN data TYPE TEXT;",
+                )
+                .unwrap();
+            let config = db.config(json!({
+                "account_data_encoding": account_data_encoding,
+                "store_account_historical_data": true,
+            }));
+            let mut client = SimplePostgresClient::new(&config).unwrap();
+
+            let owner = Pubkey::new_unique();
+            // The bulk insert at startup, then the single upsert.
+            for slot in 0..2 {
+                client
+                    .update_account(build_account(&owner, vec![1, 2, 255], slot), true)
+                    .unwrap();
+            }
+            client
+                .update_account(build_account(&owner, vec![1, 2, 255], 2), false)
+                .unwrap();
+
+            let data: Vec<String> = db
+                .client
+                .query("SELECT data FROM account", &[])
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            assert_eq!(data, vec![expected; 3]);
+        }
+    }
+
+    #[test]
+    fn test_empty_data_as_null() {
+        for empty_data_as_null in [false, true] {
+            let mut db = match TestDatabase::new() {
+                Some(db) => db,
+                None => return,
+            };
+            let config = db.config(json!({
+                "empty_data_as_null": empty_data_as_null,
+                "store_account_historical_data": true,
+            }));
+            let mut client = SimplePostgresClient::new(&config).unwrap();
+
+            let owner = Pubkey::new_unique();
+            // The bulk insert at startup, then the single upserts, of the empty data only.
+            for slot in 0..2 {
+                client
+                    .update_account(build_account(&owner, vec![], slot), true)
+                    .unwrap();
+            }
+            client
+                .update_account(build_account(&owner, vec![1], 2), false)
+                .unwrap();
+            client
+                .update_account(build_account(&owner, vec![], 3), false)
+                .unwrap();
+
+            let data: Vec<Option<Vec<u8>>> = db
+                .client
+                .query("SELECT data FROM account ORDER BY slot", &[])
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            let empty_data = if empty_data_as_null {
+                None
+            } else {
+                Some(vec![])
+            };
+            assert_eq!(
+                data,
+                vec![
+                    empty_data.clone(),
+                    empty_data.clone(),
+                    Some(vec![1]),
+                    empty_data
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_token_secondary_indexes() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "index_token_owner": true,
+            "index_token_mint": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mint = Pubkey::new_unique();
+        let token_owner = Pubkey::new_unique();
+        let data = build_token_account_data(&mint, &token_owner);
+        // The startup accounts go through the bulk index inserts, the rest are upserted one by one.
+        for slot in 0..3 {
+            client
+                .update_account(
+                    build_account(&inline_spl_token::id(), data.clone(), slot),
+                    true,
+                )
+                .unwrap();
+        }
+        client.notify_end_of_startup().unwrap();
+        client
+            .update_account(build_account(&inline_spl_token::id(), data, 3), false)
+            .unwrap();
+
+        let rows = db
+            .client
+            .query(
+                "SELECT COUNT(*) FROM spl_token_owner_index WHERE owner_key = $1",
+                &[&token_owner.as_ref()],
+            )
+            .unwrap();
+        assert_eq!(rows[0].get::<_, i64>(0), 4);
+        let rows = db
+            .client
+            .query(
+                "SELECT COUNT(*) FROM spl_token_mint_index WHERE mint_key = $1",
+                &[&mint.as_ref()],
+            )
+            .unwrap();
+        assert_eq!(rows[0].get::<_, i64>(0), 4);
+    }
+
+    #[test]
+    fn test_token_mint_index_only() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        // Nothing of the owner index is used when only the mint index is enabled.
+        db.client
+            .batch_execute("DROP TABLE spl_token_owner_index")
+            .unwrap();
+        let config = db.config(json!({ "index_token_mint": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        assert_eq!(client.pending_token_owner_index.capacity(), 0);
+
+        let mint = Pubkey::new_unique();
+        let data = build_token_account_data(&mint, &Pubkey::new_unique());
+        for slot in 0..3 {
+            client
+                .update_account(
+                    build_account(&inline_spl_token::id(), data.clone(), slot),
+                    true,
+                )
+                .unwrap();
+            assert!(client.pending_token_owner_index.is_empty());
+        }
+        client.notify_end_of_startup().unwrap();
+        client
+            .update_account(build_account(&inline_spl_token::id(), data, 3), false)
+            .unwrap();
+        assert_eq!(db.count("spl_token_mint_index"), 4);
+    }
+
+    #[test]
+    fn test_token_index_batch_size() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "index_token_owner": true,
+            "index_batch_size": 4,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let data = build_token_account_data(&Pubkey::new_unique(), &Pubkey::new_unique());
+        for slot in 0..6 {
+            client
+                .update_account(
+                    build_account(&inline_spl_token::id(), data.clone(), slot),
+                    true,
+                )
+                .unwrap();
+        }
+        assert_eq!(db.count("account"), 6);
+        assert_eq!(db.count("spl_token_owner_index"), 4);
+
+        // The entries left over are flushed at the end of startup.
+        client.notify_end_of_startup().unwrap();
+        assert_eq!(db.count("spl_token_owner_index"), 6);
+    }
+
+    #[test]
+    fn test_stake_account_index() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "index_stake_accounts": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let staker = Pubkey::new_unique();
+        let withdrawer = Pubkey::new_unique();
+        let mut data = vec![0u8; inline_stake_program::Account::get_packed_len()];
+        data[0] = 1; // Initialized
+        data[inline_stake_program::STAKE_ACCOUNT_STAKER_OFFSET
+            ..inline_stake_program::STAKE_ACCOUNT_STAKER_OFFSET + 32]
+            .copy_from_slice(staker.as_ref());
+        data[inline_stake_program::STAKE_ACCOUNT_WITHDRAWER_OFFSET
+            ..inline_stake_program::STAKE_ACCOUNT_WITHDRAWER_OFFSET + 32]
+            .copy_from_slice(withdrawer.as_ref());
+        let account = build_account(&inline_stake_program::id(), data, 1);
+        client.update_account(account.clone(), false).unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT staker, withdrawer, voter FROM stake_account_index WHERE stake_pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), staker.as_ref());
+        assert_eq!(row.get::<_, Vec<u8>>(1), withdrawer.as_ref());
+        assert_eq!(row.get::<_, Option<Vec<u8>>>(2), None);
+    }
+
+    #[test]
+    fn test_update_slot_status() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        client
+            .update_slot_status(2, Some(1), SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(2, None, SlotStatus::Rooted)
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one("SELECT parent, status FROM slot WHERE slot = 2", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Option<i64>>(0), Some(1));
+        assert_eq!(row.get::<_, String>(1), SlotStatus::Rooted.as_str());
+    }
+
+    #[test]
+    fn test_update_block_metadata() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        client
+            .update_block_metadata(UpdateBlockMetadataRequest {
+                block_info: DbBlockInfo {
+                    slot: 3,
+                    blockhash: "blockhash".to_string(),
+                    rewards: vec![],
+                    block_time: Some(1_650_000_000),
+                    block_height: Some(2),
+                },
+            })
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT blockhash, block_time FROM block WHERE slot = 3",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, String>(0), "blockhash");
+        assert_eq!(row.get::<_, Option<i64>>(1), Some(1_650_000_000));
+    }
+}
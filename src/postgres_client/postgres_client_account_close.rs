@@ -0,0 +1,147 @@
+/// Module responsible for recording the account closes into the account_close table, a feed
+/// of the closed accounts for the reclaim and cleanup tooling.
+///
+/// An account is closed by the update bringing its lamports to zero. Its prior state is its
+/// current row in the account table, read before the upsert overwrites it: the close is
+/// recorded when that row has lamports and is older than the update, with its owner and data
+/// length, as the closing update usually reassigns the account to the system program and
+/// clears its data. The accounts loaded from the snapshot at startup are not closes.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        DbAccountInfo, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{
+        AccountDataEncoding, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+    },
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::sync::Mutex,
+};
+
+impl SimplePostgresClient {
+    pub(crate) fn get_track_account_closes(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.track_account_closes, Some(true))
+    }
+
+    pub(crate) fn build_account_close_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        if Self::is_staging_table_mode(config) {
+            let msg =
+                "\"track_account_closes\" is not supported in the staging table mode".to_string();
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        // The length of the data in bytes, in the encoding of the data column.
+        let data_len = match Self::get_account_data_encoding(config) {
+            AccountDataEncoding::Bytea => "length(data)",
+            AccountDataEncoding::Hex => "length(data) / 2",
+            AccountDataEncoding::Base64 => "length(decode(data, 'base64'))",
+        };
+        let stmt = format!(
+            "INSERT INTO {} (pubkey, closed_at_slot, last_owner, last_data_len, updated_on) \
+            SELECT pubkey, $2, owner, {}, $4 FROM {} \
+            WHERE pubkey = $1 AND lamports > 0 AND (slot < $2 OR (slot = $2 AND write_version < $3)) \
+            ON CONFLICT (pubkey, closed_at_slot) DO NOTHING",
+            Self::table_name(&config.table_names, "account_close"),
+            data_len,
+            Self::table_name(&config.table_names, "account")
+        );
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Record the close of the account, when the update brings its lamports to zero. To be
+    /// called before the account is upserted.
+    pub(crate) fn insert_account_close(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if account.lamports != 0 {
+            return Ok(());
+        }
+        let updated_on = Utc::now().naive_utc();
+        let result = statement.execute(
+            client,
+            types,
+            &[
+                &account.pubkey,
+                &account.slot,
+                &account.write_version,
+                &updated_on,
+            ],
+        );
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the close of account to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_track_account_closes() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "track_account_closes": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let mut account = build_account(&owner, vec![1; 10], 1);
+        client.update_account(account.clone(), false).unwrap();
+
+        // Closed at slot 2, then an older update arrives and the account is closed again.
+        account.slot = 2;
+        account.lamports = 0;
+        account.owner = Pubkey::default().as_ref().to_vec();
+        account.data = vec![];
+        client.update_account(account.clone(), false).unwrap();
+        account.slot = 1;
+        client.update_account(account.clone(), false).unwrap();
+        account.slot = 3;
+        client.update_account(account.clone(), false).unwrap();
+
+        // Closed without a prior balance known.
+        let mut unknown = build_account(&owner, vec![], 3);
+        unknown.lamports = 0;
+        client.update_account(unknown, false).unwrap();
+
+        let rows = db
+            .client
+            .query(
+                "SELECT pubkey, closed_at_slot, last_owner, last_data_len FROM account_close",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<_, Vec<u8>>(0), account.pubkey);
+        assert_eq!(rows[0].get::<_, i64>(1), 2);
+        assert_eq!(rows[0].get::<_, Vec<u8>>(2), owner.as_ref().to_vec());
+        assert_eq!(rows[0].get::<_, i32>(3), 10);
+    }
+}
@@ -0,0 +1,604 @@
+/// Module responsible for projecting the account updates onto the configured account_columns,
+/// the columns of the account and account_audit tables the statements write.
+///
+/// The pubkey, slot and write_version columns are always written, they identify the account
+/// and order its updates. The columns not written are left out of the statements and of their
+/// parameters, so their values are neither sent nor stored, and keep their defaults, NULL
+/// unless the schema sets otherwise. Their NOT NULL constraints must be dropped.
+///
+/// When store_data_prefix_len is set, the data is truncated to its first bytes when stored, and
+/// its full length is written to the data_len column. Both are computed by the statements from
+/// the data parameter, so the data still requires the "data" column.
+///
+/// When extract_text_search is set, the strings extracted from the data are written to the
+/// search_text column, and when record_write_lag is set, the write lag to the write_lag_ms
+/// column. They are bound after the account columns, derived from the account update.
+///
+/// When account_data_chunk_threshold is set, the data larger than the threshold is written to the
+/// account_data_chunk table instead, the row storing empty data and the number of the chunks in
+/// the data_chunk_count column.
+///
+/// When record_account_slot_status is set, the best-known status of the slot is written to the
+/// slot_status column, and when classify_account_type is set, the type of the account to the
+/// account_type column. When compute_rent_exempt is set, whether the account is rent-exempt is
+/// written to the rent_exempt column.
+use {
+    super::{
+        postgres_client_account_slot_status::SlotStatuses,
+        postgres_client_account_type::classify_account_type,
+        postgres_client_data_chunks::data_chunk_count,
+        postgres_client_rent_exempt::{configured_rent, is_rent_exempt},
+        postgres_client_text_search::{
+            extract_search_text, search_text_sql, DEFAULT_TEXT_SEARCH_MAX_DATA_LEN,
+        },
+        postgres_client_write_lag::SlotClock,
+        DbAccountInfo, SimplePostgresClient, ACCOUNT_COLUMN_COUNT,
+    },
+    crate::geyser_plugin_postgres::{
+        AccountDataEncoding, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+    },
+    chrono::naive::NaiveDateTime,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::rent::Rent,
+    std::sync::Arc,
+    tokio_postgres::types::ToSql,
+};
+
+/// The columns written from an account update, in the order of the statements' parameters.
+const ACCOUNT_COLUMNS: [&str; ACCOUNT_COLUMN_COUNT] = [
+    "pubkey",
+    "slot",
+    "owner",
+    "lamports",
+    "executable",
+    "rent_epoch",
+    "data",
+    "write_version",
+    "updated_on",
+];
+const REQUIRED_ACCOUNT_COLUMNS: [&str; 3] = ["pubkey", "slot", "write_version"];
+
+/// The values of the columns derived from an account update.
+#[derive(Default)]
+pub(crate) struct DerivedAccountValues {
+    search_text: Option<String>,
+    write_lag_ms: Option<i64>,
+    data_chunk_count: Option<i32>,
+    slot_status: Option<&'static str>,
+    account_type: Option<&'static str>,
+    rent_exempt: Option<bool>,
+}
+
+/// The data bound for the accounts whose data is written to the chunks.
+static CHUNKED_DATA: Vec<u8> = Vec::new();
+
+/// The columns of the account tables written by the statements.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AccountColumns {
+    written: [bool; ACCOUNT_COLUMN_COUNT],
+    /// The number of the bytes of the data stored, None to store all of it.
+    data_prefix_len: Option<usize>,
+    /// The largest data the search_text is extracted from, None not to write the search_text.
+    text_search_max_data_len: Option<usize>,
+    /// Whether the write_lag_ms column is written.
+    record_write_lag: bool,
+    /// The largest data stored in the row, None not to write the data_chunk_count.
+    data_chunk_threshold: Option<usize>,
+    /// Whether the slot_status column is written.
+    record_slot_status: bool,
+    /// Whether the account_type column is written.
+    classify_account_type: bool,
+    /// The rent parameters the rent_exempt is computed with, None not to write the rent_exempt.
+    rent: Option<Rent>,
+}
+
+impl Default for AccountColumns {
+    fn default() -> Self {
+        Self {
+            written: [true; ACCOUNT_COLUMN_COUNT],
+            data_prefix_len: None,
+            text_search_max_data_len: None,
+            record_write_lag: false,
+            data_chunk_threshold: None,
+            record_slot_status: false,
+            classify_account_type: false,
+            rent: None,
+        }
+    }
+}
+
+impl AccountColumns {
+    /// The configured account_columns, validated against the known columns.
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let configuration_error = |msg: String| {
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConfigurationError {
+                msg,
+            }))
+        };
+        let data_prefix_len = config.store_data_prefix_len;
+        if data_prefix_len.is_some() && SimplePostgresClient::is_staging_table_mode(config) {
+            return Err(configuration_error(
+                "\"store_data_prefix_len\" is not supported in the staging table mode".to_string(),
+            ));
+        }
+        let text_search_max_data_len =
+            matches!(config.extract_text_search, Some(true)).then(|| {
+                config
+                    .text_search_max_data_len
+                    .unwrap_or(DEFAULT_TEXT_SEARCH_MAX_DATA_LEN)
+            });
+        if text_search_max_data_len.is_some() && SimplePostgresClient::is_staging_table_mode(config)
+        {
+            return Err(configuration_error(
+                "\"extract_text_search\" is not supported in the staging table mode".to_string(),
+            ));
+        }
+        let record_write_lag = matches!(config.record_write_lag, Some(true));
+        if record_write_lag && SimplePostgresClient::is_staging_table_mode(config) {
+            return Err(configuration_error(
+                "\"record_write_lag\" is not supported in the staging table mode".to_string(),
+            ));
+        }
+        let data_chunk_threshold = config.account_data_chunk_threshold;
+        if let Some(msg) = match data_chunk_threshold {
+            Some(0) => Some("\"account_data_chunk_threshold\" must be positive"),
+            Some(_) if SimplePostgresClient::is_staging_table_mode(config) => {
+                Some("\"account_data_chunk_threshold\" is not supported in the staging table mode")
+            }
+            Some(_) if data_prefix_len.is_some() => Some(
+                "\"account_data_chunk_threshold\" is not supported with \"store_data_prefix_len\"",
+            ),
+            _ => None,
+        } {
+            return Err(configuration_error(msg.to_string()));
+        }
+        let record_slot_status = matches!(config.record_account_slot_status, Some(true));
+        if record_slot_status && SimplePostgresClient::is_staging_table_mode(config) {
+            return Err(configuration_error(
+                "\"record_account_slot_status\" is not supported in the staging table mode"
+                    .to_string(),
+            ));
+        }
+        let classify_account_type = matches!(config.classify_account_type, Some(true));
+        if classify_account_type && SimplePostgresClient::is_staging_table_mode(config) {
+            return Err(configuration_error(
+                "\"classify_account_type\" is not supported in the staging table mode".to_string(),
+            ));
+        }
+        let rent = match config.compute_rent_exempt {
+            Some(true) if SimplePostgresClient::is_staging_table_mode(config) => {
+                return Err(configuration_error(
+                    "\"compute_rent_exempt\" is not supported in the staging table mode"
+                        .to_string(),
+                ));
+            }
+            Some(true) => Some(configured_rent(config).ok_or_else(|| {
+                configuration_error(
+                    "\"rent_exemption_threshold\" must be a non-negative number".to_string(),
+                )
+            })?),
+            _ => None,
+        };
+        let account_columns = match &config.account_columns {
+            Some(account_columns) => account_columns,
+            None => {
+                return Ok(Self {
+                    data_prefix_len,
+                    text_search_max_data_len,
+                    record_write_lag,
+                    data_chunk_threshold,
+                    record_slot_status,
+                    classify_account_type,
+                    rent,
+                    ..Self::default()
+                })
+            }
+        };
+
+        let mut written = ACCOUNT_COLUMNS.map(|column| REQUIRED_ACCOUNT_COLUMNS.contains(&column));
+        for column in account_columns {
+            match ACCOUNT_COLUMNS.iter().position(|known| known == column) {
+                Some(index) => written[index] = true,
+                None => {
+                    return Err(configuration_error(format!(
+                        "Unknown column {:?} in \"account_columns\", the known columns are {:?}",
+                        column, ACCOUNT_COLUMNS
+                    )))
+                }
+            }
+        }
+        let account_columns = Self {
+            written,
+            data_prefix_len: None,
+            text_search_max_data_len: None,
+            record_write_lag: false,
+            data_chunk_threshold: None,
+            record_slot_status: false,
+            classify_account_type: false,
+            rent: None,
+        };
+
+        if !account_columns.is_written("lamports")
+            && (SimplePostgresClient::get_track_lamport_deltas(config)
+                || SimplePostgresClient::get_track_account_closes(config))
+        {
+            return Err(configuration_error(
+                "\"track_lamport_deltas\" and \"track_account_closes\" require \"lamports\" in \"account_columns\""
+                    .to_string(),
+            ));
+        }
+        if account_columns != Self::default() && SimplePostgresClient::is_staging_table_mode(config)
+        {
+            return Err(configuration_error(
+                "\"account_columns\" is not supported in the staging table mode".to_string(),
+            ));
+        }
+        if data_prefix_len.is_some() && !account_columns.is_written("data") {
+            return Err(configuration_error(
+                "\"store_data_prefix_len\" requires \"data\" in \"account_columns\"".to_string(),
+            ));
+        }
+        if data_chunk_threshold.is_some() && !account_columns.is_written("data") {
+            return Err(configuration_error(
+                "\"account_data_chunk_threshold\" requires \"data\" in \"account_columns\""
+                    .to_string(),
+            ));
+        }
+        Ok(Self {
+            data_prefix_len,
+            text_search_max_data_len,
+            record_write_lag,
+            data_chunk_threshold,
+            record_slot_status,
+            classify_account_type,
+            rent,
+            ..account_columns
+        })
+    }
+
+    pub fn is_written(&self, column: &str) -> bool {
+        ACCOUNT_COLUMNS
+            .iter()
+            .zip(self.written)
+            .any(|(known, written)| *known == column && written)
+    }
+
+    /// The columns bound to the parameters of an account row.
+    fn bound_columns(&self) -> impl Iterator<Item = &'static str> + '_ {
+        ACCOUNT_COLUMNS
+            .iter()
+            .zip(self.written)
+            .filter(|(_, written)| *written)
+            .map(|(column, _)| *column)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.bound_columns()
+            .chain(self.data_prefix_len.map(|_| "data_len"))
+            .chain(self.text_search_max_data_len.map(|_| "search_text"))
+            .chain(self.record_write_lag.then(|| "write_lag_ms"))
+            .chain(self.data_chunk_threshold.map(|_| "data_chunk_count"))
+            .chain(self.record_slot_status.then(|| "slot_status"))
+            .chain(self.classify_account_type.then(|| "account_type"))
+            .chain(self.rent.map(|_| "rent_exempt"))
+    }
+
+    /// The number of the parameters of an account row, the data_len is computed from the data.
+    pub fn len(&self) -> usize {
+        self.written.iter().filter(|written| **written).count()
+            + usize::from(self.text_search_max_data_len.is_some())
+            + usize::from(self.record_write_lag)
+            + usize::from(self.data_chunk_threshold.is_some())
+            + usize::from(self.record_slot_status)
+            + usize::from(self.classify_account_type)
+            + usize::from(self.rent.is_some())
+    }
+
+    /// The column list of the INSERT statements.
+    pub fn columns_sql(&self) -> String {
+        self.columns().collect::<Vec<_>>().join(", ")
+    }
+
+    /// The values of an account row, bound from the parameter `first_param` onwards.
+    pub fn values_sql(
+        &self,
+        first_param: usize,
+        account_data_encoding: AccountDataEncoding,
+        empty_data_as_null: bool,
+    ) -> String {
+        let mut values: Vec<String> = self
+            .bound_columns()
+            .enumerate()
+            .map(|(i, column)| match column {
+                "data" => SimplePostgresClient::encode_account_data_sql(
+                    account_data_encoding,
+                    empty_data_as_null,
+                    self.data_prefix_len,
+                    first_param + i,
+                ),
+                _ => format!("${}", first_param + i),
+            })
+            .collect();
+        if self.data_prefix_len.is_some() {
+            let data_param = first_param + self.bound_columns().position(|c| c == "data").unwrap();
+            values.push(format!("LENGTH(${}::BYTEA)", data_param));
+        }
+        let mut derived_param = first_param + self.bound_columns().count();
+        if self.text_search_max_data_len.is_some() {
+            values.push(search_text_sql(derived_param));
+            derived_param += 1;
+        }
+        if self.record_write_lag {
+            values.push(format!("${}", derived_param));
+            derived_param += 1;
+        }
+        if self.data_chunk_threshold.is_some() {
+            values.push(format!("${}", derived_param));
+            derived_param += 1;
+        }
+        if self.record_slot_status {
+            values.push(format!("${}", derived_param));
+            derived_param += 1;
+        }
+        if self.classify_account_type {
+            values.push(format!("${}", derived_param));
+            derived_param += 1;
+        }
+        if self.rent.is_some() {
+            values.push(format!("${}", derived_param));
+        }
+        format!("({})", values.join(", "))
+    }
+
+    /// The columns updated by the upserts on conflict.
+    pub fn update_set_sql(&self) -> String {
+        self.columns()
+            .filter(|column| *column != "pubkey")
+            .map(|column| format!("{}=excluded.{}", column, column))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The values of the columns derived from the account written at updated_on.
+    pub fn derived_values(
+        &self,
+        account: &DbAccountInfo,
+        updated_on: &NaiveDateTime,
+        slot_clock: &Option<Arc<SlotClock>>,
+        slot_statuses: &Option<Arc<SlotStatuses>>,
+    ) -> DerivedAccountValues {
+        DerivedAccountValues {
+            search_text: self
+                .text_search_max_data_len
+                .and_then(|max_data_len| extract_search_text(&account.data, max_data_len)),
+            write_lag_ms: slot_clock
+                .as_ref()
+                .filter(|_| self.record_write_lag)
+                .and_then(|slot_clock| slot_clock.write_lag_ms(account, updated_on)),
+            data_chunk_count: self
+                .data_chunk_threshold
+                .and_then(|threshold| data_chunk_count(account, threshold)),
+            slot_status: slot_statuses
+                .as_ref()
+                .filter(|_| self.record_slot_status)
+                .map(|slot_statuses| slot_statuses.status(account.slot)),
+            account_type: self
+                .classify_account_type
+                .then(|| classify_account_type(account)),
+            rent_exempt: self.rent.map(|rent| is_rent_exempt(&rent, account)),
+        }
+    }
+
+    /// The rent parameters the rent_exempt column is computed with, when it is written.
+    pub fn rent(&self) -> Option<Rent> {
+        self.rent
+    }
+
+    /// The largest data stored in the row, when the larger data is written to the chunks.
+    pub fn data_chunk_threshold(&self) -> Option<usize> {
+        self.data_chunk_threshold
+    }
+
+    /// Push the parameters of the account row, with its derived values.
+    pub fn push_values<'a>(
+        &self,
+        account: &'a DbAccountInfo,
+        derived: &'a DerivedAccountValues,
+        updated_on: &'a NaiveDateTime,
+        values: &mut Vec<&'a (dyn ToSql + Sync)>,
+    ) {
+        let row: [&'a (dyn ToSql + Sync); ACCOUNT_COLUMN_COUNT] = [
+            &account.pubkey,
+            &account.slot,
+            &account.owner,
+            &account.lamports,
+            &account.executable,
+            &account.rent_epoch,
+            if derived.data_chunk_count.is_some() {
+                &CHUNKED_DATA
+            } else {
+                &account.data
+            },
+            &account.write_version,
+            updated_on,
+        ];
+        values.extend(
+            row.iter()
+                .zip(self.written)
+                .filter(|(_, written)| *written)
+                .map(|(value, _)| *value),
+        );
+        if self.text_search_max_data_len.is_some() {
+            values.push(&derived.search_text);
+        }
+        if self.record_write_lag {
+            values.push(&derived.write_lag_ms);
+        }
+        if self.data_chunk_threshold.is_some() {
+            values.push(&derived.data_chunk_count);
+        }
+        if self.record_slot_status {
+            values.push(&derived.slot_status);
+        }
+        if self.classify_account_type {
+            values.push(&derived.account_type);
+        }
+        if self.rent.is_some() {
+            values.push(&derived.rent_exempt);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, check_bulk_statement_placeholders, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn build_account_columns(
+        account_columns: serde_json::Value,
+    ) -> Result<AccountColumns, GeyserPluginError> {
+        let config = serde_json::from_value(json!({ "account_columns": account_columns })).unwrap();
+        AccountColumns::new(&config)
+    }
+
+    #[test]
+    fn test_account_columns() {
+        let account_columns = build_account_columns(json!(["owner", "lamports"])).unwrap();
+        assert_eq!(account_columns.len(), 5);
+        assert_eq!(
+            account_columns.columns_sql(),
+            "pubkey, slot, owner, lamports, write_version"
+        );
+        assert_eq!(
+            account_columns.values_sql(6, AccountDataEncoding::Bytea, false),
+            "($6, $7, $8, $9, $10)"
+        );
+        assert_eq!(
+            account_columns.update_set_sql(),
+            "slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, write_version=excluded.write_version"
+        );
+        let stmt = SimplePostgresClient::build_bulk_account_insert_sql(
+            "account",
+            3,
+            AccountDataEncoding::Bytea,
+            false,
+            false,
+            &account_columns,
+        );
+        check_bulk_statement_placeholders(&stmt, 3 * account_columns.len());
+
+        let account_columns = build_account_columns(json!(["data"])).unwrap();
+        assert_eq!(
+            account_columns.values_sql(1, AccountDataEncoding::Bytea, true),
+            "($1, $2, NULLIF($3::BYTEA, ''::BYTEA), $4)"
+        );
+
+        assert!(build_account_columns(json!(["balance"])).is_err());
+        let config = serde_json::from_value(json!({
+            "account_columns": ["owner"],
+            "track_lamport_deltas": true,
+        }))
+        .unwrap();
+        assert!(AccountColumns::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_write_account_columns() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute(
+                "ALTER TABLE account ALTER COLUMN executable DROP NOT NULL, \
+                ALTER COLUMN rent_epoch DROP NOT NULL, ALTER COLUMN updated_on DROP NOT NULL;",
+            )
+            .unwrap();
+        let config = db.config(json!({
+            "batch_size": 2,
+            "account_columns": ["owner", "lamports"],
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let accounts: Vec<DbAccountInfo> = (0..3)
+            .map(|_| build_account(&owner, vec![1; 100], 1))
+            .collect();
+        // Bulk inserted at startup, then upserted.
+        for account in &accounts[..2] {
+            client.update_account(account.clone(), true).unwrap();
+        }
+        client.update_account(accounts[2].clone(), false).unwrap();
+
+        let rows = db
+            .client
+            .query("SELECT owner, lamports, data, rent_epoch FROM account", &[])
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        for row in rows {
+            assert_eq!(row.get::<_, Vec<u8>>(0), owner.as_ref().to_vec());
+            assert_eq!(row.get::<_, i64>(1), 1_000_000);
+            assert_eq!(row.get::<_, Option<Vec<u8>>>(2), None);
+            assert_eq!(row.get::<_, Option<i64>>(3), None);
+        }
+    }
+
+    #[test]
+    fn test_store_data_prefix_len() {
+        let config = serde_json::from_value(json!({ "store_data_prefix_len": 8 })).unwrap();
+        let account_columns = AccountColumns::new(&config).unwrap();
+        assert_eq!(account_columns.len(), ACCOUNT_COLUMN_COUNT);
+        assert!(account_columns
+            .columns_sql()
+            .ends_with("updated_on, data_len"));
+        assert_eq!(
+            account_columns.values_sql(1, AccountDataEncoding::Bytea, false),
+            "($1, $2, $3, $4, $5, $6, SUBSTRING($7::BYTEA FROM 1 FOR 8), $8, $9, LENGTH($7::BYTEA))"
+        );
+        let config = serde_json::from_value(json!({
+            "store_data_prefix_len": 8,
+            "account_columns": ["owner"],
+        }))
+        .unwrap();
+        assert!(AccountColumns::new(&config).is_err());
+
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "store_data_prefix_len": 8,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        let account = build_account(&owner, (0..100).collect(), 1);
+        let mut update = account.clone();
+        update.slot = 2;
+        update.data = vec![1; 4];
+        client.update_account(account, false).unwrap();
+        client.update_account(update, false).unwrap();
+
+        let row = db
+            .client
+            .query_one("SELECT data, data_len FROM account", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), vec![1; 4]);
+        assert_eq!(row.get::<_, i32>(1), 4);
+        let row = db
+            .client
+            .query_one("SELECT data, data_len FROM account_audit", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), (0..8).collect::<Vec<u8>>());
+        assert_eq!(row.get::<_, i32>(1), 100);
+    }
+}
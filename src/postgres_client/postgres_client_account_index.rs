@@ -1,5 +1,8 @@
 use {
     super::{
+        postgres_client_batch_metrics::report_batch_commit,
+        postgres_client_batch_order::{report_deadlock, sort_token_index_batch},
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
         DbAccountInfo, ReadableAccountInfo, SimplePostgresClient,
         DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE,
     },
@@ -9,15 +12,18 @@ use {
         inline_spl_token_2022,
     },
     log::*,
-    postgres::{Client, Statement},
+    postgres::Client,
     solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
     solana_measure::measure::Measure,
     solana_metrics::*,
     solana_sdk::pubkey::Pubkey,
+    std::sync::Mutex,
     tokio_postgres::types,
 };
 
 const TOKEN_INDEX_COLUMN_COUNT: usize = 3;
+/// The size of the slot and the lengths of the keys of an index entry.
+const TOKEN_INDEX_ROW_OVERHEAD_BYTES: usize = 16;
 /// Struct for the secondary index for both token account's owner and mint index,
 pub struct TokenSecondaryIndexEntry {
     /// In case of token owner, the secondary key is the Pubkey of the owner and in case of
@@ -31,45 +37,52 @@ pub struct TokenSecondaryIndexEntry {
     slot: i64,
 }
 
+impl TokenSecondaryIndexEntry {
+    /// The order of the entries locking the index rows consistently.
+    pub(crate) fn sort_key(&self) -> (&[u8], &[u8], i64) {
+        (&self.secondary_key, &self.account_key, self.slot)
+    }
+}
+
 impl SimplePostgresClient {
     pub fn build_single_token_owner_index_upsert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        const BULK_OWNER_INDEX_INSERT_STATEMENT: &str =
-            "INSERT INTO spl_token_owner_index AS owner_index (owner_key, account_key, slot) \
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS owner_index (owner_key, account_key, slot) \
         VALUES ($1, $2, $3) \
         ON CONFLICT (owner_key, account_key) \
         DO UPDATE SET slot=excluded.slot \
-        WHERE owner_index.slot < excluded.slot";
+        WHERE owner_index.slot < excluded.slot",
+            Self::table_name(&config.table_names, "spl_token_owner_index")
+        );
 
-        Self::prepare_query_statement(client, config, BULK_OWNER_INDEX_INSERT_STATEMENT)
+        Self::prepare_query_statement(client, config, &stmt)
     }
 
     pub fn build_single_token_mint_index_upsert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        const BULK_MINT_INDEX_INSERT_STATEMENT: &str =
-            "INSERT INTO spl_token_mint_index AS mint_index (mint_key, account_key, slot) \
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS mint_index (mint_key, account_key, slot) \
         VALUES ($1, $2, $3) \
         ON CONFLICT (mint_key, account_key) \
         DO UPDATE SET slot=excluded.slot \
-        WHERE mint_index.slot < excluded.slot";
+        WHERE mint_index.slot < excluded.slot",
+            Self::table_name(&config.table_names, "spl_token_mint_index")
+        );
 
-        Self::prepare_query_statement(client, config, BULK_MINT_INDEX_INSERT_STATEMENT)
+        Self::prepare_query_statement(client, config, &stmt)
     }
 
-    /// Common build the token mint index bulk insert statement.
-    pub fn build_bulk_token_index_insert_statement_common(
-        client: &mut Client,
+    /// Build the SQL text of the token index bulk insert statement with `batch_size` rows.
+    fn build_bulk_token_index_insert_sql(
         table: &str,
         source_key_name: &str,
-        config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let batch_size = config
-            .batch_size
-            .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE);
+        batch_size: usize,
+    ) -> String {
         let mut stmt = format!(
             "INSERT INTO {} AS index ({}, account_key, slot) VALUES",
             table, source_key_name
@@ -89,10 +102,35 @@ impl SimplePostgresClient {
             "ON CONFLICT ({}, account_key) DO UPDATE SET slot=excluded.slot where index.slot < excluded.slot",
             source_key_name);
 
-        stmt = format!("{} {}", stmt, handle_conflict);
+        format!("{} {}", stmt, handle_conflict)
+    }
+
+    /// The batch size of the token index bulk inserts, the accounts batch size by default.
+    pub fn get_index_batch_size(config: &GeyserPluginPostgresConfig) -> usize {
+        config.index_batch_size.unwrap_or_else(|| {
+            config
+                .batch_size
+                .unwrap_or(DEFAULT_ACCOUNTS_INSERT_BATCH_SIZE)
+        })
+    }
+
+    /// Common build the token mint index bulk insert statement.
+    pub fn build_bulk_token_index_insert_statement_common(
+        client: &mut Client,
+        table: &str,
+        source_key_name: &str,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let batch_size = Self::get_index_batch_size(config);
+        let stmt = Self::build_bulk_token_index_insert_sql(
+            Self::table_name(&config.table_names, table),
+            source_key_name,
+            batch_size,
+        );
 
         info!("{}", stmt);
-        let bulk_stmt = client.prepare(&stmt);
+        let bulk_stmt =
+            PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match bulk_stmt {
             Err(err) => {
@@ -111,7 +149,7 @@ impl SimplePostgresClient {
     pub fn build_bulk_token_owner_index_insert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
+    ) -> Result<PluginStatement, GeyserPluginError> {
         Self::build_bulk_token_index_insert_statement_common(
             client,
             "spl_token_owner_index",
@@ -124,7 +162,7 @@ impl SimplePostgresClient {
     pub fn build_bulk_token_mint_index_insert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
+    ) -> Result<PluginStatement, GeyserPluginError> {
         Self::build_bulk_token_index_insert_statement_common(
             client,
             "spl_token_mint_index",
@@ -135,12 +173,18 @@ impl SimplePostgresClient {
 
     /// Execute the common token bulk insert query.
     fn bulk_insert_token_index_common(
+        table: &str,
         batch_size: usize,
         client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
         index_entries: &mut Vec<TokenSecondaryIndexEntry>,
-        query: &Statement,
+        query: &PluginStatement,
+        sort_batch_rows: bool,
     ) -> Result<(), GeyserPluginError> {
         if index_entries.len() == batch_size {
+            if sort_batch_rows {
+                sort_token_index_batch(index_entries);
+            }
             let mut measure = Measure::start("geyser-plugin-postgres-prepare-index-values");
 
             let mut values: Vec<&(dyn types::ToSql + Sync)> =
@@ -159,11 +203,23 @@ impl SimplePostgresClient {
             );
 
             let mut measure = Measure::start("geyser-plugin-postgres-update-index-account");
-            let result = client.query(query, &values);
+            debug_assert_eq!(values.len(), query.param_count());
+            let result = query.query(client, types, &values);
+            measure.stop();
+            let bytes = index_entries
+                .iter()
+                .map(|index| {
+                    index.secondary_key.len()
+                        + index.account_key.len()
+                        + TOKEN_INDEX_ROW_OVERHEAD_BYTES
+                })
+                .sum();
+            report_batch_commit(table, batch_size, bytes, measure.as_us(), result.is_ok());
 
             index_entries.clear();
 
             if let Err(err) = result {
+                report_deadlock(&err);
                 let msg = format!(
                     "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
                     err
@@ -172,7 +228,6 @@ impl SimplePostgresClient {
                 return Err(GeyserPluginError::AccountsUpdateError { msg });
             }
 
-            measure.stop();
             inc_new_counter_debug!(
                 "geyser-plugin-postgres-update-index-us",
                 measure.as_us() as usize,
@@ -197,10 +252,13 @@ impl SimplePostgresClient {
         }
         let query = client.bulk_insert_token_owner_index_stmt.as_ref().unwrap();
         Self::bulk_insert_token_index_common(
-            self.batch_size,
+            "spl_token_owner_index",
+            self.index_batch_size,
             &mut client.client,
+            &client.types,
             &mut self.pending_token_owner_index,
             query,
+            self.sort_batch_rows,
         )
     }
 
@@ -212,10 +270,13 @@ impl SimplePostgresClient {
         }
         let query = client.bulk_insert_token_mint_index_stmt.as_ref().unwrap();
         Self::bulk_insert_token_index_common(
-            self.batch_size,
+            "spl_token_mint_index",
+            self.index_batch_size,
             &mut client.client,
+            &client.types,
             &mut self.pending_token_mint_index,
             query,
+            self.sort_batch_rows,
         )
     }
 
@@ -287,7 +348,8 @@ impl SimplePostgresClient {
     /// Generic function to update a single token owner index.
     fn update_token_owner_index_generic<G: GenericTokenAccount>(
         client: &mut Client,
-        statement: &Statement,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
         token_id: &Pubkey,
         account: &DbAccountInfo,
     ) -> Result<(), GeyserPluginError> {
@@ -296,7 +358,7 @@ impl SimplePostgresClient {
                 let owner_key = owner_key.as_ref().to_vec();
                 let pubkey = account.pubkey();
                 let slot = account.slot;
-                let result = client.execute(statement, &[&owner_key, &pubkey, &slot]);
+                let result = statement.execute(client, types, &[&owner_key, &pubkey, &slot]);
                 if let Err(err) = result {
                     let msg = format!(
                         "Failed to update the token owner index to the PostgreSQL database. Error: {:?}",
@@ -314,7 +376,8 @@ impl SimplePostgresClient {
     /// Generic function to update a single token mint index.
     fn update_token_mint_index_generic<G: GenericTokenAccount>(
         client: &mut Client,
-        statement: &Statement,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
         token_id: &Pubkey,
         account: &DbAccountInfo,
     ) -> Result<(), GeyserPluginError> {
@@ -323,7 +386,7 @@ impl SimplePostgresClient {
                 let mint_key = mint_key.as_ref().to_vec();
                 let pubkey = account.pubkey();
                 let slot = account.slot;
-                let result = client.execute(statement, &[&mint_key, &pubkey, &slot]);
+                let result = statement.execute(client, types, &[&mint_key, &pubkey, &slot]);
                 if let Err(err) = result {
                     let msg = format!(
                         "Failed to update the token mint index to the PostgreSQL database. Error: {:?}",
@@ -341,11 +404,13 @@ impl SimplePostgresClient {
     /// Function for updating a single token owner index.
     pub fn update_token_owner_index(
         client: &mut Client,
-        statement: &Statement,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
         account: &DbAccountInfo,
     ) -> Result<(), GeyserPluginError> {
         Self::update_token_owner_index_generic::<inline_spl_token::Account>(
             client,
+            types,
             statement,
             &inline_spl_token::id(),
             account,
@@ -353,6 +418,7 @@ impl SimplePostgresClient {
 
         Self::update_token_owner_index_generic::<inline_spl_token_2022::Account>(
             client,
+            types,
             statement,
             &inline_spl_token_2022::id(),
             account,
@@ -362,11 +428,13 @@ impl SimplePostgresClient {
     /// Function for updating a single token mint index.
     pub fn update_token_mint_index(
         client: &mut Client,
-        statement: &Statement,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
         account: &DbAccountInfo,
     ) -> Result<(), GeyserPluginError> {
         Self::update_token_mint_index_generic::<inline_spl_token::Account>(
             client,
+            types,
             statement,
             &inline_spl_token::id(),
             account,
@@ -374,17 +442,64 @@ impl SimplePostgresClient {
 
         Self::update_token_mint_index_generic::<inline_spl_token_2022::Account>(
             client,
+            types,
             statement,
             &inline_spl_token_2022::id(),
             account,
         )
     }
 
-    /// Clean up the buffered indexes -- we do not need to
-    /// write them to disk individually as they have already been handled
-    /// when the accounts were flushed out individually in `upsert_account_internal`.
-    pub fn clear_buffered_indexes(&mut self) {
-        self.pending_token_owner_index.clear();
-        self.pending_token_mint_index.clear();
+    /// Write the buffered index entries left over from the bulk inserts individually. With
+    /// `index_batch_size` independent of the accounts `batch_size`, the entries can be of the
+    /// accounts already bulk inserted, which are not flushed out in `upsert_account_internal`.
+    pub fn flush_buffered_indexes(&mut self) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        for (statement, index_entries) in [
+            (
+                &client.insert_token_owner_index_stmt,
+                &mut self.pending_token_owner_index,
+            ),
+            (
+                &client.insert_token_mint_index_stmt,
+                &mut self.pending_token_mint_index,
+            ),
+        ] {
+            if let Some(statement) = statement {
+                for index in index_entries.iter() {
+                    let result = statement.execute(
+                        &mut client.client,
+                        &client.types,
+                        &[&index.secondary_key, &index.account_key, &index.slot],
+                    );
+                    if let Err(err) = result {
+                        let msg = format!(
+                            "Failed to flush the token index to the PostgreSQL database. Error: {:?}",
+                            err
+                        );
+                        error!("{}", msg);
+                        return Err(GeyserPluginError::AccountsUpdateError { msg });
+                    }
+                }
+            }
+            index_entries.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::check_bulk_statement_placeholders};
+
+    #[test]
+    fn test_bulk_token_index_insert_placeholders() {
+        for batch_size in [1, 2, 10, 100] {
+            let stmt = SimplePostgresClient::build_bulk_token_index_insert_sql(
+                "spl_token_owner_index",
+                "owner_key",
+                batch_size,
+            );
+            check_bulk_statement_placeholders(&stmt, batch_size * TOKEN_INDEX_COLUMN_COUNT);
+        }
     }
 }
@@ -0,0 +1,155 @@
+/// Module responsible for rebuilding the account table from the account_audit table.
+use {
+    super::SimplePostgresClient, crate::geyser_plugin_postgres::AccountRebuildConfig, log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+};
+
+const DEFAULT_ACCOUNT_REBUILD_BATCH_SIZE: i64 = 10_000;
+
+impl SimplePostgresClient {
+    /// Restore each account updated in the slot range to its latest audited state, the row
+    /// with the highest (slot, write_version). The accounts are processed in batches ordered
+    /// by the pubkey. Returns the number of accounts restored.
+    pub fn rebuild_account_from_audit(
+        &mut self,
+        rebuild_config: &AccountRebuildConfig,
+    ) -> Result<u64, GeyserPluginError> {
+        // The pubkeys returned by the insert are not ordered, the cursor is the last of them.
+        let stmt = format!("WITH restored AS (\
+        INSERT INTO {account} AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on, pre_lamports, data_len, search_text, write_lag_ms, data_chunk_count, slot_status, account_type, rent_exempt) \
+        SELECT DISTINCT ON (pubkey) pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on, pre_lamports, data_len, search_text, write_lag_ms, data_chunk_count, slot_status, account_type, rent_exempt \
+        FROM {account_audit} WHERE slot >= $1 AND slot <= $2 AND pubkey > $3 \
+        ORDER BY pubkey, slot DESC, write_version DESC LIMIT $4 \
+        ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, \
+        executable=excluded.executable, rent_epoch=excluded.rent_epoch, data=excluded.data, \
+        write_version=excluded.write_version, updated_on=excluded.updated_on, pre_lamports=excluded.pre_lamports, \
+        data_len=excluded.data_len, search_text=excluded.search_text, write_lag_ms=excluded.write_lag_ms, data_chunk_count=excluded.data_chunk_count, \
+        slot_status=excluded.slot_status, account_type=excluded.account_type, rent_exempt=excluded.rent_exempt \
+        RETURNING acct.pubkey) \
+        SELECT count(*), (SELECT pubkey FROM restored ORDER BY pubkey DESC LIMIT 1) FROM restored",
+            account = Self::table_name(&self.table_names, "account"),
+            account_audit = Self::table_name(&self.table_names, "account_audit"));
+
+        let start_slot = rebuild_config.start_slot as i64;
+        let end_slot = rebuild_config.end_slot as i64;
+        let batch_size = rebuild_config
+            .batch_size
+            .map(|batch_size| batch_size as i64)
+            .unwrap_or(DEFAULT_ACCOUNT_REBUILD_BATCH_SIZE);
+        info!(
+            "Rebuilding the account table from account_audit for slots [{}, {}]",
+            start_slot, end_slot
+        );
+
+        let statement = self.prepare_cached_statement(&stmt)?;
+        let client = self.client.get_mut().unwrap();
+
+        let mut measure = Measure::start("geyser-plugin-postgres-rebuild-account-us");
+        let mut cursor: Vec<u8> = Vec::default();
+        let mut restored_count = 0u64;
+        loop {
+            let rows = statement
+                .query(
+                    &mut client.client,
+                    &client.types,
+                    &[&start_slot, &end_slot, &cursor, &batch_size],
+                )
+                .map_err(|err| {
+                    let msg = format!(
+                        "Failed to rebuild the accounts from account_audit. Error: {:?}",
+                        err
+                    );
+                    error!("{}", msg);
+                    GeyserPluginError::AccountsUpdateError { msg }
+                })?;
+            let row = &rows[0];
+            let batch_count: i64 = row.get(0);
+            restored_count += batch_count as u64;
+            match row.get::<_, Option<Vec<u8>>>(1) {
+                Some(last_pubkey) if batch_count == batch_size => cursor = last_pubkey,
+                _ => break,
+            }
+            info!(
+                "Rebuilt {} accounts from account_audit, up to pubkey {}",
+                restored_count,
+                bs58::encode(&cursor).into_string()
+            );
+        }
+        measure.stop();
+
+        info!(
+            "Rebuilt {} accounts from account_audit for slots [{}, {}] in {}us",
+            restored_count,
+            start_slot,
+            end_slot,
+            measure.as_us()
+        );
+        Ok(restored_count)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_rebuild_account_from_audit() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "store_account_historical_data": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let pubkeys: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        // The updates in slot 10 are audited when overwritten in slot 20.
+        for slot in [10, 20] {
+            for pubkey in &pubkeys {
+                let mut account = build_account(&owner, vec![slot as u8], slot);
+                account.pubkey = pubkey.as_ref().to_vec();
+                client.update_account(account, false).unwrap();
+            }
+        }
+        assert_eq!(db.count("account_audit"), 5);
+
+        let rebuild_config = AccountRebuildConfig {
+            start_slot: 0,
+            end_slot: 15,
+            batch_size: Some(2),
+        };
+        assert_eq!(
+            client.rebuild_account_from_audit(&rebuild_config).unwrap(),
+            5
+        );
+
+        let slots: Vec<i64> = db
+            .client
+            .query("SELECT slot FROM account", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(slots, vec![10; 5]);
+
+        // No audit rows in the slot range.
+        let rebuild_config = AccountRebuildConfig {
+            start_slot: 30,
+            end_slot: 40,
+            batch_size: None,
+        };
+        assert_eq!(
+            client.rebuild_account_from_audit(&rebuild_config).unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,146 @@
+/// Module responsible for writing the accounts routed by the entries of the accounts_selector to
+/// their target tables, such as the token accounts to a token_account table.
+///
+/// The accounts_selector can be an array of entries, each selecting accounts like the
+/// accounts_selector section and optionally naming the target_table of its accounts. An account
+/// is routed by the first entry selecting it, to the account table when that entry has no
+/// target_table. The target tables must have the columns of the account table written and its
+/// primary key on pubkey, such as the tables created LIKE the account table. The routed accounts
+/// are upserted one by one, at startup too, with the upsert statement of their table; they are
+/// not stored in the account_audit table by the plugin, nor staged in the staging table mode.
+use {
+    super::{
+        postgres_client_table_names::is_valid_table_name, DbAccountInfo, SimplePostgresClient,
+    },
+    crate::{
+        accounts_selector::AccountsSelector, geyser_plugin_postgres::GeyserPluginPostgresError,
+    },
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+impl SimplePostgresClient {
+    /// Check the target tables of the accounts_selector have valid names.
+    pub(crate) fn check_account_target_tables(
+        accounts_selector: &AccountsSelector,
+    ) -> Result<(), GeyserPluginError> {
+        match accounts_selector
+            .target_tables()
+            .find(|target_table| !is_valid_table_name(target_table))
+        {
+            Some(target_table) => {
+                let msg = format!(
+                    "The \"target_table\" {:?} in \"accounts_selector\" is not a plain identifier",
+                    target_table
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::ConfigurationError { msg },
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Upsert the account into the target table it is routed to.
+    pub(crate) fn upsert_routed_account(
+        &mut self,
+        account: &DbAccountInfo,
+        target_table: &str,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        let stmt = self
+            .batch_splitter
+            .bulk_insert_sql_into(target_table, 1, &self.account_columns);
+        let statement = self.prepare_cached_statement(&stmt)?;
+        let client = self.client.get_mut().unwrap();
+        // The accounts written at startup are not closes.
+        let insert_account_close_stmt = if is_startup {
+            &None
+        } else {
+            &client.insert_account_close_stmt
+        };
+        Self::upsert_account_internal(
+            account,
+            &statement,
+            &mut client.client,
+            &client.types,
+            &None,
+            &client.insert_token_owner_index_stmt,
+            &client.insert_token_mint_index_stmt,
+            &client.upsert_stake_account_index_stmt,
+            &client.upsert_metaplex_metadata_index_stmt,
+            &client.upsert_token_delegate_index_stmt,
+            &client.upsert_token_state_index_stmt,
+            &client.upsert_program_link_stmt,
+            insert_account_close_stmt,
+            &self.account_columns,
+            &self.slot_clock,
+            &self.slot_statuses,
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn test_check_account_target_tables() {
+        let build_selector = |target_table: &str| {
+            AccountsSelector::from_entries(vec![(
+                AccountsSelector::new(&["*".to_string()], &[]),
+                Some(Arc::from(target_table)),
+            )])
+        };
+        SimplePostgresClient::check_account_target_tables(&build_selector("token_account"))
+            .unwrap();
+        assert!(
+            SimplePostgresClient::check_account_target_tables(&build_selector(
+                "token_account; DROP TABLE slot"
+            ))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_write_routed_accounts() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute("CREATE TABLE token_account (LIKE account INCLUDING ALL)")
+            .unwrap();
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let mut routed_account = build_account(&owner, vec![1], 1);
+        routed_account.target_table = Some(Arc::from("token_account"));
+        client.update_account(routed_account.clone(), true).unwrap();
+        client
+            .update_account(build_account(&owner, vec![], 1), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+        routed_account.slot = 2;
+        routed_account.data = vec![2];
+        client.update_account(routed_account, false).unwrap();
+
+        assert_eq!(db.count("account"), 1);
+        let data: Vec<u8> = db
+            .client
+            .query_one("SELECT data FROM token_account", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(data, vec![2]);
+    }
+}
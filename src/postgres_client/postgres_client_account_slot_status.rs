@@ -0,0 +1,201 @@
+/// Module responsible for the slot_status column of the account tables, the best-known status
+/// of the slot of each account update, when record_account_slot_status is set.
+///
+/// The account updates are notified while their slot is replayed, before its status, so the
+/// rows are written with the status of their slot known to the workers at that time, processed
+/// when none is. When a slot is confirmed or rooted, the rows of the account table at that slot
+/// with a lower status are updated to it, without auditing them. The workers take the
+/// notifications from a shared queue, so a row committed concurrently with the update of the
+/// status of its slot may keep the lower status. The statuses are kept for
+/// SLOT_STATUS_RETAINED_SLOTS slots below the newest root. The account_audit rows keep the
+/// status of the replaced row.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, StatementError},
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    std::{
+        collections::BTreeMap,
+        sync::{Arc, RwLock},
+    },
+};
+
+/// The statuses of the slots in the slot_status column, ordered by their progress.
+const SLOT_STATUSES: [&str; 3] = ["processed", "confirmed", "rooted"];
+const SLOT_STATUS_RETAINED_SLOTS: u64 = 1024;
+
+fn slot_status_rank(status: SlotStatus) -> usize {
+    match status {
+        SlotStatus::Processed => 0,
+        SlotStatus::Confirmed => 1,
+        SlotStatus::Rooted => 2,
+    }
+}
+
+/// The best-known statuses of the recent slots, shared by the workers.
+#[derive(Default)]
+pub(crate) struct SlotStatuses {
+    /// The rank of the status in SLOT_STATUSES by the slot.
+    ranks: RwLock<BTreeMap<u64, usize>>,
+}
+
+impl SlotStatuses {
+    /// Record the status of the slot, kept when it is further than the one known.
+    pub fn record_status(&self, slot: u64, status: SlotStatus) {
+        let rank = slot_status_rank(status);
+        let mut ranks = self.ranks.write().unwrap();
+        let known_rank = ranks.entry(slot).or_default();
+        *known_rank = rank.max(*known_rank);
+        if let SlotStatus::Rooted = status {
+            *ranks = ranks.split_off(&slot.saturating_sub(SLOT_STATUS_RETAINED_SLOTS));
+        }
+    }
+
+    /// The best-known status of the slot.
+    pub fn status(&self, slot: i64) -> &'static str {
+        let rank = self.ranks.read().unwrap().get(&(slot as u64)).copied();
+        SLOT_STATUSES[rank.unwrap_or_default()]
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_account_slot_status_update_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let statuses = format!("ARRAY['{}']", SLOT_STATUSES.join("', '"));
+        let stmt = format!(
+            "UPDATE {} SET slot_status = $1 WHERE slot = $2 \
+            AND COALESCE(array_position({statuses}, slot_status), 0) < array_position({statuses}, $1::VARCHAR)",
+            Self::table_name(&config.table_names, "account"),
+            statuses = statuses
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Share the statuses of the slots of the workers with the client.
+    pub(crate) fn set_slot_statuses(&mut self, slot_statuses: Option<Arc<SlotStatuses>>) {
+        self.slot_statuses = slot_statuses;
+    }
+
+    /// Record the status of the slot, updating the slot_status of the accounts written at it.
+    pub(crate) fn update_account_slot_status(
+        &mut self,
+        slot: u64,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        if let Some(slot_statuses) = &self.slot_statuses {
+            slot_statuses.record_status(slot, status);
+        }
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.update_account_slot_status_stmt {
+            Some(statement) if !matches!(status, SlotStatus::Processed) => statement,
+            _ => return Ok(()),
+        };
+
+        let slot = slot as i64;
+        let status_str = status.as_str();
+        let result = client
+            .client
+            .transaction()
+            .map_err(StatementError::from)
+            .and_then(|mut transaction| {
+                // Only the status of the rows is changed, they are not replaced.
+                transaction.batch_execute("SET LOCAL geyser_plugin.skip_account_audit = on")?;
+                statement.execute(&mut transaction, &client.types, &[&status_str, &slot])?;
+                Ok(transaction.commit()?)
+            });
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to update the slot_status of the accounts at slot {} to the PostgreSQL database. Error: {:?}",
+                slot, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_slot_statuses() {
+        let slot_statuses = SlotStatuses::default();
+        assert_eq!(slot_statuses.status(10), "processed");
+        slot_statuses.record_status(10, SlotStatus::Rooted);
+        // A late notification does not set the status back.
+        slot_statuses.record_status(10, SlotStatus::Confirmed);
+        assert_eq!(slot_statuses.status(10), "rooted");
+
+        slot_statuses.record_status(11, SlotStatus::Confirmed);
+        slot_statuses.record_status(10 + SLOT_STATUS_RETAINED_SLOTS + 1, SlotStatus::Rooted);
+        assert_eq!(slot_statuses.status(10), "processed");
+        assert_eq!(slot_statuses.status(11), "confirmed");
+    }
+
+    #[test]
+    fn test_record_account_slot_status() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "record_account_slot_status": true,
+            "store_account_historical_data": true,
+        }));
+        SimplePostgresClient::check_schema_columns(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client.set_slot_statuses(Some(Arc::new(SlotStatuses::default())));
+        let owner = Pubkey::new_unique();
+        for slot in [1, 2] {
+            client
+                .update_account(build_account(&owner, vec![], slot), false)
+                .unwrap();
+        }
+        client
+            .update_slot_status(1, None, SlotStatus::Confirmed)
+            .unwrap();
+        client
+            .update_account(build_account(&owner, vec![], 1), false)
+            .unwrap();
+        client
+            .update_slot_status(1, None, SlotStatus::Rooted)
+            .unwrap();
+
+        let statuses: Vec<(i64, String)> = db
+            .client
+            .query(
+                "SELECT slot, slot_status FROM account ORDER BY slot, slot_status",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (1, "rooted".to_string()),
+                (1, "rooted".to_string()),
+                (2, "processed".to_string()),
+            ]
+        );
+        assert_eq!(db.count("account_audit"), 0);
+    }
+}
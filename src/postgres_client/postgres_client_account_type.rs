@@ -0,0 +1,159 @@
+/// Module responsible for classifying the accounts written by their owner and data layout, into
+/// the account_type column of the account and account_audit tables, when classify_account_type
+/// is set, so the analysts may filter them with WHERE account_type = 'token'.
+///
+/// The classification is heuristic, from the owner and the length or the type byte of the data,
+/// without deserializing it:
+/// * 'program': the executable accounts, and the accounts owned by the BPF loaders, such as the
+///   programdata and buffer accounts.
+/// * 'token' and 'mint': the SPL Token and SPL Token 2022 accounts and mints, by the length of
+///   their data, or by the account type byte of the Token 2022 accounts with extensions.
+/// * 'stake' and 'vote': the accounts owned by the Stake and Vote programs.
+/// * 'unknown': any other account, including the other accounts of the token programs, such as
+///   the multisigs.
+use {
+    super::DbAccountInfo,
+    crate::{
+        inline_spl_token::{self, GenericTokenAccount},
+        inline_spl_token_2022, inline_stake_program,
+    },
+    solana_sdk::{bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable, vote},
+};
+
+/// The length of the data of the SPL Token mints.
+const SPL_TOKEN_MINT_LENGTH: usize = 82;
+/// The `spl_token_program_2022::extension::AccountType::Mint` ordinal value.
+const ACCOUNTTYPE_MINT: u8 = 1;
+
+/// Whether the data of the token program account is that of a mint.
+fn is_token_mint(data: &[u8], is_token_2022: bool) -> bool {
+    data.len() == SPL_TOKEN_MINT_LENGTH
+        || (is_token_2022
+            && data.get(inline_spl_token::Account::get_packed_len()) == Some(&ACCOUNTTYPE_MINT))
+}
+
+/// The type of the account, from its owner and the layout of its data.
+pub(crate) fn classify_account_type(account: &DbAccountInfo) -> &'static str {
+    let owner = account.owner.as_slice();
+    let data = account.data.as_slice();
+    if account.executable
+        || owner == bpf_loader::id().as_ref()
+        || owner == bpf_loader_deprecated::id().as_ref()
+        || owner == bpf_loader_upgradeable::id().as_ref()
+    {
+        "program"
+    } else if owner == inline_spl_token::id().as_ref() {
+        if inline_spl_token::Account::valid_account_data(data) {
+            "token"
+        } else if is_token_mint(data, false) {
+            "mint"
+        } else {
+            "unknown"
+        }
+    } else if owner == inline_spl_token_2022::id().as_ref() {
+        if inline_spl_token_2022::Account::valid_account_data(data) {
+            "token"
+        } else if is_token_mint(data, true) {
+            "mint"
+        } else {
+            "unknown"
+        }
+    } else if owner == inline_stake_program::id().as_ref() {
+        "stake"
+    } else if owner == vote::program::id().as_ref() {
+        "vote"
+    } else {
+        "unknown"
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient, SimplePostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_classify_account_type() {
+        let classify =
+            |owner: &Pubkey, data: Vec<u8>| classify_account_type(&build_account(owner, data, 1));
+        assert_eq!(classify(&inline_spl_token::id(), vec![0; 165]), "token");
+        assert_eq!(classify(&inline_spl_token::id(), vec![0; 82]), "mint");
+        assert_eq!(classify(&inline_spl_token::id(), vec![0; 355]), "unknown");
+        let mut data = vec![0; 200];
+        data[165] = 2;
+        assert_eq!(
+            classify(&inline_spl_token_2022::id(), data.clone()),
+            "token"
+        );
+        data[165] = ACCOUNTTYPE_MINT;
+        assert_eq!(classify(&inline_spl_token_2022::id(), data), "mint");
+        assert_eq!(classify(&inline_stake_program::id(), vec![0; 200]), "stake");
+        assert_eq!(classify(&vote::program::id(), vec![0; 3731]), "vote");
+        assert_eq!(
+            classify(&bpf_loader_upgradeable::id(), vec![3, 0, 0, 0]),
+            "program"
+        );
+        assert_eq!(classify(&Pubkey::new_unique(), vec![1]), "unknown");
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 1);
+        account.executable = true;
+        assert_eq!(classify_account_type(&account), "program");
+    }
+
+    #[test]
+    fn test_account_type_column() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 2,
+            "classify_account_type": true,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        // The bulk insert at startup, then the single upsert.
+        let mint = build_account(&inline_spl_token::id(), vec![0; 82], 1);
+        client.update_account(mint.clone(), true).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![], 1), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+        let stake = build_account(&inline_stake_program::id(), vec![0; 200], 2);
+        client.update_account(stake.clone(), false).unwrap();
+        let mut mint = mint;
+        mint.slot = 3;
+        client.update_account(mint, false).unwrap();
+
+        let account_types: Vec<(String, i64)> = db
+            .client
+            .query(
+                "SELECT account_type, count(*) FROM account GROUP BY account_type ORDER BY account_type",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(
+            account_types,
+            vec![
+                ("mint".to_string(), 1),
+                ("stake".to_string(), 1),
+                ("unknown".to_string(), 1)
+            ]
+        );
+        let row = db
+            .client
+            .query_one("SELECT account_type FROM account_audit", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, String>(0), "mint");
+    }
+}
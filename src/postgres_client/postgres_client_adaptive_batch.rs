@@ -0,0 +1,256 @@
+/// Module responsible for switching the account updates after startup between the single-row
+/// upserts and the bulk inserts by their arrival rate.
+///
+/// Below adaptive_bulk_threshold_per_sec, each account update is upserted as it arrives, for
+/// the lowest latency. Above it, the updates are buffered and bulk inserted in batches of
+/// batch_size, for the throughput. Each worker measures its rate over windows of
+/// RATE_WINDOW_MS, and leaves the bulk mode only when the rate falls
+/// adaptive_bulk_hysteresis_percent below the threshold, so a rate around the threshold does
+/// not flap between the modes. The buffered updates are written when the mode switches back,
+/// when the worker is idle and when it exits.
+///
+/// The token indexes, the stake index and the program links of the buffered updates are
+/// written one by one as they arrive, with the single-row statements. A later update of an
+/// account replaces the one buffered, as a bulk insert cannot update the same row twice.
+use {
+    super::{DbAccountInfo, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{
+        iter,
+        time::{Duration, Instant},
+    },
+};
+
+const RATE_WINDOW_MS: u64 = 1000;
+const DEFAULT_HYSTERESIS_PERCENT: u64 = 20;
+
+pub(crate) struct AdaptiveBatching {
+    /// The rate per second above which the updates are bulk inserted.
+    enter_bulk_rate: u64,
+    /// The rate per second below which the updates are upserted one by one again.
+    leave_bulk_rate: u64,
+    window_start: Instant,
+    window_count: u64,
+    is_bulk: bool,
+}
+
+impl AdaptiveBatching {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Option<Self> {
+        let threshold = config.adaptive_bulk_threshold_per_sec?;
+        // The account_audit rows, the pre_lamports and the closes of the replaced updates would
+        // be lost, and the staging tables are written one row at a time.
+        if matches!(config.store_account_historical_data, Some(true))
+            || SimplePostgresClient::get_track_lamport_deltas(config)
+            || SimplePostgresClient::get_track_account_closes(config)
+            || SimplePostgresClient::is_staging_table_mode(config)
+        {
+            warn!(
+                "\"adaptive_bulk_threshold_per_sec\" is ignored with \"store_account_historical_data\", \
+                \"track_lamport_deltas\", \"track_account_closes\" or the staging table mode"
+            );
+            return None;
+        }
+        let hysteresis_percent = config
+            .adaptive_bulk_hysteresis_percent
+            .unwrap_or(DEFAULT_HYSTERESIS_PERCENT)
+            .min(100);
+        Some(Self {
+            enter_bulk_rate: threshold,
+            leave_bulk_rate: threshold * (100 - hysteresis_percent) / 100,
+            window_start: Instant::now(),
+            window_count: 0,
+            is_bulk: false,
+        })
+    }
+
+    /// Count an account update arriving, returns whether it is to be bulk inserted.
+    pub fn record_arrival(&mut self, now: Instant) -> bool {
+        self.window_count += 1;
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed >= Duration::from_millis(RATE_WINDOW_MS) {
+            let rate = self.window_count * 1000 / elapsed.as_millis() as u64;
+            let is_bulk = if self.is_bulk {
+                rate >= self.leave_bulk_rate
+            } else {
+                rate > self.enter_bulk_rate
+            };
+            if is_bulk != self.is_bulk {
+                info!(
+                    "Switching the account updates to the {} at {} updates per second",
+                    if is_bulk {
+                        "bulk inserts"
+                    } else {
+                        "single-row upserts"
+                    },
+                    rate
+                );
+                self.is_bulk = is_bulk;
+            }
+            self.window_start = now;
+            self.window_count = 0;
+        }
+        self.is_bulk
+    }
+}
+
+impl SimplePostgresClient {
+    /// Update the account after startup, upserted alone or buffered for the bulk insert by the
+    /// arrival rate of the updates.
+    pub(crate) fn update_account_adaptive(
+        &mut self,
+        account: DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let is_bulk = match &mut self.adaptive_batching {
+            // The accounts left from startup are written first, by flush_buffered_writes.
+            Some(adaptive_batching) if self.slots_at_startup.is_empty() => {
+                adaptive_batching.record_arrival(Instant::now())
+            }
+            _ => false,
+        };
+        if !is_bulk {
+            self.flush_adaptive_batch()?;
+            return self.upsert_account(&account);
+        }
+
+        self.upsert_account_indexes(&account)?;
+        match self
+            .pending_account_updates
+            .iter_mut()
+            .find(|pending| pending.pubkey == account.pubkey)
+        {
+            Some(pending) => {
+                if (account.slot, account.write_version) > (pending.slot, pending.write_version) {
+                    *pending = account;
+                }
+            }
+            None => self.pending_account_updates.push(account),
+        }
+        self.bulk_insert_accounts()
+    }
+
+    /// Write the indexes of the account with the single-row statements.
+    fn upsert_account_indexes(&mut self, account: &DbAccountInfo) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        if let Some(statement) = &client.insert_token_owner_index_stmt {
+            Self::update_token_owner_index(&mut client.client, &client.types, statement, account)?;
+        }
+        if let Some(statement) = &client.insert_token_mint_index_stmt {
+            Self::update_token_mint_index(&mut client.client, &client.types, statement, account)?;
+        }
+        self.upsert_stake_account_index(account)?;
+        self.upsert_token_field_indexes(account)?;
+        self.upsert_program_link(account)
+    }
+
+    /// Bulk insert the account updates buffered in the adaptive bulk mode.
+    pub(crate) fn flush_adaptive_batch(&mut self) -> Result<(), GeyserPluginError> {
+        if self.adaptive_batching.is_none()
+            || !self.slots_at_startup.is_empty()
+            || self.pending_account_updates.is_empty()
+        {
+            return Ok(());
+        }
+        self.sort_pending_account_updates();
+        let sub_batches = self
+            .batch_splitter
+            .split(&self.pending_account_updates)
+            .unwrap_or_else(|| iter::once(0..self.pending_account_updates.len()).collect());
+        let result = self.bulk_insert_account_sub_batches(&sub_batches);
+        self.pending_account_updates.clear();
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_adaptive_batching_hysteresis() {
+        let config = serde_json::from_value(json!({
+            "adaptive_bulk_threshold_per_sec": 100,
+            "adaptive_bulk_hysteresis_percent": 20,
+        }))
+        .unwrap();
+        let mut adaptive_batching = AdaptiveBatching::new(&config).unwrap();
+        let start = adaptive_batching.window_start;
+        let mut arrive = |count: u64, secs: u64| {
+            for _ in 1..count {
+                adaptive_batching.record_arrival(start);
+            }
+            adaptive_batching.record_arrival(start + Duration::from_secs(secs))
+        };
+
+        assert!(!arrive(100, 1));
+        assert!(arrive(150, 2));
+        // Below the threshold, but not below the hysteresis band.
+        assert!(arrive(90, 3));
+        assert!(arrive(80, 4));
+        assert!(!arrive(79, 5));
+        assert!(!arrive(90, 6));
+
+        let config = serde_json::from_value(json!({
+            "adaptive_bulk_threshold_per_sec": 100,
+            "store_account_historical_data": true,
+        }))
+        .unwrap();
+        assert!(AdaptiveBatching::new(&config).is_none());
+    }
+
+    #[test]
+    fn test_adaptive_bulk_insert() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 3,
+            "adaptive_bulk_threshold_per_sec": 100,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client.adaptive_batching.as_mut().unwrap().is_bulk = true;
+
+        let owner = Pubkey::new_unique();
+        let account = build_account(&owner, vec![1], 1);
+        let mut newer = account.clone();
+        newer.slot = 2;
+        newer.data = vec![2];
+        client.update_account(account.clone(), false).unwrap();
+        client.update_account(newer, false).unwrap();
+        client
+            .update_account(build_account(&owner, vec![3], 1), false)
+            .unwrap();
+        assert_eq!(client.pending_account_updates.len(), 2);
+        assert_eq!(db.count("account"), 0);
+
+        client.flush_adaptive_batch().unwrap();
+        assert_eq!(db.count("account"), 2);
+        let data: Vec<u8> = db
+            .client
+            .query_one(
+                "SELECT data FROM account WHERE pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(data, vec![2]);
+
+        // A full batch is bulk inserted as it fills up.
+        for data in 4..7 {
+            client
+                .update_account(build_account(&owner, vec![data], 1), false)
+                .unwrap();
+        }
+        assert_eq!(db.count("account"), 5);
+    }
+}
@@ -0,0 +1,107 @@
+/// Module responsible for capping the bytes of data kept in the account_audit rows of each
+/// account, for the accounts rewritten constantly with large data.
+///
+/// The account_audit rows are pruned as the slots are rooted, along with the retention by owner
+/// and on its audit_retention_interval_slots schedule, rather than as each row is inserted, so
+/// the writes do not read the history of their account. Each pruning looks at the accounts with
+/// audit rows of the slots since the previous pruning, and once the data of the audit rows of
+/// such an account, summed from the newest row by (slot, write_version), exceeds
+/// max_audit_bytes_per_account, removes the older rows, in a single statement. The history of
+/// an account may thus exceed the cap until the next pruning.
+use {
+    super::SimplePostgresClient, log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+impl SimplePostgresClient {
+    /// Remove the oldest account_audit rows of the accounts audited since the slot, beyond the
+    /// bytes of data kept per account. Returns the number of rows removed.
+    pub(crate) fn prune_audit_bytes(
+        &mut self,
+        since_slot: u64,
+        max_bytes: u64,
+    ) -> Result<u64, GeyserPluginError> {
+        let stmt = format!(
+            "DELETE FROM {account_audit} WHERE ctid IN (SELECT row_id FROM (\
+            SELECT ctid AS row_id, sum(coalesce(length(data), 0)) OVER (PARTITION BY pubkey ORDER BY slot DESC, write_version DESC) AS total_bytes \
+            FROM {account_audit} WHERE pubkey IN (SELECT pubkey FROM {account_audit} WHERE slot >= $1)) history \
+            WHERE total_bytes > $2)",
+            account_audit = Self::table_name(&self.table_names, "account_audit")
+        );
+        let statement = self.prepare_cached_statement(&stmt)?;
+        let client = self.client.get_mut().unwrap();
+        let since_slot = since_slot as i64;
+        let max_bytes = max_bytes as i64;
+        statement
+            .execute(&mut client.client, &client.types, &[&since_slot, &max_bytes])
+            .map_err(|err| {
+                let msg = format!(
+                    "Failed to prune the account_audit rows beyond {} bytes per account in the PostgreSQL database. Error: {:?}",
+                    max_bytes, err
+                );
+                error!("{}", msg);
+                GeyserPluginError::SlotStatusUpdateError { msg }
+            })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            postgres_client_audit_retention::AuditRetention,
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_max_audit_bytes_per_account() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "store_account_historical_data": true,
+            "max_audit_bytes_per_account": 250,
+            "audit_retention_interval_slots": 100,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client.set_audit_retention(AuditRetention::new(&config).unwrap());
+
+        let owner = Pubkey::new_unique();
+        let account = build_account(&owner, vec![0; 100], 1);
+        let other = build_account(&owner, vec![0; 100], 1);
+        // The updates of slots 1 to 5 are audited when overwritten, the older ones by the
+        // plugin.
+        for slot in [1, 2, 3, 4, 5, 6, 0] {
+            for account in [&account, &other] {
+                let mut account = account.clone();
+                account.slot = slot;
+                client.update_account(account, false).unwrap();
+            }
+        }
+        // The rows are kept until the pruning due at the root.
+        assert_eq!(db.count("account_audit"), 12);
+        client
+            .update_slot_status(6, None, SlotStatus::Rooted)
+            .unwrap();
+
+        let slots: Vec<i64> = db
+            .client
+            .query(
+                "SELECT slot FROM account_audit WHERE pubkey = $1 ORDER BY slot",
+                &[&account.pubkey],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(slots, vec![4, 5]);
+        assert_eq!(db.count("account_audit"), 4);
+    }
+}
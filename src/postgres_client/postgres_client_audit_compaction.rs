@@ -0,0 +1,157 @@
+/// Module responsible for compacting the account_audit table, removing the rows identical to
+/// the preceding version of their accounts.
+///
+/// An audit row is redundant when its owner, lamports, executable, rent_epoch and data are
+/// those of the preceding row of the account by (slot, write_version), such as the updates of
+/// the accounts only touched by the transactions. The first row of each run of identical rows
+/// is kept, so only the genuine transitions remain. The table has no key, the rows are
+/// removed by their ctid.
+use {
+    super::SimplePostgresClient, crate::geyser_plugin_postgres::AuditCompactionConfig, log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+};
+
+const DEFAULT_AUDIT_COMPACTION_BATCH_SIZE: i64 = 10_000;
+
+impl SimplePostgresClient {
+    /// Remove the account_audit rows in the slot range identical to the preceding version of
+    /// their accounts. The accounts are processed in batches ordered by the pubkey, each in its
+    /// own transaction to bound the locks. Returns the number of rows removed.
+    pub fn compact_account_audit(
+        &mut self,
+        compaction_config: &AuditCompactionConfig,
+    ) -> Result<u64, GeyserPluginError> {
+        // The preceding version of the first row in the range can be before the range.
+        let stmt = format!("WITH batch AS (\
+        SELECT DISTINCT pubkey FROM {account_audit} \
+        WHERE slot >= $1 AND slot <= $2 AND pubkey > $3 ORDER BY pubkey LIMIT $4), \
+        versions AS (\
+        SELECT ctid AS row_id, slot, row_number() OVER w > 1 AS has_previous, \
+        (owner, lamports, executable, rent_epoch, data) IS NOT DISTINCT FROM \
+        (lag(owner) OVER w, lag(lamports) OVER w, lag(executable) OVER w, lag(rent_epoch) OVER w, lag(data) OVER w) AS is_unchanged \
+        FROM {account_audit} WHERE pubkey IN (SELECT pubkey FROM batch) \
+        WINDOW w AS (PARTITION BY pubkey ORDER BY slot, write_version)), \
+        removed AS (\
+        DELETE FROM {account_audit} WHERE ctid IN (\
+        SELECT row_id FROM versions WHERE slot >= $1 AND slot <= $2 AND has_previous AND is_unchanged) \
+        RETURNING 1) \
+        SELECT (SELECT count(*) FROM removed), (SELECT count(*) FROM batch), (SELECT pubkey FROM batch ORDER BY pubkey DESC LIMIT 1)",
+            account_audit = Self::table_name(&self.table_names, "account_audit"));
+
+        let start_slot = compaction_config.start_slot as i64;
+        let end_slot = compaction_config.end_slot as i64;
+        let batch_size = compaction_config
+            .batch_size
+            .map(|batch_size| batch_size as i64)
+            .unwrap_or(DEFAULT_AUDIT_COMPACTION_BATCH_SIZE);
+        info!(
+            "Compacting account_audit for slots [{}, {}]",
+            start_slot, end_slot
+        );
+
+        let statement = self.prepare_cached_statement(&stmt)?;
+        let client = self.client.get_mut().unwrap();
+
+        let mut measure = Measure::start("geyser-plugin-postgres-compact-audit-us");
+        let mut cursor: Vec<u8> = Vec::default();
+        let mut removed_count = 0u64;
+        loop {
+            let rows = statement
+                .query(
+                    &mut client.client,
+                    &client.types,
+                    &[&start_slot, &end_slot, &cursor, &batch_size],
+                )
+                .map_err(|err| {
+                    let msg = format!("Failed to compact account_audit. Error: {:?}", err);
+                    error!("{}", msg);
+                    GeyserPluginError::AccountsUpdateError { msg }
+                })?;
+            let row = &rows[0];
+            removed_count += row.get::<_, i64>(0) as u64;
+            let batch_count: i64 = row.get(1);
+            match row.get::<_, Option<Vec<u8>>>(2) {
+                Some(last_pubkey) if batch_count == batch_size => cursor = last_pubkey,
+                _ => break,
+            }
+            info!(
+                "Removed {} rows from account_audit, up to pubkey {}",
+                removed_count,
+                bs58::encode(&cursor).into_string()
+            );
+        }
+        measure.stop();
+
+        info!(
+            "Removed {} rows from account_audit for slots [{}, {}] in {}us",
+            removed_count,
+            start_slot,
+            end_slot,
+            measure.as_us()
+        );
+        Ok(removed_count)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_compact_account_audit() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "store_account_historical_data": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let pubkeys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        // The data of each account changes at slot 30 only, the updates of slots 10 to 40 are
+        // audited when overwritten.
+        for (slot, data) in [(10, 1), (20, 1), (30, 2), (40, 2), (50, 2)] {
+            for pubkey in &pubkeys {
+                let mut account = build_account(&owner, vec![data], slot);
+                account.pubkey = pubkey.as_ref().to_vec();
+                client.update_account(account, false).unwrap();
+            }
+        }
+        assert_eq!(db.count("account_audit"), 12);
+
+        // The slot 40 rows are out of the range.
+        let compaction_config = AuditCompactionConfig {
+            start_slot: 0,
+            end_slot: 35,
+            batch_size: Some(2),
+        };
+        assert_eq!(client.compact_account_audit(&compaction_config).unwrap(), 3);
+        let compaction_config = AuditCompactionConfig {
+            start_slot: 0,
+            end_slot: 50,
+            batch_size: None,
+        };
+        assert_eq!(client.compact_account_audit(&compaction_config).unwrap(), 3);
+
+        let slots: Vec<i64> = db
+            .client
+            .query(
+                "SELECT slot FROM account_audit WHERE pubkey = $1 ORDER BY slot",
+                &[&pubkeys[0].as_ref()],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(slots, vec![10, 30]);
+    }
+}
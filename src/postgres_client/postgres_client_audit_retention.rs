@@ -0,0 +1,287 @@
+/// Module responsible for pruning the account_audit rows by the owner of the accounts, when
+/// audit_retention_by_owner is set.
+///
+/// Each owner program configured keeps the audit rows of its accounts for at most
+/// max_audit_age_slots slots below the newest root, and at most max_audit_versions rows per
+/// account, the newest by (slot, write_version). The owner of an audit row is the owner of that
+/// version of the account. The rows are pruned as the slots are rooted: once the root reaches
+/// the next multiple of audit_retention_interval_slots, the worker notified of it prunes the
+/// rows of each owner in turn, each in its own statement. The account_audit table has no index
+/// on the owner, so each pruning scans it; the interval amortizes the scans. The same pruning
+/// applies max_audit_bytes_per_account, see postgres_client_audit_budget.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{
+        AuditRetentionConfig, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+    },
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+    solana_metrics::*,
+    solana_sdk::pubkey::Pubkey,
+    std::{
+        collections::BTreeMap,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
+};
+
+const DEFAULT_AUDIT_RETENTION_INTERVAL_SLOTS: u64 = 1000;
+
+/// The audit retention of the owners and the audit bytes of the accounts, shared by the workers.
+pub(crate) struct AuditRetention {
+    /// The retention of the accounts by their owner.
+    owners: Vec<(Vec<u8>, AuditRetentionConfig)>,
+    /// The bytes of data kept in the audit rows of each account, when capped.
+    max_bytes_per_account: Option<u64>,
+    interval_slots: u64,
+    /// The root from which the next pruning is due.
+    next_prune_slot: AtomicU64,
+}
+
+impl AuditRetention {
+    pub fn new(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Option<Arc<Self>>, GeyserPluginError> {
+        let audit_retention_by_owner = match &config.audit_retention_by_owner {
+            Some(audit_retention_by_owner) => audit_retention_by_owner.clone(),
+            None => BTreeMap::default(),
+        };
+        let max_bytes_per_account = config.max_audit_bytes_per_account;
+        if audit_retention_by_owner.is_empty() && max_bytes_per_account.is_none() {
+            return Ok(None);
+        }
+        if !matches!(config.store_account_historical_data, Some(true)) {
+            warn!(
+                "\"audit_retention_by_owner\" and \"max_audit_bytes_per_account\" are ignored \
+                without \"store_account_historical_data\""
+            );
+            return Ok(None);
+        }
+        let mut owners = Vec::with_capacity(audit_retention_by_owner.len());
+        for (owner, retention) in &audit_retention_by_owner {
+            let error = match Pubkey::from_str(owner) {
+                Err(_) => Some(format!(
+                    "The owner {:?} in \"audit_retention_by_owner\" is not a valid pubkey",
+                    owner
+                )),
+                Ok(_)
+                    if retention.max_audit_versions.is_none()
+                        && retention.max_audit_age_slots.is_none() =>
+                {
+                    Some(format!(
+                        "The retention of the owner {} in \"audit_retention_by_owner\" sets neither \
+                        \"max_audit_versions\" nor \"max_audit_age_slots\"",
+                        owner
+                    ))
+                }
+                Ok(owner) => {
+                    owners.push((owner.to_bytes().to_vec(), retention.clone()));
+                    None
+                }
+            };
+            if let Some(msg) = error {
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::ConfigurationError { msg },
+                )));
+            }
+        }
+        Ok(Some(Arc::new(Self {
+            owners,
+            max_bytes_per_account,
+            interval_slots: config
+                .audit_retention_interval_slots
+                .unwrap_or(DEFAULT_AUDIT_RETENTION_INTERVAL_SLOTS)
+                .max(1),
+            next_prune_slot: AtomicU64::default(),
+        })))
+    }
+
+    /// Whether the pruning is due at the root, claimed by the caller when it is. Returns the
+    /// first slot of the interval of the previous pruning, from which the audit rows may have
+    /// been inserted since, 0 for the first pruning.
+    fn take_due(&self, root: u64) -> Option<u64> {
+        let next_prune_slot = self.next_prune_slot.load(Ordering::Relaxed);
+        (root >= next_prune_slot
+            && self
+                .next_prune_slot
+                .compare_exchange(
+                    next_prune_slot,
+                    (root / self.interval_slots + 1) * self.interval_slots,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok())
+        .then(|| next_prune_slot.saturating_sub(self.interval_slots))
+    }
+}
+
+impl SimplePostgresClient {
+    /// Share the audit retention of the workers with the client.
+    pub(crate) fn set_audit_retention(&mut self, audit_retention: Option<Arc<AuditRetention>>) {
+        self.audit_retention = audit_retention;
+    }
+
+    /// Prune the account_audit rows beyond the retention of their owner, and beyond the bytes
+    /// kept per account, when due at the root.
+    pub(crate) fn prune_audit_retention(&mut self, root: u64) -> Result<(), GeyserPluginError> {
+        let (audit_retention, since_slot) = match &self.audit_retention {
+            Some(audit_retention) => match audit_retention.take_due(root) {
+                Some(since_slot) => (audit_retention.clone(), since_slot),
+                None => return Ok(()),
+            },
+            None => return Ok(()),
+        };
+        let account_audit = Self::table_name(&self.table_names, "account_audit");
+        let age_stmt = format!(
+            "DELETE FROM {} WHERE owner = $1 AND slot < $2",
+            account_audit
+        );
+        let versions_stmt = format!(
+            "DELETE FROM {account_audit} WHERE ctid IN (SELECT row_id FROM (\
+            SELECT ctid AS row_id, row_number() OVER (PARTITION BY pubkey ORDER BY slot DESC, write_version DESC) AS version \
+            FROM {account_audit} WHERE owner = $1) versions WHERE version > $2)",
+            account_audit = account_audit
+        );
+
+        let mut measure = Measure::start("geyser-plugin-postgres-prune-audit-retention");
+        let mut pruned_count = 0;
+        for (owner, retention) in &audit_retention.owners {
+            let mut prunes = Vec::with_capacity(2);
+            if let Some(max_age_slots) = retention.max_audit_age_slots {
+                prunes.push((&age_stmt, root.saturating_sub(max_age_slots) as i64));
+            }
+            if let Some(max_versions) = retention.max_audit_versions {
+                prunes.push((&versions_stmt, max_versions as i64));
+            }
+            for (stmt, bound) in prunes {
+                let statement = self.prepare_cached_statement(stmt)?;
+                let client = self.client.get_mut().unwrap();
+                match statement.execute(&mut client.client, &client.types, &[owner, &bound]) {
+                    Ok(count) => pruned_count += count,
+                    Err(err) => {
+                        let msg = format!(
+                            "Failed to prune the account_audit rows of the owner {} in the PostgreSQL database. Error: {:?}",
+                            bs58::encode(owner).into_string(),
+                            err
+                        );
+                        error!("{}", msg);
+                        return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+                    }
+                }
+            }
+        }
+        if let Some(max_bytes) = audit_retention.max_bytes_per_account {
+            pruned_count += self.prune_audit_bytes(since_slot, max_bytes)?;
+        }
+        measure.stop();
+
+        inc_new_counter_info!(
+            "geyser-plugin-postgres-audit-retention-pruned",
+            pruned_count as usize
+        );
+        info!(
+            "Pruned {} rows from account_audit by the retention of {} owners at root {} in {}us",
+            pruned_count,
+            audit_retention.owners.len(),
+            root,
+            measure.as_us()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_audit_retention_take_due() {
+        let config = serde_json::from_value(json!({
+            "store_account_historical_data": true,
+            "audit_retention_by_owner": {
+                Pubkey::new_unique().to_string(): { "max_audit_versions": 1 },
+            },
+            "audit_retention_interval_slots": 100,
+        }))
+        .unwrap();
+        let audit_retention = AuditRetention::new(&config).unwrap().unwrap();
+        assert_eq!(audit_retention.take_due(150), Some(0));
+        assert_eq!(audit_retention.take_due(150), None);
+        assert_eq!(audit_retention.take_due(199), None);
+        assert_eq!(audit_retention.take_due(230), Some(100));
+        assert_eq!(audit_retention.take_due(300), Some(200));
+
+        let config = serde_json::from_value(json!({
+            "store_account_historical_data": true,
+            "audit_retention_by_owner": { "not a pubkey": { "max_audit_versions": 1 } },
+        }))
+        .unwrap();
+        assert!(AuditRetention::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_prune_audit_retention() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let token_owner = Pubkey::new_unique();
+        let system_owner = Pubkey::new_unique();
+        let other_owner = Pubkey::new_unique();
+        let config = db.config(json!({
+            "store_account_historical_data": true,
+            "audit_retention_by_owner": {
+                token_owner.to_string(): { "max_audit_versions": 2 },
+                system_owner.to_string(): { "max_audit_age_slots": 15 },
+            },
+            "audit_retention_interval_slots": 100,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client.set_audit_retention(AuditRetention::new(&config).unwrap());
+
+        // The updates of slots 10 to 40 of each account are audited when overwritten.
+        let accounts: Vec<_> = [token_owner, system_owner, other_owner]
+            .iter()
+            .map(|owner| build_account(owner, vec![], 0))
+            .collect();
+        for slot in [10, 20, 30, 40, 50] {
+            for account in &accounts {
+                let mut account = account.clone();
+                account.slot = slot;
+                client.update_account(account, false).unwrap();
+            }
+        }
+        assert_eq!(db.count("account_audit"), 12);
+        client
+            .update_slot_status(50, None, SlotStatus::Rooted)
+            .unwrap();
+
+        let audit_slots = |db: &mut TestDatabase, owner: &Pubkey| -> Vec<i64> {
+            db.client
+                .query(
+                    "SELECT slot FROM account_audit WHERE owner = $1 ORDER BY slot",
+                    &[&owner.as_ref()],
+                )
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect()
+        };
+        assert_eq!(audit_slots(&mut db, &token_owner), vec![30, 40]);
+        assert_eq!(audit_slots(&mut db, &system_owner), vec![40]);
+        assert_eq!(audit_slots(&mut db, &other_owner), vec![10, 20, 30, 40]);
+    }
+}
@@ -0,0 +1,169 @@
+/// Module responsible for recording each committed accounts bulk insert into the batch_log
+/// table with a monotonically increasing batch_seq, when log_batches is set, so the consumers
+/// can detect the missing batches from the gaps in the sequence.
+///
+/// The sequence is shared by the workers, and is taken by a batch once it is committed, along
+/// with the range of the slots and the number of its rows. A batch failing, or committed but
+/// failing to be logged, leaves a gap. The workers commit concurrently, so a gap can also be a
+/// batch being logged: a gap is only a missing batch once the later batches have been logged for
+/// a while. The sequence resumes from the highest batch_seq of the table when the plugin is
+/// restarted.
+use {
+    super::{
+        postgres_client_unprepared::PluginStatement, DbAccountInfo, PostgresSqlClientWrapper,
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// The next batch_seq, shared by the workers.
+#[derive(Debug)]
+pub(crate) struct BatchSequence {
+    next: AtomicU64,
+}
+
+impl BatchSequence {
+    /// Resume the sequence after the highest batch_seq of the batch_log table, when log_batches
+    /// is set.
+    pub fn load(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Option<Arc<Self>>, GeyserPluginError> {
+        if !matches!(config.log_batches, Some(true)) {
+            return Ok(None);
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let stmt = format!(
+            "SELECT COALESCE(MAX(batch_seq), 0) FROM {}",
+            SimplePostgresClient::table_name(&config.table_names, "batch_log")
+        );
+        let last_seq: i64 = match client.query_one(&stmt, &[]) {
+            Ok(row) => row.get(0),
+            Err(err) => {
+                let msg = format!(
+                    "Failed to read the last batch_seq from the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )));
+            }
+        };
+        info!("Resuming the batch_seq after {}", last_seq);
+        Ok(Some(Arc::new(Self {
+            next: AtomicU64::new(last_seq as u64 + 1),
+        })))
+    }
+
+    fn take(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_batch_log_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (batch_seq, min_slot, max_slot, row_count, committed_at) \
+        VALUES ($1, $2, $3, $4, $5)",
+            Self::table_name(&config.table_names, "batch_log")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Share the batch_seq of the workers with the client.
+    pub(crate) fn set_batch_sequence(&mut self, batch_sequence: Option<Arc<BatchSequence>>) {
+        self.batch_sequence = batch_sequence;
+    }
+
+    /// Log the accounts batch committed, when log_batches is set.
+    pub(super) fn log_batch(
+        client: &mut PostgresSqlClientWrapper,
+        batch_sequence: &Option<Arc<BatchSequence>>,
+        accounts: &[DbAccountInfo],
+    ) -> Result<(), GeyserPluginError> {
+        let (statement, batch_sequence) = match (&client.insert_batch_log_stmt, batch_sequence) {
+            (Some(statement), Some(batch_sequence)) if !accounts.is_empty() => {
+                (statement, batch_sequence)
+            }
+            _ => return Ok(()),
+        };
+        let batch_seq = batch_sequence.take() as i64;
+        let min_slot = accounts.iter().map(|account| account.slot).min();
+        let max_slot = accounts.iter().map(|account| account.slot).max();
+        let row_count = accounts.len() as i32;
+        let committed_at = Utc::now().naive_utc();
+        if let Err(err) = statement.execute(
+            &mut client.client,
+            &client.types,
+            &[&batch_seq, &min_slot, &max_slot, &row_count, &committed_at],
+        ) {
+            let msg = format!(
+                "Failed to log the batch {} to the PostgreSQL database. Error: {:?}",
+                batch_seq, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_log_batches() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "log_batches": true }));
+        let owner = Pubkey::new_unique();
+        for slots in [[3, 1, 2, 4], [5, 6, 7, 8]] {
+            // Restarted between the runs.
+            let mut client = SimplePostgresClient::new(&config).unwrap();
+            client.set_batch_sequence(BatchSequence::load(&config).unwrap());
+            for slot in slots {
+                client
+                    .update_account(build_account(&owner, vec![], slot), true)
+                    .unwrap();
+            }
+        }
+
+        let rows: Vec<(i64, i64, i64, i32)> = db
+            .client
+            .query(
+                "SELECT batch_seq, min_slot, max_slot, row_count FROM batch_log ORDER BY batch_seq",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![(1, 1, 3, 2), (2, 2, 4, 2), (3, 5, 6, 2), (4, 7, 8, 2)]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+/// Module responsible for reporting each bulk insert to the metrics pipeline, as a single
+/// datapoint carrying its table, the number of rows, the estimated size of its parameters,
+/// its latency and whether it committed.
+///
+/// The counters of the bulk inserts are aggregated over the reporting interval, the
+/// geyser-plugin-postgres-batch-commit datapoints let the slow or failed batches be told apart
+/// from the rest, and correlated with their size.
+use {
+    super::{AccountBatchSplitter, DbAccountInfo},
+    solana_metrics::*,
+};
+
+/// The estimated size of the parameters of the accounts in the Bind message.
+pub(crate) fn accounts_batch_bytes(accounts: &[DbAccountInfo]) -> usize {
+    accounts
+        .iter()
+        .map(AccountBatchSplitter::account_bytes)
+        .sum()
+}
+
+/// Report a bulk insert into the table, successful or not.
+pub(crate) fn report_batch_commit(
+    table: &str,
+    batch_size: usize,
+    bytes: usize,
+    latency_us: u64,
+    success: bool,
+) {
+    datapoint_info!(
+        "geyser-plugin-postgres-batch-commit",
+        ("table", table, String),
+        ("batch_size", batch_size as i64, i64),
+        ("bytes", bytes as i64, i64),
+        ("latency_us", latency_us as i64, i64),
+        ("success", success, bool),
+    );
+}
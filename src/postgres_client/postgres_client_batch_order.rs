@@ -0,0 +1,76 @@
+/// Module responsible for ordering the rows of the bulk inserts, when sort_batch_rows is set,
+/// and for counting the bulk inserts failing on a deadlock.
+///
+/// A bulk insert locks the rows it upserts in the order of its VALUES. Two workers upserting
+/// overlapping accounts in different orders can each hold a row the other waits for, and one of
+/// them is aborted with a deadlock_detected (40P01) error. Sorting the rows of each batch by
+/// their keys makes all the workers lock the rows they share in the same order. The
+/// geyser-plugin-postgres-bulk-insert-deadlock counter tells the deadlock rate with and without
+/// the sorting.
+use {
+    super::{
+        postgres_client_unprepared::StatementError, DbAccountInfo, SimplePostgresClient,
+        TokenSecondaryIndexEntry,
+    },
+    log::*,
+    postgres::error::SqlState,
+    solana_metrics::*,
+};
+
+/// Sort the accounts of a batch by pubkey, then by slot and write_version.
+pub(crate) fn sort_accounts_batch(accounts: &mut [DbAccountInfo]) {
+    accounts.sort_unstable_by(|a, b| {
+        (&a.pubkey, a.slot, a.write_version.0).cmp(&(&b.pubkey, b.slot, b.write_version.0))
+    });
+}
+
+/// Sort the entries of a token index batch by their keys, then by slot.
+pub(crate) fn sort_token_index_batch(entries: &mut [TokenSecondaryIndexEntry]) {
+    entries.sort_unstable_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+}
+
+/// Count the bulk insert failing with the error when it is a deadlock.
+pub(crate) fn report_deadlock(err: &StatementError) {
+    if err.code() == Some(&SqlState::T_R_DEADLOCK_DETECTED) {
+        inc_new_counter_info!("geyser-plugin-postgres-bulk-insert-deadlock", 1);
+    }
+}
+
+impl SimplePostgresClient {
+    /// Sort the accounts pending for the bulk insert, when sort_batch_rows is set.
+    pub(crate) fn sort_pending_account_updates(&mut self) {
+        if self.sort_batch_rows {
+            sort_accounts_batch(&mut self.pending_account_updates);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::build_account, solana_sdk::pubkey::Pubkey};
+
+    #[test]
+    fn test_sort_accounts_batch() {
+        let owner = Pubkey::new_unique();
+        let first = build_account(&owner, vec![], 2);
+        let mut second = build_account(&owner, vec![], 1);
+        second.pubkey = vec![0xff; 32];
+        let mut older = first.clone();
+        older.slot = 1;
+        let mut accounts = vec![second.clone(), first.clone(), older];
+
+        sort_accounts_batch(&mut accounts);
+        let order: Vec<(Vec<u8>, i64)> = accounts
+            .iter()
+            .map(|account| (account.pubkey.clone(), account.slot))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (first.pubkey.clone(), 1),
+                (first.pubkey, 2),
+                (second.pubkey, 1),
+            ]
+        );
+    }
+}
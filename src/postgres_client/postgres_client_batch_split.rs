@@ -0,0 +1,258 @@
+/// Module responsible for splitting the accounts bulk inserts too large for a single message
+/// of the PostgreSQL protocol.
+///
+/// The parameters of a statement are sent in a single Bind message, which the server limits
+/// to 1 GB. A batch of large accounts can exceed it and fail as a whole. When the estimated
+/// size of the parameters of a batch is over max_batch_bytes, the batch is written in
+/// consecutive sub-batches which each fit, with the bulk insert statements of their sizes.
+/// An account over the limit on its own is written alone.
+use {
+    super::{
+        postgres_client_batch_metrics::{accounts_batch_bytes, report_batch_commit},
+        postgres_client_batch_order::report_deadlock,
+        AccountColumns, DbAccountInfo, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{AccountDataEncoding, GeyserPluginPostgresConfig},
+    chrono::Utc,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+    solana_metrics::*,
+    std::ops::Range,
+    tokio_postgres::types,
+};
+
+const DEFAULT_MAX_BATCH_BYTES: usize = 256 * 1024 * 1024;
+/// The size of the fixed width columns and the lengths of the parameters of an account row.
+const ACCOUNT_ROW_OVERHEAD_BYTES: usize = 64;
+
+/// The configuration of the bulk insert statements of the sub-batches.
+pub(crate) struct AccountBatchSplitter {
+    max_batch_bytes: usize,
+    account_table: String,
+    account_data_encoding: AccountDataEncoding,
+    empty_data_as_null: bool,
+    track_lamport_deltas: bool,
+}
+
+impl AccountBatchSplitter {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        Self {
+            max_batch_bytes: config.max_batch_bytes.unwrap_or(DEFAULT_MAX_BATCH_BYTES),
+            account_table: SimplePostgresClient::table_name(&config.table_names, "account")
+                .to_string(),
+            account_data_encoding: SimplePostgresClient::get_account_data_encoding(config),
+            empty_data_as_null: SimplePostgresClient::get_empty_data_as_null(config),
+            track_lamport_deltas: SimplePostgresClient::get_track_lamport_deltas(config),
+        }
+    }
+
+    /// The estimated size of the parameters of the account in the Bind message.
+    pub(crate) fn account_bytes(account: &DbAccountInfo) -> usize {
+        account.pubkey.len() + account.owner.len() + account.data.len() + ACCOUNT_ROW_OVERHEAD_BYTES
+    }
+
+    /// The bulk insert statement of a sub-batch of the accounts.
+    pub(crate) fn bulk_insert_sql(
+        &self,
+        batch_len: usize,
+        account_columns: &AccountColumns,
+    ) -> String {
+        self.bulk_insert_sql_into(&self.account_table, batch_len, account_columns)
+    }
+
+    /// The bulk insert statement of the accounts into the table.
+    pub(crate) fn bulk_insert_sql_into(
+        &self,
+        account_table: &str,
+        batch_len: usize,
+        account_columns: &AccountColumns,
+    ) -> String {
+        SimplePostgresClient::build_bulk_account_insert_sql(
+            account_table,
+            batch_len,
+            self.account_data_encoding,
+            self.empty_data_as_null,
+            self.track_lamport_deltas,
+            account_columns,
+        )
+    }
+
+    /// Split the accounts into the consecutive sub-batches which each fit in max_batch_bytes.
+    /// Returns None when all the accounts fit in one batch.
+    pub fn split(&self, accounts: &[DbAccountInfo]) -> Option<Vec<Range<usize>>> {
+        let total_bytes: usize = accounts.iter().map(Self::account_bytes).sum();
+        if total_bytes <= self.max_batch_bytes {
+            return None;
+        }
+
+        let mut sub_batches = Vec::new();
+        let mut start = 0;
+        let mut sub_batch_bytes = 0;
+        for (i, account) in accounts.iter().enumerate() {
+            let account_bytes = Self::account_bytes(account);
+            if i > start && sub_batch_bytes + account_bytes > self.max_batch_bytes {
+                sub_batches.push(start..i);
+                start = i;
+                sub_batch_bytes = 0;
+            }
+            sub_batch_bytes += account_bytes;
+        }
+        sub_batches.push(start..accounts.len());
+        info!(
+            "Splitting the accounts batch of {} accounts into {} sub-batches",
+            accounts.len(),
+            sub_batches.len()
+        );
+        Some(sub_batches)
+    }
+}
+
+impl SimplePostgresClient {
+    /// Insert the pending accounts in the sub-batches, in order.
+    pub(crate) fn bulk_insert_account_sub_batches(
+        &mut self,
+        sub_batches: &[Range<usize>],
+    ) -> Result<(), GeyserPluginError> {
+        let updated_on = Utc::now().naive_utc();
+        for sub_batch in sub_batches {
+            let stmt = self
+                .batch_splitter
+                .bulk_insert_sql(sub_batch.len(), &self.account_columns);
+            let statement = self.prepare_cached_statement(&stmt)?;
+
+            let mut values: Vec<&(dyn types::ToSql + Sync)> =
+                Vec::with_capacity(sub_batch.len() * self.account_columns.len());
+            let accounts = &self.pending_account_updates[sub_batch.clone()];
+            let derived_values: Vec<_> = accounts
+                .iter()
+                .map(|account| {
+                    self.account_columns.derived_values(
+                        account,
+                        &updated_on,
+                        &self.slot_clock,
+                        &self.slot_statuses,
+                    )
+                })
+                .collect();
+            for (account, derived) in accounts.iter().zip(&derived_values) {
+                self.account_columns
+                    .push_values(account, derived, &updated_on, &mut values);
+            }
+
+            let mut measure = Measure::start("geyser-plugin-postgres-update-account");
+            let client = self.client.get_mut().unwrap();
+            let result = statement.query(&mut client.client, &client.types, &values);
+            measure.stop();
+            report_batch_commit(
+                "account",
+                sub_batch.len(),
+                accounts_batch_bytes(&self.pending_account_updates[sub_batch.clone()]),
+                measure.as_us(),
+                result.is_ok(),
+            );
+            if let Err(err) = result {
+                report_deadlock(&err);
+                let msg = format!(
+                    "Failed to persist the update of account to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                if !self.isolate_failing_rows {
+                    return Err(GeyserPluginError::AccountsUpdateError { msg });
+                }
+                self.insert_failed_batch_rows(sub_batch.clone())?;
+                continue;
+            }
+            Self::log_batch(
+                client,
+                &self.batch_sequence,
+                &self.pending_account_updates[sub_batch.clone()],
+            )?;
+
+            self.bulk_insert_latency.record(measure.as_us());
+            inc_new_counter_debug!(
+                "geyser-plugin-postgres-update-account-us",
+                measure.as_us() as usize,
+                10000,
+                10000
+            );
+            inc_new_counter_debug!(
+                "geyser-plugin-postgres-update-account-count",
+                sub_batch.len(),
+                10000,
+                10000
+            );
+        }
+        self.count_batch_for_reprepare()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn build_accounts(data_sizes: &[usize]) -> Vec<DbAccountInfo> {
+        let owner = Pubkey::new_unique();
+        data_sizes
+            .iter()
+            .map(|data_size| build_account(&owner, vec![1; *data_size], 1))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_account_batch() {
+        let config = serde_json::from_value(json!({ "max_batch_bytes": 3000 })).unwrap();
+        let splitter = AccountBatchSplitter::new(&config);
+
+        assert_eq!(splitter.split(&build_accounts(&[1000, 1000])), None);
+        assert_eq!(
+            splitter.split(&build_accounts(&[1000, 1000, 1000, 1000])),
+            Some(vec![0..2, 2..4])
+        );
+        // The account over the limit on its own is alone in its sub-batch.
+        assert_eq!(
+            splitter.split(&build_accounts(&[1000, 5000, 100, 100])),
+            Some(vec![0..1, 1..2, 2..4])
+        );
+    }
+
+    #[test]
+    fn test_bulk_insert_large_accounts() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 4,
+            "max_batch_bytes": 2 * 1024 * 1024,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let accounts = build_accounts(&[1024 * 1024, 1024 * 1024, 3 * 1024 * 1024, 10]);
+        for account in &accounts {
+            client.update_account(account.clone(), true).unwrap();
+        }
+        assert_eq!(db.count("account"), 4);
+
+        for account in &accounts {
+            let data: Vec<u8> = db
+                .client
+                .query_one(
+                    "SELECT data FROM account WHERE pubkey = $1",
+                    &[&account.pubkey],
+                )
+                .unwrap()
+                .get(0);
+            assert_eq!(data, account.data);
+        }
+    }
+}
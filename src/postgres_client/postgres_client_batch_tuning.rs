@@ -0,0 +1,219 @@
+/// Module responsible for tuning the size of the accounts bulk inserts by their commit latency,
+/// when adaptive_batching is set.
+///
+/// The batch size starts at batch_size, bounded by adaptive_batch_min_size and
+/// adaptive_batch_max_size. After each committed batch, it is doubled when the commit took less
+/// than half of adaptive_batch_target_latency_ms, halved when it took longer than the target,
+/// and kept otherwise, so it settles where doubling it would overshoot the target. A step
+/// crossing a bound is not taken rather than clamped to it, so the batch sizes are only the
+/// halves and doubles of batch_size between the bounds, which keeps the number of the
+/// statements cached logarithmic in the bounds.
+/// The batches of a size other than batch_size are inserted with the statements of the
+/// statement cache. The effective batch size is reported as the
+/// geyser-plugin-postgres-adaptive-batch-size datapoint on each change and every
+/// BATCH_SIZE_REPORT_INTERVAL_MS.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_metrics::*,
+    solana_sdk::timing::AtomicInterval,
+};
+
+const DEFAULT_TARGET_LATENCY_MS: u64 = 100;
+const DEFAULT_MIN_BATCH_SIZE: usize = 1;
+const BATCH_SIZE_REPORT_INTERVAL_MS: u64 = 30_000;
+
+pub(crate) struct BatchSizeTuner {
+    target_latency_us: u64,
+    min_batch_size: usize,
+    max_batch_size: usize,
+    batch_size: usize,
+    last_report: AtomicInterval,
+}
+
+impl BatchSizeTuner {
+    pub fn new(
+        config: &GeyserPluginPostgresConfig,
+        batch_size: usize,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        if !matches!(config.adaptive_batching, Some(true)) {
+            return Ok(None);
+        }
+        let target_latency_ms = config
+            .adaptive_batch_target_latency_ms
+            .unwrap_or(DEFAULT_TARGET_LATENCY_MS);
+        let min_batch_size = config
+            .adaptive_batch_min_size
+            .unwrap_or(DEFAULT_MIN_BATCH_SIZE);
+        let max_batch_size = config.adaptive_batch_max_size.unwrap_or(batch_size);
+        let error = if target_latency_ms == 0 {
+            Some("\"adaptive_batch_target_latency_ms\" must be positive")
+        } else if min_batch_size == 0 {
+            Some("\"adaptive_batch_min_size\" must be positive")
+        } else if min_batch_size > max_batch_size {
+            Some("\"adaptive_batch_min_size\" must not exceed \"adaptive_batch_max_size\"")
+        } else {
+            None
+        };
+        if let Some(msg) = error {
+            let msg = msg.to_string();
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        Ok(Some(Self {
+            target_latency_us: target_latency_ms * 1000,
+            min_batch_size,
+            max_batch_size,
+            batch_size: batch_size.clamp(min_batch_size, max_batch_size),
+            last_report: AtomicInterval::default(),
+        }))
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Record the commit latency of a batch, adjusting the batch size.
+    pub fn record_commit(&mut self, latency_us: u64) {
+        let batch_size =
+            if latency_us > self.target_latency_us && self.batch_size / 2 >= self.min_batch_size {
+                self.batch_size / 2
+            } else if latency_us < self.target_latency_us / 2
+                && self.batch_size * 2 <= self.max_batch_size
+            {
+                self.batch_size * 2
+            } else {
+                self.batch_size
+            };
+        if batch_size != self.batch_size {
+            debug!(
+                "Tuning the accounts batch size from {} to {} at a commit latency of {} us",
+                self.batch_size, batch_size, latency_us
+            );
+            self.batch_size = batch_size;
+            self.report();
+        } else if self
+            .last_report
+            .should_update(BATCH_SIZE_REPORT_INTERVAL_MS)
+        {
+            self.report();
+        }
+    }
+
+    fn report(&self) {
+        datapoint_info!(
+            "geyser-plugin-postgres-adaptive-batch-size",
+            ("batch_size", self.batch_size as i64, i64),
+        );
+    }
+}
+
+impl SimplePostgresClient {
+    /// The size of the accounts bulk inserts, tuned when adaptive_batching is set.
+    pub(crate) fn effective_batch_size(&self) -> usize {
+        self.batch_size_tuner
+            .as_ref()
+            .map_or(self.batch_size, BatchSizeTuner::batch_size)
+    }
+
+    /// Record the commit latency of an accounts bulk insert for the batch size tuning.
+    pub(crate) fn record_batch_commit_latency(&mut self, latency_us: u64) {
+        if let Some(batch_size_tuner) = &mut self.batch_size_tuner {
+            batch_size_tuner.record_commit(latency_us);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn build_config(config: serde_json::Value) -> GeyserPluginPostgresConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn test_batch_size_tuner() {
+        let config = build_config(json!({
+            "adaptive_batching": true,
+            "adaptive_batch_target_latency_ms": 10,
+            "adaptive_batch_min_size": 2,
+            "adaptive_batch_max_size": 32,
+        }));
+        let mut tuner = BatchSizeTuner::new(&config, 10).unwrap().unwrap();
+        assert_eq!(tuner.batch_size(), 10);
+        tuner.record_commit(1_000);
+        assert_eq!(tuner.batch_size(), 20);
+        // Doubling past the maximum is not taken rather than clamped to 32.
+        tuner.record_commit(1_000);
+        assert_eq!(tuner.batch_size(), 20);
+        // Between half of the target and the target, the batch size is kept.
+        tuner.record_commit(7_000);
+        assert_eq!(tuner.batch_size(), 20);
+        for _ in 0..5 {
+            tuner.record_commit(20_000);
+        }
+        assert_eq!(tuner.batch_size(), 2);
+        tuner.record_commit(1_000);
+        assert_eq!(tuner.batch_size(), 4);
+
+        assert!(BatchSizeTuner::new(&build_config(json!({})), 10)
+            .unwrap()
+            .is_none());
+        assert!(BatchSizeTuner::new(
+            &build_config(json!({
+                "adaptive_batching": true,
+                "adaptive_batch_min_size": 20,
+            })),
+            10
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_adaptive_batching() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 2,
+            "adaptive_batching": true,
+            "adaptive_batch_target_latency_ms": 60_000,
+            "adaptive_batch_max_size": 8,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        let update_accounts = |client: &mut SimplePostgresClient, count: u8| {
+            for i in 0..count {
+                client
+                    .update_account(build_account(&owner, vec![i], 1), true)
+                    .unwrap();
+            }
+        };
+        update_accounts(&mut client, 2);
+        assert_eq!(db.count("account"), 2);
+        // The batch of 2 committed well below the target, the next batch is of 4.
+        assert_eq!(client.effective_batch_size(), 4);
+        update_accounts(&mut client, 3);
+        assert_eq!(db.count("account"), 2);
+        update_accounts(&mut client, 1);
+        assert_eq!(db.count("account"), 6);
+        assert_eq!(client.effective_batch_size(), 8);
+        update_accounts(&mut client, 1);
+        client.notify_end_of_startup().unwrap();
+        assert_eq!(db.count("account"), 7);
+    }
+}
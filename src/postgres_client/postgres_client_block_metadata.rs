@@ -2,12 +2,13 @@ use {
     crate::{
         geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
         postgres_client::{
-            postgres_client_transaction::DbReward, SimplePostgresClient, UpdateBlockMetadataRequest,
+            postgres_client_transaction::DbReward, postgres_client_unprepared::PluginStatement,
+            SimplePostgresClient, UpdateBlockMetadataRequest,
         },
     },
     chrono::Utc,
     log::*,
-    postgres::{Client, Statement},
+    postgres::{types::ToSql, Client},
     solana_geyser_plugin_interface::geyser_plugin_interface::{
         GeyserPluginError, ReplicaBlockInfo,
     },
@@ -36,16 +37,48 @@ impl<'a> From<&ReplicaBlockInfo<'a>> for DbBlockInfo {
     }
 }
 
+const DEFAULT_BLOCK_TIME_BUCKET_SECS: u64 = 60;
+
 impl SimplePostgresClient {
+    /// The granularity of the block_time_bucket column in seconds, when it is stored.
+    pub(crate) fn get_block_time_bucket_secs(config: &GeyserPluginPostgresConfig) -> Option<i64> {
+        if let Some(true) = config.store_block_time_bucket {
+            Some(
+                config
+                    .block_time_bucket_secs
+                    .unwrap_or(DEFAULT_BLOCK_TIME_BUCKET_SECS)
+                    .max(1) as i64,
+            )
+        } else {
+            None
+        }
+    }
+
+    /// The block time truncated to the start of its bucket.
+    fn block_time_bucket(block_time: i64, bucket_secs: i64) -> i64 {
+        block_time - block_time.rem_euclid(bucket_secs)
+    }
+
     pub(crate) fn build_block_metadata_upsert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt =
-            "INSERT INTO block (slot, blockhash, rewards, block_time, block_height, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6)";
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let block_table = Self::table_name(&config.table_names, "block");
+        let mut stmt = if Self::get_block_time_bucket_secs(config).is_some() {
+            format!("INSERT INTO {} (slot, blockhash, rewards, block_time, block_height, updated_on, block_time_bucket) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7)", block_table)
+        } else {
+            format!(
+                "INSERT INTO {} (slot, blockhash, rewards, block_time, block_height, updated_on) \
+        VALUES ($1, $2, $3, $4, $5, $6)",
+                block_table
+            )
+        };
+        if let Some(true) = config.insert_placeholder_blocks {
+            stmt.push_str(&Self::placeholder_block_conflict_clause(config));
+        }
 
-        let stmt = client.prepare(stmt);
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -64,23 +97,34 @@ impl SimplePostgresClient {
         &mut self,
         block_info: UpdateBlockMetadataRequest,
     ) -> Result<(), GeyserPluginError> {
+        let block_time_bucket_secs = self.block_time_bucket_secs;
         let client = self.client.get_mut().unwrap();
         let statement = &client.update_block_metadata_stmt;
+        let types = &client.types;
         let client = &mut client.client;
         let updated_on = Utc::now().naive_utc();
 
         let block_info = block_info.block_info;
-        let result = client.query(
-            statement,
-            &[
-                &block_info.slot,
-                &block_info.blockhash,
-                &block_info.rewards,
-                &block_info.block_time,
-                &block_info.block_height,
-                &updated_on,
-            ],
-        );
+        if let (Some(slot_clock), Some(block_time)) = (&self.slot_clock, block_info.block_time) {
+            slot_clock.record_block_time(block_info.slot as u64, block_time);
+        }
+        let block_time_bucket = block_time_bucket_secs.map(|bucket_secs| {
+            block_info
+                .block_time
+                .map(|block_time| Self::block_time_bucket(block_time, bucket_secs))
+        });
+        let mut values: Vec<&(dyn ToSql + Sync)> = vec![
+            &block_info.slot,
+            &block_info.blockhash,
+            &block_info.rewards,
+            &block_info.block_time,
+            &block_info.block_height,
+            &updated_on,
+        ];
+        if let Some(block_time_bucket) = &block_time_bucket {
+            values.push(block_time_bucket);
+        }
+        let result = statement.query(client, types, &values);
 
         if let Err(err) = result {
             let msg = format!(
@@ -93,3 +137,51 @@ impl SimplePostgresClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{tests::TestDatabase, PostgresClient},
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_block_time_bucket() {
+        assert_eq!(SimplePostgresClient::block_time_bucket(3_659, 60), 3_600);
+        assert_eq!(SimplePostgresClient::block_time_bucket(3_600, 3_600), 3_600);
+        assert_eq!(SimplePostgresClient::block_time_bucket(-1, 60), -60);
+
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "store_block_time_bucket": true,
+            "block_time_bucket_secs": 3600,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        for (slot, block_time) in [(1, Some(7_250)), (2, None)] {
+            client
+                .update_block_metadata(UpdateBlockMetadataRequest {
+                    block_info: DbBlockInfo {
+                        slot,
+                        blockhash: String::default(),
+                        rewards: Vec::default(),
+                        block_time,
+                        block_height: None,
+                    },
+                })
+                .unwrap();
+        }
+
+        let buckets: Vec<Option<i64>> = db
+            .client
+            .query("SELECT block_time_bucket FROM block ORDER BY slot", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(buckets, vec![Some(7_200), None]);
+    }
+}
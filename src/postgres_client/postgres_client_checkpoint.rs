@@ -0,0 +1,253 @@
+/// Module responsible for maintaining the checkpoint table, the highest rooted slot below which
+/// all the notifications are written, when track_slot_checkpoint is set.
+///
+/// The workers write the notifications concurrently and out of order, so a slot being rooted
+/// does not mean the notifications of the slots before it are written. Each work item is given
+/// a sequence number as it is dispatched, in the order of the notifications, and the sequences
+/// of the items not yet written are kept. A rooted slot is checkpointed once every item
+/// dispatched up to its root notification, itself included, is written: when the lowest
+/// pending sequence is above the sequence of its root notification. The account updates
+/// buffered for a bulk insert stay pending until the batch is written. An item which fails
+/// or is dropped is never written, so the checkpoint stops before it until the plugin is
+/// restarted: the tracking is stopped and its sequences released, so they do not accumulate,
+/// and the geyser-plugin-postgres-checkpoint-stopped counter is incremented.
+use {
+    super::{
+        abort, postgres_client_unprepared::PluginStatement, PostgresClientWorker,
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_metrics::*,
+    std::{
+        collections::{BTreeSet, VecDeque},
+        sync::Mutex,
+    },
+};
+
+#[derive(Default)]
+struct CheckpointState {
+    next_sequence: u64,
+    /// The sequences of the work items dispatched and not yet written.
+    pending: BTreeSet<u64>,
+    /// The sequences of the root notifications and their slots, not yet checkpointed.
+    rooted: VecDeque<(u64, u64)>,
+    checkpoint_slot: Option<u64>,
+    /// Set once an item is not written, the checkpoint no longer advances.
+    stopped: bool,
+}
+
+/// The tracking of the work items written, shared by the workers.
+#[derive(Default)]
+pub(crate) struct SlotCheckpoint {
+    state: Mutex<CheckpointState>,
+}
+
+impl SlotCheckpoint {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Option<Self> {
+        matches!(config.track_slot_checkpoint, Some(true)).then(Self::default)
+    }
+
+    /// Get the sequence of a work item dispatched, with the slot it roots if any.
+    pub fn dispatch(&self, rooted_slot: Option<u64>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        if state.stopped {
+            return sequence;
+        }
+        state.pending.insert(sequence);
+        if let Some(slot) = rooted_slot {
+            state.rooted.push_back((sequence, slot));
+        }
+        sequence
+    }
+
+    /// Mark the work items written, returns the checkpoint slot when it advances.
+    pub fn complete(&self, sequences: &[u64]) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return None;
+        }
+        for sequence in sequences {
+            state.pending.remove(sequence);
+        }
+        let lowest_pending = state
+            .pending
+            .iter()
+            .next()
+            .copied()
+            .unwrap_or(state.next_sequence);
+        let mut advanced = None;
+        while let Some((sequence, slot)) = state.rooted.front().copied() {
+            if sequence >= lowest_pending {
+                break;
+            }
+            state.rooted.pop_front();
+            if state.checkpoint_slot < Some(slot) {
+                state.checkpoint_slot = Some(slot);
+                advanced = Some(slot);
+            }
+        }
+        advanced
+    }
+
+    /// Stop the checkpoint before the work item which is not written, releasing the sequences
+    /// tracked.
+    pub fn stop(&self, sequence: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return;
+        }
+        state.stopped = true;
+        state.pending.clear();
+        state.rooted.clear();
+        error!(
+            "The checkpoint stops at slot {:?} before the work item {:?} which is not written, \
+            until the plugin is restarted",
+            state.checkpoint_slot, sequence
+        );
+        inc_new_counter_info!("geyser-plugin-postgres-checkpoint-stopped", 1);
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_checkpoint_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS cp (id, highest_contiguous_rooted_slot, updated_on) \
+        VALUES (0, $1, $2) \
+        ON CONFLICT (id) DO UPDATE SET highest_contiguous_rooted_slot=excluded.highest_contiguous_rooted_slot, \
+        updated_on=excluded.updated_on \
+        WHERE cp.highest_contiguous_rooted_slot < excluded.highest_contiguous_rooted_slot",
+            Self::table_name(&config.table_names, "checkpoint")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Advance the checkpoint to the slot, unless it is already beyond.
+    pub(crate) fn update_checkpoint(&mut self, slot: u64) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.upsert_checkpoint_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+        let slot = slot as i64;
+        let updated_on = Utc::now().naive_utc();
+        if let Err(err) =
+            statement.execute(&mut client.client, &client.types, &[&slot, &updated_on])
+        {
+            let msg = format!(
+                "Failed to persist the checkpoint to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+impl PostgresClientWorker {
+    /// Mark the work item handled, or the buffered account updates written when the sequence
+    /// is None, and advance the checkpoint. The account updates which may be buffered are held
+    /// until no account update is buffered.
+    pub(crate) fn complete_work(
+        &mut self,
+        checkpoint: &SlotCheckpoint,
+        sequence: Option<u64>,
+        is_account_update: bool,
+        failed: bool,
+        panic_on_db_errors: bool,
+    ) {
+        if failed {
+            // The buffered account updates may be lost with the failed write.
+            self.held_sequences.clear();
+            checkpoint.stop(sequence);
+            return;
+        }
+        let mut completed = Vec::new();
+        match sequence {
+            Some(sequence) if is_account_update && self.pending_count() > 0 => {
+                self.held_sequences.push(sequence)
+            }
+            Some(sequence) => completed.push(sequence),
+            None => {}
+        }
+        if self.pending_count() == 0 {
+            completed.append(&mut self.held_sequences);
+        }
+        if let Some(slot) = checkpoint.complete(&completed) {
+            if let Err(err) = self.client.update_checkpoint(slot) {
+                error!("Failed to update the checkpoint: ({})", err);
+                if panic_on_db_errors {
+                    abort();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_slot_checkpoint() {
+        let checkpoint = SlotCheckpoint::default();
+        let account = checkpoint.dispatch(None);
+        let root_1 = checkpoint.dispatch(Some(1));
+        let transaction = checkpoint.dispatch(None);
+        let root_2 = checkpoint.dispatch(Some(2));
+        let root_3 = checkpoint.dispatch(Some(3));
+
+        // The roots are written before the account update of slot 1.
+        assert_eq!(checkpoint.complete(&[root_1, root_2]), None);
+        assert_eq!(checkpoint.complete(&[account]), Some(1));
+        // The gap of the transaction holds the checkpoint at slot 1.
+        assert_eq!(checkpoint.complete(&[root_3]), None);
+        assert_eq!(checkpoint.complete(&[transaction]), Some(3));
+    }
+
+    #[test]
+    fn test_slot_checkpoint_stop() {
+        let checkpoint = SlotCheckpoint::default();
+        let root_1 = checkpoint.dispatch(Some(1));
+        assert_eq!(checkpoint.complete(&[root_1]), Some(1));
+
+        // The account update is dropped, the later roots are not checkpointed nor kept.
+        let account = checkpoint.dispatch(None);
+        checkpoint.stop(Some(account));
+        let root_2 = checkpoint.dispatch(Some(2));
+        assert_eq!(checkpoint.complete(&[root_2]), None);
+        let state = checkpoint.state.lock().unwrap();
+        assert!(state.pending.is_empty());
+        assert!(state.rooted.is_empty());
+        assert_eq!(state.checkpoint_slot, Some(1));
+    }
+
+    #[test]
+    fn test_update_checkpoint() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "track_slot_checkpoint": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        for slot in [5, 7, 6] {
+            client.update_checkpoint(slot).unwrap();
+        }
+        let slot: i64 = db
+            .client
+            .query_one("SELECT highest_contiguous_rooted_slot FROM checkpoint", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(slot, 7);
+    }
+}
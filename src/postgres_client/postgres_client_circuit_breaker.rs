@@ -0,0 +1,272 @@
+/// Module for the circuit breaker pausing the writes to the PostgreSQL database when it is
+/// overloaded, so the workers do not make things worse by hammering it.
+///
+/// The breaker is shared by the workers. It is closed while the rate of the failed or slow
+/// writes in the recent window is below the threshold. Then it opens, pausing or dropping
+/// the writes for the cooldown, after which it is half-open and a single write probes the
+/// recovery: the breaker closes if the probe succeeds, and opens again otherwise.
+///
+/// Only the writes executing a statement, a single upsert or the flush of a bulk insert, are
+/// recorded. An account update buffered for a later bulk insert has no outcome of its own; when
+/// it was admitted as the probe, the probe passes to the next write.
+use {
+    crate::geyser_plugin_postgres::CircuitBreakerConfig,
+    log::*,
+    solana_metrics::*,
+    std::{
+        collections::VecDeque,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+const DEFAULT_CIRCUIT_BREAKER_WINDOW_SIZE: usize = 100;
+const DEFAULT_CIRCUIT_BREAKER_FAILURE_RATE_PERCENT: u64 = 50;
+const DEFAULT_CIRCUIT_BREAKER_SLOW_WRITE_THRESHOLD_MS: u64 = 1000;
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_MS: u64 = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CircuitState {
+    Closed,
+    /// Open until the instant.
+    Open(Instant),
+    /// Probing the recovery with a single write.
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// The state reported in the metrics.
+    fn code(&self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::HalfOpen => 1,
+            CircuitState::Open(_) => 2,
+        }
+    }
+}
+
+/// Whether a worker may write a work item.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum WriteAdmission {
+    Allow,
+    /// The breaker is open, retry later.
+    Wait,
+    /// The breaker is open and the work item is dropped.
+    Drop,
+}
+
+struct CircuitBreakerState {
+    state: CircuitState,
+    /// Whether each of the recent writes failed, the oldest first.
+    outcomes: VecDeque<bool>,
+    failure_count: usize,
+}
+
+pub(crate) struct CircuitBreaker {
+    window_size: usize,
+    failure_rate_percent: u64,
+    slow_write_threshold_us: u64,
+    cooldown: Duration,
+    drop_writes_when_open: bool,
+    state: Mutex<CircuitBreakerState>,
+    dropped_count: AtomicU64,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            window_size: config
+                .window_size
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_WINDOW_SIZE)
+                .max(1),
+            failure_rate_percent: config
+                .failure_rate_percent
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_FAILURE_RATE_PERCENT),
+            slow_write_threshold_us: config
+                .slow_write_threshold_ms
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_SLOW_WRITE_THRESHOLD_MS)
+                * 1000,
+            cooldown: Duration::from_millis(
+                config
+                    .cooldown_ms
+                    .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_MS),
+            ),
+            drop_writes_when_open: config.drop_writes_when_open.unwrap_or(false),
+            state: Mutex::new(CircuitBreakerState {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::default(),
+                failure_count: 0,
+            }),
+            dropped_count: AtomicU64::default(),
+        }
+    }
+
+    /// Check whether a write is admitted. When the cooldown has elapsed, the first caller is
+    /// admitted as the probe of the recovery.
+    pub fn admit(&self) -> WriteAdmission {
+        let mut state = self.state.lock().unwrap();
+        match state.state {
+            CircuitState::Closed => WriteAdmission::Allow,
+            CircuitState::Open(until) if Instant::now() >= until => {
+                self.transition(&mut state, CircuitState::HalfOpen);
+                WriteAdmission::Allow
+            }
+            CircuitState::Open(_) | CircuitState::HalfOpen => {
+                if self.drop_writes_when_open {
+                    self.dropped_count.fetch_add(1, Ordering::Relaxed);
+                    WriteAdmission::Drop
+                } else {
+                    WriteAdmission::Wait
+                }
+            }
+        }
+    }
+
+    /// Record the outcome of an admitted write. A write slower than the threshold counts as
+    /// failed.
+    pub fn record(&self, failed: bool, latency_us: u64) {
+        let failed = failed || latency_us > self.slow_write_threshold_us;
+        let mut state = self.state.lock().unwrap();
+        match state.state {
+            CircuitState::Closed => {
+                state.outcomes.push_back(failed);
+                if failed {
+                    state.failure_count += 1;
+                }
+                if state.outcomes.len() > self.window_size
+                    && state.outcomes.pop_front() == Some(true)
+                {
+                    state.failure_count -= 1;
+                }
+                if state.outcomes.len() == self.window_size
+                    && state.failure_count as u64 * 100
+                        >= self.failure_rate_percent * self.window_size as u64
+                {
+                    self.transition(
+                        &mut state,
+                        CircuitState::Open(Instant::now() + self.cooldown),
+                    );
+                }
+            }
+            CircuitState::HalfOpen => {
+                let next_state = if failed {
+                    CircuitState::Open(Instant::now() + self.cooldown)
+                } else {
+                    CircuitState::Closed
+                };
+                self.transition(&mut state, next_state);
+            }
+            // The writes admitted before the breaker opened.
+            CircuitState::Open(_) => {}
+        }
+    }
+
+    /// Record that an admitted write was buffered rather than written. When it was the probe,
+    /// the next write is admitted as the probe instead.
+    pub fn record_buffered(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.state == CircuitState::HalfOpen {
+            state.state = CircuitState::Open(Instant::now());
+        }
+    }
+
+    fn transition(&self, state: &mut CircuitBreakerState, next_state: CircuitState) {
+        match next_state {
+            CircuitState::Open(_) => warn!(
+                "Opened the circuit breaker, pausing the writes to the PostgreSQL database for {:?}",
+                self.cooldown
+            ),
+            CircuitState::HalfOpen => info!("Probing the recovery of the PostgreSQL database"),
+            CircuitState::Closed => info!("Closed the circuit breaker, resuming the writes"),
+        }
+        state.state = next_state;
+        state.outcomes.clear();
+        state.failure_count = 0;
+        datapoint_info!(
+            "geyser-plugin-postgres-circuit-breaker",
+            ("state", next_state.code(), i64),
+            (
+                "dropped-count",
+                self.dropped_count.load(Ordering::Relaxed) as i64,
+                i64
+            ),
+        );
+    }
+
+    /// The state reported in the metrics: 0 when closed, 1 when half-open and 2 when open.
+    pub fn state_code(&self) -> i64 {
+        self.state.lock().unwrap().state.code()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    fn build_circuit_breaker(cooldown_ms: u64, drop_writes_when_open: bool) -> CircuitBreaker {
+        let config = serde_json::from_value(json!({
+            "window_size": 4,
+            "failure_rate_percent": 50,
+            "slow_write_threshold_ms": 10,
+            "cooldown_ms": cooldown_ms,
+            "drop_writes_when_open": drop_writes_when_open,
+        }))
+        .unwrap();
+        CircuitBreaker::new(&config)
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens() {
+        let breaker = build_circuit_breaker(60_000, false);
+        for failed in [true, false, false, false, false] {
+            breaker.record(failed, 100);
+        }
+        assert_eq!(breaker.admit(), WriteAdmission::Allow);
+
+        // A slow write counts as failed, 2 of the last 4 writes opens the breaker.
+        breaker.record(true, 100);
+        breaker.record(false, 20_000);
+        assert_eq!(breaker.state_code(), 2);
+        assert_eq!(breaker.admit(), WriteAdmission::Wait);
+
+        let breaker = build_circuit_breaker(60_000, true);
+        for _ in 0..4 {
+            breaker.record(true, 100);
+        }
+        assert_eq!(breaker.admit(), WriteAdmission::Drop);
+        assert_eq!(breaker.dropped_count.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_circuit_breaker_half_open() {
+        let breaker = build_circuit_breaker(0, false);
+        for _ in 0..4 {
+            breaker.record(true, 100);
+        }
+
+        // The cooldown elapsed, a single probe is admitted.
+        assert_eq!(breaker.admit(), WriteAdmission::Allow);
+        assert_eq!(breaker.state_code(), 1);
+        assert_eq!(breaker.admit(), WriteAdmission::Wait);
+
+        // The probe failed.
+        breaker.record(true, 100);
+        assert_eq!(breaker.state_code(), 2);
+
+        // The probe is buffered, the next write probes instead.
+        assert_eq!(breaker.admit(), WriteAdmission::Allow);
+        breaker.record_buffered();
+        assert_eq!(breaker.admit(), WriteAdmission::Allow);
+        assert_eq!(breaker.state_code(), 1);
+
+        breaker.record(false, 100);
+        assert_eq!(breaker.state_code(), 0);
+        assert_eq!(breaker.admit(), WriteAdmission::Allow);
+        // The buffered writes leave the closed breaker as is.
+        breaker.record_buffered();
+        assert_eq!(breaker.state_code(), 0);
+    }
+}
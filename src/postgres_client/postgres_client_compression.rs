@@ -0,0 +1,61 @@
+/// Module responsible for compressing the serialized data written into the BYTEA columns, for
+/// the data configured with a DataCompression, such as compress_transaction_data.
+///
+/// 'zstd' compresses into a zstd frame at ZSTD_LEVEL, a fast level with most of the ratio of
+/// the higher ones. 'lz4' compresses into a lz4 block prefixed with the size of the data as
+/// a little-endian u32, as lz4_flex::compress_prepend_size. The rows are marked with the
+/// compression_marker of the codec, so the readers decompress them by the marker and the
+/// codec can be changed across the restarts.
+use {crate::geyser_plugin_postgres::DataCompression, std::io};
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// The marker of the codec written along with the compressed data, None when not compressed.
+pub(crate) fn compression_marker(compression: DataCompression) -> Option<i16> {
+    match compression {
+        DataCompression::None => None,
+        DataCompression::Zstd => Some(1),
+        DataCompression::Lz4 => Some(2),
+    }
+}
+
+/// Compress the data with the codec.
+pub(crate) fn compress(compression: DataCompression, data: &[u8]) -> io::Result<Vec<u8>> {
+    match compression {
+        DataCompression::None => Ok(data.to_vec()),
+        DataCompression::Zstd => zstd::bulk::compress(data, ZSTD_LEVEL),
+        DataCompression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Decompress the data written with the codec of the marker.
+    pub(crate) fn decompress(marker: Option<i16>, data: &[u8]) -> Vec<u8> {
+        match marker {
+            None => data.to_vec(),
+            Some(1) => zstd::decode_all(data).unwrap(),
+            Some(2) => lz4_flex::decompress_size_prepended(data).unwrap(),
+            Some(marker) => panic!("unknown compression marker {}", marker),
+        }
+    }
+
+    #[test]
+    fn test_compress() {
+        let data: Vec<u8> = (0..4096).map(|i| (i % 16) as u8).collect();
+        for compression in [
+            DataCompression::None,
+            DataCompression::Zstd,
+            DataCompression::Lz4,
+        ] {
+            let compressed = compress(compression, &data).unwrap();
+            let marker = compression_marker(compression);
+            if marker.is_some() {
+                assert!(compressed.len() < data.len() / 4);
+            }
+            assert_eq!(decompress(marker, &compressed), data);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+/// Module responsible for recording what changed in the configuration since the plugin was last
+/// loaded into the config_changelog table, when record_config_changelog is set, so the data
+/// changes driven by the configuration are traceable across the deploys.
+///
+/// The configuration recorded into the plugin_config_audit table is compared with the last row
+/// of the table, in the same transaction as it is recorded. Each field which differs is a row of
+/// the changelog, by its path in the recorded JSONB object, such as config.batch_size,
+/// effective.indexes or accounts_selector.owners, with the previous and the new values, NULL when
+/// absent. The objects are compared field by field, the arrays and the other values, and the
+/// objects added or removed, as a whole. Nothing is recorded for the first configuration
+/// recorded.
+use {
+    super::SimplePostgresClient, crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::NaiveDateTime, postgres::Transaction, serde_json::Value,
+};
+
+/// A field of the recorded configuration which changed.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ConfigChange {
+    pub field: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+}
+
+fn diff_value(path: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    match (old, new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => {
+            let mut fields: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+            fields.sort();
+            fields.dedup();
+            for field in fields {
+                let field_path = if path.is_empty() {
+                    field.clone()
+                } else {
+                    format!("{}.{}", path, field)
+                };
+                diff_value(
+                    &field_path,
+                    old_fields.get(field).unwrap_or(&Value::Null),
+                    new_fields.get(field).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (old, new) if old != new => changes.push(ConfigChange {
+            field: path.to_string(),
+            old_value: (!old.is_null()).then(|| old.clone()),
+            new_value: (!new.is_null()).then(|| new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// The fields changed between the recorded configurations, by their paths.
+pub(crate) fn diff_config(old: &Value, new: &Value) -> Vec<ConfigChange> {
+    let mut changes = Vec::new();
+    diff_value("", old, new, &mut changes);
+    changes
+}
+
+impl SimplePostgresClient {
+    /// The id and the configuration of the last plugin_config_audit row, before recording the
+    /// configuration, when the changelog is recorded.
+    pub(crate) fn last_config_audit(
+        transaction: &mut Transaction,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Option<(i64, Value)>, postgres::Error> {
+        if !matches!(config.record_config_changelog, Some(true)) {
+            return Ok(None);
+        }
+        let stmt = format!(
+            "SELECT id, config::TEXT FROM {} ORDER BY id DESC LIMIT 1",
+            Self::table_name(&config.table_names, "plugin_config_audit")
+        );
+        Ok(transaction.query_opt(&stmt, &[])?.map(|row| {
+            let config_audit: String = row.get(1);
+            (
+                row.get(0),
+                serde_json::from_str(&config_audit).unwrap_or(Value::Null),
+            )
+        }))
+    }
+
+    /// Record the fields changed since the last configuration recorded. Returns the number of
+    /// the fields changed.
+    pub(crate) fn record_config_changelog(
+        transaction: &mut Transaction,
+        config: &GeyserPluginPostgresConfig,
+        recorded_on: &NaiveDateTime,
+        (previous_id, previous_config): (i64, Value),
+        (config_audit_id, config_audit): (i64, &Value),
+    ) -> Result<usize, postgres::Error> {
+        let stmt = format!(
+            "INSERT INTO {} (recorded_on, config_audit_id, previous_config_audit_id, field, \
+            old_value, new_value) \
+            VALUES ($1, $2, $3, $4, CAST($5::TEXT AS JSONB), CAST($6::TEXT AS JSONB))",
+            Self::table_name(&config.table_names, "config_changelog")
+        );
+        let changes = diff_config(&previous_config, config_audit);
+        for change in &changes {
+            let old_value = change.old_value.as_ref().map(Value::to_string);
+            let new_value = change.new_value.as_ref().map(Value::to_string);
+            transaction.execute(
+                &stmt,
+                &[
+                    recorded_on,
+                    &config_audit_id,
+                    &previous_id,
+                    &change.field,
+                    &old_value,
+                    &new_value,
+                ],
+            )?;
+        }
+        Ok(changes.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_diff_config() {
+        let old = json!({
+            "config": { "batch_size": 10, "index_token_owner": true, "threads": null },
+            "accounts_selector": { "owners": ["a"] },
+            "effective": { "indexes": ["token_owner"] },
+        });
+        let new = json!({
+            "config": { "batch_size": 20, "index_token_owner": true, "threads": 4 },
+            "accounts_selector": { "owners": ["a", "b"] },
+            "effective": { "indexes": [] },
+            "transaction_selector": { "mentions": ["*"] },
+        });
+        let changes = diff_config(&old, &new);
+        let fields: Vec<&str> = changes.iter().map(|change| change.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "accounts_selector.owners",
+                "config.batch_size",
+                "config.threads",
+                "effective.indexes",
+                "transaction_selector",
+            ]
+        );
+        assert_eq!(
+            changes[2],
+            ConfigChange {
+                field: "config.threads".to_string(),
+                old_value: None,
+                new_value: Some(json!(4)),
+            }
+        );
+        assert!(diff_config(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_record_config_changelog() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config_file = json!({
+            "audit_plugin_config": true,
+            "record_config_changelog": true,
+            "batch_size": 50,
+            "index_token_owner": true,
+        });
+        let config = db.config(config_file.clone());
+        SimplePostgresClient::record_config_audit(&config, &config_file).unwrap();
+        // Nothing changed since the first configuration recorded.
+        SimplePostgresClient::record_config_audit(&config, &config_file).unwrap();
+        assert_eq!(db.count("config_changelog"), 0);
+
+        let config_file = json!({
+            "audit_plugin_config": true,
+            "record_config_changelog": true,
+            "batch_size": 100,
+            "index_token_mint": true,
+        });
+        let config = db.config(config_file.clone());
+        SimplePostgresClient::record_config_audit(&config, &config_file).unwrap();
+        let rows: Vec<(String, Option<String>, Option<String>)> = db
+            .client
+            .query(
+                "SELECT field, old_value::TEXT, new_value::TEXT FROM config_changelog \
+                WHERE config_audit_id = previous_config_audit_id + 1 ORDER BY field",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+        let row = |field: &str, old_value: Option<&str>, new_value: Option<&str>| {
+            (
+                field.to_string(),
+                old_value.map(str::to_string),
+                new_value.map(str::to_string),
+            )
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row("config.batch_size", Some("50"), Some("100")),
+                row("config.index_token_mint", None, Some("true")),
+                row("config.index_token_owner", Some("true"), None),
+                row("effective.batch_size", Some("50"), Some("100")),
+                row("effective.index_batch_size", Some("50"), Some("100")),
+                row(
+                    "effective.indexes",
+                    Some("[\"token_owner\"]"),
+                    Some("[\"token_mint\"]")
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,179 @@
+/// Module responsible for validating the connection configuration at load time, before any
+/// connection is attempted, so a malformed connection string or a missing field fails the
+/// startup with an error naming the field, instead of the error of the connection.
+///
+/// Each of "connection_str", "accounts_connection_str" and "metadata_connection_str" set is
+/// parsed, and must name a host and a user, with a port for every host or a single one, as the
+/// connection requires. The connection string of "pg_service" is checked alike. Otherwise
+/// "host" and "user" must be given and not empty, and "port" must be positive. The errors do not
+/// echo the connection strings, which may hold the passwords.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    postgres::{config::Host, Config},
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::error::Error,
+};
+
+fn configuration_error(msg: String) -> GeyserPluginError {
+    error!("{}", msg);
+    GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConfigurationError {
+        msg,
+    }))
+}
+
+/// The problem of the connection string of the field, if any.
+fn check_connection_str(field: &str, connection_str: &str) -> Result<(), String> {
+    // The cause names the option which is invalid.
+    let config: Config =
+        connection_str
+            .parse()
+            .map_err(|err: postgres::Error| match err.source() {
+                Some(cause) => format!("Invalid \"{}\": {}: {}", field, err, cause),
+                None => format!("Invalid \"{}\": {}", field, err),
+            })?;
+    let hosts = config.get_hosts();
+    if hosts.is_empty() {
+        return Err(format!("\"{}\" does not specify the \"host\"", field));
+    }
+    if hosts
+        .iter()
+        .any(|host| matches!(host, Host::Tcp(host) if host.is_empty()))
+    {
+        return Err(format!("\"{}\" specifies an empty \"host\"", field));
+    }
+    match config.get_user() {
+        None => return Err(format!("\"{}\" does not specify the \"user\"", field)),
+        Some("") => return Err(format!("\"{}\" specifies an empty \"user\"", field)),
+        Some(_) => {}
+    }
+    let ports = config.get_ports().len();
+    if ports > 1 && ports != hosts.len() {
+        return Err(format!(
+            "\"{}\" specifies {} ports for {} hosts, it must specify one or one per host",
+            field,
+            ports,
+            hosts.len()
+        ));
+    }
+    Ok(())
+}
+
+/// The problem of the "host", "user" and "port" fields, if any.
+fn check_host_user_port(config: &GeyserPluginPostgresConfig) -> Result<(), String> {
+    for (field, value) in [("host", &config.host), ("user", &config.user)] {
+        match value.as_deref() {
+            None => {
+                return Err(format!(
+                "\"{}\" must be specified when neither \"connection_str\" nor \"pg_service\" is",
+                field
+            ))
+            }
+            Some(value) if value.trim().is_empty() => {
+                return Err(format!("\"{}\" must not be empty", field))
+            }
+            Some(_) => {}
+        }
+    }
+    if config.port == Some(0) {
+        return Err("\"port\" must be positive".to_string());
+    }
+    Ok(())
+}
+
+impl SimplePostgresClient {
+    /// Check the connection configuration, before connecting.
+    pub fn check_connection_config(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let connection_strs = [
+            ("connection_str", &config.connection_str),
+            ("accounts_connection_str", &config.accounts_connection_str),
+            ("metadata_connection_str", &config.metadata_connection_str),
+        ];
+        for (field, connection_str) in connection_strs {
+            if let Some(connection_str) = connection_str {
+                check_connection_str(field, connection_str).map_err(configuration_error)?;
+            }
+        }
+        if config.connection_str.is_some() || config.accounts_connection_str.is_some() {
+            return Ok(());
+        }
+        match &config.pg_service {
+            Some(service) => {
+                let connection_str = Self::pg_service_connection_str(config, service)?;
+                check_connection_str("pg_service", &connection_str).map_err(configuration_error)
+            }
+            None => check_host_user_port(config).map_err(configuration_error),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    fn check(config: serde_json::Value) -> Result<(), String> {
+        let config = serde_json::from_value(config).unwrap();
+        SimplePostgresClient::check_connection_config(&config).map_err(|err| match err {
+            GeyserPluginError::Custom(err) => {
+                match err.downcast_ref::<GeyserPluginPostgresError>().unwrap() {
+                    GeyserPluginPostgresError::ConfigurationError { msg } => msg.clone(),
+                    err => panic!("unexpected error {:?}", err),
+                }
+            }
+            err => panic!("unexpected error {:?}", err),
+        })
+    }
+
+    #[test]
+    fn test_check_connection_config() {
+        assert!(check(json!({ "host": "localhost", "user": "solana" })).is_ok());
+        assert!(check(json!({
+            "connection_str": "host=localhost user=solana password=secret port=5432",
+        }))
+        .is_ok());
+        assert!(
+            check(json!({ "connection_str": "postgresql://solana@db1,db2:5432/solana" })).is_ok()
+        );
+
+        let err =
+            check(json!({ "connection_str": "host=localhost user=solana port=abc" })).unwrap_err();
+        assert!(err.starts_with("Invalid \"connection_str\""), "{}", err);
+        assert!(err.contains("port"), "{}", err);
+        let err =
+            check(json!({ "connection_str": "host=localhost password='secret" })).unwrap_err();
+        assert!(!err.contains("secret"), "{}", err);
+        assert_eq!(
+            check(json!({ "connection_str": "user=solana" })).unwrap_err(),
+            "\"connection_str\" does not specify the \"host\""
+        );
+        assert_eq!(
+            check(json!({
+                "connection_str": "host=localhost user=solana",
+                "metadata_connection_str": "host=metadata",
+            }))
+            .unwrap_err(),
+            "\"metadata_connection_str\" does not specify the \"user\""
+        );
+        assert_eq!(
+            check(json!({ "accounts_connection_str": "host=a,b,c user=solana port=1,2" }))
+                .unwrap_err(),
+            "\"accounts_connection_str\" specifies 2 ports for 3 hosts, it must specify one or one per host"
+        );
+
+        assert_eq!(
+            check(json!({ "host": "localhost" })).unwrap_err(),
+            "\"user\" must be specified when neither \"connection_str\" nor \"pg_service\" is"
+        );
+        assert_eq!(
+            check(json!({ "host": " ", "user": "solana" })).unwrap_err(),
+            "\"host\" must not be empty"
+        );
+        assert_eq!(
+            check(json!({ "host": "localhost", "user": "solana", "port": 0 })).unwrap_err(),
+            "\"port\" must be positive"
+        );
+    }
+}
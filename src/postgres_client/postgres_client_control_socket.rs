@@ -0,0 +1,293 @@
+/// Module responsible for the control socket, a Unix socket accepting the commands inspecting
+/// and controlling the workers while the plugin runs, when control_socket is set.
+///
+/// Each request is a JSON object on its own line, such as {"command": "status"}, answered by a
+/// JSON object on its own line, with "ok" true and the result, or "ok" false and the "error":
+///
+/// * "status": whether the writes are paused and the startup is done, and the workers connected.
+/// * "stats": the work items queued and in flight, and the writes dropped while paused.
+/// * "flush": requests the workers to write the account updates they buffer for the bulk
+///   inserts, which they do before their next work item, or once the writes are resumed.
+/// * "pause" and "resume": pause and resume the writes, like the control file.
+///
+/// The socket is created only accessible to the user of the validator, and removed when the
+/// plugin is unloaded. The connections are served one at a time.
+use {
+    super::{
+        abort, postgres_client_checkpoint::SlotCheckpoint,
+        postgres_client_ingestion_control::IngestionControl, DbWork, PostgresClientWorker,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresError,
+    crossbeam_channel::Sender,
+    log::*,
+    serde_json::json,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{
+        fs,
+        io::{self, BufRead, BufReader, ErrorKind, Write},
+        os::unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread::{self, sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+const CONTROL_SOCKET_ACCEPT_INTERVAL_MS: u64 = 100;
+/// The connections idle for longer are closed, not to hold the socket.
+const CONTROL_SOCKET_READ_TIMEOUT_MS: u64 = 5000;
+
+/// The state of the workers the commands inspect and control.
+pub(crate) struct ControlTargets {
+    pub ingestion_control: Arc<IngestionControl>,
+    pub worker_count: usize,
+    pub initialized_worker_count: Arc<AtomicUsize>,
+    pub is_startup_done: Arc<AtomicBool>,
+    pub inflight_count: Arc<AtomicUsize>,
+    pub sender: Sender<DbWork>,
+}
+
+impl ControlTargets {
+    /// Answer the request line.
+    fn handle_request(&self, line: &str) -> serde_json::Value {
+        let request: serde_json::Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => return json!({ "ok": false, "error": format!("invalid request: {}", err) }),
+        };
+        let ingestion_control = &self.ingestion_control;
+        match request["command"].as_str() {
+            Some("status") => json!({
+                "ok": true,
+                "paused": ingestion_control.is_paused(),
+                "startup_done": self.is_startup_done.load(Ordering::Relaxed),
+                "workers": self.worker_count,
+                "connected_workers": self.initialized_worker_count.load(Ordering::Relaxed),
+            }),
+            Some("stats") => json!({
+                "ok": true,
+                "queued": self.sender.len(),
+                "inflight": self.inflight_count.load(Ordering::Relaxed),
+                "dropped": ingestion_control.dropped_count(),
+                "flush_generation": ingestion_control.flush_generation(),
+            }),
+            Some("flush") => json!({
+                "ok": true,
+                "flush_generation": ingestion_control.request_flush(),
+            }),
+            Some(command @ ("pause" | "resume")) => {
+                let paused = command == "pause";
+                let was_paused = ingestion_control.set_paused_by_socket(paused);
+                json!({ "ok": true, "paused": paused, "was_paused": was_paused })
+            }
+            Some(command) => {
+                json!({ "ok": false, "error": format!("unknown command {:?}", command) })
+            }
+            None => json!({ "ok": false, "error": "missing the \"command\"" }),
+        }
+    }
+
+    /// Answer the requests of the connection until it is closed, idle or the plugin exits.
+    fn serve_connection(&self, stream: UnixStream, exit: &AtomicBool) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_millis(CONTROL_SOCKET_READ_TIMEOUT_MS)))?;
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = self.handle_request(&line);
+            writeln!(writer, "{}", response)?;
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+    exit: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl ControlSocket {
+    /// Listen on the socket, replacing the socket left by a previous run.
+    pub fn start(path: &str, targets: ControlTargets) -> Result<Self, GeyserPluginError> {
+        let path = PathBuf::from(path);
+        let socket_error = |msg: String| {
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConfigurationError {
+                msg,
+            }))
+        };
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                return Err(socket_error(format!(
+                    "The \"control_socket\" {:?} exists and is not a socket",
+                    path
+                )));
+            }
+            let _ = fs::remove_file(&path);
+        }
+        let listener = UnixListener::bind(&path)
+            .and_then(|listener| {
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+                listener.set_nonblocking(true)?;
+                Ok(listener)
+            })
+            .map_err(|err| {
+                socket_error(format!(
+                    "Failed to listen on the \"control_socket\" {:?}. Error: {:?}",
+                    path, err
+                ))
+            })?;
+        info!("Listening for the control commands on {:?}", path);
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_clone = exit.clone();
+        let listener = Builder::new()
+            .name("control-socket".to_string())
+            .spawn(move || {
+                while !exit_clone.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = targets.serve_connection(stream, &exit_clone) {
+                                debug!("The control connection was closed: {:?}", err);
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                            sleep(Duration::from_millis(CONTROL_SOCKET_ACCEPT_INTERVAL_MS));
+                        }
+                        Err(err) => {
+                            error!("Failed to accept a control connection: {:?}", err);
+                            sleep(Duration::from_millis(CONTROL_SOCKET_ACCEPT_INTERVAL_MS));
+                        }
+                    }
+                }
+            })
+            .unwrap();
+        Ok(Self {
+            path,
+            exit,
+            listener: Some(listener),
+        })
+    }
+
+    /// Stop listening and remove the socket.
+    pub fn join(&mut self) -> thread::Result<()> {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(listener) = self.listener.take() {
+            listener.join()?;
+        }
+        let _ = fs::remove_file(&self.path);
+        Ok(())
+    }
+}
+
+impl PostgresClientWorker {
+    /// Write the buffered account updates when a flush was requested since the last one, unless
+    /// the writes are paused.
+    pub(super) fn flush_on_request(
+        &mut self,
+        ingestion_control: &Option<Arc<IngestionControl>>,
+        inflight_count: &AtomicUsize,
+        checkpoint: &Option<Arc<SlotCheckpoint>>,
+        panic_on_db_errors: bool,
+    ) {
+        let ingestion_control = match ingestion_control {
+            Some(ingestion_control) => ingestion_control,
+            None => return,
+        };
+        let flush_generation = ingestion_control.flush_generation();
+        if flush_generation == self.flushed_generation || ingestion_control.is_paused() {
+            return;
+        }
+        self.flushed_generation = flush_generation;
+
+        let pending_before = self.pending_count();
+        let result = if self.is_startup_done {
+            self.client.flush_adaptive_batch()
+        } else {
+            self.client.flush_buffered_writes()
+        };
+        if let Err(err) = &result {
+            error!("Error in flushing the accounts batch on request: ({})", err);
+            if panic_on_db_errors {
+                abort();
+            }
+        }
+        if let Some(checkpoint) = checkpoint {
+            self.complete_work(checkpoint, None, false, result.is_err(), panic_on_db_errors);
+        }
+        inflight_count.fetch_sub(pending_before - self.pending_count(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::postgres_client_circuit_breaker::WriteAdmission};
+
+    #[test]
+    fn test_control_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("control.sock");
+        let config = serde_json::from_value(json!({
+            "control_socket": path.to_str().unwrap(),
+        }))
+        .unwrap();
+        let ingestion_control = Arc::new(IngestionControl::new(&config).unwrap());
+        let (sender, _receiver) = crossbeam_channel::bounded(1);
+        let targets = ControlTargets {
+            ingestion_control: ingestion_control.clone(),
+            worker_count: 2,
+            initialized_worker_count: Arc::new(AtomicUsize::new(2)),
+            is_startup_done: Arc::new(AtomicBool::new(false)),
+            inflight_count: Arc::new(AtomicUsize::new(3)),
+            sender,
+        };
+        // A stale socket is replaced.
+        drop(UnixListener::bind(&path).unwrap());
+        let mut control_socket = ControlSocket::start(path.to_str().unwrap(), targets).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        let stream = UnixStream::connect(&path).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut request = |request: &str| -> serde_json::Value {
+            writeln!(writer, "{}", request).unwrap();
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+        };
+
+        let status = request(r#"{"command": "status"}"#);
+        assert_eq!(status["ok"], true);
+        assert_eq!(status["paused"], false);
+        assert_eq!(status["connected_workers"], 2);
+
+        assert_eq!(request(r#"{"command": "pause"}"#)["paused"], true);
+        assert_eq!(ingestion_control.admit(), WriteAdmission::Wait);
+        assert_eq!(request(r#"{"command": "resume"}"#)["was_paused"], true);
+        assert_eq!(ingestion_control.admit(), WriteAdmission::Allow);
+
+        assert_eq!(request(r#"{"command": "flush"}"#)["flush_generation"], 1);
+        let stats = request(r#"{"command": "stats"}"#);
+        assert_eq!(stats["inflight"], 3);
+        assert_eq!(stats["flush_generation"], 1);
+
+        assert_eq!(request(r#"{"command": "restart"}"#)["ok"], false);
+        assert_eq!(request("status")["ok"], false);
+
+        control_socket.join().unwrap();
+        assert!(!path.exists());
+    }
+}
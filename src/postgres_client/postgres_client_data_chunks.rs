@@ -0,0 +1,173 @@
+/// Module responsible for writing the data of the very large accounts to the account_data_chunk
+/// table, over multiple rows, when account_data_chunk_threshold is set.
+///
+/// The data larger than the threshold is split into chunks of at most the threshold bytes, each
+/// written to its own row keyed by the pubkey, slot and write_version of the update and the
+/// chunk_index, from 0, with its own statement, so no message nor value holds the whole data.
+/// The account and account_audit rows then store empty data and the number of the chunks in
+/// data_chunk_count, NULL for the data stored in the row. The chunks are written before the
+/// row, so the data of a row with a data_chunk_count is reassembled by concatenating the chunks
+/// of its pubkey, slot and write_version, ordered by the chunk_index, of which there are
+/// data_chunk_count. The chunks are stored as raw bytes whatever the account_data_encoding.
+use {
+    super::{postgres_client_unprepared::PluginStatement, DbAccountInfo, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+/// The number of the chunks of the account data larger than the threshold, None otherwise.
+pub(crate) fn data_chunk_count(account: &DbAccountInfo, threshold: usize) -> Option<i32> {
+    let len = account.data.len();
+    (len > threshold).then(|| ((len + threshold - 1) / threshold) as i32)
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_account_data_chunk_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (pubkey, slot, write_version, chunk_index, chunk_bytes, updated_on) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            ON CONFLICT (pubkey, slot, write_version, chunk_index) DO NOTHING",
+            Self::table_name(&config.table_names, "account_data_chunk")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Write the chunks of the account data larger than the threshold, before its row.
+    pub(crate) fn insert_account_data_chunks(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let threshold = match self.account_columns.data_chunk_threshold() {
+            Some(threshold) if account.data.len() > threshold => threshold,
+            _ => return Ok(()),
+        };
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.insert_account_data_chunk_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+
+        let updated_on = Utc::now().naive_utc();
+        for (chunk_index, chunk_bytes) in account.data.chunks(threshold).enumerate() {
+            let chunk_index = chunk_index as i32;
+            if let Err(err) = statement.execute(
+                &mut client.client,
+                &client.types,
+                &[
+                    &account.pubkey,
+                    &account.slot,
+                    &account.write_version,
+                    &chunk_index,
+                    &chunk_bytes,
+                    &updated_on,
+                ],
+            ) {
+                let msg = format!(
+                    "Failed to persist the chunk {} of the data of account {} at slot {} to the PostgreSQL database. Error: {:?}",
+                    chunk_index,
+                    bs58::encode(&account.pubkey).into_string(),
+                    account.slot,
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_data_chunk_count() {
+        let owner = Pubkey::new_unique();
+        assert_eq!(
+            data_chunk_count(&build_account(&owner, vec![0; 8], 1), 8),
+            None
+        );
+        assert_eq!(
+            data_chunk_count(&build_account(&owner, vec![0; 9], 1), 8),
+            Some(2)
+        );
+        assert_eq!(
+            data_chunk_count(&build_account(&owner, vec![0; 24], 1), 8),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn test_write_account_data_chunks() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "account_data_chunk_threshold": 4 }));
+        SimplePostgresClient::check_schema_columns(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        let large_data: Vec<u8> = (0..10).collect();
+        client
+            .update_account(build_account(&owner, large_data.clone(), 1), true)
+            .unwrap();
+        client
+            .update_account(build_account(&owner, vec![1, 2, 3], 1), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+        client
+            .update_account(build_account(&owner, vec![7; 5], 2), false)
+            .unwrap();
+
+        // The data of the rows reassembled from their chunks.
+        let rows: Vec<(i64, Option<i32>, Vec<u8>)> = db
+            .client
+            .query(
+                "SELECT a.slot, a.data_chunk_count, \
+                CASE WHEN a.data_chunk_count IS NULL THEN a.data \
+                ELSE (SELECT string_agg(c.chunk_bytes, ''::BYTEA ORDER BY c.chunk_index) \
+                FROM account_data_chunk c WHERE c.pubkey = a.pubkey AND c.slot = a.slot \
+                AND c.write_version = a.write_version) END \
+                FROM account a ORDER BY a.slot, a.data_chunk_count",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (1, Some(3), large_data),
+                (1, None, vec![1, 2, 3]),
+                (2, Some(2), vec![7; 5]),
+            ]
+        );
+        assert_eq!(db.count("account_data_chunk"), 5);
+        let empty_data_count: i64 = db
+            .client
+            .query_one(
+                "SELECT COUNT(*) FROM account WHERE data_chunk_count IS NOT NULL AND length(data) = 0",
+                &[],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(empty_data_count, 2);
+    }
+}
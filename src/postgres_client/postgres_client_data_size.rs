@@ -0,0 +1,84 @@
+/// Module for the distribution of the sizes of the account data queued for writing, telling
+/// whether the write volume is dominated by many small accounts or a few large ones, to size
+/// batch_size and max_batch_bytes.
+use {
+    solana_metrics::{datapoint::DataPoint, submit},
+    solana_sdk::timing::AtomicInterval,
+};
+
+/// The exclusive upper bounds of the buckets of the non-empty data in bytes. The empty data
+/// has its own bucket, and the sizes from the last bound on are counted in an overflow bucket.
+const DATA_SIZE_BUCKET_BOUNDS: [usize; 5] = [128, 1024, 10 * 1024, 100 * 1024, 1024 * 1024];
+const DATA_SIZE_BUCKET_COUNT: usize = DATA_SIZE_BUCKET_BOUNDS.len() + 2;
+/// The datapoint field names of the buckets.
+const DATA_SIZE_BUCKET_FIELDS: [&str; DATA_SIZE_BUCKET_COUNT] = [
+    "eq-0", "lt-128b", "lt-1kb", "lt-10kb", "lt-100kb", "lt-1mb", "ge-1mb",
+];
+const DATA_SIZE_REPORT_INTERVAL_MS: u64 = 30_000;
+
+/// The counts of the account data sizes by bucket, reported and reset periodically as the
+/// geyser-plugin-postgres-account-data-size datapoint.
+pub struct DataSizeHistogram {
+    counts: [u64; DATA_SIZE_BUCKET_COUNT],
+    last_report: AtomicInterval,
+}
+
+impl Default for DataSizeHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; DATA_SIZE_BUCKET_COUNT],
+            last_report: AtomicInterval::default(),
+        }
+    }
+}
+
+impl DataSizeHistogram {
+    fn bucket(data_len: usize) -> usize {
+        if data_len == 0 {
+            return 0;
+        }
+        1 + DATA_SIZE_BUCKET_BOUNDS
+            .iter()
+            .position(|bound| data_len < *bound)
+            .unwrap_or(DATA_SIZE_BUCKET_BOUNDS.len())
+    }
+
+    /// Record the size of the account data, reporting the counts when the interval has elapsed.
+    pub fn record(&mut self, data_len: usize) {
+        self.counts[Self::bucket(data_len)] += 1;
+        if self.last_report.should_update(DATA_SIZE_REPORT_INTERVAL_MS) {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        let mut point = DataPoint::new("geyser-plugin-postgres-account-data-size");
+        for (field, count) in DATA_SIZE_BUCKET_FIELDS.iter().zip(self.counts.iter()) {
+            point.add_field_i64(field, *count as i64);
+        }
+        submit(point, log::Level::Info);
+        self.counts = [0; DATA_SIZE_BUCKET_COUNT];
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_size_histogram_buckets() {
+        assert_eq!(DataSizeHistogram::bucket(0), 0);
+        assert_eq!(DataSizeHistogram::bucket(1), 1);
+        assert_eq!(DataSizeHistogram::bucket(127), 1);
+        assert_eq!(DataSizeHistogram::bucket(128), 2);
+        assert_eq!(DataSizeHistogram::bucket(165), 2);
+        assert_eq!(DataSizeHistogram::bucket(1024 * 1024 - 1), 5);
+        assert_eq!(DataSizeHistogram::bucket(10 * 1024 * 1024), 6);
+
+        let mut histogram = DataSizeHistogram::default();
+        for data_len in [0, 82, 3_000, 3_000, 2_000_000] {
+            histogram.record(data_len);
+        }
+        assert_eq!(histogram.counts, [1, 1, 0, 2, 0, 0, 1]);
+    }
+}
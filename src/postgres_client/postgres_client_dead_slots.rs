@@ -0,0 +1,164 @@
+/// Module responsible for marking the slots abandoned by the cluster as dead in the slot table.
+///
+/// The plugin is not notified when a slot is dead or skipped, so it is inferred when a slot
+/// is rooted: the slots below the root which are neither rooted nor one of its ancestors are
+/// on an abandoned fork. The ancestors are found by walking the parents of the slots, as
+/// notified with the processed and confirmed statuses. The slots never produced have no row
+/// in the slot table at all.
+use {
+    super::{postgres_client_unprepared::PluginStatement, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+impl SimplePostgresClient {
+    pub(crate) fn build_dead_slots_update_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        // The walk of the ancestors stops below the oldest undecided slot, or where a parent is
+        // not known yet. Only the slots above the oldest ancestor reached are decided, the
+        // others are left for a later root.
+        let stmt = format!(
+            "WITH RECURSIVE ancestors AS ( \
+            SELECT slot, parent FROM {slot_table} WHERE slot = $1 \
+            UNION ALL \
+            SELECT s.slot, s.parent FROM {slot_table} s JOIN ancestors a ON s.slot = a.parent \
+            WHERE a.slot > (SELECT COALESCE(min(slot), $1) FROM {slot_table} \
+            WHERE slot < $1 AND status <> 'rooted' AND NOT dead)) \
+        UPDATE {slot_table} SET dead = true, updated_on = $2 \
+        WHERE slot < $1 AND slot > (SELECT min(slot) FROM ancestors) \
+        AND status <> 'rooted' AND NOT dead AND slot NOT IN (SELECT slot FROM ancestors)",
+            slot_table = Self::table_name(&config.table_names, "slot")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Mark the slots below the rooted slot which are not its ancestors as dead.
+    pub(crate) fn mark_dead_slots(&mut self, root: u64) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.update_dead_slots_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+
+        let root = root as i64;
+        let updated_on = Utc::now().naive_utc();
+        match statement.execute(&mut client.client, &client.types, &[&root, &updated_on]) {
+            Err(err) => {
+                let msg = format!(
+                    "Failed to mark the dead slots below the root {} in the PostgreSQL database. Error: {:?}",
+                    root, err
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::SlotStatusUpdateError { msg })
+            }
+            Ok(dead_count) => {
+                if dead_count > 0 {
+                    info!(
+                        "Marked {} slots below the root {} as dead",
+                        dead_count, root
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{tests::TestDatabase, PostgresClient},
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    fn dead_slots(db: &mut TestDatabase) -> Vec<i64> {
+        db.client
+            .query("SELECT slot FROM slot WHERE dead ORDER BY slot", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect()
+    }
+
+    #[test]
+    fn test_mark_dead_slots() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "track_dead_slots": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        // 1 <- 2 <- 4 <- 6 is the rooted chain, 3 forks off 2 and 5 off 3.
+        client
+            .update_slot_status(1, None, SlotStatus::Rooted)
+            .unwrap();
+        for (slot, parent) in [(2, 1), (3, 2), (4, 2), (5, 3), (6, 4)] {
+            client
+                .update_slot_status(slot, Some(parent), SlotStatus::Processed)
+                .unwrap();
+        }
+        client
+            .update_slot_status(6, None, SlotStatus::Rooted)
+            .unwrap();
+        assert_eq!(dead_slots(&mut db), vec![3, 5]);
+
+        // The ancestors rooted after the root are not marked dead, and a slot off the chain
+        // is dead even if its parent is not known.
+        client
+            .update_slot_status(4, None, SlotStatus::Rooted)
+            .unwrap();
+        client
+            .update_slot_status(8, None, SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(7, Some(6), SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(9, Some(7), SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(9, None, SlotStatus::Rooted)
+            .unwrap();
+        assert_eq!(dead_slots(&mut db), vec![3, 5, 8]);
+        let status: String = db
+            .client
+            .query_one("SELECT status FROM slot WHERE slot = 2", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(status, "processed");
+    }
+
+    #[test]
+    fn test_dead_slots_unknown_parent() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "track_dead_slots": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        client
+            .update_slot_status(1, None, SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(2, Some(1), SlotStatus::Processed)
+            .unwrap();
+        client
+            .update_slot_status(3, None, SlotStatus::Processed)
+            .unwrap();
+        // The parent of the root is not known, nothing can be decided.
+        client
+            .update_slot_status(3, None, SlotStatus::Rooted)
+            .unwrap();
+        assert!(dead_slots(&mut db).is_empty());
+    }
+}
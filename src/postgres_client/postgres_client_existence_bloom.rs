@@ -0,0 +1,228 @@
+/// Module responsible for the bloom filter of the pubkeys in the account table, answering most
+/// of the existence checks of skip_upsert_existing_accounts_at_startup in memory, when
+/// startup_existence_bloom is set.
+///
+/// The filter is loaded once when the plugin is loaded, streaming the pubkeys of the account
+/// table, and shared by the workers until the end of startup. It is sized for
+/// startup_existence_bloom_size pubkeys, by default the row count estimated by PostgreSQL, at
+/// a false positive rate of startup_existence_bloom_fp_rate. The accounts it does not contain
+/// were not stored when it was loaded and are written without a query; only the accounts it
+/// may contain are checked in the account table, which rules out the false positives. The
+/// pubkeys are uniformly distributed, so the probe positions are derived from their bytes
+/// directly rather than hashed.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    postgres::fallible_iterator::FallibleIterator,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+    std::{iter, sync::Arc},
+};
+
+const DEFAULT_FP_RATE: f64 = 0.01;
+
+pub(crate) struct ExistenceBloom {
+    bits: Vec<u64>,
+    hash_count: u64,
+}
+
+impl ExistenceBloom {
+    /// An empty filter sized for the number of keys at the false positive rate.
+    fn new(size: usize, fp_rate: f64) -> Self {
+        let size = size.max(1) as f64;
+        let bit_count = (-size * fp_rate.ln() / 2f64.ln().powi(2)).ceil().max(64.0);
+        let hash_count = (bit_count / size * 2f64.ln()).round().max(1.0);
+        Self {
+            bits: vec![0; (bit_count as usize + 63) / 64],
+            hash_count: hash_count as u64,
+        }
+    }
+
+    /// The bit positions of the key, by double hashing its first bytes.
+    fn positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let word = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            for (byte, key_byte) in bytes.iter_mut().zip(key.iter().skip(offset)) {
+                *byte = *key_byte;
+            }
+            u64::from_le_bytes(bytes)
+        };
+        let (h1, h2) = (word(0), word(8) | 1);
+        let bit_count = self.bits.len() as u64 * 64;
+        (0..self.hash_count)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count) as usize)
+    }
+
+    fn insert(&mut self, key: &[u8]) {
+        for position in self.positions(key) {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    /// Whether the key may have been inserted, false when it certainly was not.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        self.positions(key)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+
+    /// Load the filter of the pubkeys in the account table, when configured.
+    pub fn load(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Option<Arc<Self>>, GeyserPluginError> {
+        if !matches!(config.startup_existence_bloom, Some(true)) {
+            return Ok(None);
+        }
+        if !matches!(config.skip_upsert_existing_accounts_at_startup, Some(true)) {
+            warn!(
+                "\"startup_existence_bloom\" is ignored without \"skip_upsert_existing_accounts_at_startup\""
+            );
+            return Ok(None);
+        }
+        let fp_rate = config
+            .startup_existence_bloom_fp_rate
+            .unwrap_or(DEFAULT_FP_RATE);
+        if !(fp_rate > 0.0 && fp_rate < 1.0) {
+            let msg = format!(
+                "\"startup_existence_bloom_fp_rate\" must be between 0 and 1, not {}",
+                fp_rate
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+
+        let mut measure = Measure::start("geyser-plugin-postgres-load-existence-bloom");
+        let account_table = SimplePostgresClient::table_name(&config.table_names, "account");
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let result = (|| {
+            let size = match config.startup_existence_bloom_size {
+                Some(size) => size,
+                None => {
+                    let row = client.query_one(
+                        "SELECT GREATEST(reltuples, 0)::BIGINT FROM pg_class WHERE oid = $1::TEXT::REGCLASS",
+                        &[&account_table],
+                    )?;
+                    row.get::<_, i64>(0) as usize
+                }
+            };
+            let mut bloom = Self::new(size, fp_rate);
+            let mut count = 0;
+            let mut rows = client.query_raw(
+                format!("SELECT pubkey FROM {}", account_table).as_str(),
+                iter::empty::<i64>(),
+            )?;
+            while let Some(row) = rows.next()? {
+                bloom.insert(row.get::<_, &[u8]>(0));
+                count += 1;
+            }
+            Ok::<_, postgres::Error>((bloom, size, count))
+        })();
+        let (bloom, size, count) = match result {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                let msg = format!(
+                    "Failed to load the existing pubkeys from the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )));
+            }
+        };
+        measure.stop();
+        if count > size {
+            warn!(
+                "The existence bloom filter sized for {} pubkeys holds {}, its false positive rate is above {}",
+                size, count, fp_rate
+            );
+        }
+        info!(
+            "Loaded {} existing pubkeys into a bloom filter of {} bytes in {}ms",
+            count,
+            bloom.bits.len() * 8,
+            measure.as_ms()
+        );
+        Ok(Some(Arc::new(bloom)))
+    }
+}
+
+impl SimplePostgresClient {
+    /// Share the existence bloom filter of the workers with the client.
+    pub(crate) fn set_existence_bloom(&mut self, existence_bloom: Option<Arc<ExistenceBloom>>) {
+        self.existence_bloom = existence_bloom;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_existence_bloom() {
+        let pubkeys: Vec<Pubkey> = (0..1000).map(|_| Pubkey::new_unique()).collect();
+        let mut bloom = ExistenceBloom::new(pubkeys.len(), 0.01);
+        for pubkey in &pubkeys {
+            bloom.insert(pubkey.as_ref());
+        }
+        assert!(pubkeys
+            .iter()
+            .all(|pubkey| bloom.may_contain(pubkey.as_ref())));
+        let false_positive_count = (0..10_000)
+            .filter(|_| bloom.may_contain(solana_sdk::pubkey::new_rand().as_ref()))
+            .count();
+        assert!(false_positive_count < 300, "{}", false_positive_count);
+    }
+
+    #[test]
+    fn test_startup_existence_bloom() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let owner = Pubkey::new_unique();
+        let stored_account = build_account(&owner, vec![1], 1);
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+        client
+            .update_account(stored_account.clone(), false)
+            .unwrap();
+
+        let config = db.config(json!({
+            "batch_size": 2,
+            "skip_upsert_existing_accounts_at_startup": true,
+            "startup_existence_bloom": true,
+            "startup_existence_bloom_size": 100,
+        }));
+        let bloom = ExistenceBloom::load(&config).unwrap().unwrap();
+        assert!(bloom.may_contain(&stored_account.pubkey));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client.set_existence_bloom(Some(bloom));
+        let mut account = stored_account.clone();
+        account.slot = 2;
+        account.data = vec![2];
+        client.update_account(account, true).unwrap();
+        client
+            .update_account(build_account(&owner, vec![2], 2), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+
+        let rows: Vec<(i64, Vec<u8>)> = db
+            .client
+            .query("SELECT slot, data FROM account ORDER BY slot", &[])
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(rows, vec![(1, vec![1]), (2, vec![2])]);
+    }
+}
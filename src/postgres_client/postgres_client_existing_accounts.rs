@@ -0,0 +1,152 @@
+/// Module responsible for skipping the accounts already in the account table while the
+/// accounts are loaded from the snapshot at startup, when
+/// skip_upsert_existing_accounts_at_startup is set.
+///
+/// On a restart against a populated database, most of the accounts of the snapshot are
+/// already stored, and writing them again is the bulk of the startup. The existence of the
+/// accounts of each batch is checked with a single query taking their pubkeys as an array
+/// parameter, instead of one query per account, and only the accounts not found are written.
+/// With startup_existence_bloom, only the accounts the bloom filter may contain are queried.
+use {
+    super::{postgres_client_unprepared::PluginStatement, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{collections::HashSet, iter},
+};
+
+impl SimplePostgresClient {
+    pub(crate) fn build_existing_accounts_select_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "SELECT pubkey FROM {} WHERE pubkey = ANY($1::BYTEA[])",
+            Self::table_name(&config.table_names, "account")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Remove the pending accounts already in the account table, at startup.
+    pub(crate) fn remove_existing_accounts(&mut self) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.select_existing_accounts_stmt {
+            Some(statement)
+                if !self.slots_at_startup.is_empty()
+                    && !self.pending_account_updates.is_empty() =>
+            {
+                statement
+            }
+            _ => return Ok(()),
+        };
+        let pubkeys: Vec<&[u8]> = self
+            .pending_account_updates
+            .iter()
+            .map(|account| account.pubkey.as_slice())
+            .filter(|pubkey| {
+                self.existence_bloom
+                    .as_ref()
+                    .map_or(true, |existence_bloom| existence_bloom.may_contain(pubkey))
+            })
+            .collect();
+        if pubkeys.is_empty() {
+            return Ok(());
+        }
+        let existing: HashSet<Vec<u8>> =
+            match statement.query(&mut client.client, &client.types, &[&pubkeys]) {
+                Ok(rows) => rows.iter().map(|row| row.get(0)).collect(),
+                Err(err) => {
+                    let msg = format!(
+                    "Failed to check the existing accounts in the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                    error!("{}", msg);
+                    return Err(GeyserPluginError::AccountsUpdateError { msg });
+                }
+            };
+        if !existing.is_empty() {
+            debug!(
+                "Skipping {} of {} accounts already stored",
+                existing.len(),
+                self.pending_account_updates.len()
+            );
+            self.pending_account_updates
+                .retain(|account| !existing.contains(&account.pubkey));
+        }
+        Ok(())
+    }
+
+    /// Bulk insert the pending accounts not already in the account table.
+    pub(crate) fn bulk_insert_new_accounts(&mut self) -> Result<(), GeyserPluginError> {
+        self.remove_existing_accounts()?;
+        if self.pending_account_updates.is_empty() {
+            return Ok(());
+        }
+        let sub_batches = self
+            .batch_splitter
+            .split(&self.pending_account_updates)
+            .unwrap_or_else(|| iter::once(0..self.pending_account_updates.len()).collect());
+        let result = self.bulk_insert_account_sub_batches(&sub_batches);
+        self.pending_account_updates.clear();
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            DbAccountInfo, PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_skip_upsert_existing_accounts_at_startup() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let owner = Pubkey::new_unique();
+        let accounts: Vec<DbAccountInfo> =
+            (0..5).map(|_| build_account(&owner, vec![1], 1)).collect();
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+        for account in &accounts[..2] {
+            client.update_account(account.clone(), false).unwrap();
+        }
+
+        // Restarted from a later snapshot, with a batch of the two stored accounts and one
+        // new, then the two other new accounts left to the end of startup.
+        let config = db.config(json!({
+            "batch_size": 3,
+            "skip_upsert_existing_accounts_at_startup": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        for account in &accounts {
+            let mut account = account.clone();
+            account.slot = 2;
+            account.data = vec![2];
+            client.update_account(account, true).unwrap();
+        }
+        assert_eq!(db.count("account"), 3);
+        client.notify_end_of_startup().unwrap();
+
+        let rows: Vec<(Vec<u8>, Vec<u8>)> = db
+            .client
+            .query("SELECT pubkey, data FROM account", &[])
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(rows.len(), 5);
+        for (pubkey, data) in rows {
+            let stored = accounts[..2].iter().any(|account| account.pubkey == pubkey);
+            assert_eq!(data, if stored { vec![1] } else { vec![2] });
+        }
+    }
+}
@@ -0,0 +1,129 @@
+/// Module responsible for applying the configured fillfactor of the tables when the plugin is
+/// loaded.
+///
+/// The plugin does not create the tables, which are created by scripts/create_schema.sql, so
+/// the fillfactor is set with ALTER TABLE. It applies to the pages filled from then on, the
+/// existing pages are repacked by VACUUM FULL or CLUSTER. A lower fillfactor leaves room in
+/// each page for the HOT updates of the rows, such as the upserts of the account table.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+/// The fillfactor range accepted by PostgreSQL for the tables.
+const MIN_FILLFACTOR: u8 = 10;
+const MAX_FILLFACTOR: u8 = 100;
+
+impl SimplePostgresClient {
+    /// Build the statements setting the fillfactor of the tables, validating the table names
+    /// and the fillfactors.
+    pub(crate) fn build_fillfactor_statements(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Vec<String>, GeyserPluginError> {
+        let table_fillfactor = match &config.table_fillfactor {
+            Some(table_fillfactor) => table_fillfactor,
+            None => return Ok(Vec::default()),
+        };
+        table_fillfactor
+            .iter()
+            .map(|(table, fillfactor)| {
+                let is_valid_table = table.starts_with(|c: char| c.is_ascii_lowercase())
+                    && table
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+                if !is_valid_table || !(MIN_FILLFACTOR..=MAX_FILLFACTOR).contains(fillfactor) {
+                    let msg = format!(
+                        "Invalid \"table_fillfactor\" entry {:?}: {}, the fillfactor must be between {} and {}",
+                        table, fillfactor, MIN_FILLFACTOR, MAX_FILLFACTOR
+                    );
+                    error!("{}", msg);
+                    return Err(GeyserPluginError::Custom(Box::new(
+                        GeyserPluginPostgresError::ConfigurationError { msg },
+                    )));
+                }
+                Ok(format!(
+                    "ALTER TABLE \"{}\" SET (fillfactor = {})",
+                    table, fillfactor
+                ))
+            })
+            .collect()
+    }
+
+    /// Set the fillfactor of the tables, on a short-lived connection.
+    pub fn apply_table_fillfactor(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let statements = Self::build_fillfactor_statements(config)?;
+        if statements.is_empty() {
+            return Ok(());
+        }
+
+        let mut client = Self::connect_to_db(config)?;
+        for statement in statements {
+            if let Err(err) = client.batch_execute(&statement) {
+                let msg = format!(
+                    "Failed to set the fillfactor with {:?}. Error: {:?}",
+                    statement, err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )));
+            }
+            info!("{}", statement);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_build_fillfactor_statements() {
+        let config = serde_json::from_value(json!({
+            "table_fillfactor": { "account": 80, "slot": 90 },
+        }))
+        .unwrap();
+        assert_eq!(
+            SimplePostgresClient::build_fillfactor_statements(&config).unwrap(),
+            vec![
+                "ALTER TABLE \"account\" SET (fillfactor = 80)",
+                "ALTER TABLE \"slot\" SET (fillfactor = 90)",
+            ]
+        );
+
+        for table_fillfactor in [
+            json!({ "account": 5 }),
+            json!({ "account\"; DROP TABLE slot; --": 80 }),
+            json!({ "Account": 80 }),
+        ] {
+            let config =
+                serde_json::from_value(json!({ "table_fillfactor": table_fillfactor })).unwrap();
+            assert!(SimplePostgresClient::build_fillfactor_statements(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_apply_table_fillfactor() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "table_fillfactor": { "account": 80 } }));
+        SimplePostgresClient::apply_table_fillfactor(&config).unwrap();
+
+        let options: Vec<String> = db
+            .client
+            .query_one(
+                "SELECT reloptions FROM pg_class WHERE oid = 'account'::regclass",
+                &[],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(options, vec!["fillfactor=80"]);
+    }
+}
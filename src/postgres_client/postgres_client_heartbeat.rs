@@ -0,0 +1,258 @@
+/// Module responsible for the heartbeat, when heartbeat_interval_secs is set: a background thread
+/// updating the single row of the heartbeat table every heartbeat_interval_secs seconds with the
+/// current time and the highest slot notified, also when no data is written, so the consumers
+/// can tell a quiet plugin from a dead one.
+///
+/// The heartbeat has its own connection, so it is not held back by the writes queued for the
+/// workers. When heartbeat_channel is set, each heartbeat is also sent with pg_notify on that
+/// channel, as a JSON payload such as {"beat_on": "2022-04-15T05:20:00.000", "highest_slot": 5}. A
+/// heartbeat failing is logged, and the connection is opened again for the next one.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    chrono::{naive::NaiveDateTime, Utc},
+    log::*,
+    postgres::Client,
+    serde_json::json,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+const HEARTBEAT_EXIT_CHECK_MS: u64 = 100;
+
+/// The connection of the heartbeat and its statements.
+struct HeartbeatClient {
+    client: Client,
+    upsert_heartbeat_stmt: PluginStatement,
+    notify_heartbeat_stmt: Option<PluginStatement>,
+    types: Mutex<Option<TypeRegistry>>,
+}
+
+impl SimplePostgresClient {
+    fn connect_heartbeat(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<HeartbeatClient, GeyserPluginError> {
+        let mut client = Self::connect_to_db(config)?;
+        let stmt = format!(
+            "INSERT INTO {} (id, beat_on, highest_slot) VALUES (0, $1, $2) \
+            ON CONFLICT (id) DO UPDATE SET beat_on=excluded.beat_on, highest_slot=excluded.highest_slot",
+            Self::table_name(&config.table_names, "heartbeat")
+        );
+        let upsert_heartbeat_stmt = Self::prepare_query_statement(&mut client, config, &stmt)?;
+        let notify_heartbeat_stmt = match &config.heartbeat_channel {
+            Some(_) => Some(Self::prepare_query_statement(
+                &mut client,
+                config,
+                "SELECT pg_notify($1, $2)",
+            )?),
+            None => None,
+        };
+        Ok(HeartbeatClient {
+            client,
+            upsert_heartbeat_stmt,
+            notify_heartbeat_stmt,
+            types: Mutex::default(),
+        })
+    }
+
+    /// Write the heartbeat with the highest slot notified, if any.
+    fn write_heartbeat(
+        heartbeat_client: &mut HeartbeatClient,
+        config: &GeyserPluginPostgresConfig,
+        beat_on: &NaiveDateTime,
+        highest_slot: Option<u64>,
+    ) -> Result<(), GeyserPluginError> {
+        let HeartbeatClient {
+            client,
+            upsert_heartbeat_stmt,
+            notify_heartbeat_stmt,
+            types,
+        } = heartbeat_client;
+        let highest_slot = highest_slot.map(|slot| slot as i64);
+        let mut result = upsert_heartbeat_stmt
+            .execute(client, types, &[beat_on, &highest_slot])
+            .map(|_| ());
+        if let (Ok(()), Some(statement), Some(channel)) =
+            (&result, notify_heartbeat_stmt, &config.heartbeat_channel)
+        {
+            let payload = json!({
+                "beat_on": beat_on.format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+                "highest_slot": highest_slot,
+            })
+            .to_string();
+            result = statement
+                .query(client, types, &[channel, &payload])
+                .map(|_| ());
+        }
+        result.map_err(|err| {
+            let msg = format!(
+                "Failed to write the heartbeat to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataStoreConnectionError { msg },
+            ))
+        })
+    }
+}
+
+/// The background thread writing the heartbeats.
+pub(crate) struct Heartbeat {
+    exit: Arc<AtomicBool>,
+    /// The highest slot notified, 0 until a slot is notified.
+    highest_slot: Arc<AtomicU64>,
+    beater: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Start the heartbeat when heartbeat_interval_secs is set. The first heartbeat is written
+    /// before it returns.
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let interval_ms = match config.heartbeat_interval_secs {
+            None => return Ok(None),
+            Some(0) => {
+                let msg = "\"heartbeat_interval_secs\" must be positive".to_string();
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::ConfigurationError { msg },
+                )));
+            }
+            Some(interval_secs) => interval_secs.saturating_mul(1000),
+        };
+        let mut heartbeat_client = SimplePostgresClient::connect_heartbeat(config)?;
+        SimplePostgresClient::write_heartbeat(
+            &mut heartbeat_client,
+            config,
+            &Utc::now().naive_utc(),
+            None,
+        )?;
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_clone = exit.clone();
+        let highest_slot = Arc::new(AtomicU64::default());
+        let highest_slot_clone = highest_slot.clone();
+        let config = config.clone();
+        let beater = Builder::new()
+            .name("heartbeat".to_string())
+            .spawn(move || {
+                let mut heartbeat_client = Some(heartbeat_client);
+                let mut waited_ms = 0;
+                while !exit_clone.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(HEARTBEAT_EXIT_CHECK_MS));
+                    waited_ms += HEARTBEAT_EXIT_CHECK_MS;
+                    if waited_ms < interval_ms {
+                        continue;
+                    }
+                    waited_ms = 0;
+                    if heartbeat_client.is_none() {
+                        heartbeat_client = SimplePostgresClient::connect_heartbeat(&config).ok();
+                    }
+                    if let Some(client) = &mut heartbeat_client {
+                        let highest_slot = match highest_slot_clone.load(Ordering::Relaxed) {
+                            0 => None,
+                            slot => Some(slot),
+                        };
+                        let beat_on = Utc::now().naive_utc();
+                        // The errors are logged, the connection is opened again for the next
+                        // heartbeat.
+                        if SimplePostgresClient::write_heartbeat(
+                            client,
+                            &config,
+                            &beat_on,
+                            highest_slot,
+                        )
+                        .is_err()
+                        {
+                            heartbeat_client = None;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        Ok(Some(Self {
+            exit,
+            highest_slot,
+            beater: Some(beater),
+        }))
+    }
+
+    /// Record the slot notified, kept when it is the highest.
+    pub fn observe_slot(&self, slot: u64) {
+        self.highest_slot.fetch_max(slot, Ordering::Relaxed);
+    }
+
+    pub fn join(&mut self) -> thread::Result<()> {
+        self.exit.store(true, Ordering::Relaxed);
+        match self.beater.take() {
+            Some(beater) => beater.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::tests::TestDatabase,
+        postgres::fallible_iterator::FallibleIterator,
+        serde_json::{json, Value},
+    };
+
+    #[test]
+    fn test_heartbeat() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        assert!(Heartbeat::new(&db.config(json!({}))).unwrap().is_none());
+        assert!(Heartbeat::new(&db.config(json!({ "heartbeat_interval_secs": 0 }))).is_err());
+
+        db.client.batch_execute("LISTEN geyser_heartbeat").unwrap();
+        let config = db.config(json!({
+            "heartbeat_interval_secs": 1,
+            "heartbeat_channel": "geyser_heartbeat",
+        }));
+        let mut heartbeat = Heartbeat::new(&config).unwrap().unwrap();
+        let highest_slot: Option<i64> = db
+            .client
+            .query_one("SELECT highest_slot FROM heartbeat", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(highest_slot, None);
+
+        heartbeat.observe_slot(7);
+        heartbeat.observe_slot(5);
+        sleep(Duration::from_millis(1500));
+        heartbeat.join().unwrap();
+        let row = db
+            .client
+            .query_one("SELECT highest_slot, beat_on FROM heartbeat", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Option<i64>>(0), Some(7));
+        assert_eq!(db.count("heartbeat"), 1);
+
+        let payloads: Vec<Value> = db
+            .client
+            .notifications()
+            .iter()
+            .map(|notification| Ok(serde_json::from_str(notification.payload()).unwrap()))
+            .collect()
+            .unwrap();
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0]["highest_slot"], Value::Null);
+        assert_eq!(payloads[1]["highest_slot"], json!(7));
+    }
+}
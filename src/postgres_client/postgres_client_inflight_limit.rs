@@ -0,0 +1,144 @@
+/// Module responsible for limiting the batches in flight per worker to
+/// max_inflight_batches_per_worker, dispatched but not yet written to the database.
+///
+/// The work is measured in batches: an account update is a batch_size-th of a bulk insert, any
+/// other work item is written on its own and is a batch. The workers share the queue of the
+/// work items, so the batches queued are spread over the workers still running, and each
+/// worker also holds the account updates it buffers and the work item it writes. The
+/// notifications block while no running worker is below the limit, and fail once no worker
+/// is running, as nothing would ever free the queue.
+use {
+    super::DbWorkItem,
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    std::sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+pub(crate) struct InflightLimit {
+    batch_size: usize,
+    /// The limit per worker, in account updates.
+    max_worker_units: usize,
+    /// The work queued for the workers, in account updates.
+    queued_units: AtomicUsize,
+    /// The work held by each worker, in account updates.
+    worker_units: Vec<AtomicUsize>,
+    /// Whether each worker is still running.
+    running_workers: Vec<AtomicBool>,
+}
+
+impl InflightLimit {
+    pub fn new(
+        config: &GeyserPluginPostgresConfig,
+        batch_size: usize,
+        worker_count: usize,
+    ) -> Option<Self> {
+        let max_batches = config.max_inflight_batches_per_worker?;
+        Some(Self {
+            batch_size,
+            max_worker_units: max_batches * batch_size,
+            queued_units: AtomicUsize::default(),
+            worker_units: (0..worker_count).map(|_| AtomicUsize::default()).collect(),
+            running_workers: (0..worker_count).map(|_| AtomicBool::new(true)).collect(),
+        })
+    }
+
+    /// The work of the work item, in account updates.
+    pub fn units(&self, item: &DbWorkItem) -> usize {
+        match item {
+            DbWorkItem::UpdateAccount(_) => 1,
+            _ => self.batch_size,
+        }
+    }
+
+    /// Count the work item queued for the workers.
+    pub fn queue(&self, units: usize) {
+        self.queued_units.fetch_add(units, Ordering::Relaxed);
+    }
+
+    /// Count the work item dropped before the workers received it.
+    pub fn unqueue(&self, units: usize) {
+        self.queued_units.fetch_sub(units, Ordering::Relaxed);
+    }
+
+    /// Count the work item received by the worker, holding its account updates buffered.
+    pub fn receive(&self, worker: usize, units: usize, buffered_units: usize) {
+        self.queued_units.fetch_sub(units, Ordering::Relaxed);
+        self.hold(worker, buffered_units + units);
+    }
+
+    /// Set the work held by the worker.
+    pub fn hold(&self, worker: usize, units: usize) {
+        self.worker_units[worker].store(units, Ordering::Relaxed);
+    }
+
+    /// Count the worker as stopped, its work is no longer freed.
+    pub fn stop(&self, worker: usize) {
+        self.running_workers[worker].store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the limit is reached by all the running workers, None when no worker is running.
+    pub fn is_reached(&self) -> Option<bool> {
+        let running: Vec<usize> = (0..self.worker_units.len())
+            .filter(|worker| self.running_workers[*worker].load(Ordering::Relaxed))
+            .collect();
+        let queued_units = self.queued_units.load(Ordering::Relaxed) / running.len().max(1);
+        running
+            .iter()
+            .map(|worker| self.worker_units[*worker].load(Ordering::Relaxed) + queued_units)
+            .min()
+            .map(|units| units >= self.max_worker_units)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{tests::build_account, UpdateAccountRequest, UpdateSlotRequest},
+        chrono::Utc,
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_inflight_limit() {
+        let config =
+            serde_json::from_value(json!({ "max_inflight_batches_per_worker": 2 })).unwrap();
+        let limit = InflightLimit::new(&config, 10, 2).unwrap();
+        let account = DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+            account: build_account(&Pubkey::new_unique(), vec![], 1),
+            is_startup: false,
+        }));
+        let slot = DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot: 1,
+            parent: None,
+            slot_status: SlotStatus::Rooted,
+            observed_on: Utc::now().naive_utc(),
+            transaction_tally: None,
+        }));
+        assert_eq!(limit.units(&account), 1);
+        assert_eq!(limit.units(&slot), 10);
+
+        // Two batches queued per worker reach the limit.
+        for _ in 0..3 {
+            limit.queue(limit.units(&slot));
+        }
+        assert_eq!(limit.is_reached(), Some(false));
+        limit.queue(limit.units(&slot));
+        assert_eq!(limit.is_reached(), Some(true));
+
+        // A worker which is behind does not hold the other back.
+        limit.receive(0, 10, 15);
+        limit.receive(0, 10, 0);
+        limit.hold(1, 0);
+        assert_eq!(limit.is_reached(), Some(false));
+        limit.stop(1);
+        assert_eq!(limit.is_reached(), Some(true));
+        limit.unqueue(20);
+        limit.stop(0);
+        assert_eq!(limit.is_reached(), None);
+
+        let config = serde_json::from_value(json!({})).unwrap();
+        assert!(InflightLimit::new(&config, 10, 2).is_none());
+    }
+}
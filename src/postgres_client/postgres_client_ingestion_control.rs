@@ -0,0 +1,192 @@
+/// Module responsible for pausing and resuming the writes to the PostgreSQL database through
+/// a control file, for the database maintenance without unloading the plugin.
+///
+/// The workers read the control file at most once per CONTROL_FILE_CHECK_INTERVAL_MS. While
+/// it contains "pause", the workers stop writing: they hold their work items, so the
+/// notifications queue up and eventually block the validator, or drop them with
+/// drop_writes_when_paused. Any other content, such as "resume", or a missing file resumes
+/// the writes.
+///
+/// The control socket pauses and resumes the writes likewise, independently of the control
+/// file, and requests the workers to flush the account updates they buffer.
+use {
+    super::postgres_client_circuit_breaker::WriteAdmission,
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    solana_metrics::*,
+    solana_sdk::timing::AtomicInterval,
+    std::{
+        fs,
+        path::PathBuf,
+        sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+const CONTROL_FILE_CHECK_INTERVAL_MS: u64 = 1000;
+const CONTROL_FILE_PAUSE: &str = "pause";
+
+pub(crate) struct IngestionControl {
+    control_file: Option<PathBuf>,
+    drop_writes_when_paused: bool,
+    /// Whether the writes are paused by the control file.
+    paused: AtomicBool,
+    /// Whether the writes are paused by the control socket.
+    paused_by_socket: AtomicBool,
+    last_check: AtomicInterval,
+    dropped_count: AtomicU64,
+    /// The number of the flushes requested by the control socket.
+    flush_generation: AtomicU64,
+}
+
+impl IngestionControl {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Option<Self> {
+        if config.control_file.is_none() && config.control_socket.is_none() {
+            return None;
+        }
+        Some(Self {
+            control_file: config.control_file.as_ref().map(PathBuf::from),
+            drop_writes_when_paused: matches!(config.drop_writes_when_paused, Some(true)),
+            paused: AtomicBool::new(false),
+            paused_by_socket: AtomicBool::new(false),
+            last_check: AtomicInterval::default(),
+            dropped_count: AtomicU64::default(),
+            flush_generation: AtomicU64::default(),
+        })
+    }
+
+    /// Read the control file, when it was not read within the check interval.
+    fn check_control_file(&self) {
+        if self.control_file.is_some()
+            && self
+                .last_check
+                .should_update(CONTROL_FILE_CHECK_INTERVAL_MS)
+        {
+            self.read_control_file();
+        }
+    }
+
+    fn read_control_file(&self) {
+        let control_file = match &self.control_file {
+            Some(control_file) => control_file,
+            None => return,
+        };
+        let paused = matches!(
+            fs::read_to_string(control_file),
+            Ok(contents) if contents.trim() == CONTROL_FILE_PAUSE
+        );
+        if self.paused.swap(paused, Ordering::Relaxed) != paused {
+            if paused {
+                warn!(
+                    "Paused the writes to the PostgreSQL database by the control file {:?}",
+                    control_file
+                );
+            } else {
+                info!(
+                    "Resumed the writes to the PostgreSQL database, {} writes were dropped",
+                    self.dropped_count.load(Ordering::Relaxed)
+                );
+            }
+            datapoint_info!(
+                "geyser-plugin-postgres-ingestion-control",
+                ("paused", paused as i64, i64),
+                (
+                    "dropped-count",
+                    self.dropped_count.load(Ordering::Relaxed) as i64,
+                    i64
+                ),
+            );
+        }
+    }
+
+    /// Pause or resume the writes by the control socket, returns whether they were paused by it.
+    pub fn set_paused_by_socket(&self, paused: bool) -> bool {
+        let was_paused = self.paused_by_socket.swap(paused, Ordering::Relaxed);
+        if was_paused != paused {
+            if paused {
+                warn!("Paused the writes to the PostgreSQL database by the control socket");
+            } else {
+                info!(
+                    "Resumed the writes to the PostgreSQL database by the control socket, {} writes were dropped",
+                    self.dropped_count.load(Ordering::Relaxed)
+                );
+            }
+        }
+        was_paused
+    }
+
+    /// Whether the writes are paused by the control file or the control socket.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed) || self.paused_by_socket.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped_count.load(Ordering::Relaxed)
+    }
+
+    /// Request the workers to flush their buffered account updates, returns the generation of
+    /// the flush.
+    pub fn request_flush(&self) -> u64 {
+        self.flush_generation.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn flush_generation(&self) -> u64 {
+        self.flush_generation.load(Ordering::Relaxed)
+    }
+
+    /// Check whether a write is admitted.
+    pub fn admit(&self) -> WriteAdmission {
+        self.check_control_file();
+        if !self.is_paused() {
+            WriteAdmission::Allow
+        } else if self.drop_writes_when_paused {
+            self.dropped_count.fetch_add(1, Ordering::Relaxed);
+            WriteAdmission::Drop
+        } else {
+            WriteAdmission::Wait
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    fn build_ingestion_control(
+        control_file: &str,
+        drop_writes_when_paused: bool,
+    ) -> IngestionControl {
+        let config = serde_json::from_value(json!({
+            "control_file": control_file,
+            "drop_writes_when_paused": drop_writes_when_paused,
+        }))
+        .unwrap();
+        IngestionControl::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_ingestion_control() {
+        let dir = tempfile::tempdir().unwrap();
+        let control_file = dir.path().join("control");
+        let control = build_ingestion_control(control_file.to_str().unwrap(), false);
+
+        // The missing control file does not pause the writes.
+        assert_eq!(control.admit(), WriteAdmission::Allow);
+
+        fs::write(&control_file, "pause\n").unwrap();
+        control.read_control_file();
+        assert_eq!(control.admit(), WriteAdmission::Wait);
+
+        fs::write(&control_file, "resume").unwrap();
+        control.read_control_file();
+        assert_eq!(control.admit(), WriteAdmission::Allow);
+
+        let control = build_ingestion_control(control_file.to_str().unwrap(), true);
+        fs::write(&control_file, "pause").unwrap();
+        control.read_control_file();
+        assert_eq!(control.admit(), WriteAdmission::Drop);
+        assert_eq!(control.dropped_count.load(Ordering::Relaxed), 1);
+        fs::remove_file(&control_file).unwrap();
+        control.read_control_file();
+        assert_eq!(control.admit(), WriteAdmission::Allow);
+    }
+}
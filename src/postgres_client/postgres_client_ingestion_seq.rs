@@ -0,0 +1,82 @@
+/// Module responsible for recording the ingestion_seq, an increasing offset usable as a cursor,
+/// to the seq column of the account and transaction tables, when record_ingestion_seq is set.
+///
+/// The seq is assigned by the ingestion_seq triggers from the ingestion_seq sequence to each
+/// row inserted or updated, by any connection. The triggers are created by the optional
+/// create_ingestion_seq.sql script rather than create_schema.sql, so the deployments not using
+/// the feature pay neither for the triggers nor for the seq indexes; the plugin checks the
+/// triggers are installed when it is loaded. The seq is taken without a lock, so the workers
+/// writing concurrently are not serialized, and a seq may commit after a higher one is already
+/// visible: the consumers resuming with WHERE seq > last_seen ORDER BY seq should resume from a
+/// margin below the last seq seen, covering the writes in flight, and skip the rows already
+/// read. The seqs of the writes rolled back, and of the inserts skipped by their conflict, are
+/// left as gaps. An account updated again is moved to a new seq.
+use super::postgres_client_schema_check::RequiredTrigger;
+
+/// The triggers of create_ingestion_seq.sql.
+pub(crate) const INGESTION_SEQ_TRIGGERS: [RequiredTrigger; 2] = [
+    (
+        "create_ingestion_seq.sql",
+        "account",
+        "account_ingestion_seq_trigger",
+    ),
+    (
+        "create_ingestion_seq.sql",
+        "transaction",
+        "transaction_ingestion_seq_trigger",
+    ),
+];
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        crate::postgres_client::{
+            postgres_client_transaction::tests::build_test_log_transaction_request,
+            tests::{build_account, TestDatabase},
+            PostgresClient, SimplePostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn seq(db: &mut TestDatabase, stmt: &str, key: &[u8]) -> Option<i64> {
+        db.client.query_one(stmt, &[&key]).unwrap().get(0)
+    }
+
+    #[test]
+    fn test_record_ingestion_seq() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let account_seq = "SELECT seq FROM account WHERE pubkey = $1";
+        let transaction_seq = "SELECT seq FROM transaction WHERE signature = $1";
+        let config = db.config(json!({ "record_ingestion_seq": true }));
+        assert!(SimplePostgresClient::check_schema_triggers(&config).is_err());
+        db.client
+            .batch_execute(include_str!("../../scripts/create_ingestion_seq.sql"))
+            .unwrap();
+        SimplePostgresClient::check_schema_triggers(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 1);
+        client.update_account(account.clone(), false).unwrap();
+        let request = build_test_log_transaction_request(1, 1);
+        let signature = request.transaction_info.signature.clone();
+        client.log_transaction(request).unwrap();
+        let first = seq(&mut db, account_seq, &account.pubkey).unwrap();
+        let second = seq(&mut db, transaction_seq, &signature).unwrap();
+        assert!(second > first);
+
+        // An account updated again is moved to a new seq.
+        account.slot = 2;
+        client.update_account(account.clone(), false).unwrap();
+        assert!(seq(&mut db, account_seq, &account.pubkey).unwrap() > second);
+
+        // The seq is assigned to the writes of every connection once the script is applied.
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+        let account = build_account(&Pubkey::new_unique(), vec![], 3);
+        client.update_account(account.clone(), false).unwrap();
+        assert!(seq(&mut db, account_seq, &account.pubkey).unwrap() > second);
+    }
+}
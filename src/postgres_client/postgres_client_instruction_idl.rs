@@ -0,0 +1,560 @@
+/// Module responsible for decoding the instructions of a program by its Anchor IDL into the
+/// transaction_instruction table.
+///
+/// An Anchor instruction starts with an 8-byte discriminator, the first 8 bytes of the SHA-256
+/// of "global:<instruction name in snake case>", or as listed in the IDL, followed by the Borsh
+/// encoded arguments. The arguments of the primitive types, public keys, strings, bytes, and
+/// the vectors, options and arrays of them are decoded into a JSON object. The arguments of the
+/// types defined by the IDL are not decoded, leaving args NULL. The instructions of the
+/// program with an unknown discriminator are stored without their name.
+use {
+    super::{
+        postgres_client_transaction::DbTransaction,
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{
+        GeyserPluginPostgresConfig, GeyserPluginPostgresError, JsonColumnType,
+    },
+    log::*,
+    postgres::Client,
+    serde_derive::Deserialize,
+    serde_json::{Map, Value},
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::{hash::hash, pubkey::Pubkey},
+    std::{convert::TryInto, fs, str::FromStr, sync::Mutex},
+};
+
+const DISCRIMINATOR_LEN: usize = 8;
+
+#[derive(Deserialize)]
+struct Idl {
+    instructions: Vec<IdlInstruction>,
+}
+
+#[derive(Deserialize)]
+struct IdlInstruction {
+    name: String,
+    #[serde(default)]
+    discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    args: Vec<IdlField>,
+}
+
+#[derive(Deserialize)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: Value,
+}
+
+/// The instructions of the program, by their discriminators.
+pub(crate) struct InstructionIdl {
+    program_id: Vec<u8>,
+    instructions: Vec<([u8; DISCRIMINATOR_LEN], IdlInstruction)>,
+}
+
+/// An instruction of the program in a transaction, decoded into the transaction_instruction
+/// columns.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DbDecodedInstruction {
+    pub instruction_index: i16,
+    pub inner_index: i16,
+    pub instruction_name: Option<String>,
+    pub args: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// The Anchor name of the instruction in snake case, the IDLs list them in camel case.
+fn to_snake_case(name: &str) -> String {
+    let mut snake_case = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake_case.push('_');
+            }
+            snake_case.push(c.to_ascii_lowercase());
+        } else {
+            snake_case.push(c);
+        }
+    }
+    snake_case
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (taken, rest) = data.split_at(len);
+    *data = rest;
+    Some(taken)
+}
+
+fn take_array<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    take(data, N).map(|bytes| bytes.try_into().unwrap())
+}
+
+fn take_len(data: &mut &[u8]) -> Option<usize> {
+    take_array(data).map(|bytes| u32::from_le_bytes(bytes) as usize)
+}
+
+/// Decode a Borsh encoded value of the IDL type, None when the type is not supported or the
+/// data is short.
+fn decode_value(ty: &Value, data: &mut &[u8]) -> Option<Value> {
+    match ty {
+        Value::String(ty) => Some(match ty.as_str() {
+            "bool" => Value::from(take_array::<1>(data)?[0] != 0),
+            "u8" => Value::from(u8::from_le_bytes(take_array(data)?)),
+            "i8" => Value::from(i8::from_le_bytes(take_array(data)?)),
+            "u16" => Value::from(u16::from_le_bytes(take_array(data)?)),
+            "i16" => Value::from(i16::from_le_bytes(take_array(data)?)),
+            "u32" => Value::from(u32::from_le_bytes(take_array(data)?)),
+            "i32" => Value::from(i32::from_le_bytes(take_array(data)?)),
+            "u64" => Value::from(u64::from_le_bytes(take_array(data)?)),
+            "i64" => Value::from(i64::from_le_bytes(take_array(data)?)),
+            "f32" => Value::from(f32::from_le_bytes(take_array(data)?)),
+            "f64" => Value::from(f64::from_le_bytes(take_array(data)?)),
+            // Beyond the JSON numbers.
+            "u128" => Value::from(u128::from_le_bytes(take_array(data)?).to_string()),
+            "i128" => Value::from(i128::from_le_bytes(take_array(data)?).to_string()),
+            "publicKey" | "pubkey" => Value::from(bs58::encode(take(data, 32)?).into_string()),
+            "string" => {
+                let len = take_len(data)?;
+                Value::from(String::from_utf8(take(data, len)?.to_vec()).ok()?)
+            }
+            "bytes" => {
+                let len = take_len(data)?;
+                Value::from(bs58::encode(take(data, len)?).into_string())
+            }
+            _ => return None,
+        }),
+        Value::Object(ty) => {
+            if let Some(item_ty) = ty.get("vec") {
+                let len = take_len(data)?;
+                (0..len)
+                    .map(|_| decode_value(item_ty, data))
+                    .collect::<Option<Vec<_>>>()
+                    .map(Value::from)
+            } else if let Some(item_ty) = ty.get("option") {
+                match take_array::<1>(data)?[0] {
+                    0 => Some(Value::Null),
+                    _ => decode_value(item_ty, data),
+                }
+            } else if let Some(Value::Array(array)) = ty.get("array") {
+                let len = array.get(1)?.as_u64()?;
+                (0..len)
+                    .map(|_| decode_value(array.first()?, data))
+                    .collect::<Option<Vec<_>>>()
+                    .map(Value::from)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+impl InstructionIdl {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let idl_config = match &config.instruction_idl {
+            Some(idl_config) => idl_config,
+            None => return Ok(None),
+        };
+        let configuration_error = |msg: String| {
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConfigurationError {
+                msg,
+            }))
+        };
+        let program_id = Pubkey::from_str(&idl_config.program_id).map_err(|err| {
+            configuration_error(format!(
+                "Invalid \"instruction_idl\" program id {:?}: {}",
+                idl_config.program_id, err
+            ))
+        })?;
+        let idl: Idl = fs::read_to_string(&idl_config.idl_path)
+            .map_err(|err| err.to_string())
+            .and_then(|idl| serde_json::from_str(&idl).map_err(|err| err.to_string()))
+            .map_err(|err| {
+                configuration_error(format!(
+                    "Failed to load the \"instruction_idl\" file {:?}: {}",
+                    idl_config.idl_path, err
+                ))
+            })?;
+        Ok(Some(Self::from_idl(program_id, idl)))
+    }
+
+    fn from_idl(program_id: Pubkey, idl: Idl) -> Self {
+        let instructions = idl
+            .instructions
+            .into_iter()
+            .map(|instruction| {
+                let discriminator = match instruction
+                    .discriminator
+                    .as_deref()
+                    .and_then(|discriminator| discriminator.try_into().ok())
+                {
+                    Some(discriminator) => discriminator,
+                    None => {
+                        let preimage = format!("global:{}", to_snake_case(&instruction.name));
+                        hash(preimage.as_bytes()).as_ref()[..DISCRIMINATOR_LEN]
+                            .try_into()
+                            .unwrap()
+                    }
+                };
+                (discriminator, instruction)
+            })
+            .collect();
+        Self {
+            program_id: program_id.as_ref().to_vec(),
+            instructions,
+        }
+    }
+
+    /// Decode the instruction data into the instruction name and the arguments as JSON.
+    fn decode(&self, data: &[u8]) -> (Option<String>, Option<String>) {
+        let instruction = data.get(..DISCRIMINATOR_LEN).and_then(|discriminator| {
+            self.instructions
+                .iter()
+                .find(|(known, _)| known == discriminator)
+        });
+        let instruction = match instruction {
+            Some((_, instruction)) => instruction,
+            None => return (None, None),
+        };
+
+        let mut args_data = &data[DISCRIMINATOR_LEN..];
+        let args = instruction
+            .args
+            .iter()
+            .map(|arg| Some((arg.name.clone(), decode_value(&arg.ty, &mut args_data)?)))
+            .collect::<Option<Map<String, Value>>>()
+            .map(|args| Value::Object(args).to_string());
+        (Some(instruction.name.clone()), args)
+    }
+
+    /// Decode the instructions and the inner instructions of the program in the transaction.
+    pub fn decode_transaction(&self, transaction: &DbTransaction) -> Vec<DbDecodedInstruction> {
+        let (mut account_keys, instructions): (Vec<&Vec<u8>>, _) =
+            match (&transaction.legacy_message, &transaction.v0_loaded_message) {
+                (Some(message), _) => {
+                    (message.account_keys.iter().collect(), &message.instructions)
+                }
+                (None, Some(loaded_message)) => (
+                    loaded_message.message.account_keys.iter().collect(),
+                    &loaded_message.message.instructions,
+                ),
+                (None, None) => return Vec::default(),
+            };
+        if let Some(loaded_message) = &transaction.v0_loaded_message {
+            account_keys.extend(&loaded_message.loaded_addresses.writable);
+            account_keys.extend(&loaded_message.loaded_addresses.readonly);
+        }
+        let is_program = |program_id_index: i16| {
+            matches!(
+                account_keys.get(program_id_index as usize),
+                Some(program_id) if **program_id == self.program_id
+            )
+        };
+
+        let mut decoded = Vec::default();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let inner_instructions = transaction
+                .meta
+                .inner_instructions
+                .iter()
+                .flatten()
+                .filter(|inner_instructions| inner_instructions.index as usize == index)
+                .flat_map(|inner_instructions| inner_instructions.instructions.iter());
+            for (inner_index, instruction) in std::iter::once(instruction)
+                .chain(inner_instructions)
+                .enumerate()
+            {
+                if !is_program(instruction.program_id_index) {
+                    continue;
+                }
+                let (instruction_name, args) = self.decode(&instruction.data);
+                decoded.push(DbDecodedInstruction {
+                    instruction_index: index as i16,
+                    inner_index: inner_index as i16,
+                    instruction_name,
+                    args,
+                    data: instruction.data.clone(),
+                });
+            }
+        }
+        decoded
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn get_json_column_type(config: &GeyserPluginPostgresConfig) -> JsonColumnType {
+        config.json_column_type.unwrap_or(JsonColumnType::Jsonb)
+    }
+
+    pub(crate) fn build_transaction_instructions_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (slot, signature, instruction_index, inner_index, \
+        program_id, instruction_name, args, data) \
+        SELECT $1, $2, instruction_index, inner_index, $3, instruction_name, args::{}, data \
+        FROM unnest($4::SMALLINT[], $5::SMALLINT[], $6::VARCHAR[], $7::TEXT[], $8::BYTEA[]) \
+        AS i (instruction_index, inner_index, instruction_name, args, data) \
+        ON CONFLICT (slot, signature, instruction_index, inner_index) DO NOTHING",
+            Self::table_name(&config.table_names, "transaction_instruction"),
+            match Self::get_json_column_type(config) {
+                JsonColumnType::Json => "JSON",
+                JsonColumnType::Jsonb => "JSONB",
+            }
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Store the instructions of the program in the transaction decoded by the IDL.
+    pub(crate) fn insert_transaction_instructions(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        instruction_idl: &InstructionIdl,
+        transaction: &DbTransaction,
+    ) -> Result<(), GeyserPluginError> {
+        let decoded = instruction_idl.decode_transaction(transaction);
+        if decoded.is_empty() {
+            return Ok(());
+        }
+
+        let instruction_indexes: Vec<i16> = decoded.iter().map(|i| i.instruction_index).collect();
+        let inner_indexes: Vec<i16> = decoded.iter().map(|i| i.inner_index).collect();
+        let instruction_names: Vec<&Option<String>> =
+            decoded.iter().map(|i| &i.instruction_name).collect();
+        let args: Vec<&Option<String>> = decoded.iter().map(|i| &i.args).collect();
+        let data: Vec<&Vec<u8>> = decoded.iter().map(|i| &i.data).collect();
+        let result = statement.execute(
+            client,
+            types,
+            &[
+                &transaction.slot,
+                &transaction.signature,
+                &instruction_idl.program_id,
+                &instruction_indexes,
+                &inner_indexes,
+                &instruction_names,
+                &args,
+                &data,
+            ],
+        );
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the decoded instructions to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            postgres_client_transaction::{build_db_transaction, LogTransactionRequest},
+            tests::TestDatabase,
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfo,
+        solana_sdk::{
+            instruction::{AccountMeta, Instruction},
+            message::Message,
+            signature::Signature,
+            transaction::{SanitizedTransaction, Transaction},
+        },
+        solana_transaction_status::TransactionStatusMeta,
+    };
+
+    fn build_idl() -> Idl {
+        serde_json::from_value(json!({
+            "instructions": [
+                {
+                    "name": "initializeMarket",
+                    "accounts": [],
+                    "args": [
+                        { "name": "fee", "type": "u64" },
+                        { "name": "label", "type": "string" },
+                        { "name": "admin", "type": { "option": "publicKey" } },
+                        { "name": "weights", "type": { "vec": "u16" } },
+                    ]
+                },
+                {
+                    "name": "configure",
+                    "accounts": [],
+                    "args": [{ "name": "params", "type": { "defined": "Params" } }]
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn build_instruction_data(name: &str, args: &[u8]) -> Vec<u8> {
+        let mut data = hash(format!("global:{}", name).as_bytes()).as_ref()[..8].to_vec();
+        data.extend_from_slice(args);
+        data
+    }
+
+    fn build_market_args() -> Vec<u8> {
+        let admin = Pubkey::new_from_array([1; 32]);
+        let mut args = 500u64.to_le_bytes().to_vec();
+        args.extend_from_slice(&3u32.to_le_bytes());
+        args.extend_from_slice(b"SOL");
+        args.push(1);
+        args.extend_from_slice(admin.as_ref());
+        args.extend_from_slice(&2u32.to_le_bytes());
+        args.extend_from_slice(&7u16.to_le_bytes());
+        args.extend_from_slice(&9u16.to_le_bytes());
+        args
+    }
+
+    #[test]
+    fn test_decode_instruction() {
+        let idl = InstructionIdl::from_idl(Pubkey::new_unique(), build_idl());
+
+        let data = build_instruction_data("initialize_market", &build_market_args());
+        let (name, args) = idl.decode(&data);
+        assert_eq!(name.as_deref(), Some("initializeMarket"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&args.unwrap()).unwrap(),
+            json!({
+                "fee": 500,
+                "label": "SOL",
+                "admin": Pubkey::new_from_array([1; 32]).to_string(),
+                "weights": [7, 9],
+            })
+        );
+
+        // The arguments of the defined types, or short, are not decoded.
+        let data = build_instruction_data("configure", &[1, 2, 3]);
+        assert_eq!(idl.decode(&data), (Some("configure".to_string()), None));
+        let data = build_instruction_data("initialize_market", &[1, 2, 3]);
+        assert_eq!(
+            idl.decode(&data),
+            (Some("initializeMarket".to_string()), None)
+        );
+
+        assert_eq!(idl.decode(&[1, 2, 3]), (None, None));
+        assert_eq!(
+            idl.decode(&build_instruction_data("unknown", &[])),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn test_log_transaction_instructions() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let program_id = Pubkey::new_unique();
+        let dir = tempfile::tempdir().unwrap();
+        let idl_path = dir.path().join("idl.json");
+        fs::write(
+            &idl_path,
+            json!({
+                "instructions": [{
+                    "name": "initializeMarket",
+                    "args": [{ "name": "fee", "type": "u64" }]
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config = db.config(json!({
+            "instruction_idl": {
+                "program_id": program_id.to_string(),
+                "idl_path": idl_path.to_str().unwrap(),
+            }
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let payer = Pubkey::new_unique();
+        let instructions = [
+            Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[1],
+                vec![AccountMeta::new(payer, true)],
+            ),
+            Instruction::new_with_bytes(
+                program_id,
+                &build_instruction_data("initialize_market", &42u64.to_le_bytes()),
+                vec![AccountMeta::new(payer, true)],
+            ),
+        ];
+        let message = Message::new(&instructions, Some(&payer));
+        let transaction =
+            SanitizedTransaction::try_from_legacy_transaction(Transaction::new_unsigned(message))
+                .unwrap();
+        let transaction_status_meta = TransactionStatusMeta::default();
+        let log_transaction = |client: &mut SimplePostgresClient, slot: u64| {
+            let transaction_info = ReplicaTransactionInfo {
+                signature: &Signature::new_unique(),
+                is_vote: false,
+                transaction: &transaction,
+                transaction_status_meta: &transaction_status_meta,
+            };
+            client
+                .log_transaction(LogTransactionRequest {
+                    transaction_info: build_db_transaction(slot, &transaction_info, 1),
+                    encoded: None,
+                    account_writes: None,
+                })
+                .unwrap();
+        };
+        log_transaction(&mut client, 1);
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT instruction_index, inner_index, program_id, instruction_name, args::TEXT \
+                FROM transaction_instruction",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, i16>(0), 1);
+        assert_eq!(row.get::<_, i16>(1), 0);
+        assert_eq!(row.get::<_, Vec<u8>>(2), program_id.as_ref().to_vec());
+        assert_eq!(row.get::<_, String>(3), "initializeMarket");
+        assert_eq!(row.get::<_, String>(4), "{\"fee\": 42}");
+
+        // The json column keeps the text of the arguments as decoded, and is checked.
+        db.client
+            .batch_execute("ALTER TABLE transaction_instruction ALTER COLUMN args TYPE JSON")
+            .unwrap();
+        let err = SimplePostgresClient::check_schema_columns(&config).unwrap_err();
+        assert!(format!("{:?}", err)
+            .contains("column args of table transaction_instruction is json, expected jsonb"));
+        let config = db.config(json!({
+            "instruction_idl": {
+                "program_id": program_id.to_string(),
+                "idl_path": idl_path.to_str().unwrap(),
+            },
+            "json_column_type": "json",
+        }));
+        SimplePostgresClient::check_schema_columns(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        log_transaction(&mut client, 2);
+        let args: String = db
+            .client
+            .query_one(
+                "SELECT args::TEXT FROM transaction_instruction WHERE slot = 2",
+                &[],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(args, "{\"fee\":42}");
+    }
+}
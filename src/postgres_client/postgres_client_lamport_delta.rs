@@ -0,0 +1,82 @@
+/// Module responsible for recording the previous lamports of the accounts, so the balance
+/// deltas of the account_audit rows are computed without window functions.
+///
+/// The last known balance of an account is its current row in the account table, which is
+/// shared by all the workers and persists across restarts. The upserts copy its lamports to
+/// the pre_lamports column when overwriting it, and the audit trigger carries the column
+/// into account_audit. The pre_lamports is NULL when no prior balance is known: for the first
+/// update of an account not yet in the account table, and for the updates at older slots
+/// inserted directly into account_audit.
+use {super::SimplePostgresClient, crate::geyser_plugin_postgres::GeyserPluginPostgresConfig};
+
+impl SimplePostgresClient {
+    pub(crate) fn get_track_lamport_deltas(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.track_lamport_deltas, Some(true))
+    }
+
+    /// The assignment appended to the ON CONFLICT DO UPDATE SET clause of the account upserts,
+    /// where acct is the row being overwritten.
+    pub(crate) fn pre_lamports_update_sql(track_lamport_deltas: bool) -> &'static str {
+        if track_lamport_deltas {
+            ", pre_lamports=acct.lamports"
+        } else {
+            ""
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_track_lamport_deltas() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "track_lamport_deltas": true,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 10);
+        for (slot, lamports) in [(10, 100), (11, 150), (12, 120)] {
+            account.slot = slot;
+            account.lamports = lamports;
+            client.update_account(account.clone(), false).unwrap();
+        }
+        // An update at an older slot has no known prior balance.
+        account.slot = 9;
+        account.lamports = 90;
+        client.update_account(account.clone(), false).unwrap();
+
+        let row = db
+            .client
+            .query_one("SELECT lamports, pre_lamports FROM account", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 120);
+        assert_eq!(row.get::<_, Option<i64>>(1), Some(150));
+
+        let rows: Vec<(i64, Option<i64>)> = db
+            .client
+            .query(
+                "SELECT lamports, pre_lamports FROM account_audit ORDER BY slot",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(rows, vec![(90, None), (100, None), (150, Some(100))]);
+    }
+}
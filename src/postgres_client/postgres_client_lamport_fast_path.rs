@@ -0,0 +1,337 @@
+/// Module responsible for writing the balance-only account updates with a lightweight UPDATE of
+/// the lamports, when lamport_fast_path is set, instead of the upsert rewriting the whole row
+/// and its data.
+///
+/// Each client caches the version, the slot and the write_version, of the accounts it last
+/// wrote, with the hash of their owner, executable, rent_epoch and data. An update of a cached
+/// account with the same hash, newer and with lamports, only sets the lamports, the slot, the
+/// write_version and the updated_on of the row, on the condition that the row is still the
+/// cached version. The row written by another worker in the meantime fails the condition, and
+/// the update falls back to the upsert. The update is seen by the triggers of the account table
+/// as the upsert is. The secondary indexes, unchanged with the data, are not written, and keep
+/// the slot of the version which changed them. The rent_exempt column, depending on the
+/// lamports, is computed again when it is written. It applies to the accounts written one by one
+/// after the startup, not to the batched ones. The cache holds up to
+/// LAMPORT_FAST_PATH_CACHE_CAPACITY accounts and is cleared when full.
+use {
+    super::{
+        postgres_client_account_columns::AccountColumns,
+        postgres_client_rent_exempt::is_rent_exempt, postgres_client_write_version::DbWriteVersion,
+        DbAccountInfo, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    chrono::Utc,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_metrics::*,
+    solana_sdk::{
+        hash::{hashv, Hash},
+        rent::Rent,
+    },
+    std::collections::HashMap,
+    tokio_postgres::types::ToSql,
+};
+
+const LAMPORT_FAST_PATH_CACHE_CAPACITY: usize = 100_000;
+
+/// The version of an account last written by the client, and the hash of its other fields.
+struct CachedAccount {
+    slot: i64,
+    write_version: DbWriteVersion,
+    hash: Hash,
+}
+
+pub(crate) struct LamportFastPath {
+    /// The statement updating the lamports of the cached version of an account.
+    sql: String,
+    /// Whether the updated_on column is written.
+    writes_updated_on: bool,
+    /// The rent parameters the rent_exempt column is computed with, when it is written.
+    rent: Option<Rent>,
+    accounts: HashMap<Vec<u8>, CachedAccount>,
+}
+
+impl LamportFastPath {
+    pub fn new(
+        config: &GeyserPluginPostgresConfig,
+        account_columns: &AccountColumns,
+    ) -> Result<Option<Self>, GeyserPluginError> {
+        if !matches!(config.lamport_fast_path, Some(true)) {
+            return Ok(None);
+        }
+        let unsupported = if SimplePostgresClient::is_staging_table_mode(config) {
+            Some("the staging table mode")
+        } else if matches!(config.record_write_lag, Some(true)) {
+            Some("\"record_write_lag\"")
+        } else if matches!(config.record_account_slot_status, Some(true)) {
+            Some("\"record_account_slot_status\"")
+        } else if !account_columns.is_written("lamports") {
+            Some("\"account_columns\" without \"lamports\"")
+        } else {
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            let msg = format!(
+                "\"lamport_fast_path\" is not supported with {}",
+                unsupported
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+
+        // The optional columns take the parameters following the six of the version.
+        let writes_updated_on = account_columns.is_written("updated_on");
+        let rent = account_columns.rent();
+        let mut optional_columns = Vec::new();
+        for (column, written) in [
+            ("updated_on", writes_updated_on),
+            ("rent_exempt", rent.is_some()),
+        ] {
+            if written {
+                optional_columns.push(format!(", {}=${}", column, 7 + optional_columns.len()));
+            }
+        }
+        let sql = format!(
+            "UPDATE {} AS acct SET lamports=$4, slot=$5, write_version=$6{}{} \
+            WHERE pubkey=$1 AND slot=$2 AND write_version=$3",
+            SimplePostgresClient::table_name(&config.table_names, "account"),
+            optional_columns.concat(),
+            SimplePostgresClient::pre_lamports_update_sql(
+                SimplePostgresClient::get_track_lamport_deltas(config)
+            ),
+        );
+        Ok(Some(Self {
+            sql,
+            writes_updated_on,
+            rent,
+            accounts: HashMap::default(),
+        }))
+    }
+
+    fn hash_fields(account: &DbAccountInfo) -> Hash {
+        hashv(&[
+            &account.owner,
+            &[account.executable as u8],
+            &account.rent_epoch.to_le_bytes(),
+            &account.data,
+        ])
+    }
+
+    /// The cached version of the account, when the update only changes its lamports.
+    fn cached_version(&self, account: &DbAccountInfo) -> Option<(i64, DbWriteVersion)> {
+        let cached = self.accounts.get(&account.pubkey)?;
+        ((account.slot, account.write_version) > (cached.slot, cached.write_version)
+            && account.lamports > 0
+            && account.target_table.is_none()
+            && cached.hash == Self::hash_fields(account))
+        .then(|| (cached.slot, cached.write_version))
+    }
+
+    fn record(&mut self, account: &DbAccountInfo) {
+        if self.accounts.len() >= LAMPORT_FAST_PATH_CACHE_CAPACITY
+            && !self.accounts.contains_key(&account.pubkey)
+        {
+            self.accounts.clear();
+        }
+        self.accounts.insert(
+            account.pubkey.clone(),
+            CachedAccount {
+                slot: account.slot,
+                write_version: account.write_version,
+                hash: Self::hash_fields(account),
+            },
+        );
+    }
+}
+
+impl SimplePostgresClient {
+    /// Update the lamports of the account only, when it only changes them since the version
+    /// cached. Returns whether the account was written.
+    pub(crate) fn update_account_lamports(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<bool, GeyserPluginError> {
+        let (sql, writes_updated_on, rent_exempt, (cached_slot, cached_write_version)) =
+            match &self.lamport_fast_path {
+                Some(fast_path) => match fast_path.cached_version(account) {
+                    Some(cached_version) => (
+                        fast_path.sql.clone(),
+                        fast_path.writes_updated_on,
+                        fast_path.rent.map(|rent| is_rent_exempt(&rent, account)),
+                        cached_version,
+                    ),
+                    None => return Ok(false),
+                },
+                None => return Ok(false),
+            };
+        let statement = self.prepare_cached_statement(&sql)?;
+        let client = self.client.get_mut().unwrap();
+        let updated_on = Utc::now().naive_utc();
+        let mut values: Vec<&(dyn ToSql + Sync)> = vec![
+            &account.pubkey,
+            &cached_slot,
+            &cached_write_version,
+            &account.lamports,
+            &account.slot,
+            &account.write_version,
+        ];
+        if writes_updated_on {
+            values.push(&updated_on);
+        }
+        if let Some(rent_exempt) = &rent_exempt {
+            values.push(rent_exempt);
+        }
+        let result = statement.execute(&mut client.client, &client.types, &values);
+        match result {
+            Ok(0) => Ok(false),
+            Ok(_) => {
+                inc_new_counter_debug!("geyser-plugin-postgres-lamport-fast-path", 1);
+                self.record_account_written(account);
+                Ok(true)
+            }
+            Err(err) => {
+                let msg = format!(
+                    "Failed to update the lamports of account to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::AccountsUpdateError { msg })
+            }
+        }
+    }
+
+    /// Cache the version of the account written, for the lamport fast path.
+    pub(crate) fn record_account_written(&mut self, account: &DbAccountInfo) {
+        if let Some(fast_path) = &mut self.lamport_fast_path {
+            fast_path.record(account);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_lamport_fast_path() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "lamport_fast_path": true,
+            "store_account_historical_data": true,
+            "track_lamport_deltas": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![7; 100], 10);
+        account.lamports = 100;
+        client.update_account(account.clone(), false).unwrap();
+        let cached_version = |client: &SimplePostgresClient, account: &DbAccountInfo| {
+            client
+                .lamport_fast_path
+                .as_ref()
+                .unwrap()
+                .cached_version(account)
+        };
+
+        // A balance-only change takes the fast path.
+        account.slot = 11;
+        account.write_version.0 += 1;
+        account.lamports = 90;
+        assert_eq!(
+            cached_version(&client, &account),
+            Some((10, DbWriteVersion(account.write_version.0 - 1)))
+        );
+        assert!(client.update_account_lamports(&account).unwrap());
+
+        // The data change does not, nor does an update of the row by another worker.
+        let mut data_change = account.clone();
+        data_change.slot = 12;
+        data_change.data = vec![8; 100];
+        assert!(cached_version(&client, &data_change).is_none());
+        db.client
+            .execute(
+                "UPDATE account SET slot = 13, lamports = 70 WHERE pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap();
+        let mut stale = account.clone();
+        stale.slot = 12;
+        stale.lamports = 80;
+        assert!(cached_version(&client, &stale).is_some());
+        assert!(!client.update_account_lamports(&stale).unwrap());
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT lamports, slot, pre_lamports, data FROM account WHERE pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 70);
+        assert_eq!(row.get::<_, i64>(1), 13);
+        assert_eq!(row.get::<_, Option<i64>>(2), Some(100));
+        assert_eq!(row.get::<_, Vec<u8>>(3), vec![7; 100]);
+        // The versions overwritten by the fast path and by the other worker are audited.
+        assert_eq!(db.count("account_audit"), 2);
+
+        let config = db.config(json!({
+            "lamport_fast_path": true,
+            "record_write_lag": true,
+        }));
+        assert!(SimplePostgresClient::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_lamport_fast_path_rent_exempt() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "lamport_fast_path": true,
+            "compute_rent_exempt": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let rent_exempt = |db: &mut TestDatabase, account: &DbAccountInfo| -> Option<bool> {
+            db.client
+                .query_one(
+                    "SELECT rent_exempt FROM account WHERE pubkey = $1",
+                    &[&account.pubkey],
+                )
+                .unwrap()
+                .get(0)
+        };
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![7; 100], 10);
+        let minimum_balance = Rent::default().minimum_balance(account.data.len()) as i64;
+        account.lamports = minimum_balance;
+        client.update_account(account.clone(), false).unwrap();
+        assert_eq!(rent_exempt(&mut db, &account), Some(true));
+
+        // The balance-only changes crossing the threshold either way are written by the fast
+        // path with the rent_exempt computed again.
+        for (slot, lamports, expected) in [
+            (11, minimum_balance - 1, false),
+            (12, minimum_balance + 1, true),
+        ] {
+            account.slot = slot;
+            account.write_version.0 += 1;
+            account.lamports = lamports;
+            assert!(client.update_account_lamports(&account).unwrap());
+            assert_eq!(rent_exempt(&mut db, &account), Some(expected));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+/// Module for the latency histograms of the writes to the PostgreSQL database, showing the
+/// latency distribution, e.g. the tail latency from lock contention, which the means hide.
+use {
+    solana_metrics::{datapoint::DataPoint, submit},
+    solana_sdk::timing::AtomicInterval,
+};
+
+/// The inclusive upper bounds of the histogram buckets in microseconds. The latencies above
+/// the last bound are counted in an overflow bucket.
+const LATENCY_BUCKET_BOUNDS_US: [u64; 10] = [
+    500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000,
+];
+const LATENCY_BUCKET_COUNT: usize = LATENCY_BUCKET_BOUNDS_US.len() + 1;
+/// The datapoint field names of the buckets.
+const LATENCY_BUCKET_FIELDS: [&str; LATENCY_BUCKET_COUNT] = [
+    "le-500us", "le-1ms", "le-2.5ms", "le-5ms", "le-10ms", "le-25ms", "le-50ms", "le-100ms",
+    "le-250ms", "le-1s", "gt-1s",
+];
+const LATENCY_REPORT_INTERVAL_MS: u64 = 30_000;
+
+/// A latency histogram of bucketed counters, reported and reset periodically as a datapoint.
+/// Each bucket counts the latencies since the last report which are in the bucket only, so
+/// the percentiles are computed from the cumulative sum of the buckets.
+pub struct LatencyHistogram {
+    name: &'static str,
+    counts: [u64; LATENCY_BUCKET_COUNT],
+    last_report: AtomicInterval,
+}
+
+impl LatencyHistogram {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            counts: [0; LATENCY_BUCKET_COUNT],
+            last_report: AtomicInterval::default(),
+        }
+    }
+
+    fn bucket(latency_us: u64) -> usize {
+        LATENCY_BUCKET_BOUNDS_US
+            .iter()
+            .position(|bound| latency_us <= *bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_US.len())
+    }
+
+    /// Record the latency, reporting the histogram when the report interval has elapsed.
+    pub fn record(&mut self, latency_us: u64) {
+        self.counts[Self::bucket(latency_us)] += 1;
+        if self.last_report.should_update(LATENCY_REPORT_INTERVAL_MS) {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        let mut point = DataPoint::new(self.name);
+        for (field, count) in LATENCY_BUCKET_FIELDS.iter().zip(self.counts.iter()) {
+            point.add_field_i64(field, *count as i64);
+        }
+        submit(point, log::Level::Info);
+        self.counts = [0; LATENCY_BUCKET_COUNT];
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram_buckets() {
+        assert_eq!(LatencyHistogram::bucket(0), 0);
+        assert_eq!(LatencyHistogram::bucket(500), 0);
+        assert_eq!(LatencyHistogram::bucket(501), 1);
+        assert_eq!(LatencyHistogram::bucket(30_000), 6);
+        assert_eq!(LatencyHistogram::bucket(1_000_000), 9);
+        assert_eq!(LatencyHistogram::bucket(1_000_001), 10);
+
+        let mut histogram = LatencyHistogram::new("test-latency");
+        for latency_us in [100, 200, 3_000, 2_000_000] {
+            histogram.record(latency_us);
+        }
+        assert_eq!(histogram.counts, [2, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1]);
+    }
+}
@@ -0,0 +1,186 @@
+/// Module responsible for writing the slots, the blocks and the transactions to another database
+/// than the accounts, when accounts_connection_str or metadata_connection_str is set, so the
+/// two workloads may be physically separated.
+///
+/// Each worker holds a second client, connected to the metadata database, writing the slot,
+/// slot_timing, block and transaction tables and the tables derived from the transactions,
+/// while its main client writes the accounts, their indexes and audit. A slot status is split
+/// between them: the metadata client writes the slot, its dead slots and placeholder blocks,
+/// and the main client applies it to the account tables. Both databases are set up with the
+/// whole schema, as each client prepares all its statements when it connects.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+};
+
+impl SimplePostgresClient {
+    /// The configuration of the connections to the metadata database, when it is another
+    /// database than the one of the accounts.
+    pub(crate) fn metadata_config(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Option<GeyserPluginPostgresConfig> {
+        if config.accounts_connection_str.is_none() && config.metadata_connection_str.is_none() {
+            return None;
+        }
+        let mut metadata_config = config.clone();
+        metadata_config.accounts_connection_str = None;
+        metadata_config.metadata_connection_str = None;
+        if let Some(metadata_connection_str) = &config.metadata_connection_str {
+            metadata_config.connection_str = Some(metadata_connection_str.clone());
+        }
+        let accounts_connection_str = config
+            .accounts_connection_str
+            .as_ref()
+            .or(config.connection_str.as_ref());
+        (metadata_config.connection_str.is_none()
+            || metadata_config.connection_str.as_ref() != accounts_connection_str)
+            .then(|| metadata_config)
+    }
+
+    /// Write the slot status to the slot table, with the dead slots and the placeholder block
+    /// of a root.
+    pub(crate) fn update_slot_metadata(
+        &mut self,
+        slot: u64,
+        parent: Option<u64>,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+
+        let statement = match parent {
+            Some(_) => &client.update_slot_with_parent_stmt,
+            None => &client.update_slot_without_parent_stmt,
+        };
+
+        Self::upsert_slot_status_internal(
+            slot,
+            parent,
+            status,
+            &mut client.client,
+            &client.types,
+            statement,
+        )?;
+
+        if let SlotStatus::Rooted = status {
+            self.mark_dead_slots(slot)?;
+            self.insert_placeholder_block(slot)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the slot status to the account tables.
+    pub(crate) fn update_slot_accounts(
+        &mut self,
+        slot: u64,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        self.update_account_slot_status(slot, status)?;
+
+        if let SlotStatus::Rooted = status {
+            self.prune_audit_retention(slot)?;
+            self.prune_recent_accounts(slot)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::TestDatabase, DbWorkItem, PostgresClientWorker, UpdateSlotRequest,
+        },
+        chrono::Utc,
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_metadata_config() {
+        let config: GeyserPluginPostgresConfig =
+            serde_json::from_value(json!({ "connection_str": "dbname=solana" })).unwrap();
+        assert!(SimplePostgresClient::metadata_config(&config).is_none());
+
+        // The metadata database defaults to the connection_str.
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "connection_str": "dbname=solana",
+            "accounts_connection_str": "dbname=accounts",
+        }))
+        .unwrap();
+        let metadata_config = SimplePostgresClient::metadata_config(&config).unwrap();
+        assert_eq!(
+            metadata_config.connection_str.as_deref(),
+            Some("dbname=solana")
+        );
+        assert!(metadata_config.accounts_connection_str.is_none());
+
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "connection_str": "dbname=solana",
+            "metadata_connection_str": "dbname=metadata",
+        }))
+        .unwrap();
+        let metadata_config = SimplePostgresClient::metadata_config(&config).unwrap();
+        assert_eq!(
+            metadata_config.connection_str.as_deref(),
+            Some("dbname=metadata")
+        );
+        assert!(metadata_config.metadata_connection_str.is_none());
+
+        // The same database is written through a single connection.
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "connection_str": "dbname=solana",
+            "metadata_connection_str": "dbname=solana",
+        }))
+        .unwrap();
+        assert!(SimplePostgresClient::metadata_config(&config).is_none());
+    }
+
+    #[test]
+    fn test_metadata_client() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut config = db.config(json!({}));
+        let connection_str = config.connection_str.take().unwrap();
+        config.accounts_connection_str = Some(format!(
+            "{} application_name=geyser_accounts",
+            connection_str
+        ));
+        config.metadata_connection_str = Some(format!(
+            "{} application_name=geyser_metadata",
+            connection_str
+        ));
+        let mut worker = PostgresClientWorker::new(config, None, None, None, None, None).unwrap();
+        assert!(worker.metadata_client.is_some());
+
+        let failed = worker.handle_work_item(
+            DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+                slot: 10,
+                parent: Some(9),
+                slot_status: SlotStatus::Rooted,
+                observed_on: Utc::now().naive_utc(),
+                transaction_tally: None,
+            })),
+            false,
+        );
+        assert!(!failed);
+        assert_eq!(db.count("slot"), 1);
+        let application_names: Vec<String> = db
+            .client
+            .query(
+                "SELECT application_name FROM pg_stat_activity \
+                WHERE application_name LIKE 'geyser_%' ORDER BY application_name",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(
+            application_names,
+            vec!["geyser_accounts", "geyser_metadata"]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+/// Module responsible for maintaining the Metaplex token metadata index in the PostgreSQL
+/// database.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        DbAccountInfo, ReadableAccountInfo, SimplePostgresClient,
+    },
+    crate::{geyser_plugin_postgres::GeyserPluginPostgresConfig, inline_metaplex_token_metadata},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::sync::Mutex,
+};
+
+impl SimplePostgresClient {
+    pub fn build_metaplex_metadata_index_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS metadata_index (metadata_pubkey, mint, update_authority, \
+        name, symbol, uri, slot) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7) \
+        ON CONFLICT (metadata_pubkey) \
+        DO UPDATE SET mint=excluded.mint, update_authority=excluded.update_authority, \
+        name=excluded.name, symbol=excluded.symbol, uri=excluded.uri, slot=excluded.slot \
+        WHERE metadata_index.slot <= excluded.slot",
+            Self::table_name(&config.table_names, "metaplex_metadata_index")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Function for updating a single Metaplex metadata index entry.
+    pub fn update_metaplex_metadata_index(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if account.owner() != inline_metaplex_token_metadata::id().as_ref() {
+            return Ok(());
+        }
+
+        if let Some(metadata) =
+            inline_metaplex_token_metadata::Account::unpack_account(account.data())
+        {
+            let metadata_pubkey = account.pubkey();
+            let mint = metadata.mint.as_ref().to_vec();
+            let update_authority = metadata.update_authority.as_ref().to_vec();
+            let slot = account.slot;
+            let result = statement.execute(
+                client,
+                types,
+                &[
+                    &metadata_pubkey,
+                    &mint,
+                    &update_authority,
+                    &metadata.name,
+                    &metadata.symbol,
+                    &metadata.uri,
+                    &slot,
+                ],
+            );
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to update the Metaplex metadata index to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the Metaplex metadata index for an account queued for the bulk insert.
+    /// The metadata of a mint is written when it is created and when its update authority
+    /// edits it, a trickle beside the token accounts, so it is upserted on its own.
+    pub fn upsert_metaplex_metadata_index(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        match &client.upsert_metaplex_metadata_index_stmt {
+            Some(statement) => Self::update_metaplex_metadata_index(
+                &mut client.client,
+                &client.types,
+                statement,
+                account,
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            inline_metaplex_token_metadata::tests::build_metadata_account_data,
+            postgres_client::{
+                tests::{build_account, TestDatabase},
+                PostgresClient,
+            },
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_metaplex_metadata_index() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "index_metaplex_metadata": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let update_authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let build_metadata_account = |name: &str, slot: i64| {
+            let data = build_metadata_account_data(
+                &update_authority,
+                &mint,
+                name,
+                "NFT",
+                "https://arweave.net/abc",
+                &[0, 0, 0, 0, 1],
+            );
+            build_account(&inline_metaplex_token_metadata::id(), data, slot)
+        };
+        let account = build_metadata_account("First", 5);
+        let metadata_pubkey = account.pubkey.clone();
+        client.update_account(account, false).unwrap();
+        let mut account = build_metadata_account("Renamed", 6);
+        account.pubkey = metadata_pubkey.clone();
+        client.update_account(account, false).unwrap();
+        // An older update does not overwrite the entry.
+        let mut account = build_metadata_account("Stale", 4);
+        account.pubkey = metadata_pubkey.clone();
+        client.update_account(account, false).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![4; 100], 6), false)
+            .unwrap();
+        assert_eq!(db.count("metaplex_metadata_index"), 1);
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT mint, update_authority, name, symbol, uri, slot \
+                FROM metaplex_metadata_index WHERE metadata_pubkey = $1",
+                &[&metadata_pubkey],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), mint.as_ref());
+        assert_eq!(row.get::<_, Vec<u8>>(1), update_authority.as_ref());
+        assert_eq!(row.get::<_, String>(2), "Renamed");
+        assert_eq!(row.get::<_, String>(3), "NFT");
+        assert_eq!(row.get::<_, String>(4), "https://arweave.net/abc");
+        assert_eq!(row.get::<_, i64>(5), 6);
+    }
+}
@@ -0,0 +1,129 @@
+/// Module responsible for maintaining the number of accounts owned by each program in the
+/// account_count_by_owner view, when maintain_owner_counts is set.
+///
+/// The counts are maintained incrementally by the account_count_by_owner triggers on the
+/// account table, which see the prior owner and lamports of each updated account. An account
+/// is counted from its first update with lamports, moved between the counts when its owner
+/// changes, and no longer counted when it is closed. The triggers are created by the optional
+/// create_owner_counts.sql script rather than create_schema.sql, so the deployments not using
+/// the feature do not pay for the transition tables of each statement; the plugin checks the
+/// triggers are installed when it is loaded, and they count the writes of every connection.
+/// The deltas are added to the count of the owner in the shard of the writing backend, so the
+/// workers updating the accounts of the same owner do not all wait on a single row, and the
+/// view sums the shards.
+use super::postgres_client_schema_check::RequiredTrigger;
+
+/// The triggers of create_owner_counts.sql.
+pub(crate) const OWNER_COUNT_TRIGGERS: [RequiredTrigger; 3] = [
+    (
+        "create_owner_counts.sql",
+        "account",
+        "account_count_by_owner_insert_trigger",
+    ),
+    (
+        "create_owner_counts.sql",
+        "account",
+        "account_count_by_owner_update_trigger",
+    ),
+    (
+        "create_owner_counts.sql",
+        "account",
+        "account_count_by_owner_delete_trigger",
+    ),
+];
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient, SimplePostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn owner_counts(db: &mut TestDatabase) -> Vec<(Vec<u8>, i64)> {
+        db.client
+            .query(
+                "SELECT owner, account_count FROM account_count_by_owner ORDER BY owner",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect()
+    }
+
+    #[test]
+    fn test_maintain_owner_counts() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 2,
+            "maintain_owner_counts": true,
+        }));
+        db.client
+            .batch_execute(include_str!("../../scripts/create_owner_counts.sql"))
+            .unwrap();
+        SimplePostgresClient::check_schema_triggers(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        owners.sort();
+        let accounts: Vec<_> = [0, 0, 1]
+            .iter()
+            .map(|owner| build_account(&owners[*owner], vec![], 1))
+            .collect();
+        // The bulk insert at startup, then the single upsert.
+        for account in &accounts[..2] {
+            client.update_account(account.clone(), true).unwrap();
+        }
+        client.update_account(accounts[2].clone(), false).unwrap();
+        assert_eq!(
+            owner_counts(&mut db),
+            vec![
+                (owners[0].as_ref().to_vec(), 2),
+                (owners[1].as_ref().to_vec(), 1)
+            ]
+        );
+
+        // The owner of an account changes, and another account is closed.
+        let mut account = accounts[0].clone();
+        account.owner = owners[1].as_ref().to_vec();
+        account.slot = 2;
+        client.update_account(account, false).unwrap();
+        let mut account = accounts[2].clone();
+        account.lamports = 0;
+        account.slot = 2;
+        client.update_account(account, false).unwrap();
+        // An update of an account which keeps its owner.
+        let mut account = accounts[1].clone();
+        account.data = vec![1];
+        account.slot = 2;
+        client.update_account(account, false).unwrap();
+        assert_eq!(
+            owner_counts(&mut db),
+            vec![
+                (owners[0].as_ref().to_vec(), 1),
+                (owners[1].as_ref().to_vec(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_owner_counts_not_installed() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let err = SimplePostgresClient::check_schema_triggers(
+            &db.config(json!({ "maintain_owner_counts": true })),
+        )
+        .unwrap_err();
+        assert!(format!("{:?}", err).contains("create_owner_counts.sql"));
+        SimplePostgresClient::check_schema_triggers(&db.config(json!({}))).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+/// Module responsible for resolving the connection parameters of the pg_service connection
+/// service from the connection service files, the way libpq resolves `service=name`.
+///
+/// The service is looked up in the per-user service file, PGSERVICEFILE or ~/.pg_service.conf,
+/// then in the system-wide pg_service.conf of PGSYSCONFDIR, /etc/postgresql-common by
+/// default; the first file defining the service is used. The explicit "host", "user" and
+/// "port" take precedence over the parameters of the service, which stand in for the ones not
+/// set. The PostgreSQL client is not libpq, so the service parameters must be among the
+/// connection parameters it supports.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{env, fs, path::PathBuf},
+};
+
+const DEFAULT_PGSYSCONFDIR: &str = "/etc/postgresql-common";
+
+/// The service files, in the order they are searched.
+fn service_file_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(service_file) = env::var_os("PGSERVICEFILE") {
+        paths.push(PathBuf::from(service_file));
+    } else if let Some(home) = env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join(".pg_service.conf"));
+    }
+    let sysconfdir = env::var_os("PGSYSCONFDIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_PGSYSCONFDIR));
+    paths.push(sysconfdir.join("pg_service.conf"));
+    paths
+}
+
+/// The parameters of the service in the contents of a service file, None when the file does
+/// not define it.
+fn parse_service(contents: &str, service: &str) -> Option<Vec<(String, String)>> {
+    let mut parameters = None;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            if parameters.is_some() {
+                break;
+            }
+            if section.trim() == service {
+                parameters = Some(Vec::new());
+            }
+        } else if let (Some(parameters), Some((key, value))) =
+            (parameters.as_mut(), line.split_once('='))
+        {
+            parameters.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    parameters
+}
+
+/// Quote the value of a connection parameter.
+fn quote_value(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+impl SimplePostgresClient {
+    /// The connection string of the pg_service service, searched in the service files, with the
+    /// explicit host, user and port taking precedence.
+    pub(crate) fn service_connection_str(
+        config: &GeyserPluginPostgresConfig,
+        service: &str,
+        service_files: &[PathBuf],
+    ) -> Result<String, GeyserPluginError> {
+        let parameters = service_files.iter().find_map(|path| {
+            let contents = fs::read_to_string(path).ok()?;
+            let parameters = parse_service(&contents, service)?;
+            info!("Using the connection service {:?} of {:?}", service, path);
+            Some(parameters)
+        });
+        let mut parameters = match parameters {
+            Some(parameters) => parameters,
+            None => {
+                let msg = format!(
+                    "The connection service {:?} of \"pg_service\" is not defined in the service files {:?}",
+                    service, service_files
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::ConfigurationError { msg },
+                )));
+            }
+        };
+
+        let explicit = [
+            ("host", config.host.clone()),
+            ("user", config.user.clone()),
+            ("port", config.port.map(|port| port.to_string())),
+        ];
+        for (key, value) in explicit {
+            if let Some(value) = value {
+                parameters.retain(|(service_key, _)| service_key != key);
+                parameters.push((key.to_string(), value));
+            }
+        }
+        Ok(parameters
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, quote_value(value)))
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    /// The connection string of the pg_service service, searched in the default service files.
+    pub(crate) fn pg_service_connection_str(
+        config: &GeyserPluginPostgresConfig,
+        service: &str,
+    ) -> Result<String, GeyserPluginError> {
+        Self::service_connection_str(config, service, &service_file_paths())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn test_service_connection_str() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_file = dir.path().join(".pg_service.conf");
+        let system_file = dir.path().join("pg_service.conf");
+        fs::write(
+            &user_file,
+            "# The services of the user\n[geyser]\nhost=db.internal\nport = 5433\ndbname=solana\n\n[other]\nhost=other\n",
+        )
+        .unwrap();
+        fs::write(
+            &system_file,
+            "[geyser]\nhost=unused\n[shared]\nuser=o'brien\n",
+        )
+        .unwrap();
+        let service_files = [user_file, system_file];
+
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(
+            SimplePostgresClient::service_connection_str(&config, "geyser", &service_files)
+                .unwrap(),
+            "host='db.internal' port='5433' dbname='solana'"
+        );
+        assert_eq!(
+            SimplePostgresClient::service_connection_str(&config, "shared", &service_files)
+                .unwrap(),
+            "user='o\\'brien'"
+        );
+
+        // The explicit fields take precedence over the service.
+        let config: GeyserPluginPostgresConfig =
+            serde_json::from_value(json!({ "host": "10.0.0.1", "user": "solana" })).unwrap();
+        assert_eq!(
+            SimplePostgresClient::service_connection_str(&config, "geyser", &service_files)
+                .unwrap(),
+            "port='5433' dbname='solana' host='10.0.0.1' user='solana'"
+        );
+
+        assert!(
+            SimplePostgresClient::service_connection_str(&config, "missing", &service_files)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+/// Module responsible for inserting a placeholder row into the block table for each slot rooted
+/// without its block metadata, when insert_placeholder_blocks is set, so the rooted slots of the
+/// slot table all join a block row.
+///
+/// The validator notifies the block metadata of a slot when its bank is frozen after replay,
+/// which precedes the slot being rooted. A slot is rooted without block metadata when the plugin
+/// did not see its replay, such as the snapshot slot the validator restarts from, or when the
+/// block metadata failed to write. When the slot is rooted, a row with null block metadata is
+/// inserted unless the slot already has one. The workers take the notifications from a shared
+/// queue, so the block metadata may still be written after the root of its slot: it then
+/// replaces the placeholder, recognized by its null blockhash. The skipped slots are never
+/// rooted and get no placeholder, as they get no row in the slot table.
+use {
+    super::{postgres_client_unprepared::PluginStatement, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::Utc,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+impl SimplePostgresClient {
+    pub(crate) fn build_placeholder_block_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (slot, updated_on) VALUES ($1, $2) ON CONFLICT (slot) DO NOTHING",
+            Self::table_name(&config.table_names, "block")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// The clause of the block metadata insert replacing the placeholder of its slot.
+    pub(crate) fn placeholder_block_conflict_clause(config: &GeyserPluginPostgresConfig) -> String {
+        let block_time_bucket = if Self::get_block_time_bucket_secs(config).is_some() {
+            ", block_time_bucket=excluded.block_time_bucket"
+        } else {
+            ""
+        };
+        format!(
+            " ON CONFLICT (slot) DO UPDATE SET blockhash=excluded.blockhash, \
+            rewards=excluded.rewards, block_time=excluded.block_time, \
+            block_height=excluded.block_height, updated_on=excluded.updated_on{} \
+            WHERE {}.blockhash IS NULL",
+            block_time_bucket,
+            Self::table_name(&config.table_names, "block")
+        )
+    }
+
+    /// Insert the placeholder block of the rooted slot, unless its block metadata is written.
+    pub(crate) fn insert_placeholder_block(&mut self, root: u64) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.insert_placeholder_block_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+
+        let root = root as i64;
+        let updated_on = Utc::now().naive_utc();
+        match statement.execute(&mut client.client, &client.types, &[&root, &updated_on]) {
+            Err(err) => {
+                let msg = format!(
+                    "Failed to insert the placeholder block of the rooted slot {} into the PostgreSQL database. Error: {:?}",
+                    root, err
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::SlotStatusUpdateError { msg })
+            }
+            Ok(inserted_count) => {
+                if inserted_count > 0 {
+                    info!(
+                        "Inserted a placeholder block for the slot {} rooted without block metadata",
+                        root
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::TestDatabase, DbBlockInfo, PostgresClient, UpdateBlockMetadataRequest,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_insert_placeholder_blocks() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "insert_placeholder_blocks": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let update_block_metadata = |client: &mut SimplePostgresClient, slot: i64| {
+            client
+                .update_block_metadata(UpdateBlockMetadataRequest {
+                    block_info: DbBlockInfo {
+                        slot,
+                        blockhash: format!("blockhash{}", slot),
+                        rewards: vec![],
+                        block_time: Some(1_650_000_000 + slot),
+                        block_height: Some(slot),
+                    },
+                })
+                .unwrap();
+        };
+
+        // The slot 1 has its block metadata before its root, the slot 2 gets it after.
+        update_block_metadata(&mut client, 1);
+        for slot in 1..=3 {
+            client
+                .update_slot_status(slot, None, SlotStatus::Rooted)
+                .unwrap();
+        }
+        update_block_metadata(&mut client, 2);
+
+        let blocks: Vec<(i64, Option<String>, Option<i64>)> = db
+            .client
+            .query(
+                "SELECT slot, blockhash, block_height FROM block ORDER BY slot",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (1, Some("blockhash1".to_string()), Some(1)),
+                (2, Some("blockhash2".to_string()), Some(2)),
+                (3, None, None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+/// Module responsible for recording the version and configuration of the plugin into the
+/// plugin_metadata table each time the plugin is loaded.
+///
+/// The rows written by the plugin carry their updated_on time, which falls after the
+/// started_on time of the plugin_metadata row of the load which wrote them. The configuration
+/// is recorded by its hash, as it may hold the credentials in the connection_str.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    chrono::Utc,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::hash::hash,
+};
+
+/// The version of the plugin, as built.
+pub const PLUGIN_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+impl SimplePostgresClient {
+    /// The SHA-256 hash of the configuration, telling whether two loads were configured alike.
+    pub(crate) fn config_hash(config: &GeyserPluginPostgresConfig) -> String {
+        hash(&serde_json::to_vec(config).unwrap()).to_string()
+    }
+
+    /// Record the plugin version, the load time and the configuration hash, on a short-lived
+    /// connection.
+    pub fn record_plugin_metadata(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (plugin_version, started_on, config_hash) VALUES ($1, $2, $3)",
+            Self::table_name(&config.table_names, "plugin_metadata")
+        );
+
+        let mut client = Self::connect_to_db(config)?;
+        let started_on = Utc::now().naive_utc();
+        let config_hash = Self::config_hash(config);
+        if let Err(err) = client.execute(&stmt, &[&PLUGIN_VERSION, &started_on, &config_hash]) {
+            let msg = format!(
+                "Failed to record the plugin metadata to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataSchemaError { msg },
+            )));
+        }
+        info!(
+            "Recorded the plugin version {} with the config hash {}",
+            PLUGIN_VERSION, config_hash
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*, crate::postgres_client::tests::TestDatabase, chrono::naive::NaiveDateTime,
+        serde_json::json,
+    };
+
+    #[test]
+    fn test_record_plugin_metadata() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "record_plugin_metadata": true }));
+        SimplePostgresClient::record_plugin_metadata(&config).unwrap();
+        SimplePostgresClient::record_plugin_metadata(&db.config(json!({ "batch_size": 3 })))
+            .unwrap();
+
+        let rows = db
+            .client
+            .query(
+                "SELECT plugin_version, started_on, config_hash FROM plugin_metadata ORDER BY id",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get::<_, String>(0), PLUGIN_VERSION);
+        assert!(rows[0].get::<_, NaiveDateTime>(1) <= rows[1].get::<_, NaiveDateTime>(1));
+        assert_eq!(
+            rows[0].get::<_, String>(2),
+            SimplePostgresClient::config_hash(&config)
+        );
+        assert_ne!(rows[0].get::<_, String>(2), rows[1].get::<_, String>(2));
+    }
+}
@@ -0,0 +1,171 @@
+/// Module responsible for linking the upgradeable programs to their programdata accounts
+/// in the PostgreSQL database.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        DbAccountInfo, ReadableAccountInfo, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::{
+        bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+        program_utils::limited_deserialize,
+    },
+    std::sync::Mutex,
+};
+
+impl SimplePostgresClient {
+    pub fn build_program_link_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS link (program_pubkey, programdata_pubkey, slot) \
+        VALUES ($1, $2, $3) \
+        ON CONFLICT (program_pubkey) \
+        DO UPDATE SET programdata_pubkey=excluded.programdata_pubkey, slot=excluded.slot \
+        WHERE link.slot <= excluded.slot",
+            Self::table_name(&config.table_names, "program_link")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Get the programdata account address of an upgradeable program account.
+    fn get_programdata_address(account: &DbAccountInfo) -> Option<Vec<u8>> {
+        if !account.executable() || account.owner() != bpf_loader_upgradeable::id().as_ref() {
+            return None;
+        }
+
+        match limited_deserialize(account.data()) {
+            Ok(UpgradeableLoaderState::Program {
+                programdata_address,
+            }) => Some(programdata_address.as_ref().to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Function for updating the program link of a single program account.
+    pub fn update_program_link(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if let Some(programdata_pubkey) = Self::get_programdata_address(account) {
+            let program_pubkey = account.pubkey();
+            let slot = account.slot;
+            let result = statement.execute(
+                client,
+                types,
+                &[&program_pubkey, &programdata_pubkey, &slot],
+            );
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to update the program link to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the program link for an account queued for the bulk insert.
+    /// A program account is only written when the program is deployed or closed, the upgrades
+    /// write its programdata account, so the links are upserted one by one.
+    pub fn upsert_program_link(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        match &client.upsert_program_link_stmt {
+            Some(statement) => {
+                Self::update_program_link(&mut client.client, &client.types, statement, account)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn build_program_account(programdata_address: &Pubkey, slot: i64) -> DbAccountInfo {
+        let state = UpgradeableLoaderState::Program {
+            programdata_address: *programdata_address,
+        };
+        let mut data = vec![2, 0, 0, 0];
+        data.extend_from_slice(programdata_address.as_ref());
+        assert_eq!(limited_deserialize(&data), Ok(state));
+
+        let mut account = build_account(&bpf_loader_upgradeable::id(), data, slot);
+        account.executable = true;
+        account
+    }
+
+    #[test]
+    fn test_get_programdata_address() {
+        let programdata_address = Pubkey::new_unique();
+        let account = build_program_account(&programdata_address, 1);
+        assert_eq!(
+            SimplePostgresClient::get_programdata_address(&account),
+            Some(programdata_address.as_ref().to_vec())
+        );
+
+        let mut account = build_program_account(&programdata_address, 1);
+        account.executable = false;
+        assert_eq!(
+            SimplePostgresClient::get_programdata_address(&account),
+            None
+        );
+
+        let account = build_account(&bpf_loader_upgradeable::id(), vec![3, 0, 0, 0], 1);
+        assert_eq!(
+            SimplePostgresClient::get_programdata_address(&account),
+            None
+        );
+    }
+
+    #[test]
+    fn test_update_program_link() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "index_programs": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let programdata_address = Pubkey::new_unique();
+        let account = build_program_account(&programdata_address, 5);
+        let program_pubkey = account.pubkey.clone();
+        client.update_account(account, false).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![], 5), false)
+            .unwrap();
+        assert_eq!(db.count("program_link"), 1);
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT programdata_pubkey, slot FROM program_link WHERE program_pubkey = $1",
+                &[&program_pubkey],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), programdata_address.as_ref());
+        assert_eq!(row.get::<_, i64>(1), 5);
+    }
+}
@@ -0,0 +1,185 @@
+/// Module responsible for limiting the rate of the account updates sent to the workers to
+/// max_accounts_per_sec, for sharing the PostgreSQL server with the other tenants.
+///
+/// The updates are admitted by a token bucket holding up to a second of updates, refilled at
+/// max_accounts_per_sec, so a burst up to the rate passes at once. Past the rate, the updates
+/// are handled by rate_limit_policy: 'block' waits for the bucket to refill, slowing the
+/// notifications down, 'drop' drops the update, leaving a gap in the data. The updates at
+/// startup, the snapshot the validator restores from, and the other notifications are not
+/// limited. Whether the updates are throttled is reported as the
+/// geyser-plugin-postgres-rate-limit datapoint every RATE_LIMIT_REPORT_INTERVAL_MS, with the
+/// number of the updates throttled and dropped.
+use {
+    crate::geyser_plugin_postgres::{
+        GeyserPluginPostgresConfig, GeyserPluginPostgresError, RateLimitPolicy,
+    },
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_metrics::*,
+    solana_sdk::timing::AtomicInterval,
+    std::{
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+};
+
+const RATE_LIMIT_REPORT_INTERVAL_MS: u64 = 5_000;
+
+pub(crate) struct AccountRateLimiter {
+    max_accounts_per_sec: u64,
+    policy: RateLimitPolicy,
+    /// The updates which may be admitted at once, up to max_accounts_per_sec.
+    tokens: f64,
+    last_refill: Instant,
+    /// Whether the last update exceeded the rate.
+    throttled: bool,
+    throttled_count: u64,
+    dropped_count: u64,
+    last_report: AtomicInterval,
+}
+
+impl AccountRateLimiter {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let max_accounts_per_sec = match config.max_accounts_per_sec {
+            Some(0) => {
+                let msg = "\"max_accounts_per_sec\" must be positive".to_string();
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::ConfigurationError { msg },
+                )));
+            }
+            Some(max_accounts_per_sec) => max_accounts_per_sec,
+            None => return Ok(None),
+        };
+        Ok(Some(Self {
+            max_accounts_per_sec,
+            policy: config.rate_limit_policy.unwrap_or(RateLimitPolicy::Block),
+            tokens: max_accounts_per_sec as f64,
+            last_refill: Instant::now(),
+            throttled: false,
+            throttled_count: 0,
+            dropped_count: 0,
+            last_report: AtomicInterval::default(),
+        }))
+    }
+
+    /// Take a token at the time, or return the time to wait for the next one.
+    fn take_token(&mut self, now: Instant) -> Result<(), Duration> {
+        let rate = self.max_accounts_per_sec as f64;
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Whether the account update is sent, waiting for the rate with the 'block' policy.
+    pub fn admit(&mut self) -> bool {
+        self.maybe_report();
+        let wait = match self.take_token(Instant::now()) {
+            Ok(()) => {
+                self.throttled = false;
+                return true;
+            }
+            Err(wait) => wait,
+        };
+        self.throttled = true;
+        self.throttled_count += 1;
+        if self.policy == RateLimitPolicy::Drop {
+            self.dropped_count += 1;
+            return false;
+        }
+        sleep(wait);
+        while let Err(wait) = self.take_token(Instant::now()) {
+            sleep(wait);
+        }
+        true
+    }
+
+    fn maybe_report(&self) {
+        if !self
+            .last_report
+            .should_update(RATE_LIMIT_REPORT_INTERVAL_MS)
+        {
+            return;
+        }
+        datapoint_info!(
+            "geyser-plugin-postgres-rate-limit",
+            ("throttled", self.throttled as i64, i64),
+            (
+                "max-accounts-per-sec",
+                self.max_accounts_per_sec as i64,
+                i64
+            ),
+            ("throttled-count", self.throttled_count as i64, i64),
+            ("dropped-count", self.dropped_count as i64, i64),
+        );
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, serde_json::json};
+
+    fn build_rate_limiter(config: serde_json::Value) -> AccountRateLimiter {
+        AccountRateLimiter::new(&serde_json::from_value(config).unwrap())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rate_limiter_take_token() {
+        let mut rate_limiter = build_rate_limiter(json!({ "max_accounts_per_sec": 10 }));
+        let start = rate_limiter.last_refill;
+        // A burst of a second of updates passes at once.
+        for _ in 0..10 {
+            assert!(rate_limiter.take_token(start).is_ok());
+        }
+        assert_eq!(
+            rate_limiter.take_token(start),
+            Err(Duration::from_millis(100))
+        );
+        let later = start + Duration::from_millis(250);
+        assert!(rate_limiter.take_token(later).is_ok());
+        assert!(rate_limiter.take_token(later).is_ok());
+        assert!(rate_limiter.take_token(later).is_err());
+        // The bucket holds no more than a second of updates.
+        let much_later = later + Duration::from_secs(60);
+        for _ in 0..10 {
+            assert!(rate_limiter.take_token(much_later).is_ok());
+        }
+        assert!(rate_limiter.take_token(much_later).is_err());
+
+        let config = serde_json::from_value(json!({ "max_accounts_per_sec": 0 })).unwrap();
+        assert!(AccountRateLimiter::new(&config).is_err());
+        let config = serde_json::from_value(json!({})).unwrap();
+        assert!(AccountRateLimiter::new(&config).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rate_limiter_admit() {
+        let mut rate_limiter = build_rate_limiter(json!({
+            "max_accounts_per_sec": 2,
+            "rate_limit_policy": "drop",
+        }));
+        assert!(rate_limiter.admit());
+        assert!(rate_limiter.admit());
+        assert!(!rate_limiter.admit());
+        assert!(rate_limiter.throttled);
+        assert_eq!(rate_limiter.dropped_count, 1);
+
+        // The 'block' policy waits for the next token.
+        let mut rate_limiter = build_rate_limiter(json!({ "max_accounts_per_sec": 10 }));
+        let start = Instant::now();
+        for _ in 0..12 {
+            assert!(rate_limiter.admit());
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(rate_limiter.throttled_count, 2);
+        assert_eq!(rate_limiter.dropped_count, 0);
+    }
+}
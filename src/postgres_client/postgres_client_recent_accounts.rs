@@ -0,0 +1,191 @@
+/// Module responsible for maintaining the recent_account table, the accounts updated within the
+/// last recent_account_window_slots slots, when maintain_recent_account_table is set, so the
+/// queries of the recent state hit a small table rather than the account table.
+///
+/// The table is written by the account_recent triggers on the account table, for the
+/// connections which set the geyser_plugin.maintain_recent_accounts session setting. Each row
+/// written to the account table, by any of the write paths, is thus copied in the same
+/// statement, and both tables commit or roll back together. The plugin sets the setting at the
+/// end of startup, after the buffered startup writes are flushed, so the accounts loaded from
+/// the snapshot, which are not recent updates, are left out. The rows below the window are
+/// deleted as the slots are rooted, by the worker notified of each root, through the
+/// recent_account_slot index. A row copied at an older slot than the window, such as a late
+/// write, is deleted by the next root.
+///
+/// The table and its triggers are created by the optional create_recent_accounts.sql script
+/// rather than create_schema.sql, so the deployments not using the feature do not pay for the
+/// transition tables of each statement; the plugin checks the triggers are installed when it is
+/// loaded.
+use {
+    super::{postgres_client_schema_check::RequiredTrigger, SimplePostgresClient},
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_metrics::*,
+};
+
+const DEFAULT_RECENT_ACCOUNT_WINDOW_SLOTS: u64 = 10_000;
+
+/// The triggers of create_recent_accounts.sql.
+pub(crate) const RECENT_ACCOUNT_TRIGGERS: [RequiredTrigger; 2] = [
+    (
+        "create_recent_accounts.sql",
+        "account",
+        "account_recent_insert_trigger",
+    ),
+    (
+        "create_recent_accounts.sql",
+        "account",
+        "account_recent_update_trigger",
+    ),
+];
+
+impl SimplePostgresClient {
+    /// The slots of the recent_account table, when it is maintained.
+    pub(crate) fn get_recent_account_window_slots(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Option<u64> {
+        matches!(config.maintain_recent_account_table, Some(true)).then(|| {
+            config
+                .recent_account_window_slots
+                .unwrap_or(DEFAULT_RECENT_ACCOUNT_WINDOW_SLOTS)
+        })
+    }
+
+    /// Enable the account_recent triggers for the session.
+    pub(crate) fn set_maintain_recent_accounts(
+        client: &mut Client,
+    ) -> Result<(), GeyserPluginError> {
+        if let Err(err) = client.batch_execute("SET geyser_plugin.maintain_recent_accounts = on") {
+            let msg = format!(
+                "Failed to set geyser_plugin.maintain_recent_accounts. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(())
+    }
+
+    /// Start copying the account updates to the recent_account table at the end of startup,
+    /// after the buffered startup writes are flushed.
+    pub(crate) fn start_recent_accounts(&mut self) -> Result<(), GeyserPluginError> {
+        if self.recent_account_window_slots.is_none() {
+            return Ok(());
+        }
+        let client = self.client.get_mut().unwrap();
+        Self::set_maintain_recent_accounts(&mut client.client)?;
+        info!("Started maintaining the recent_account table at the end of startup");
+        Ok(())
+    }
+
+    /// Delete the recent_account rows updated before the window ending at the root.
+    pub(crate) fn prune_recent_accounts(&mut self, root: u64) -> Result<(), GeyserPluginError> {
+        let window_slots = match self.recent_account_window_slots {
+            Some(window_slots) if root > window_slots => window_slots,
+            _ => return Ok(()),
+        };
+        let stmt = format!(
+            "DELETE FROM {} WHERE slot < $1",
+            Self::table_name(&self.table_names, "recent_account")
+        );
+        let statement = self.prepare_cached_statement(&stmt)?;
+        let client = self.client.get_mut().unwrap();
+        let min_slot = (root - window_slots) as i64;
+        match statement.execute(&mut client.client, &client.types, &[&min_slot]) {
+            Ok(count) => {
+                inc_new_counter_debug!(
+                    "geyser-plugin-postgres-recent-account-pruned",
+                    count as usize
+                );
+                Ok(())
+            }
+            Err(err) => {
+                let msg = format!(
+                    "Failed to prune the recent_account rows before slot {} in the PostgreSQL database. Error: {:?}",
+                    min_slot, err
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::SlotStatusUpdateError { msg })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_recent_account_table() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 1,
+            "maintain_recent_account_table": true,
+            "recent_account_window_slots": 100,
+        }));
+        assert!(SimplePostgresClient::check_schema_triggers(&config).is_err());
+        db.client
+            .batch_execute(include_str!("../../scripts/create_recent_accounts.sql"))
+            .unwrap();
+        SimplePostgresClient::check_schema_triggers(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        // The accounts loaded at startup are left out.
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![], 10), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+        assert_eq!(db.count("account"), 1);
+        assert_eq!(db.count("recent_account"), 0);
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![1], 20);
+        client.update_account(account.clone(), false).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![], 150), false)
+            .unwrap();
+        account.slot = 160;
+        account.data = vec![2];
+        client.update_account(account.clone(), false).unwrap();
+        // An older update of the account is not copied over the newer one.
+        account.slot = 30;
+        account.data = vec![3];
+        client.update_account(account.clone(), false).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![], 40), false)
+            .unwrap();
+        assert_eq!(db.count("recent_account"), 3);
+
+        client
+            .update_slot_status(200, Some(199), SlotStatus::Rooted)
+            .unwrap();
+        let rows: Vec<(Vec<u8>, i64, Vec<u8>)> = db
+            .client
+            .query(
+                "SELECT pubkey, slot, data FROM recent_account ORDER BY slot",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].1, 150);
+        assert_eq!(rows[1], (account.pubkey.clone(), 160, vec![2]));
+    }
+}
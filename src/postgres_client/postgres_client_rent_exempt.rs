@@ -0,0 +1,116 @@
+/// Module responsible for writing whether each account is rent-exempt to the rent_exempt column
+/// of the account and account_audit tables, when compute_rent_exempt is set, so the rent-reclaim
+/// tooling may find the accounts below the rent-exempt minimum balance.
+///
+/// An account is rent-exempt when its lamports are at least the minimum balance for the length
+/// of its data, as computed by the runtime from the rent sysvar. The rent parameters differ
+/// across the clusters, so they are configured by rent_lamports_per_byte_year and
+/// rent_exemption_threshold, defaulting to those of the Solana clusters. The length is that of
+/// the full data, also when only a prefix of it is stored.
+use {
+    super::DbAccountInfo,
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    solana_sdk::rent::{Rent, DEFAULT_EXEMPTION_THRESHOLD, DEFAULT_LAMPORTS_PER_BYTE_YEAR},
+};
+
+/// The rent parameters of the cluster, None when the exemption threshold is not a non-negative
+/// number.
+pub(crate) fn configured_rent(config: &GeyserPluginPostgresConfig) -> Option<Rent> {
+    let exemption_threshold = config
+        .rent_exemption_threshold
+        .unwrap_or(DEFAULT_EXEMPTION_THRESHOLD);
+    (exemption_threshold.is_finite() && exemption_threshold >= 0.0).then(|| Rent {
+        lamports_per_byte_year: config
+            .rent_lamports_per_byte_year
+            .unwrap_or(DEFAULT_LAMPORTS_PER_BYTE_YEAR),
+        exemption_threshold,
+        ..Rent::default()
+    })
+}
+
+/// Whether the account holds at least the rent-exempt minimum balance for its data.
+pub(crate) fn is_rent_exempt(rent: &Rent, account: &DbAccountInfo) -> bool {
+    rent.is_exempt(account.lamports as u64, account.data.len())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient, SimplePostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_is_rent_exempt() {
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({})).unwrap();
+        let rent = configured_rent(&config).unwrap();
+        // The minimum balance of an account without data, (128 + 0) * 3480 * 2.
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 1);
+        account.lamports = 890_880;
+        assert!(is_rent_exempt(&rent, &account));
+        account.lamports -= 1;
+        assert!(!is_rent_exempt(&rent, &account));
+        account.data = vec![0; 10];
+        account.lamports = 890_880;
+        assert!(!is_rent_exempt(&rent, &account));
+
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "rent_lamports_per_byte_year": 1000,
+            "rent_exemption_threshold": 1.0,
+        }))
+        .unwrap();
+        let rent = configured_rent(&config).unwrap();
+        account.lamports = 138_000;
+        assert!(is_rent_exempt(&rent, &account));
+
+        let config: GeyserPluginPostgresConfig =
+            serde_json::from_value(json!({ "rent_exemption_threshold": -1.0 })).unwrap();
+        assert!(configured_rent(&config).is_none());
+    }
+
+    #[test]
+    fn test_rent_exempt_column() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "compute_rent_exempt": true,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        // The bulk insert at startup, then the single upsert.
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 1);
+        client.update_account(account.clone(), true).unwrap();
+        client
+            .update_account(build_account(&Pubkey::new_unique(), vec![0; 200], 1), true)
+            .unwrap();
+        client.notify_end_of_startup().unwrap();
+        account.slot = 2;
+        account.lamports = 100;
+        client.update_account(account, false).unwrap();
+
+        let rent_exempt: Vec<(i64, bool)> = db
+            .client
+            .query(
+                "SELECT lamports, rent_exempt FROM account ORDER BY slot",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        assert_eq!(rent_exempt, vec![(1_000_000, false), (100, false)]);
+        let row = db
+            .client
+            .query_one("SELECT rent_exempt FROM account_audit", &[])
+            .unwrap();
+        assert!(row.get::<_, bool>(0));
+    }
+}
@@ -0,0 +1,286 @@
+/// Module responsible for periodically re-preparing the statements of a connection.
+///
+/// The backend of a long-running connection keeps the plans of its prepared statements,
+/// which for the multi-row insert statements of a large batch_size are big. When enabled,
+/// the statements are prepared again every reprepare_interval_batches account batches,
+/// bounding the memory of the backend.
+///
+/// The replaced statements are deallocated on the server when their handles are dropped.
+/// DEALLOCATE ALL is not used, as it would also deallocate the type lookup statements the
+/// driver prepares and caches for the lifetime of the connection.
+use {
+    super::{PreparedStatementCache, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+};
+
+/// The state of the periodic re-preparation of the statements.
+pub(crate) struct StatementReprepare {
+    interval_batches: u64,
+    batches_since_prepare: u64,
+    /// The statements are built from the configuration.
+    config: GeyserPluginPostgresConfig,
+}
+
+impl StatementReprepare {
+    /// Returns None when the re-preparation is not enabled.
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Option<Self> {
+        match config.reprepare_interval_batches {
+            Some(interval_batches) if interval_batches > 0 => Some(Self {
+                interval_batches,
+                batches_since_prepare: 0,
+                config: config.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Count an account batch, returns true when the statements are due to be re-prepared.
+    fn count_batch(&mut self) -> bool {
+        self.batches_since_prepare += 1;
+        if self.batches_since_prepare >= self.interval_batches {
+            self.batches_since_prepare = 0;
+            return true;
+        }
+        false
+    }
+}
+
+impl SimplePostgresClient {
+    /// Count a bulk inserted account batch, re-preparing the statements when the interval
+    /// is reached.
+    pub(crate) fn count_batch_for_reprepare(&mut self) -> Result<(), GeyserPluginError> {
+        let due = match &mut self.statement_reprepare {
+            Some(reprepare) => reprepare.count_batch(),
+            None => false,
+        };
+        if due {
+            self.reprepare_statements()?;
+        }
+        Ok(())
+    }
+
+    /// Prepare all the statements of the connection again, deallocating the replaced ones.
+    /// The optional statements are only prepared again if they are enabled.
+    fn reprepare_statements(&mut self) -> Result<(), GeyserPluginError> {
+        let config = match &self.statement_reprepare {
+            Some(reprepare) => &reprepare.config,
+            None => return Ok(()),
+        };
+        let mut measure = Measure::start("geyser-plugin-postgres-reprepare-statements");
+        let wrapper = self.client.get_mut().unwrap();
+
+        let client = &mut wrapper.client;
+        wrapper.bulk_account_insert_stmt =
+            Self::build_bulk_account_insert_statement(client, config)?;
+        wrapper.update_account_stmt = Self::build_single_account_upsert_statement(client, config)?;
+        wrapper.update_slot_with_parent_stmt =
+            Self::build_slot_upsert_statement_with_parent(client, config)?;
+        wrapper.update_slot_without_parent_stmt =
+            Self::build_slot_upsert_statement_without_parent(client, config)?;
+        wrapper.update_transaction_log_stmt =
+            Self::build_transaction_info_upsert_statement(client, config)?;
+        wrapper.update_block_metadata_stmt =
+            Self::build_block_metadata_upsert_statement(client, config)?;
+        if wrapper.update_slot_transaction_tally_stmt.is_some() {
+            wrapper.update_slot_transaction_tally_stmt = Some(
+                Self::build_slot_transaction_tally_update_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_transaction_signatures_stmt.is_some() {
+            wrapper.insert_transaction_signatures_stmt = Some(
+                Self::build_transaction_signatures_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_transaction_account_writes_stmt.is_some() {
+            wrapper.insert_transaction_account_writes_stmt = Some(
+                Self::build_transaction_account_writes_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_transaction_instructions_stmt.is_some() {
+            wrapper.insert_transaction_instructions_stmt = Some(
+                Self::build_transaction_instructions_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_account_audit_stmt.is_some() {
+            wrapper.insert_account_audit_stmt =
+                Some(Self::build_account_audit_insert_statement(client, config)?);
+        }
+        if wrapper.insert_token_owner_index_stmt.is_some() {
+            wrapper.insert_token_owner_index_stmt = Some(
+                Self::build_single_token_owner_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_token_mint_index_stmt.is_some() {
+            wrapper.insert_token_mint_index_stmt = Some(
+                Self::build_single_token_mint_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.bulk_insert_token_owner_index_stmt.is_some() {
+            wrapper.bulk_insert_token_owner_index_stmt = Some(
+                Self::build_bulk_token_owner_index_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.bulk_insert_token_mint_index_stmt.is_some() {
+            wrapper.bulk_insert_token_mint_index_stmt = Some(
+                Self::build_bulk_token_mint_index_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_stake_account_index_stmt.is_some() {
+            wrapper.upsert_stake_account_index_stmt = Some(
+                Self::build_stake_account_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_metaplex_metadata_index_stmt.is_some() {
+            wrapper.upsert_metaplex_metadata_index_stmt = Some(
+                Self::build_metaplex_metadata_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_token_delegate_index_stmt.is_some() {
+            wrapper.upsert_token_delegate_index_stmt = Some(
+                Self::build_token_delegate_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_token_state_index_stmt.is_some() {
+            wrapper.upsert_token_state_index_stmt = Some(
+                Self::build_token_state_index_upsert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_slot_timing_stmt.is_some() {
+            wrapper.upsert_slot_timing_stmt =
+                Some(Self::build_slot_timing_upsert_statement(client, config)?);
+        }
+        if wrapper.insert_account_close_stmt.is_some() {
+            wrapper.insert_account_close_stmt =
+                Some(Self::build_account_close_insert_statement(client, config)?);
+        }
+        if wrapper.select_existing_accounts_stmt.is_some() {
+            wrapper.select_existing_accounts_stmt = Some(
+                Self::build_existing_accounts_select_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_batch_log_stmt.is_some() {
+            wrapper.insert_batch_log_stmt =
+                Some(Self::build_batch_log_insert_statement(client, config)?);
+        }
+        if wrapper.upsert_checkpoint_stmt.is_some() {
+            wrapper.upsert_checkpoint_stmt =
+                Some(Self::build_checkpoint_upsert_statement(client, config)?);
+        }
+        if wrapper.update_dead_slots_stmt.is_some() {
+            wrapper.update_dead_slots_stmt =
+                Some(Self::build_dead_slots_update_statement(client, config)?);
+        }
+        if wrapper.insert_placeholder_block_stmt.is_some() {
+            wrapper.insert_placeholder_block_stmt = Some(
+                Self::build_placeholder_block_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.upsert_program_link_stmt.is_some() {
+            wrapper.upsert_program_link_stmt =
+                Some(Self::build_program_link_upsert_statement(client, config)?);
+        }
+        if wrapper.insert_account_staging_stmt.is_some() {
+            wrapper.insert_account_staging_stmt = Some(
+                Self::build_account_staging_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.insert_account_data_chunk_stmt.is_some() {
+            wrapper.insert_account_data_chunk_stmt = Some(
+                Self::build_account_data_chunk_insert_statement(client, config)?,
+            );
+        }
+        if wrapper.update_account_slot_status_stmt.is_some() {
+            wrapper.update_account_slot_status_stmt = Some(
+                Self::build_account_slot_status_update_statement(client, config)?,
+            );
+        }
+        // The cached statements are deallocated as well, they are prepared again on use.
+        wrapper.statement_cache = PreparedStatementCache::new(
+            wrapper.statement_cache.capacity(),
+            Self::use_prepared_statements(config),
+        );
+
+        measure.stop();
+        info!(
+            "Re-prepared the statements of the PostgreSQL database connection in {}us",
+            measure.as_us()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn prepared_statement_count(client: &mut SimplePostgresClient) -> i64 {
+        client
+            .client
+            .get_mut()
+            .unwrap()
+            .client
+            .query_one("SELECT count(*) FROM pg_prepared_statements", &[])
+            .unwrap()
+            .get(0)
+    }
+
+    #[test]
+    fn test_reprepare_statements() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 2,
+            "reprepare_interval_batches": 2,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let statement_count = prepared_statement_count(&mut client);
+        client.prepare_cached_statement("SELECT 1").unwrap();
+
+        let owner = Pubkey::new_unique();
+        for _ in 0..4 {
+            client
+                .update_account(build_account(&owner, vec![], 1), true)
+                .unwrap();
+        }
+        // The cached statement was dropped along with all the others.
+        assert_eq!(prepared_statement_count(&mut client), statement_count);
+
+        // The re-prepared statements are used by the following batches.
+        for _ in 0..2 {
+            client
+                .update_account(build_account(&owner, vec![], 1), true)
+                .unwrap();
+        }
+        client
+            .update_account(build_account(&owner, vec![], 2), false)
+            .unwrap();
+        assert_eq!(db.count("account"), 7);
+    }
+
+    #[test]
+    fn test_reprepare_disabled() {
+        let config = serde_json::from_value(json!({ "reprepare_interval_batches": 0 })).unwrap();
+        assert!(StatementReprepare::new(&config).is_none());
+
+        let config = serde_json::from_value(json!({ "reprepare_interval_batches": 3 })).unwrap();
+        let mut reprepare = StatementReprepare::new(&config).unwrap();
+        assert_eq!(
+            (0..6).map(|_| reprepare.count_batch()).collect::<Vec<_>>(),
+            vec![false, false, true, false, false, true]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+/// Module responsible for isolating the rows failing an accounts bulk insert, when
+/// isolate_failing_rows is set.
+///
+/// A bulk insert fails as a whole when any of its rows fails, such as an account too large for
+/// the column. The rows of a failed batch are then inserted one by one, so the rows which fail
+/// on their own are skipped, logged and counted by the geyser-plugin-postgres-skipped-rows
+/// counter, and the other rows are committed.
+use {
+    super::SimplePostgresClient, chrono::Utc, log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError, solana_metrics::*,
+    std::ops::Range, tokio_postgres::types,
+};
+
+impl SimplePostgresClient {
+    /// Insert the pending accounts of the failed batch one by one, skipping the failing ones.
+    pub(crate) fn insert_failed_batch_rows(
+        &mut self,
+        batch: Range<usize>,
+    ) -> Result<(), GeyserPluginError> {
+        let stmt = self
+            .batch_splitter
+            .bulk_insert_sql(1, &self.account_columns);
+        let statement = self.prepare_cached_statement(&stmt)?;
+        info!(
+            "Inserting the {} accounts of the failed batch one by one",
+            batch.len()
+        );
+
+        let updated_on = Utc::now().naive_utc();
+        let client = self.client.get_mut().unwrap();
+        let mut committed = Vec::new();
+        let mut skipped_count = 0;
+        for account in &self.pending_account_updates[batch] {
+            let derived = self.account_columns.derived_values(
+                account,
+                &updated_on,
+                &self.slot_clock,
+                &self.slot_statuses,
+            );
+            let mut values: Vec<&(dyn types::ToSql + Sync)> =
+                Vec::with_capacity(self.account_columns.len());
+            self.account_columns
+                .push_values(account, &derived, &updated_on, &mut values);
+            match statement.query(&mut client.client, &client.types, &values) {
+                Ok(_) => {
+                    if self.batch_sequence.is_some() {
+                        committed.push(account.clone());
+                    }
+                }
+                Err(err) => {
+                    error!(
+                        "Skipped the update of account {} at slot {} failing on its own. Error: {:?}",
+                        bs58::encode(&account.pubkey).into_string(),
+                        account.slot,
+                        err
+                    );
+                    skipped_count += 1;
+                }
+            }
+        }
+        inc_new_counter_info!("geyser-plugin-postgres-skipped-rows", skipped_count);
+        Self::log_batch(client, &self.batch_sequence, &committed)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_isolate_failing_rows() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute(
+                "ALTER TABLE account ADD CONSTRAINT account_slot_check CHECK (slot < 100)",
+            )
+            .unwrap();
+        let owner = Pubkey::new_unique();
+        let slots = [1, 100, 2, 3];
+
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+        for slot in slots {
+            // Fails with the batch holding the account at slot 100.
+            let _ = client.update_account(build_account(&owner, vec![], slot), true);
+        }
+        assert_eq!(db.count("account"), 2);
+
+        db.client.batch_execute("TRUNCATE account").unwrap();
+        let config = db.config(json!({ "isolate_failing_rows": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        for slot in slots {
+            client
+                .update_account(build_account(&owner, vec![], slot), true)
+                .unwrap();
+        }
+        let mut slots: Vec<i64> = db
+            .client
+            .query("SELECT slot FROM account", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        slots.sort_unstable();
+        assert_eq!(slots, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,441 @@
+/// Module responsible for checking the tables the plugin writes have the columns its statements
+/// need when the plugin is loaded, so a schema drifting from create_schema.sql is reported by
+/// the table and the column missing or mistyped, rather than by the statement failing to be
+/// prepared.
+///
+/// The columns are looked up in information_schema.columns of the current schema, or of the
+/// schema qualifying the name of the table in table_names. The types
+/// are compared by their information_schema data_type; the composite and enum columns are
+/// USER-DEFINED and the array columns ARRAY.
+///
+/// The features maintained by the triggers of an optional script, rather than create_schema.sql,
+/// are checked to have their triggers installed, so a feature enabled without its script is
+/// reported with the script to apply.
+use {
+    super::{
+        postgres_client_ingestion_seq::INGESTION_SEQ_TRIGGERS,
+        postgres_client_owner_counts::OWNER_COUNT_TRIGGERS,
+        postgres_client_recent_accounts::RECENT_ACCOUNT_TRIGGERS, AccountColumns,
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{
+        AccountDataEncoding, DataCompression, GeyserPluginPostgresConfig,
+        GeyserPluginPostgresError, JsonColumnType,
+    },
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{collections::HashMap, sync::Mutex},
+};
+
+/// A trigger of an optional script: the script, the table and the name of the trigger.
+pub(crate) type RequiredTrigger = (&'static str, &'static str, &'static str);
+
+/// A column written by the statements: its table, its name and the data types accepted.
+type RequiredColumn = (&'static str, &'static str, &'static [&'static str]);
+
+const BIGINT: &[&str] = &["bigint"];
+const BYTEA: &[&str] = &["bytea"];
+const BOOLEAN: &[&str] = &["boolean"];
+const SMALLINT: &[&str] = &["smallint"];
+const TIMESTAMP: &[&str] = &["timestamp without time zone"];
+const VARCHAR: &[&str] = &["character varying", "text"];
+const USER_DEFINED: &[&str] = &["USER-DEFINED"];
+const ARRAY: &[&str] = &["ARRAY"];
+
+const SLOT_COLUMNS: [RequiredColumn; 4] = [
+    ("slot", "slot", BIGINT),
+    ("slot", "parent", BIGINT),
+    ("slot", "status", VARCHAR),
+    ("slot", "updated_on", TIMESTAMP),
+];
+
+const BLOCK_COLUMNS: [RequiredColumn; 6] = [
+    ("block", "slot", BIGINT),
+    ("block", "blockhash", VARCHAR),
+    ("block", "rewards", ARRAY),
+    ("block", "block_time", BIGINT),
+    ("block", "block_height", BIGINT),
+    ("block", "updated_on", TIMESTAMP),
+];
+
+const TRANSACTION_COLUMNS: [RequiredColumn; 11] = [
+    ("transaction", "slot", BIGINT),
+    ("transaction", "signature", BYTEA),
+    ("transaction", "is_vote", BOOLEAN),
+    ("transaction", "message_type", SMALLINT),
+    ("transaction", "legacy_message", USER_DEFINED),
+    ("transaction", "v0_loaded_message", USER_DEFINED),
+    ("transaction", "signatures", ARRAY),
+    ("transaction", "message_hash", BYTEA),
+    ("transaction", "meta", USER_DEFINED),
+    ("transaction", "write_version", BIGINT),
+    ("transaction", "updated_on", TIMESTAMP),
+];
+
+const COMPRESSED_TRANSACTION_COLUMNS: [RequiredColumn; 3] = [
+    ("transaction", "data_compression", SMALLINT),
+    ("transaction", "compressed_message", BYTEA),
+    ("transaction", "compressed_meta", BYTEA),
+];
+
+const TOKEN_OWNER_INDEX_COLUMNS: [RequiredColumn; 3] = [
+    ("spl_token_owner_index", "owner_key", BYTEA),
+    ("spl_token_owner_index", "account_key", BYTEA),
+    ("spl_token_owner_index", "slot", BIGINT),
+];
+
+const TOKEN_MINT_INDEX_COLUMNS: [RequiredColumn; 3] = [
+    ("spl_token_mint_index", "mint_key", BYTEA),
+    ("spl_token_mint_index", "account_key", BYTEA),
+    ("spl_token_mint_index", "slot", BIGINT),
+];
+
+const STAKE_ACCOUNT_INDEX_COLUMNS: [RequiredColumn; 7] = [
+    ("stake_account_index", "stake_pubkey", BYTEA),
+    ("stake_account_index", "withdrawer", BYTEA),
+    ("stake_account_index", "staker", BYTEA),
+    ("stake_account_index", "voter", BYTEA),
+    ("stake_account_index", "delegated_stake", BIGINT),
+    ("stake_account_index", "activation_epoch", BIGINT),
+    ("stake_account_index", "slot", BIGINT),
+];
+
+const METAPLEX_METADATA_INDEX_COLUMNS: [RequiredColumn; 7] = [
+    ("metaplex_metadata_index", "metadata_pubkey", BYTEA),
+    ("metaplex_metadata_index", "mint", BYTEA),
+    ("metaplex_metadata_index", "update_authority", BYTEA),
+    ("metaplex_metadata_index", "name", VARCHAR),
+    ("metaplex_metadata_index", "symbol", VARCHAR),
+    ("metaplex_metadata_index", "uri", VARCHAR),
+    ("metaplex_metadata_index", "slot", BIGINT),
+];
+
+const TOKEN_DELEGATE_INDEX_COLUMNS: [RequiredColumn; 3] = [
+    ("spl_token_delegate_index", "account_key", BYTEA),
+    ("spl_token_delegate_index", "delegate_key", BYTEA),
+    ("spl_token_delegate_index", "slot", BIGINT),
+];
+
+const TOKEN_STATE_INDEX_COLUMNS: [RequiredColumn; 4] = [
+    ("spl_token_state_index", "account_key", BYTEA),
+    ("spl_token_state_index", "state", VARCHAR),
+    ("spl_token_state_index", "close_authority", BYTEA),
+    ("spl_token_state_index", "slot", BIGINT),
+];
+
+const ACCOUNT_DATA_CHUNK_COLUMNS: [RequiredColumn; 6] = [
+    ("account_data_chunk", "pubkey", BYTEA),
+    ("account_data_chunk", "slot", BIGINT),
+    (
+        "account_data_chunk",
+        "write_version",
+        &["bigint", "numeric"],
+    ),
+    ("account_data_chunk", "chunk_index", &["integer"]),
+    ("account_data_chunk", "chunk_bytes", BYTEA),
+    ("account_data_chunk", "updated_on", TIMESTAMP),
+];
+
+const BATCH_LOG_COLUMNS: [RequiredColumn; 5] = [
+    ("batch_log", "batch_seq", BIGINT),
+    ("batch_log", "min_slot", BIGINT),
+    ("batch_log", "max_slot", BIGINT),
+    ("batch_log", "row_count", &["integer"]),
+    ("batch_log", "committed_at", TIMESTAMP),
+];
+
+/// The data types accepted for a column of the account tables.
+fn account_column_types(
+    column: &str,
+    account_data_encoding: AccountDataEncoding,
+) -> &'static [&'static str] {
+    match column {
+        "pubkey" | "owner" => BYTEA,
+        "executable" | "rent_exempt" => BOOLEAN,
+        "data" => match account_data_encoding {
+            AccountDataEncoding::Bytea => BYTEA,
+            AccountDataEncoding::Hex | AccountDataEncoding::Base64 => VARCHAR,
+        },
+        // The write_version_type is checked by check_write_version_type.
+        "write_version" => &["bigint", "numeric"],
+        "data_len" | "data_chunk_count" => &["integer"],
+        "search_text" => &["tsvector"],
+        "slot_status" | "account_type" => VARCHAR,
+        "updated_on" => TIMESTAMP,
+        _ => BIGINT,
+    }
+}
+
+/// The name of the first accepted data type in the messages, in its SQL spelling.
+fn data_type_name(data_types: &[&str]) -> String {
+    match data_types[0] {
+        "character varying" => "varchar".to_string(),
+        "timestamp without time zone" => "timestamp".to_string(),
+        "USER-DEFINED" => "of a composite type".to_string(),
+        "ARRAY" => "array".to_string(),
+        data_type => data_type.to_string(),
+    }
+}
+
+impl SimplePostgresClient {
+    /// The columns written by the statements prepared for the configuration.
+    fn required_columns(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Vec<RequiredColumn>, GeyserPluginError> {
+        let account_columns = AccountColumns::new(config)?;
+        let account_data_encoding = Self::get_account_data_encoding(config);
+        let mut account_tables = vec!["account"];
+        if let Some(true) = config.store_account_historical_data {
+            account_tables.push("account_audit");
+        }
+
+        let mut columns = Vec::new();
+        for table in account_tables {
+            for column in account_columns.columns() {
+                columns.push((
+                    table,
+                    column,
+                    account_column_types(column, account_data_encoding),
+                ));
+            }
+            if Self::get_track_lamport_deltas(config) {
+                columns.push((table, "pre_lamports", BIGINT));
+            }
+        }
+        if config.account_data_chunk_threshold.is_some() {
+            columns.extend(ACCOUNT_DATA_CHUNK_COLUMNS);
+        }
+        columns.extend(SLOT_COLUMNS);
+        columns.extend(BLOCK_COLUMNS);
+        if Self::get_block_time_bucket_secs(config).is_some() {
+            columns.push(("block", "block_time_bucket", BIGINT));
+        }
+        columns.extend(TRANSACTION_COLUMNS);
+        if matches!(
+            config.compress_transaction_data,
+            Some(DataCompression::Zstd | DataCompression::Lz4)
+        ) {
+            columns.extend(COMPRESSED_TRANSACTION_COLUMNS);
+        }
+        if let Some(true) = config.record_ingestion_seq {
+            columns.push(("account", "seq", BIGINT));
+            columns.push(("transaction", "seq", BIGINT));
+        }
+        if config.instruction_idl.is_some() {
+            let args_types: &[&str] = match Self::get_json_column_type(config) {
+                JsonColumnType::Json => &["json"],
+                JsonColumnType::Jsonb => &["jsonb"],
+            };
+            columns.push(("transaction_instruction", "args", args_types));
+        }
+        for (enabled, table_columns) in [
+            (config.index_token_owner, &TOKEN_OWNER_INDEX_COLUMNS[..]),
+            (config.index_token_mint, &TOKEN_MINT_INDEX_COLUMNS[..]),
+            (
+                config.index_stake_accounts,
+                &STAKE_ACCOUNT_INDEX_COLUMNS[..],
+            ),
+            (
+                config.index_metaplex_metadata,
+                &METAPLEX_METADATA_INDEX_COLUMNS[..],
+            ),
+            (
+                config.index_token_delegate,
+                &TOKEN_DELEGATE_INDEX_COLUMNS[..],
+            ),
+            (config.index_token_state, &TOKEN_STATE_INDEX_COLUMNS[..]),
+            (config.log_batches, &BATCH_LOG_COLUMNS[..]),
+        ] {
+            if let Some(true) = enabled {
+                columns.extend_from_slice(table_columns);
+            }
+        }
+        Ok(columns)
+    }
+
+    /// Check the tables have the columns the statements need, on a short-lived connection.
+    pub fn check_schema_columns(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let required_columns: Vec<_> = Self::required_columns(config)?
+            .into_iter()
+            .map(|(table, column, data_types)| {
+                (
+                    Self::table_name(&config.table_names, table),
+                    column,
+                    data_types,
+                )
+            })
+            .collect();
+        let mut tables: Vec<&str> = required_columns
+            .iter()
+            .map(|(table, _, _)| *table)
+            .collect();
+        tables.dedup();
+
+        // The tables qualified by their schema in table_names are looked up by the qualified
+        // name.
+        let mut client = Self::connect_to_db(config)?;
+        let rows = client.query(
+            "SELECT CASE WHEN table_schema = current_schema() THEN table_name::TEXT \
+            ELSE table_schema || '.' || table_name END, column_name::TEXT, data_type::TEXT \
+            FROM information_schema.columns \
+            WHERE (table_schema = current_schema() AND table_name::TEXT = ANY($1)) \
+            OR table_schema || '.' || table_name = ANY($1)",
+            &[&tables],
+        );
+        let existing: HashMap<(String, String), String> = match rows {
+            Ok(rows) => rows
+                .iter()
+                .map(|row| ((row.get(0), row.get(1)), row.get(2)))
+                .collect(),
+            Err(err) => {
+                let msg = format!(
+                    "Failed to look up the columns of the tables in the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )));
+            }
+        };
+
+        let mut problems = Vec::new();
+        for (table, column, data_types) in required_columns {
+            match existing.get(&(table.to_string(), column.to_string())) {
+                None => problems.push(format!(
+                    "table {} is missing column {} {}",
+                    table,
+                    column,
+                    data_type_name(data_types)
+                )),
+                Some(data_type) if !data_types.contains(&data_type.as_str()) => {
+                    problems.push(format!(
+                        "column {} of table {} is {}, expected {}",
+                        column,
+                        table,
+                        data_type,
+                        data_type_name(data_types)
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let msg = format!(
+            "The schema does not match the statements of the plugin: {}",
+            problems.join("; ")
+        );
+        error!("{}", msg);
+        Err(GeyserPluginError::Custom(Box::new(
+            GeyserPluginPostgresError::DataSchemaError { msg },
+        )))
+    }
+
+    /// The triggers of the optional scripts needed by the configuration.
+    fn required_triggers(config: &GeyserPluginPostgresConfig) -> Vec<RequiredTrigger> {
+        let mut triggers = Vec::new();
+        if let Some(true) = config.record_ingestion_seq {
+            triggers.extend(INGESTION_SEQ_TRIGGERS);
+        }
+        if let Some(true) = config.maintain_owner_counts {
+            triggers.extend(OWNER_COUNT_TRIGGERS);
+        }
+        if let Some(true) = config.maintain_recent_account_table {
+            triggers.extend(RECENT_ACCOUNT_TRIGGERS);
+        }
+        triggers
+    }
+
+    /// Check the triggers of the optional scripts needed by the configuration are installed, on
+    /// a short-lived connection.
+    pub fn check_schema_triggers(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let required_triggers = Self::required_triggers(config);
+        if required_triggers.is_empty() {
+            return Ok(());
+        }
+        let mut client = Self::connect_to_db(config)?;
+        let types = Mutex::default();
+        let statement = Self::prepare_query_statement(
+            &mut client,
+            config,
+            "SELECT EXISTS (SELECT 1 FROM pg_trigger WHERE tgrelid = to_regclass($1) AND tgname = $2)",
+        )?;
+
+        let mut missing = Vec::new();
+        for (script, table, trigger) in required_triggers {
+            let table = Self::table_name(&config.table_names, table);
+            let exists = statement
+                .query(&mut client, &types, &[&table, &trigger])
+                .map(|rows| rows.first().map_or(false, |row| row.get::<_, bool>(0)));
+            match exists {
+                Ok(true) => {}
+                Ok(false) => missing.push(format!(
+                    "table {} is missing trigger {}, created by {}",
+                    table, trigger, script
+                )),
+                Err(err) => {
+                    let msg = format!(
+                        "Failed to look up the triggers of the tables in the PostgreSQL database. Error: {:?}",
+                        err
+                    );
+                    error!("{}", msg);
+                    return Err(GeyserPluginError::Custom(Box::new(
+                        GeyserPluginPostgresError::DataSchemaError { msg },
+                    )));
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let msg = format!(
+            "The optional schema scripts needed by the configuration are not applied: {}",
+            missing.join("; ")
+        );
+        error!("{}", msg);
+        Err(GeyserPluginError::Custom(Box::new(
+            GeyserPluginPostgresError::DataSchemaError { msg },
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_check_schema_columns() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "store_account_historical_data": true,
+            "track_lamport_deltas": true,
+            "index_token_owner": true,
+            "index_token_mint": true,
+            "index_stake_accounts": true,
+            "index_token_delegate": true,
+            "index_token_state": true,
+        }));
+        SimplePostgresClient::check_schema_columns(&config).unwrap();
+
+        db.client
+            .batch_execute(
+                "ALTER TABLE account DROP COLUMN write_version CASCADE; \
+                ALTER TABLE slot ALTER COLUMN parent TYPE INTEGER",
+            )
+            .unwrap();
+        let err = SimplePostgresClient::check_schema_columns(&config).unwrap_err();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("table account is missing column write_version bigint"));
+        assert!(msg.contains("column parent of table slot is integer, expected bigint"));
+        assert!(!msg.contains("account_audit"));
+    }
+}
@@ -0,0 +1,310 @@
+/// Module responsible for the serialization threads, which encode the parameters of the
+/// transactions into the binary format of the PostgreSQL protocol ahead of the writer threads.
+///
+/// Encoding the composite parameters of a large transaction, its message and status meta,
+/// is CPU work which otherwise happens on a writer thread while it holds its database
+/// connection. When serialization_threads is set, the transactions are first sent to the
+/// serialization queue. A serialization thread encodes the composite parameters into byte
+/// buffers and hands the transaction over to the writers' queue, and the writer thread sends
+/// the buffers as they are. The other work items go straight to the writers' queue.
+///
+/// With compress_transaction_data, the encoded buffers are compressed, by the serialization
+/// threads when enabled or else by the writer threads, and written into the compressed
+/// columns, so the readers decompress them and decode them as their composite types.
+use {
+    super::{
+        postgres_client_compression::compress, postgres_client_transaction::TransactionColumns,
+        DbWork, DbWorkItem, LogTransactionRequest, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{
+        DataCompression, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+    },
+    bytes::BytesMut,
+    crossbeam_channel::{Receiver, RecvTimeoutError, Sender},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+    solana_metrics::*,
+    std::{
+        error::Error,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread::{Builder, JoinHandle},
+        time::Duration,
+    },
+    tokio_postgres::types::{to_sql_checked, IsNull, ToSql, Type},
+};
+
+const SERIALIZATION_RECV_TIMEOUT_MS: u64 = 500;
+
+/// A parameter already encoded in the binary format of its type, None for NULL.
+#[derive(Debug)]
+pub struct EncodedParam(Option<Vec<u8>>);
+
+impl EncodedParam {
+    /// Encode the value for the type, NULL when the column is not written.
+    fn encode(
+        value: &(dyn ToSql + Sync),
+        ty: &Option<Type>,
+    ) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        let ty = match ty {
+            Some(ty) => ty,
+            None => return Ok(Self(None)),
+        };
+        let mut buf = BytesMut::new();
+        match value.to_sql_checked(ty, &mut buf)? {
+            IsNull::Yes => Ok(Self(None)),
+            IsNull::No => Ok(Self(Some(buf.to_vec()))),
+        }
+    }
+
+    /// Compress the encoded value, NULL is left as is.
+    fn compress(self, compression: DataCompression) -> Result<Self, Box<dyn Error + Sync + Send>> {
+        match (self.0, compression) {
+            (Some(bytes), DataCompression::Zstd | DataCompression::Lz4) => {
+                Ok(Self(Some(compress(compression, &bytes)?)))
+            }
+            (bytes, _) => Ok(Self(bytes)),
+        }
+    }
+}
+
+impl ToSql for EncodedParam {
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match &self.0 {
+            Some(bytes) => {
+                out.extend_from_slice(bytes);
+                Ok(IsNull::No)
+            }
+            None => Ok(IsNull::Yes),
+        }
+    }
+
+    /// The parameter was encoded for the type of the statement's parameter.
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// The encoded composite parameters of the transaction upsert statement.
+#[derive(Debug)]
+pub struct EncodedTransactionParams {
+    pub legacy_message: EncodedParam,
+    pub v0_loaded_message: EncodedParam,
+    pub meta: EncodedParam,
+}
+
+/// The types of the composite parameters of the transaction upsert statement.
+#[derive(Clone)]
+pub(crate) struct TransactionParamTypes {
+    /// None when the column is not written.
+    legacy_message: Option<Type>,
+    v0_loaded_message: Option<Type>,
+    meta: Option<Type>,
+    compression: DataCompression,
+}
+
+impl TransactionParamTypes {
+    /// Look the types up on a short-lived connection.
+    fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        Self::load(&mut client, config)
+    }
+
+    /// Look the types up from the composite columns of the transaction table. on_load rejects
+    /// serialization_threads and compress_transaction_data when the statements are not
+    /// prepared.
+    pub(crate) fn load(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Self, GeyserPluginError> {
+        let stmt = format!(
+            "SELECT legacy_message, v0_loaded_message, meta FROM {} LIMIT 0",
+            SimplePostgresClient::table_name(&config.table_names, "transaction")
+        );
+        let statement = client.prepare(&stmt).map_err(|err| {
+            let msg = format!(
+                "Error in looking up the types of the transaction columns: ({})",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError { msg }))
+        })?;
+        let columns = TransactionColumns::new(config);
+        let column_type = |index: usize, written: bool| {
+            written.then(|| statement.columns()[index].type_().clone())
+        };
+        Ok(Self {
+            legacy_message: column_type(0, columns.message()),
+            v0_loaded_message: column_type(1, columns.message()),
+            meta: column_type(2, columns.meta()),
+            compression: columns.compression(),
+        })
+    }
+
+    pub(crate) fn encode(
+        &self,
+        request: &LogTransactionRequest,
+    ) -> Result<EncodedTransactionParams, Box<dyn Error + Sync + Send>> {
+        let transaction_info = &request.transaction_info;
+        Ok(EncodedTransactionParams {
+            legacy_message: EncodedParam::encode(
+                &transaction_info.legacy_message,
+                &self.legacy_message,
+            )?
+            .compress(self.compression)?,
+            v0_loaded_message: EncodedParam::encode(
+                &transaction_info.v0_loaded_message,
+                &self.v0_loaded_message,
+            )?
+            .compress(self.compression)?,
+            meta: EncodedParam::encode(&transaction_info.meta, &self.meta)?
+                .compress(self.compression)?,
+        })
+    }
+}
+
+/// Encode the transactions received, then hand all the work items over to the writers.
+fn serialize_work_items(
+    types: TransactionParamTypes,
+    receiver: Receiver<DbWork>,
+    sender: Sender<DbWork>,
+    exit: Arc<AtomicBool>,
+) {
+    while !exit.load(Ordering::Relaxed) {
+        let mut work =
+            match receiver.recv_timeout(Duration::from_millis(SERIALIZATION_RECV_TIMEOUT_MS)) {
+                Ok(work) => work,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+        if let DbWorkItem::LogTransaction(request) = &mut work.item {
+            let mut measure = Measure::start("geyser-plugin-postgres-serialize-transaction");
+            match types.encode(request) {
+                Ok(encoded) => request.encoded = Some(encoded),
+                // The writer encodes the transaction and reports the error.
+                Err(err) => warn!("Failed to serialize the transaction: {}", err),
+            }
+            measure.stop();
+            inc_new_counter_debug!(
+                "geyser-plugin-postgres-serialize-transaction-us",
+                measure.as_us() as usize,
+                100000,
+                100000
+            );
+        }
+
+        if sender.send(work).is_err() {
+            break;
+        }
+    }
+}
+
+/// Spawn the serialization threads, handing the work items over to the writers' queue.
+pub(crate) fn spawn_serializers(
+    config: &GeyserPluginPostgresConfig,
+    thread_count: usize,
+    receiver: Receiver<DbWork>,
+    sender: Sender<DbWork>,
+    exit: Arc<AtomicBool>,
+) -> Result<Vec<JoinHandle<()>>, GeyserPluginError> {
+    let types = TransactionParamTypes::new(config)?;
+    Ok((0..thread_count)
+        .map(|i| {
+            let types = types.clone();
+            let receiver = receiver.clone();
+            let sender = sender.clone();
+            let exit = exit.clone();
+            Builder::new()
+                .name(format!("serializer-{}", i))
+                .spawn(move || serialize_work_items(types, receiver, sender, exit))
+                .unwrap()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            postgres_client_transaction::tests::build_test_log_transaction_request,
+            tests::TestDatabase, PostgresClient,
+        },
+        crossbeam_channel::unbounded,
+        serde_json::json,
+    };
+
+    fn query_transaction(db: &mut TestDatabase, signature: &[u8]) -> Vec<String> {
+        let row = db
+            .client
+            .query_one(
+                "SELECT legacy_message::text, v0_loaded_message::text, meta::text \
+                FROM transaction WHERE signature = $1",
+                &[&signature],
+            )
+            .unwrap();
+        (0..3)
+            .map(|i| row.get::<_, Option<String>>(i).unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn test_serialize_transaction() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({}));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let (serialization_sender, serialization_receiver) = unbounded();
+        let (sender, receiver) = unbounded();
+        let exit = Arc::new(AtomicBool::new(false));
+        let serializers =
+            spawn_serializers(&config, 2, serialization_receiver, sender, exit.clone()).unwrap();
+
+        let request = build_test_log_transaction_request(1, 54);
+        // The same transaction under another signature, encoded by the writer.
+        let mut transaction_info = request.transaction_info.clone();
+        transaction_info.signature = vec![2u8; 64];
+        serialization_sender
+            .send(DbWork {
+                sequence: None,
+                item: DbWorkItem::LogTransaction(Box::new(request)),
+            })
+            .unwrap();
+        let request = match receiver.recv().unwrap().item {
+            DbWorkItem::LogTransaction(request) => request,
+            _ => panic!("Expected the transaction"),
+        };
+        assert!(request.encoded.is_some());
+        client.log_transaction(*request).unwrap();
+        client
+            .log_transaction(LogTransactionRequest {
+                transaction_info,
+                encoded: None,
+                account_writes: None,
+            })
+            .unwrap();
+        assert_eq!(
+            query_transaction(&mut db, &[1u8; 64]),
+            query_transaction(&mut db, &[2u8; 64])
+        );
+
+        exit.store(true, Ordering::Relaxed);
+        for serializer in serializers {
+            serializer.join().unwrap();
+        }
+    }
+}
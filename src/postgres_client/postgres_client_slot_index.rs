@@ -0,0 +1,129 @@
+/// Module responsible for creating the indexes on the slot column of the large, append-mostly
+/// tables when the plugin is loaded, when slot_index_type is set, for the queries by slot range.
+///
+/// The account_audit and transaction rows are inserted about in the slot order, so a BRIN
+/// index, keeping the slot range of each block range of the table, is a fraction of the size
+/// of a B-tree and prunes most of the table from a slot range scan. The slot leads the primary
+/// key of the transaction table, so only the account_audit table gets a B-tree index. The
+/// indexes are created when missing, blocking the writes to the table while they are built, and
+/// are not dropped when the type changes.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{
+        GeyserPluginPostgresConfig, GeyserPluginPostgresError, SlotIndexType,
+    },
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+/// The tables indexed on their slot, by their default names, and whether their primary key
+/// already leads with the slot.
+const SLOT_INDEXED_TABLES: [(&str, bool); 2] = [("account_audit", false), ("transaction", true)];
+
+impl SimplePostgresClient {
+    /// Build the statements creating the slot indexes of the configured type.
+    pub(crate) fn build_slot_index_statements(config: &GeyserPluginPostgresConfig) -> Vec<String> {
+        let method = match config.slot_index_type {
+            Some(SlotIndexType::Btree) => "btree",
+            Some(SlotIndexType::Brin) => "brin",
+            Some(SlotIndexType::None) | None => return Vec::default(),
+        };
+        SLOT_INDEXED_TABLES
+            .iter()
+            .filter(|(_, slot_keyed)| method == "brin" || !slot_keyed)
+            .map(|(table, _)| {
+                format!(
+                    "CREATE INDEX IF NOT EXISTS {table}_slot_{method} ON {} USING {method} (slot)",
+                    Self::table_name(&config.table_names, table),
+                    table = table,
+                    method = method
+                )
+            })
+            .collect()
+    }
+
+    /// Create the slot indexes when missing, on a short-lived connection.
+    pub fn ensure_slot_indexes(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let statements = Self::build_slot_index_statements(config);
+        if statements.is_empty() {
+            return Ok(());
+        }
+
+        let mut client = Self::connect_to_db(config)?;
+        for statement in statements {
+            if let Err(err) = client.batch_execute(&statement) {
+                let msg = format!(
+                    "Failed to create the slot index with {:?}. Error: {:?}",
+                    statement, err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )));
+            }
+            info!("{}", statement);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_build_slot_index_statements() {
+        let build_statements = |config: serde_json::Value| {
+            SimplePostgresClient::build_slot_index_statements(
+                &serde_json::from_value(config).unwrap(),
+            )
+        };
+        assert_eq!(
+            build_statements(json!({ "slot_index_type": "brin" })),
+            vec![
+                "CREATE INDEX IF NOT EXISTS account_audit_slot_brin ON account_audit USING brin (slot)",
+                "CREATE INDEX IF NOT EXISTS transaction_slot_brin ON transaction USING brin (slot)",
+            ]
+        );
+        assert_eq!(
+            build_statements(json!({
+                "slot_index_type": "btree",
+                "table_names": { "account_audit": "geyser.account_history" },
+            })),
+            vec![
+                "CREATE INDEX IF NOT EXISTS account_audit_slot_btree ON geyser.account_history USING btree (slot)",
+            ]
+        );
+        assert!(build_statements(json!({ "slot_index_type": "none" })).is_empty());
+        assert!(build_statements(json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_ensure_slot_indexes() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "slot_index_type": "brin" }));
+        SimplePostgresClient::ensure_slot_indexes(&config).unwrap();
+        // The indexes created are found on the next load.
+        SimplePostgresClient::ensure_slot_indexes(&config).unwrap();
+
+        let indexes: Vec<String> = db
+            .client
+            .query(
+                "SELECT indexdef FROM pg_indexes WHERE schemaname = current_schema() AND indexname LIKE '%_slot_brin' \
+                ORDER BY indexname",
+                &[],
+            )
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(indexes.len(), 2);
+        assert!(indexes[0].contains("account_audit USING brin (slot)"));
+        assert!(indexes[1].contains("transaction USING brin (slot)"));
+    }
+}
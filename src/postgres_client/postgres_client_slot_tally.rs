@@ -0,0 +1,165 @@
+/// Module responsible for tallying the transactions of each slot and writing the tallies
+/// to the slot table when the slot is rooted, when tally_slot_transactions is set.
+///
+/// The tallies are accumulated by the ParallelPostgresClient on the notifying thread, as the
+/// transactions of a slot are dispatched to all the workers. When the slot is rooted, its
+/// tally is removed and sent along with the slot status update, and the worker writes it
+/// after upserting the slot.
+use {
+    super::{
+        postgres_client_unprepared::PluginStatement, ParallelPostgresClient, SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+/// The number of transactions notified for a slot.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SlotTransactionTally {
+    pub num_successful: u64,
+    pub num_failed: u64,
+}
+
+impl SlotTransactionTally {
+    pub fn num_transactions(&self) -> u64 {
+        self.num_successful + self.num_failed
+    }
+}
+
+impl SimplePostgresClient {
+    pub fn build_slot_transaction_tally_update_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "UPDATE {} SET num_transactions=$2, \
+        num_successful=$3, num_failed=$4 WHERE slot=$1",
+            Self::table_name(&config.table_names, "slot")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Write the transaction tally of a rooted slot.
+    pub fn update_slot_transaction_tally(
+        &mut self,
+        slot: u64,
+        tally: &SlotTransactionTally,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.update_slot_transaction_tally_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+        let slot = slot as i64;
+        let num_transactions = tally.num_transactions() as i64;
+        let num_successful = tally.num_successful as i64;
+        let num_failed = tally.num_failed as i64;
+
+        let result = statement.execute(
+            &mut client.client,
+            &client.types,
+            &[&slot, &num_transactions, &num_successful, &num_failed],
+        );
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the slot transaction tally to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+
+        Ok(())
+    }
+}
+
+impl ParallelPostgresClient {
+    /// Take the tally of the rooted slot. The roots are notified in ascending order, so the
+    /// tallies of the older slots are of the abandoned forks and are dropped.
+    pub(crate) fn take_rooted_slot_tally(&mut self, slot: u64) -> Option<SlotTransactionTally> {
+        let tallies = self.slot_transaction_tallies.as_mut()?;
+        let tally = tallies.remove(&slot);
+        tallies.retain(|tally_slot, _| *tally_slot > slot);
+        tally
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            notification_sink::NotificationSink,
+            postgres_client::{tests::TestDatabase, PostgresClient},
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+    };
+
+    #[test]
+    fn test_update_slot_transaction_tally() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "tally_slot_transactions": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        client
+            .update_slot_status(7, Some(6), SlotStatus::Rooted)
+            .unwrap();
+        let tally = SlotTransactionTally {
+            num_successful: 3,
+            num_failed: 1,
+        };
+        client.update_slot_transaction_tally(7, &tally).unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT num_transactions, num_successful, num_failed FROM slot WHERE slot = 7",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), 4);
+        assert_eq!(row.get::<_, i64>(1), 3);
+        assert_eq!(row.get::<_, i64>(2), 1);
+    }
+
+    #[test]
+    fn test_take_rooted_slot_tally() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "tally_slot_transactions": true }));
+        let mut client = ParallelPostgresClient::new(&config).unwrap();
+
+        client.tally_transaction(5, true);
+        client.tally_transaction(6, true);
+        client.tally_transaction(6, false);
+        client.tally_transaction(8, true);
+
+        assert_eq!(
+            client.take_rooted_slot_tally(6),
+            Some(SlotTransactionTally {
+                num_successful: 1,
+                num_failed: 1,
+            })
+        );
+        // The tally of slot 5 was dropped as an abandoned fork.
+        assert_eq!(client.take_rooted_slot_tally(5), None);
+        assert_eq!(client.take_rooted_slot_tally(7), None);
+        assert_eq!(
+            client.take_rooted_slot_tally(8),
+            Some(SlotTransactionTally {
+                num_successful: 1,
+                num_failed: 0,
+            })
+        );
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+/// Module responsible for recording the plugin observed slot status transition times
+/// to the PostgreSQL database.
+use {
+    super::{postgres_client_unprepared::PluginStatement, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    chrono::naive::NaiveDateTime,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+};
+
+impl SimplePostgresClient {
+    pub fn build_slot_timing_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        // Only the first observation of each status is kept.
+        let stmt = format!(
+            "INSERT INTO {} AS timing (slot, processed_on, confirmed_on, rooted_on) \
+        VALUES ($1, $2, $3, $4) \
+        ON CONFLICT (slot) \
+        DO UPDATE SET processed_on=COALESCE(timing.processed_on, excluded.processed_on), \
+        confirmed_on=COALESCE(timing.confirmed_on, excluded.confirmed_on), \
+        rooted_on=COALESCE(timing.rooted_on, excluded.rooted_on)",
+            Self::table_name(&config.table_names, "slot_timing")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Record the time at which the plugin was notified of the slot status.
+    pub fn update_slot_timing(
+        &mut self,
+        slot: u64,
+        status: SlotStatus,
+        observed_on: NaiveDateTime,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        let statement = match &client.upsert_slot_timing_stmt {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+
+        let observed_on = Some(observed_on);
+        let (processed_on, confirmed_on, rooted_on) = match status {
+            SlotStatus::Processed => (observed_on, None, None),
+            SlotStatus::Confirmed => (None, observed_on, None),
+            SlotStatus::Rooted => (None, None, observed_on),
+        };
+        let slot = slot as i64;
+
+        let result = statement.execute(
+            &mut client.client,
+            &client.types,
+            &[&slot, &processed_on, &confirmed_on, &rooted_on],
+        );
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the slot timing to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::SlotStatusUpdateError { msg });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*, crate::postgres_client::tests::TestDatabase, chrono::Duration, serde_json::json,
+    };
+
+    #[test]
+    fn test_update_slot_timing() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "track_slot_timing": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let processed_on =
+            NaiveDateTime::parse_from_str("2022-04-15 05:20:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let confirmed_on = processed_on + Duration::milliseconds(400);
+        let rooted_on = processed_on + Duration::milliseconds(13_000);
+        client
+            .update_slot_timing(5, SlotStatus::Processed, processed_on)
+            .unwrap();
+        client
+            .update_slot_timing(5, SlotStatus::Confirmed, confirmed_on)
+            .unwrap();
+        // A later notification of the same status does not move the observed time.
+        client
+            .update_slot_timing(5, SlotStatus::Confirmed, rooted_on)
+            .unwrap();
+        client
+            .update_slot_timing(5, SlotStatus::Rooted, rooted_on)
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT confirmed_on, processed_to_confirmed_ms, confirmed_to_rooted_ms, \
+                processed_to_rooted_ms FROM slot_timing WHERE slot = 5",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, NaiveDateTime>(0), confirmed_on);
+        assert_eq!(row.get::<_, i64>(1), 400);
+        assert_eq!(row.get::<_, i64>(2), 12_600);
+        assert_eq!(row.get::<_, i64>(3), 13_000);
+    }
+
+    #[test]
+    fn test_slot_timing_disabled() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        let observed_on =
+            NaiveDateTime::parse_from_str("2022-04-15 05:20:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        client
+            .update_slot_timing(5, SlotStatus::Processed, observed_on)
+            .unwrap();
+
+        assert_eq!(db.count("slot_timing"), 0);
+    }
+}
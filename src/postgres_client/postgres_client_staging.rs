@@ -0,0 +1,630 @@
+/// Module responsible for the staging table mode, in which the account updates are written
+/// to per-slot staging tables and merged into the account table in the background, taking
+/// the contention of the upserts off the hot write path.
+///
+/// The staging tables are the partitions of the account_staging table, listed by the slot,
+/// and are created by the workers on the first write of each slot. The merger thread
+/// periodically merges the staging tables of the slots STAGING_MERGE_SLOT_LAG slots older
+/// than the newest staged slot into the account table and drops them. The writes to a slot
+/// are expected to be done by then. At shutdown, all the staging tables are merged.
+///
+/// A staging table is merged in a single transaction, holding its lock and writing its rows
+/// to the WAL at once. When staging_merge_chunk_rows is set, its rows are first moved to the
+/// account table in chunks, each deleting the rows it merges in its own transaction; the rows
+/// written meanwhile are merged by the final transaction, which drops the table.
+///
+/// When staging_unlogged is set, the staging tables are created UNLOGGED: their writes skip the
+/// WAL, and they are emptied by the crash recovery of the server.
+///
+/// The staging tables have the default columns of the account table, STAGING_COLUMNS, merged as
+/// they are. The options writing other account columns, account_columns and the derived
+/// columns such as data_len, search_text, account_type, rent_exempt, write_lag_ms and
+/// slot_status, are rejected in the staging table mode by AccountColumns::new. The merges are
+/// seen by the triggers of the account table as the upserts are, which maintain the owner counts
+/// and assign the ingestion_seq when their optional scripts are applied. The recent_account
+/// table, whose triggers are enabled by a setting of the workers' sessions, is not supported.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, StatementError, TypeRegistry},
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    postgres::{error::SqlState, Client},
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_measure::measure::Measure,
+    std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        thread::{self, sleep, Builder, JoinHandle},
+        time::Duration,
+    },
+};
+
+const STAGING_TABLE_PREFIX: &str = "account_staging_";
+/// The number of slots the staging table of a slot is kept after the newest staged slot.
+const STAGING_MERGE_SLOT_LAG: u64 = 32;
+const DEFAULT_STAGING_MERGE_INTERVAL_MS: u64 = 1000;
+/// The columns of the staging tables merged into the account tables, the default columns of the
+/// account table.
+const STAGING_COLUMNS: &str =
+    "pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on";
+/// The interval to check for the exit while waiting for the next merge.
+const STAGING_MERGE_EXIT_CHECK_MS: u64 = 100;
+
+impl SimplePostgresClient {
+    pub(crate) fn is_staging_table_mode(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.staging_table_mode, Some(true))
+    }
+
+    pub(crate) fn build_account_staging_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let data = Self::encode_account_data_sql(
+            Self::get_account_data_encoding(config),
+            Self::get_empty_data_as_null(config),
+            None,
+            7,
+        );
+        let stmt = format!("INSERT INTO {} (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on) \
+        VALUES ($1, $2, $3, $4, $5, $6, {}, $8, $9)", Self::table_name(&config.table_names, "account_staging"), data);
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Create the staging table of the slot unless this client already did.
+    pub(crate) fn ensure_staging_table(&mut self, slot: i64) -> Result<(), GeyserPluginError> {
+        let slot = slot as u64;
+        if self.staged_slots.contains(&slot) {
+            return Ok(());
+        }
+
+        let client = self.client.get_mut().unwrap();
+        let stmt = format!(
+            "CREATE {}TABLE IF NOT EXISTS {}{} PARTITION OF {} FOR VALUES IN ({})",
+            if self.staging_unlogged {
+                "UNLOGGED "
+            } else {
+                ""
+            },
+            STAGING_TABLE_PREFIX,
+            slot,
+            Self::table_name(&self.table_names, "account_staging"),
+            slot
+        );
+        if let Err(err) = client.client.batch_execute(&stmt) {
+            // The workers creating the same staging table concurrently can race past the
+            // existence check.
+            let created_concurrently = matches!(
+                err.code(),
+                Some(&SqlState::UNIQUE_VIOLATION) | Some(&SqlState::DUPLICATE_TABLE)
+            );
+            if !created_concurrently {
+                let msg = format!(
+                    "Failed to create the staging table of slot {}. Error: {:?}",
+                    slot, err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        // The staging tables of the older slots are merged and dropped in the background.
+        if let Some(oldest_slot) = slot.checked_sub(STAGING_MERGE_SLOT_LAG) {
+            self.staged_slots
+                .retain(|staged_slot| *staged_slot > oldest_slot);
+        }
+        self.staged_slots.insert(slot);
+        Ok(())
+    }
+
+    /// List the slots of the staging tables in ascending order.
+    fn get_staged_slots(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Vec<u64>, StatementError> {
+        let statement = PluginStatement::prepare(
+            client,
+            "SELECT c.relname::text FROM pg_inherits i JOIN pg_class c ON c.oid = i.inhrelid \
+            WHERE i.inhparent = $1::text::regclass",
+            Self::use_prepared_statements(config),
+        )?;
+        let rows = statement.query(
+            client,
+            types,
+            &[&Self::table_name(&config.table_names, "account_staging")],
+        )?;
+        let mut slots: Vec<u64> = rows
+            .iter()
+            .filter_map(|row| {
+                row.get::<_, String>(0)
+                    .strip_prefix(STAGING_TABLE_PREFIX)
+                    .and_then(|slot| slot.parse().ok())
+            })
+            .collect();
+        slots.sort_unstable();
+        Ok(slots)
+    }
+
+    /// Build the statement upserting the latest update of each account of the source into the
+    /// account table, returning the updates applied when storing the historical data.
+    fn build_staging_upsert_sql(source: &str, config: &GeyserPluginPostgresConfig) -> String {
+        format!(
+            "INSERT INTO {account} AS acct ({columns}) \
+            SELECT DISTINCT ON (pubkey) {columns} FROM {source} ORDER BY pubkey, slot DESC, write_version DESC \
+            ON CONFLICT (pubkey) DO UPDATE SET slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, \
+            executable=excluded.executable, rent_epoch=excluded.rent_epoch, data=excluded.data, \
+            write_version=excluded.write_version, updated_on=excluded.updated_on{pre_lamports} \
+            WHERE acct.slot < excluded.slot OR (acct.slot = excluded.slot AND acct.write_version < excluded.write_version){returning}",
+            account = Self::table_name(&config.table_names, "account"),
+            columns = STAGING_COLUMNS,
+            source = source,
+            pre_lamports = Self::pre_lamports_update_sql(Self::get_track_lamport_deltas(config)),
+            returning = if Self::stores_staged_history(config) {
+                " RETURNING acct.pubkey, acct.write_version"
+            } else {
+                ""
+            },
+        )
+    }
+
+    /// Build the statement inserting the updates of the source not applied by the upsert,
+    /// returned by the `applied` query, into the account_audit table.
+    fn build_staging_audit_insert_sql(source: &str, config: &GeyserPluginPostgresConfig) -> String {
+        format!(
+            "INSERT INTO {account_audit} ({columns}) SELECT {columns} FROM {source} staged \
+            WHERE NOT EXISTS (SELECT 1 FROM applied \
+            WHERE applied.pubkey = staged.pubkey AND applied.write_version = staged.write_version)",
+            account_audit = Self::table_name(&config.table_names, "account_audit"),
+            columns = STAGING_COLUMNS,
+            source = source,
+        )
+    }
+
+    fn stores_staged_history(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.store_account_historical_data, Some(true))
+    }
+
+    /// Build the statement merging a staging table into the account table. The latest update
+    /// of each account in the staging table is upserted. When storing the historical data,
+    /// the other updates, and the latest ones not applied because the account was updated at
+    /// a newer slot, are inserted directly into the account_audit table.
+    fn build_staging_merge_sql(table: &str, config: &GeyserPluginPostgresConfig) -> String {
+        let upsert = Self::build_staging_upsert_sql(table, config);
+        if !Self::stores_staged_history(config) {
+            return upsert;
+        }
+        format!(
+            "WITH applied AS ({}) {}",
+            upsert,
+            Self::build_staging_audit_insert_sql(table, config)
+        )
+    }
+
+    /// Build the statement merging a chunk of the rows of a staging table, deleted from it as
+    /// they are merged. Returns the number of the rows merged.
+    fn build_staging_chunk_merge_sql(
+        table: &str,
+        chunk_rows: u64,
+        config: &GeyserPluginPostgresConfig,
+    ) -> String {
+        let mut queries = vec![
+            format!(
+                "moved AS (DELETE FROM {table} WHERE ctid IN (SELECT ctid FROM {table} LIMIT {chunk_rows}) \
+                RETURNING {columns})",
+                table = table,
+                chunk_rows = chunk_rows,
+                columns = STAGING_COLUMNS,
+            ),
+            format!(
+                "applied AS ({})",
+                Self::build_staging_upsert_sql("moved", config)
+            ),
+        ];
+        if Self::stores_staged_history(config) {
+            queries.push(format!(
+                "audited AS ({})",
+                Self::build_staging_audit_insert_sql("moved", config)
+            ));
+        }
+        format!("WITH {} SELECT count(*) FROM moved", queries.join(", "))
+    }
+
+    /// Merge the staging table in chunks of chunk_rows rows, each committed on its own, until
+    /// a chunk is not full.
+    fn merge_staging_table_chunks(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        table: &str,
+        chunk_rows: u64,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), StatementError> {
+        let stmt = Self::build_staging_chunk_merge_sql(table, chunk_rows, config);
+        let statement =
+            PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config))?;
+        let mut merged_rows = 0;
+        loop {
+            let chunk_merged_rows: i64 = statement
+                .query(client, types, &[])?
+                .first()
+                .map_or(0, |row| row.get(0));
+            merged_rows += chunk_merged_rows;
+            info!(
+                "Merged {} rows of the staging table {}, {} so far",
+                chunk_merged_rows, table, merged_rows
+            );
+            if (chunk_merged_rows as u64) < chunk_rows {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Merge the staging tables into the account table and drop them. Unless `merge_all` is
+    /// set, the staging tables of the recent slots are kept. Returns the number of staging
+    /// tables merged.
+    pub(crate) fn merge_staging_tables(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        config: &GeyserPluginPostgresConfig,
+        merge_all: bool,
+    ) -> Result<usize, GeyserPluginError> {
+        let to_error = |err: StatementError| {
+            let msg = format!(
+                "Failed to merge the staging tables into the account table. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::AccountsUpdateError { msg }
+        };
+
+        let slots = Self::get_staged_slots(client, types, config).map_err(to_error)?;
+        let newest_slot = match slots.last() {
+            Some(newest_slot) => *newest_slot,
+            None => return Ok(0),
+        };
+
+        let mut merged_count = 0;
+        for slot in slots {
+            if !merge_all && slot + STAGING_MERGE_SLOT_LAG > newest_slot {
+                break;
+            }
+            let mut measure = Measure::start("geyser-plugin-postgres-merge-staging-table");
+            let table = format!("{}{}", STAGING_TABLE_PREFIX, slot);
+            if let Some(chunk_rows) = config.staging_merge_chunk_rows {
+                Self::merge_staging_table_chunks(client, types, &table, chunk_rows, config)
+                    .map_err(to_error)?;
+            }
+            // The lock blocks the late writes to the staging table until it is dropped, so
+            // that none of them is lost.
+            let stmt = format!(
+                "BEGIN; LOCK TABLE {table} IN SHARE ROW EXCLUSIVE MODE; {merge}; DROP TABLE {table}; COMMIT;",
+                table = table,
+                merge = Self::build_staging_merge_sql(&table, config),
+            );
+            if let Err(err) = client.batch_execute(&stmt) {
+                let _ = client.batch_execute("ROLLBACK");
+                return Err(to_error(err.into()));
+            }
+            measure.stop();
+            debug!(
+                "Merged the staging table {} in {}us",
+                table,
+                measure.as_us()
+            );
+            merged_count += 1;
+        }
+        Ok(merged_count)
+    }
+}
+
+/// The background thread merging the staging tables into the account table.
+pub(crate) struct StagingMerger {
+    exit: Arc<AtomicBool>,
+    merger: Option<JoinHandle<()>>,
+}
+
+impl StagingMerger {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        if matches!(config.maintain_recent_account_table, Some(true)) {
+            let msg =
+                "\"maintain_recent_account_table\" is not supported in the staging table mode"
+                    .to_string();
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let types = Mutex::default();
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_clone = exit.clone();
+        let config = config.clone();
+        let merge_interval_ms = config
+            .staging_merge_interval_ms
+            .unwrap_or(DEFAULT_STAGING_MERGE_INTERVAL_MS);
+
+        let merger = Builder::new()
+            .name("staging-merger".to_string())
+            .spawn(move || {
+                let mut waited_ms = 0;
+                while !exit_clone.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(STAGING_MERGE_EXIT_CHECK_MS));
+                    waited_ms += STAGING_MERGE_EXIT_CHECK_MS;
+                    if waited_ms < merge_interval_ms {
+                        continue;
+                    }
+                    waited_ms = 0;
+                    // The errors are logged, the staging tables are merged on the next try.
+                    let _ = SimplePostgresClient::merge_staging_tables(
+                        &mut client,
+                        &types,
+                        &config,
+                        false,
+                    );
+                }
+
+                match SimplePostgresClient::merge_staging_tables(&mut client, &types, &config, true)
+                {
+                    Ok(merged_count) => {
+                        info!("Merged the {} remaining staging tables", merged_count)
+                    }
+                    Err(err) => error!("Failed to merge the remaining staging tables: {}", err),
+                }
+            })
+            .unwrap();
+
+        Ok(Self {
+            exit,
+            merger: Some(merger),
+        })
+    }
+
+    /// Stop the merger after merging all the staging tables. The writers to the staging
+    /// tables must have stopped.
+    pub fn join(&mut self) -> thread::Result<()> {
+        self.exit.store(true, Ordering::Relaxed);
+        match self.merger.take() {
+            Some(merger) => merger.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            DbWriteVersion, PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_staging_table_mode() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "staging_table_mode": true,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 10);
+        for (slot, write_version) in [(10, 1), (10, 2), (11, 3)] {
+            account.slot = slot;
+            account.write_version = DbWriteVersion(write_version);
+            client.update_account(account.clone(), false).unwrap();
+        }
+        let newest_slot = 10 + STAGING_MERGE_SLOT_LAG;
+        account.slot = newest_slot as i64;
+        account.write_version = DbWriteVersion(4);
+        client.update_account(account, false).unwrap();
+        assert_eq!(db.count("account"), 0);
+        assert_eq!(db.count("account_staging"), 4);
+
+        // Only the staging tables of the slots old enough are merged.
+        assert_eq!(
+            SimplePostgresClient::merge_staging_tables(
+                &mut db.client,
+                &Mutex::default(),
+                &config,
+                false
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(
+            SimplePostgresClient::get_staged_slots(&mut db.client, &Mutex::default(), &config)
+                .unwrap(),
+            vec![11, newest_slot]
+        );
+        let write_version: i64 = db
+            .client
+            .query_one("SELECT write_version FROM account", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(write_version, 2);
+        assert_eq!(db.count("account_audit"), 1);
+
+        assert_eq!(
+            SimplePostgresClient::merge_staging_tables(
+                &mut db.client,
+                &Mutex::default(),
+                &config,
+                true
+            )
+            .unwrap(),
+            2
+        );
+        let write_version: i64 = db
+            .client
+            .query_one("SELECT write_version FROM account", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(write_version, 4);
+        // The overwritten updates are audited by the trigger.
+        assert_eq!(db.count("account_audit"), 3);
+        assert_eq!(db.count("account_staging"), 0);
+    }
+
+    #[test]
+    fn test_staging_unlogged() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut relpersistence = |staging_unlogged: bool, slot: i64| -> i8 {
+            let config = db.config(json!({
+                "staging_table_mode": true,
+                "staging_unlogged": staging_unlogged,
+            }));
+            let mut client = SimplePostgresClient::new(&config).unwrap();
+            client
+                .update_account(build_account(&Pubkey::new_unique(), vec![], slot), false)
+                .unwrap();
+            db.client
+                .query_one(
+                    "SELECT relpersistence FROM pg_class WHERE relname = $1",
+                    &[&format!("{}{}", STAGING_TABLE_PREFIX, slot)],
+                )
+                .unwrap()
+                .get(0)
+        };
+        assert_eq!(relpersistence(false, 1), b'p' as i8);
+        assert_eq!(relpersistence(true, 2), b'u' as i8);
+    }
+
+    #[test]
+    fn test_staging_merge_chunks() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "staging_table_mode": true,
+            "store_account_historical_data": true,
+            "staging_merge_chunk_rows": 2,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let accounts: Vec<_> = (0..3).map(|_| build_account(&owner, vec![], 10)).collect();
+        for write_version in [1, 2] {
+            for account in &accounts {
+                let mut account = account.clone();
+                account.write_version = DbWriteVersion(write_version);
+                client.update_account(account, false).unwrap();
+            }
+        }
+        assert_eq!(
+            SimplePostgresClient::merge_staging_tables(
+                &mut db.client,
+                &Mutex::default(),
+                &config,
+                true
+            )
+            .unwrap(),
+            1
+        );
+        let write_versions: Vec<i64> = db
+            .client
+            .query("SELECT write_version FROM account", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(write_versions, vec![2; 3]);
+        assert_eq!(db.count("account_audit"), 3);
+        assert_eq!(db.count("account_staging"), 0);
+    }
+
+    #[test]
+    fn test_staging_unsupported_options() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        // The options writing other columns than those of the staging tables.
+        for overrides in [
+            json!({ "account_columns": ["pubkey", "slot", "write_version", "lamports"] }),
+            json!({ "store_data_prefix_len": 8 }),
+            json!({ "extract_text_search": true }),
+            json!({ "record_write_lag": true }),
+            json!({ "account_data_chunk_threshold": 8 }),
+            json!({ "record_account_slot_status": true }),
+            json!({ "classify_account_type": true }),
+            json!({ "compute_rent_exempt": true }),
+        ] {
+            let mut config = overrides.clone();
+            config["staging_table_mode"] = json!(true);
+            assert!(
+                SimplePostgresClient::new(&db.config(config)).is_err(),
+                "{} is accepted in the staging table mode",
+                overrides
+            );
+        }
+        let config = db.config(json!({
+            "staging_table_mode": true,
+            "maintain_recent_account_table": true,
+        }));
+        assert!(StagingMerger::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_staging_merge_triggers() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute(include_str!("../../scripts/create_owner_counts.sql"))
+            .unwrap();
+        db.client
+            .batch_execute(include_str!("../../scripts/create_ingestion_seq.sql"))
+            .unwrap();
+        let config = db.config(json!({
+            "staging_table_mode": true,
+            "maintain_owner_counts": true,
+            "record_ingestion_seq": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        for _ in 0..2 {
+            client
+                .update_account(build_account(&owner, vec![], 10), false)
+                .unwrap();
+        }
+        SimplePostgresClient::merge_staging_tables(
+            &mut db.client,
+            &Mutex::default(),
+            &config,
+            true,
+        )
+        .unwrap();
+        let account_count: i64 = db
+            .client
+            .query_one(
+                "SELECT account_count FROM account_count_by_owner WHERE owner = $1",
+                &[&owner.as_ref()],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(account_count, 2);
+        let seq_count: i64 = db
+            .client
+            .query_one("SELECT count(seq) FROM account", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(seq_count, 2);
+    }
+}
@@ -0,0 +1,99 @@
+/// Module responsible for maintaining the stake account index in the PostgreSQL database.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        DbAccountInfo, ReadableAccountInfo, SimplePostgresClient,
+    },
+    crate::{geyser_plugin_postgres::GeyserPluginPostgresConfig, inline_stake_program},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::sync::Mutex,
+};
+
+impl SimplePostgresClient {
+    pub fn build_stake_account_index_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS stake_index (stake_pubkey, withdrawer, staker, voter, \
+        delegated_stake, activation_epoch, slot) \
+        VALUES ($1, $2, $3, $4, $5, $6, $7) \
+        ON CONFLICT (stake_pubkey) \
+        DO UPDATE SET withdrawer=excluded.withdrawer, staker=excluded.staker, voter=excluded.voter, \
+        delegated_stake=excluded.delegated_stake, activation_epoch=excluded.activation_epoch, \
+        slot=excluded.slot \
+        WHERE stake_index.slot <= excluded.slot",
+            Self::table_name(&config.table_names, "stake_account_index")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Function for updating a single stake account index entry.
+    pub fn update_stake_account_index(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if account.owner() != inline_stake_program::id().as_ref() {
+            return Ok(());
+        }
+
+        if let Some(stake_account) = inline_stake_program::Account::unpack_account(account.data()) {
+            let withdrawer = stake_account.withdrawer.as_ref().to_vec();
+            let staker = stake_account.staker.as_ref().to_vec();
+            let delegation = stake_account.delegation.as_ref();
+            let voter = delegation.map(|delegation| delegation.voter.as_ref().to_vec());
+            let delegated_stake = delegation.map(|delegation| delegation.delegated_stake as i64);
+            let activation_epoch = delegation.map(|delegation| delegation.activation_epoch as i64);
+            let pubkey = account.pubkey();
+            let slot = account.slot;
+            let result = statement.execute(
+                client,
+                types,
+                &[
+                    &pubkey,
+                    &withdrawer,
+                    &staker,
+                    &voter,
+                    &delegated_stake,
+                    &activation_epoch,
+                    &slot,
+                ],
+            );
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to update the stake account index to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the stake account index for an account queued for the bulk insert.
+    /// The stake accounts are mostly written at the epoch boundaries, when the rewards are paid
+    /// in a burst, so the upsert is kept per account, current with the account update, rather
+    /// than buffered into a batch lagging behind it.
+    pub fn upsert_stake_account_index(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        match &client.upsert_stake_account_index_stmt {
+            Some(statement) => Self::update_stake_account_index(
+                &mut client.client,
+                &client.types,
+                statement,
+                account,
+            ),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/// Module responsible for skipping the account_audit writes while the accounts are loaded
+/// from the snapshot at startup, which are not real history.
+///
+/// The audit rows are written by the plugin for the updates at older slots, and by the
+/// account_update_trigger for the overwritten rows. The trigger skips the connections which
+/// set the geyser_plugin.skip_account_audit session setting, which the plugin sets until the
+/// end of startup.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+};
+
+impl SimplePostgresClient {
+    /// Whether the historical data is stored after the startup only.
+    pub(crate) fn skip_account_audit_during_startup(config: &GeyserPluginPostgresConfig) -> bool {
+        matches!(config.store_account_historical_data, Some(true))
+            && matches!(config.store_historical_data_during_startup, Some(false))
+    }
+
+    /// Set whether the account_update_trigger skips the account updates of the session.
+    pub(crate) fn set_skip_account_audit(
+        client: &mut Client,
+        skip: bool,
+    ) -> Result<(), GeyserPluginError> {
+        let stmt = format!(
+            "SET geyser_plugin.skip_account_audit = {}",
+            if skip { "on" } else { "off" }
+        );
+        if let Err(err) = client.batch_execute(&stmt) {
+            let msg = format!(
+                "Failed to set geyser_plugin.skip_account_audit to {}. Error: {:?}",
+                skip, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataStoreConnectionError { msg },
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resume storing the historical data at the end of startup, after the buffered startup
+    /// writes are flushed.
+    pub(crate) fn resume_account_audit(&mut self) -> Result<(), GeyserPluginError> {
+        if !self.skip_account_audit {
+            return Ok(());
+        }
+        let client = self.client.get_mut().unwrap();
+        Self::set_skip_account_audit(&mut client.client, false)?;
+        self.skip_account_audit = false;
+        info!("Resumed storing the historical account data at the end of startup");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_skip_account_audit_during_startup() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "batch_size": 1,
+            "store_account_historical_data": true,
+            "store_historical_data_during_startup": false,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let mut account = build_account(&Pubkey::new_unique(), vec![], 10);
+        for slot in [10, 11] {
+            account.slot = slot;
+            client.update_account(account.clone(), true).unwrap();
+        }
+        client.notify_end_of_startup().unwrap();
+        assert_eq!(db.count("account_audit"), 0);
+
+        account.slot = 12;
+        client.update_account(account.clone(), false).unwrap();
+        // An update at an older slot is audited directly.
+        account.slot = 9;
+        client.update_account(account, false).unwrap();
+        assert_eq!(db.count("account_audit"), 2);
+    }
+}
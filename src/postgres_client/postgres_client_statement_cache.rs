@@ -0,0 +1,135 @@
+/// Module responsible for caching the prepared statements built at runtime, such as the
+/// statements for the custom tables.
+use {
+    super::{postgres_client_unprepared::PluginStatement, SimplePostgresClient},
+    crate::geyser_plugin_postgres::GeyserPluginPostgresError,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::collections::HashMap,
+};
+
+/// A bounded cache of the prepared statements keyed by their SQL text. When full, the least
+/// recently used statement is evicted. The statement is deallocated on the server once the
+/// last handle to it is dropped.
+pub(crate) struct PreparedStatementCache {
+    capacity: usize,
+    /// Whether the statements are prepared on the server, otherwise only their SQL is kept.
+    use_prepared_statements: bool,
+    /// The statement and the tick at which it was last used.
+    statements: HashMap<String, (PluginStatement, u64)>,
+    tick: u64,
+}
+
+impl PreparedStatementCache {
+    pub fn new(capacity: usize, use_prepared_statements: bool) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            use_prepared_statements,
+            statements: HashMap::default(),
+            tick: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the cached statement for the SQL, preparing and caching it if it is not cached.
+    pub fn get_or_prepare(
+        &mut self,
+        client: &mut Client,
+        sql: &str,
+    ) -> Result<PluginStatement, postgres::Error> {
+        self.tick += 1;
+        if let Some((statement, last_used)) = self.statements.get_mut(sql) {
+            *last_used = self.tick;
+            return Ok(statement.clone());
+        }
+
+        let statement = PluginStatement::prepare(client, sql, self.use_prepared_statements)?;
+        if self.statements.len() >= self.capacity {
+            self.evict_least_recently_used();
+        }
+        self.statements
+            .insert(sql.to_string(), (statement.clone(), self.tick));
+        Ok(statement)
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let lru = self
+            .statements
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(sql, _)| sql.clone());
+        if let Some(sql) = lru {
+            debug!("Evicting the prepared statement: {}", sql);
+            self.statements.remove(&sql);
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Get the prepared statement for the SQL from the bounded statement cache.
+    pub fn prepare_cached_statement(
+        &mut self,
+        sql: &str,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        client
+            .statement_cache
+            .get_or_prepare(&mut client.client, sql)
+            .map_err(|err| {
+                let msg = format!(
+                    "Error in preparing for the statement {} for PostgreSQL database: {}",
+                    sql, err
+                );
+                error!("{}", msg);
+                GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                    msg,
+                }))
+            })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_prepared_statement_cache_eviction() {
+        let db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "max_prepared_statements": 2 }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let first = "SELECT slot FROM slot WHERE slot = $1";
+        let second = "SELECT slot FROM block WHERE slot = $1";
+        let third = "SELECT slot FROM account WHERE slot = $1";
+        client.prepare_cached_statement(first).unwrap();
+        client.prepare_cached_statement(second).unwrap();
+        // Touch the first statement so the second one is the least recently used.
+        client.prepare_cached_statement(first).unwrap();
+        client.prepare_cached_statement(third).unwrap();
+
+        let wrapper = client.client.get_mut().unwrap();
+        let cache = &wrapper.statement_cache;
+        assert_eq!(cache.statements.len(), 2);
+        assert!(cache.statements.contains_key(first));
+        assert!(!cache.statements.contains_key(second));
+        assert!(cache.statements.contains_key(third));
+
+        // The evicted statement is deallocated on the server.
+        let evicted: i64 = wrapper
+            .client
+            .query_one(
+                "SELECT count(*) FROM pg_prepared_statements WHERE statement = $1",
+                &[&second],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(evicted, 0);
+    }
+}
@@ -0,0 +1,188 @@
+/// Module responsible for guarding the statement builders against the accidental changes across
+/// the versions, when record_statement_checksums is set: the SQL text of the statements
+/// prepared by a worker when it connects is hashed into the statement_checksums table, and a
+/// statement whose hash differs from the one last recorded for the same configuration is
+/// warned about, so the operators notice the change of behavior.
+///
+/// The statements are read back from the pg_prepared_statements view of the session, in the
+/// order they were prepared, so each builder needs no change. They are named by their verb and
+/// their table, such as "INSERT account", numbered from the second statement of the same name
+/// on, such as "INSERT account #2". The statements prepared later at runtime, such as those
+/// of the custom tables, are not covered. The first worker records them for the plugin.
+use {
+    super::{postgres_client_plugin_metadata::PLUGIN_VERSION, SimplePostgresClient},
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    chrono::Utc,
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::hash::hash,
+    std::collections::HashMap,
+};
+
+/// The name of the statement, its verb and its table.
+fn statement_name(sql: &str) -> String {
+    let words: Vec<&str> = sql.split_whitespace().collect();
+    let verb = words.first().copied().unwrap_or_default().to_uppercase();
+    let table_after = |keyword: &str| {
+        words
+            .iter()
+            .position(|word| word.eq_ignore_ascii_case(keyword))
+            .and_then(|position| words.get(position + 1))
+    };
+    let table = match verb.as_str() {
+        "INSERT" => table_after("INTO"),
+        "UPDATE" => words.get(1),
+        _ => table_after("FROM"),
+    };
+    match table {
+        Some(table) => format!("{} {}", verb, table.trim_end_matches('(')),
+        None => verb,
+    }
+}
+
+/// The statements by their names, each with the hash of its SQL text, in base58.
+fn statement_checksums(statements: &[String]) -> Vec<(String, String)> {
+    let mut name_counts: HashMap<String, usize> = HashMap::default();
+    statements
+        .iter()
+        .map(|sql| {
+            let name = statement_name(sql);
+            let count = name_counts.entry(name.clone()).or_default();
+            *count += 1;
+            let name = if *count > 1 {
+                format!("{} #{}", name, count)
+            } else {
+                name
+            };
+            (name, hash(sql.as_bytes()).to_string())
+        })
+        .collect()
+}
+
+impl SimplePostgresClient {
+    /// Record the checksums of the statements prepared by the client, warning about those
+    /// changed since they were last recorded for the configuration. Returns the names of the
+    /// statements changed.
+    pub(crate) fn check_statement_checksums(
+        &mut self,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<Vec<String>, GeyserPluginError> {
+        let table = Self::table_name(&config.table_names, "statement_checksums");
+        let config_hash = Self::config_hash(config);
+        let client = &mut self.client.get_mut().unwrap().client;
+        let result = client
+            .query(
+                "SELECT statement FROM pg_prepared_statements \
+                ORDER BY prepare_time, length(name), name",
+                &[],
+            )
+            .and_then(|rows| {
+                let statements: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+                let recorded: HashMap<String, (String, String)> = client
+                    .query(
+                        &format!(
+                            "SELECT DISTINCT ON (statement_name) statement_name, checksum, plugin_version \
+                            FROM {} WHERE config_hash = $1 ORDER BY statement_name, recorded_on DESC",
+                            table
+                        ),
+                        &[&config_hash],
+                    )?
+                    .iter()
+                    .map(|row| (row.get(0), (row.get(1), row.get(2))))
+                    .collect();
+
+                let stmt = format!(
+                    "INSERT INTO {} (config_hash, statement_name, plugin_version, checksum, recorded_on) \
+                    VALUES ($1, $2, $3, $4, $5) \
+                    ON CONFLICT (config_hash, statement_name, plugin_version) \
+                    DO UPDATE SET checksum=excluded.checksum, recorded_on=excluded.recorded_on",
+                    table
+                );
+                let recorded_on = Utc::now().naive_utc();
+                let mut changed = Vec::new();
+                for (name, checksum) in statement_checksums(&statements) {
+                    if let Some((recorded_checksum, plugin_version)) = recorded.get(&name) {
+                        if *recorded_checksum != checksum {
+                            warn!(
+                                "The SQL of the statement \"{}\" changed since it was recorded by the plugin version {}",
+                                name, plugin_version
+                            );
+                            changed.push(name.clone());
+                        }
+                    }
+                    client.execute(
+                        &stmt,
+                        &[&config_hash, &name, &PLUGIN_VERSION, &checksum, &recorded_on],
+                    )?;
+                }
+                info!(
+                    "Recorded the checksums of {} statements, {} changed",
+                    statements.len(),
+                    changed.len()
+                );
+                Ok(changed)
+            });
+        result.map_err(|err| {
+            let msg = format!(
+                "Failed to record the statement checksums to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError { msg }))
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {super::*, crate::postgres_client::tests::TestDatabase, serde_json::json};
+
+    #[test]
+    fn test_statement_checksums() {
+        assert_eq!(
+            statement_checksums(&[
+                "INSERT INTO account AS acct (pubkey) VALUES ($1)".to_string(),
+                "INSERT INTO account AS acct (pubkey) VALUES ($1), ($2)".to_string(),
+                "UPDATE slot SET status = $2 WHERE slot = $1".to_string(),
+                "DELETE FROM account_audit WHERE slot < $1".to_string(),
+            ])
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+            vec![
+                "INSERT account",
+                "INSERT account #2",
+                "UPDATE slot",
+                "DELETE account_audit"
+            ]
+        );
+
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "record_statement_checksums": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        assert!(client
+            .check_statement_checksums(&config)
+            .unwrap()
+            .is_empty());
+        let recorded = db.count("statement_checksums");
+        assert!(recorded > 0);
+
+        // A statement changed since the last load is reported.
+        db.client
+            .execute(
+                "UPDATE statement_checksums SET checksum = 'changed' \
+                WHERE statement_name = 'INSERT account'",
+                &[],
+            )
+            .unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        assert_eq!(
+            client.check_statement_checksums(&config).unwrap(),
+            vec!["INSERT account".to_string()]
+        );
+        assert_eq!(db.count("statement_checksums"), recorded);
+    }
+}
@@ -0,0 +1,154 @@
+/// Module responsible for the names of the tables in the statements, overridden individually by
+/// table_names, such as {"account": "sol_account"}, for the schemas with their own naming
+/// conventions.
+///
+/// The tables are named by their default names, those of create_schema.sql, in table_names. The
+/// names must be plain identifiers, optionally qualified by their schema such as
+/// "geyser.account", and are not quoted, so they are folded to the lower case. The triggers,
+/// views and functions of the schema referring to the tables are not renamed by the plugin.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+    log::*,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::collections::BTreeMap,
+};
+
+/// The tables written or read by the statements, by their default names.
+const TABLES: [&str; 27] = [
+    "account",
+    "account_audit",
+    "account_close",
+    "account_data_chunk",
+    "account_staging",
+    "batch_log",
+    "block",
+    "checkpoint",
+    "config_changelog",
+    "heartbeat",
+    "metaplex_metadata_index",
+    "plugin_config_audit",
+    "plugin_metadata",
+    "program_link",
+    "recent_account",
+    "slot",
+    "slot_timing",
+    "spl_token_delegate_index",
+    "spl_token_mint_index",
+    "spl_token_owner_index",
+    "spl_token_state_index",
+    "stake_account_index",
+    "statement_checksums",
+    "transaction",
+    "transaction_account_write",
+    "transaction_instruction",
+    "transaction_signatures",
+];
+
+/// Whether the name is a plain identifier, optionally qualified by its schema.
+pub(crate) fn is_valid_table_name(name: &str) -> bool {
+    let parts: Vec<&str> = name.split('.').collect();
+    parts.len() <= 2
+        && parts.iter().all(|part| {
+            matches!(part.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+impl SimplePostgresClient {
+    /// The name of the table in the statements, the configured one or its default name.
+    pub(crate) fn table_name<'a>(
+        table_names: &'a Option<BTreeMap<String, String>>,
+        table: &'a str,
+    ) -> &'a str {
+        table_names
+            .as_ref()
+            .and_then(|table_names| table_names.get(table))
+            .map_or(table, String::as_str)
+    }
+
+    /// Check the table_names name known tables with valid names.
+    pub fn check_table_names(config: &GeyserPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+        for (table, name) in config.table_names.iter().flatten() {
+            let msg = if !TABLES.contains(&table.as_str()) {
+                format!(
+                    "Unknown table {:?} in \"table_names\", the known tables are {:?}",
+                    table, TABLES
+                )
+            } else if !is_valid_table_name(name) {
+                format!(
+                    "The name {:?} of the table {:?} in \"table_names\" is not a plain identifier",
+                    name, table
+                )
+            } else {
+                continue;
+            };
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::ConfigurationError { msg },
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_check_table_names() {
+        let check = |table_names: serde_json::Value| {
+            let config = serde_json::from_value(json!({ "table_names": table_names })).unwrap();
+            SimplePostgresClient::check_table_names(&config)
+        };
+        check(json!({ "account": "sol_account", "slot": "geyser.sol_slot" })).unwrap();
+        assert!(check(json!({ "accounts": "sol_account" })).is_err());
+        assert!(check(json!({ "account": "account; DROP TABLE slot" })).is_err());
+        assert!(check(json!({ "account": "a.b.c" })).is_err());
+        assert!(check(json!({ "account": "1account" })).is_err());
+    }
+
+    #[test]
+    fn test_write_renamed_tables() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute(
+                "ALTER TABLE account RENAME TO sol_account; ALTER TABLE slot RENAME TO sol_slot",
+            )
+            .unwrap();
+        let config = db.config(json!({
+            "table_names": { "account": "sol_account", "slot": "sol_slot" },
+        }));
+        SimplePostgresClient::check_schema_columns(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        for slot in 1..=3 {
+            client
+                .update_account(build_account(&owner, vec![], slot), true)
+                .unwrap();
+        }
+        client.notify_end_of_startup().unwrap();
+        client
+            .update_account(build_account(&owner, vec![], 4), false)
+            .unwrap();
+        client
+            .update_slot_status(4, Some(3), SlotStatus::Rooted)
+            .unwrap();
+        assert_eq!(db.count("sol_account"), 4);
+        assert_eq!(db.count("sol_slot"), 4);
+    }
+}
@@ -0,0 +1,143 @@
+/// Module responsible for extracting the text of the account data into the search_text column,
+/// a tsvector for the full-text search of the accounts holding UTF-8 strings, such as the NFT
+/// metadata, when extract_text_search is set.
+///
+/// The strings are extracted like strings(1) does: the data is decoded as UTF-8, the invalid
+/// bytes and the control characters, such as the zero padding and the length prefixes, split it
+/// into runs, and the runs of at least MIN_SEARCH_TEXT_RUN_LEN characters are kept, joined by
+/// spaces. The text is turned into the tsvector by the server with the 'simple' configuration,
+/// which lowercases the words without stemming them, whatever their language. The accounts with
+/// more data than text_search_max_data_len, or no string, get a NULL search_text.
+use std::str;
+
+/// The minimum number of the characters of a string extracted, shorter runs are mostly bytes of
+/// binary fields which happen to be printable.
+const MIN_SEARCH_TEXT_RUN_LEN: usize = 4;
+
+pub(crate) const DEFAULT_TEXT_SEARCH_MAX_DATA_LEN: usize = 10 * 1024;
+
+/// The value of the search_text column bound to the parameter.
+pub(crate) fn search_text_sql(param: usize) -> String {
+    format!("to_tsvector('simple', ${})", param)
+}
+
+/// Push the runs of the valid UTF-8 text long enough to the extracted text.
+fn push_runs(text: &str, extracted: &mut String) {
+    for run in text.split(char::is_control) {
+        if run.chars().count() >= MIN_SEARCH_TEXT_RUN_LEN {
+            if !extracted.is_empty() {
+                extracted.push(' ');
+            }
+            extracted.push_str(run);
+        }
+    }
+}
+
+/// Extract the strings of the account data, None for the data larger than max_data_len or
+/// holding no string.
+pub(crate) fn extract_search_text(data: &[u8], max_data_len: usize) -> Option<String> {
+    if data.len() > max_data_len {
+        return None;
+    }
+    let mut extracted = String::new();
+    let mut remaining = data;
+    loop {
+        match str::from_utf8(remaining) {
+            Ok(text) => {
+                push_runs(text, &mut extracted);
+                break;
+            }
+            Err(err) => {
+                let (valid, rest) = remaining.split_at(err.valid_up_to());
+                push_runs(str::from_utf8(valid).unwrap(), &mut extracted);
+                // The invalid sequence ends the run, an incomplete one ends the data.
+                match err.error_len() {
+                    Some(error_len) => remaining = &rest[error_len..],
+                    None => break,
+                }
+            }
+        }
+    }
+    (!extracted.is_empty()).then(|| extracted)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient, SimplePostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_extract_search_text() {
+        let mut data = vec![4, 0, 0, 0];
+        data.extend_from_slice(b"Degen Ape #1234");
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0xff, b'a', b'b', 0xfe]);
+        data.extend_from_slice("Café Zürich".as_bytes());
+        data.extend_from_slice(&[1, 2, 3, b'x', b'y', b'z']);
+        assert_eq!(
+            extract_search_text(&data, 1024).as_deref(),
+            Some("Degen Ape #1234 Café Zürich")
+        );
+        assert_eq!(extract_search_text(&data, 16), None);
+        assert_eq!(extract_search_text(&[0, 1, b'a', b'b', 0xff], 1024), None);
+        // The incomplete sequence at the end of the data.
+        assert_eq!(
+            extract_search_text(&[b'u', b'r', b'i', b':', 0xe2, 0x82], 1024).as_deref(),
+            Some("uri:")
+        );
+    }
+
+    #[test]
+    fn test_write_search_text() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "extract_text_search": true,
+            "text_search_max_data_len": 64,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0; 8];
+        data.extend_from_slice(b"Solana Monkey Business");
+        client
+            .update_account(build_account(&owner, data, 1), true)
+            .unwrap();
+        client
+            .update_account(build_account(&owner, vec![b'a'; 100], 1), true)
+            .unwrap();
+        client
+            .update_account(build_account(&owner, b"Okay Bears".to_vec(), 2), false)
+            .unwrap();
+
+        let count_matches = |db: &mut TestDatabase, query: &str| -> i64 {
+            db.client
+                .query_one(
+                    "SELECT COUNT(*) FROM account WHERE search_text @@ plainto_tsquery('simple', $1)",
+                    &[&query],
+                )
+                .unwrap()
+                .get(0)
+        };
+        assert_eq!(count_matches(&mut db, "monkey business"), 1);
+        assert_eq!(count_matches(&mut db, "bears"), 1);
+        assert_eq!(count_matches(&mut db, "solana bears"), 0);
+        let null_count: i64 = db
+            .client
+            .query_one(
+                "SELECT COUNT(*) FROM account WHERE search_text IS NULL",
+                &[],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(null_count, 1);
+    }
+}
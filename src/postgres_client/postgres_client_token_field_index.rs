@@ -0,0 +1,301 @@
+/// Module responsible for maintaining the token delegate and token state indexes, the fields of
+/// the SPL token accounts beyond the owner and the mint, when index_token_delegate or
+/// index_token_state is set.
+///
+/// Unlike the owner and mint indexes, which keep a row per (key, account) pair, these indexes
+/// keep a row per token account, overwritten by its newer updates, as the delegate and the
+/// close authority of an account are revoked and its state changes over its lifetime.
+use {
+    super::{
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        DbAccountInfo, ReadableAccountInfo, SimplePostgresClient,
+    },
+    crate::{
+        geyser_plugin_postgres::GeyserPluginPostgresConfig,
+        inline_spl_token::{self, GenericTokenAccount},
+        inline_spl_token_2022,
+    },
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    solana_sdk::pubkey::Pubkey,
+    std::sync::Mutex,
+};
+
+/// The name of the AccountState of a token account, as stored in the state column.
+fn token_account_state_name(state: u8) -> &'static str {
+    match state {
+        0 => "uninitialized",
+        1 => "initialized",
+        2 => "frozen",
+        _ => "unknown",
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_token_delegate_index_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS delegate_index (account_key, delegate_key, slot) \
+        VALUES ($1, $2, $3) \
+        ON CONFLICT (account_key) \
+        DO UPDATE SET delegate_key=excluded.delegate_key, slot=excluded.slot \
+        WHERE delegate_index.slot <= excluded.slot",
+            Self::table_name(&config.table_names, "spl_token_delegate_index")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    pub(crate) fn build_token_state_index_upsert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} AS state_index (account_key, state, close_authority, slot) \
+        VALUES ($1, $2, $3, $4) \
+        ON CONFLICT (account_key) \
+        DO UPDATE SET state=excluded.state, close_authority=excluded.close_authority, \
+        slot=excluded.slot \
+        WHERE state_index.slot <= excluded.slot",
+            Self::table_name(&config.table_names, "spl_token_state_index")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Generic function to update the token delegate index entry of a single account.
+    fn update_token_delegate_index_generic<G: GenericTokenAccount>(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        token_id: &Pubkey,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if account.owner() != token_id.as_ref() || !G::valid_account_data(account.data()) {
+            return Ok(());
+        }
+        let delegate_key =
+            G::unpack_account_delegate(account.data()).map(|delegate| delegate.as_ref().to_vec());
+        let pubkey = account.pubkey();
+        let slot = account.slot;
+        let result = statement.execute(client, types, &[&pubkey, &delegate_key, &slot]);
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to update the token delegate index to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+
+        Ok(())
+    }
+
+    /// Generic function to update the token state index entry of a single account.
+    fn update_token_state_index_generic<G: GenericTokenAccount>(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        token_id: &Pubkey,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        if account.owner() != token_id.as_ref() {
+            return Ok(());
+        }
+        if let Some(state) = G::unpack_account_state(account.data()) {
+            let state = token_account_state_name(state);
+            let close_authority = G::unpack_account_close_authority(account.data())
+                .map(|close_authority| close_authority.as_ref().to_vec());
+            let pubkey = account.pubkey();
+            let slot = account.slot;
+            let result =
+                statement.execute(client, types, &[&pubkey, &state, &close_authority, &slot]);
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to update the token state index to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Function for updating a single token delegate index entry.
+    pub(crate) fn update_token_delegate_index(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        Self::update_token_delegate_index_generic::<inline_spl_token::Account>(
+            client,
+            types,
+            statement,
+            &inline_spl_token::id(),
+            account,
+        )?;
+
+        Self::update_token_delegate_index_generic::<inline_spl_token_2022::Account>(
+            client,
+            types,
+            statement,
+            &inline_spl_token_2022::id(),
+            account,
+        )
+    }
+
+    /// Function for updating a single token state index entry.
+    pub(crate) fn update_token_state_index(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        Self::update_token_state_index_generic::<inline_spl_token::Account>(
+            client,
+            types,
+            statement,
+            &inline_spl_token::id(),
+            account,
+        )?;
+
+        Self::update_token_state_index_generic::<inline_spl_token_2022::Account>(
+            client,
+            types,
+            statement,
+            &inline_spl_token_2022::id(),
+            account,
+        )
+    }
+
+    /// Update the token delegate and state indexes for an account queued for the bulk insert.
+    pub(crate) fn upsert_token_field_indexes(
+        &mut self,
+        account: &DbAccountInfo,
+    ) -> Result<(), GeyserPluginError> {
+        let client = self.client.get_mut().unwrap();
+        if let Some(statement) = &client.upsert_token_delegate_index_stmt {
+            Self::update_token_delegate_index(
+                &mut client.client,
+                &client.types,
+                statement,
+                account,
+            )?;
+        }
+        if let Some(statement) = &client.upsert_token_state_index_stmt {
+            Self::update_token_state_index(&mut client.client, &client.types, statement, account)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            inline_spl_token::{
+                SPL_TOKEN_ACCOUNT_CLOSE_AUTHORITY_OFFSET, SPL_TOKEN_ACCOUNT_DELEGATE_OFFSET,
+                SPL_TOKEN_ACCOUNT_STATE_OFFSET,
+            },
+            postgres_client::{
+                tests::{build_account, TestDatabase},
+                PostgresClient,
+            },
+        },
+        serde_json::json,
+    };
+
+    fn token_account_data(delegate: Option<&Pubkey>, state: u8) -> Vec<u8> {
+        let mut data = vec![0u8; inline_spl_token::Account::get_packed_len()];
+        if let Some(delegate) = delegate {
+            let offset = SPL_TOKEN_ACCOUNT_DELEGATE_OFFSET;
+            data[offset] = 1;
+            data[offset + 4..offset + 36].copy_from_slice(delegate.as_ref());
+        }
+        data[SPL_TOKEN_ACCOUNT_STATE_OFFSET] = state;
+        data
+    }
+
+    #[test]
+    fn test_unpack_token_account_fields() {
+        let delegate = Pubkey::new_unique();
+        let close_authority = Pubkey::new_unique();
+        let mut data = token_account_data(Some(&delegate), 2);
+        let offset = SPL_TOKEN_ACCOUNT_CLOSE_AUTHORITY_OFFSET;
+        data[offset] = 1;
+        data[offset + 4..offset + 36].copy_from_slice(close_authority.as_ref());
+
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_delegate(&data),
+            Some(&delegate)
+        );
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_state(&data),
+            Some(2)
+        );
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_close_authority(&data),
+            Some(&close_authority)
+        );
+
+        let data = token_account_data(None, 1);
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_delegate(&data),
+            None
+        );
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_close_authority(&data),
+            None
+        );
+        assert_eq!(
+            inline_spl_token::Account::unpack_account_state(&data[..64]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_token_field_indexes() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "index_token_delegate": true,
+            "index_token_state": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let delegate = Pubkey::new_unique();
+        let mut account = build_account(
+            &inline_spl_token::id(),
+            token_account_data(Some(&delegate), 1),
+            1,
+        );
+        client.update_account(account.clone(), false).unwrap();
+        // The delegate is revoked and the account frozen, then an older update arrives.
+        account.slot = 3;
+        account.data = token_account_data(None, 2);
+        client.update_account(account.clone(), false).unwrap();
+        account.slot = 2;
+        account.data = token_account_data(Some(&delegate), 1);
+        client.update_account(account.clone(), false).unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT d.delegate_key, s.state FROM spl_token_delegate_index d \
+                JOIN spl_token_state_index s ON s.account_key = d.account_key",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, Option<Vec<u8>>>(0), None);
+        assert_eq!(row.get::<_, String>(1), "frozen");
+    }
+}
@@ -2,12 +2,19 @@
 /// database.
 use {
     crate::{
-        geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
-        postgres_client::{DbWorkItem, ParallelPostgresClient, SimplePostgresClient},
+        geyser_plugin_postgres::{
+            DataCompression, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+        },
+        postgres_client::{
+            postgres_client_compression::compression_marker,
+            postgres_client_serialization::EncodedTransactionParams,
+            postgres_client_transaction_accounts::DbTransactionAccountWrites,
+            postgres_client_unprepared::PluginStatement, SimplePostgresClient,
+        },
     },
     chrono::Utc,
     log::*,
-    postgres::{Client, Statement},
+    postgres::Client,
     postgres_types::{FromSql, ToSql},
     solana_geyser_plugin_interface::geyser_plugin_interface::{
         GeyserPluginError, ReplicaTransactionInfo,
@@ -24,7 +31,6 @@ use {
     solana_transaction_status::{
         InnerInstructions, Reward, TransactionStatusMeta, TransactionTokenBalance,
     },
-    std::sync::atomic::Ordering,
 };
 
 const MAX_TRANSACTION_STATUS_LEN: usize = 256;
@@ -135,6 +141,7 @@ pub struct DbLoadedMessageV0 {
     pub loaded_addresses: DbLoadedAddresses,
 }
 
+#[derive(Clone)]
 pub struct DbTransaction {
     pub signature: Vec<u8>,
     pub is_vote: bool,
@@ -153,6 +160,10 @@ pub struct DbTransaction {
 
 pub struct LogTransactionRequest {
     pub transaction_info: DbTransaction,
+    /// The composite parameters encoded by a serialization thread, when enabled.
+    pub encoded: Option<EncodedTransactionParams>,
+    /// The accounts written by the transaction, when index_transaction_accounts is set.
+    pub account_writes: Option<DbTransactionAccountWrites>,
 }
 
 impl From<&MessageAddressTableLookup> for DbTransactionMessageAddressTableLookup {
@@ -479,7 +490,7 @@ impl From<&TransactionStatusMeta> for DbTransactionStatusMeta {
     }
 }
 
-fn build_db_transaction(
+pub(crate) fn build_db_transaction(
     slot: u64,
     transaction_info: &ReplicaTransactionInfo,
     transaction_write_version: u64,
@@ -518,25 +529,115 @@ fn build_db_transaction(
     }
 }
 
+/// The columns of the transaction table, in the order of the parameters.
+const TRANSACTION_COLUMNS: [&str; 14] = [
+    "signature",
+    "is_vote",
+    "slot",
+    "message_type",
+    "legacy_message",
+    "v0_loaded_message",
+    "signatures",
+    "message_hash",
+    "meta",
+    "write_version",
+    "updated_on",
+    "data_compression",
+    "compressed_message",
+    "compressed_meta",
+];
+
+/// The columns of the transaction table written, without the message columns when
+/// store_transaction_message is false, the meta when store_transaction_meta is false and the
+/// signatures when store_transaction_signature_array is false. The columns left out are NULL.
+/// With compress_transaction_data, the message and the meta are written into the compressed
+/// columns rather than into the composite columns.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TransactionColumns {
+    message: bool,
+    meta: bool,
+    signatures: bool,
+    compression: DataCompression,
+}
+
+impl TransactionColumns {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        Self {
+            message: !matches!(config.store_transaction_message, Some(false)),
+            meta: !matches!(config.store_transaction_meta, Some(false)),
+            signatures: !matches!(config.store_transaction_signature_array, Some(false)),
+            compression: config
+                .compress_transaction_data
+                .unwrap_or(DataCompression::None),
+        }
+    }
+
+    pub fn message(&self) -> bool {
+        self.message
+    }
+
+    pub fn meta(&self) -> bool {
+        self.meta
+    }
+
+    pub fn compression(&self) -> DataCompression {
+        self.compression
+    }
+
+    /// Whether each column of TRANSACTION_COLUMNS is written.
+    fn enabled(&self) -> [bool; 14] {
+        let compressed = self.compression != DataCompression::None;
+        [
+            true,
+            true,
+            true,
+            true,
+            self.message && !compressed,
+            self.message && !compressed,
+            self.signatures,
+            self.message,
+            self.meta && !compressed,
+            true,
+            true,
+            compressed,
+            self.message && compressed,
+            self.meta && compressed,
+        ]
+    }
+
+    /// The names of the columns written.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.select(TRANSACTION_COLUMNS)
+    }
+
+    /// The values of the columns written, out of the values of TRANSACTION_COLUMNS.
+    pub fn select<T>(&self, values: [T; 14]) -> Vec<T> {
+        values
+            .into_iter()
+            .zip(self.enabled())
+            .filter_map(|(value, enabled)| enabled.then(|| value))
+            .collect()
+    }
+}
+
 impl SimplePostgresClient {
     pub(crate) fn build_transaction_info_upsert_statement(
         client: &mut Client,
         config: &GeyserPluginPostgresConfig,
-    ) -> Result<Statement, GeyserPluginError> {
-        let stmt = "INSERT INTO transaction AS txn (signature, is_vote, slot, message_type, legacy_message, \
-        v0_loaded_message, signatures, message_hash, meta, write_version, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) \
-        ON CONFLICT (slot, signature) DO UPDATE SET is_vote=excluded.is_vote, \
-        message_type=excluded.message_type, \
-        legacy_message=excluded.legacy_message, \
-        v0_loaded_message=excluded.v0_loaded_message, \
-        signatures=excluded.signatures, \
-        message_hash=excluded.message_hash, \
-        meta=excluded.meta, \
-        write_version=excluded.write_version, \
-        updated_on=excluded.updated_on";
-
-        let stmt = client.prepare(stmt);
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let columns = TransactionColumns::new(config).names();
+        let values: Vec<String> = (1..=columns.len())
+            .map(|index| format!("${}", index))
+            .collect();
+        let stmt = format!(
+            "INSERT INTO {} AS txn ({}) VALUES ({}) \
+        ON CONFLICT (slot, signature) DO NOTHING",
+            Self::table_name(&config.table_names, "transaction"),
+            columns.join(", "),
+            values.join(", ")
+        );
+
+        let stmt = PluginStatement::prepare(client, &stmt, Self::use_prepared_statements(config));
 
         match stmt {
             Err(err) => {
@@ -551,32 +652,131 @@ impl SimplePostgresClient {
         }
     }
 
+    /// Make sure the transaction table has a unique index on (slot, signature), on a short-lived
+    /// connection, creating it when missing. The transactions re-notified for the slots replayed
+    /// after a restart conflict with it and are skipped by the insert.
+    pub fn ensure_transaction_unique_index(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let table = Self::table_name(&config.table_names, "transaction");
+        let mut client = Self::connect_to_db(config)?;
+        let result = client
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_index i WHERE i.indrelid = $1::TEXT::REGCLASS \
+                AND i.indisunique AND i.indpred IS NULL \
+                AND (SELECT array_agg(a.attname::TEXT ORDER BY a.attname) FROM pg_attribute a \
+                WHERE a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)) = ARRAY['signature', 'slot'])",
+                &[&table],
+            )
+            .and_then(|row| {
+                if row.get(0) {
+                    return Ok(());
+                }
+                let statement = format!(
+                    "CREATE UNIQUE INDEX transaction_slot_signature ON {} (slot, signature)",
+                    table
+                );
+                info!("{}", statement);
+                client.batch_execute(&statement)
+            });
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to ensure the unique index on (slot, signature) of the table {:?}. Error: {:?}",
+                table, err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::Custom(Box::new(
+                GeyserPluginPostgresError::DataSchemaError { msg },
+            )));
+        }
+        Ok(())
+    }
+
+    /// Build the statement inserting all the signatures of a transaction, bound as an array.
+    pub(crate) fn build_transaction_signatures_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (signature, slot, transaction_signature, signature_index) \
+        SELECT sig.signature, $2, $3, (sig.ordinality - 1)::SMALLINT \
+        FROM unnest($1::BYTEA[]) WITH ORDINALITY AS sig(signature, ordinality) \
+        ON CONFLICT (signature, slot) DO NOTHING",
+            Self::table_name(&config.table_names, "transaction_signatures")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
     pub(crate) fn log_transaction_impl(
         &mut self,
         transaction_log_info: LogTransactionRequest,
     ) -> Result<(), GeyserPluginError> {
+        // The transactions compressed are encoded here unless a serialization thread did.
+        let writer_encoded;
+        let encoded = match (&transaction_log_info.encoded, &self.transaction_encoder) {
+            (None, Some(transaction_encoder)) => {
+                writer_encoded =
+                    transaction_encoder
+                        .encode(&transaction_log_info)
+                        .map_err(|err| {
+                            let msg = format!("Failed to encode the transaction. Error: {:?}", err);
+                            error!("{}", msg);
+                            GeyserPluginError::AccountsUpdateError { msg }
+                        })?;
+                Some(&writer_encoded)
+            }
+            (encoded, _) => encoded.as_ref(),
+        };
         let client = self.client.get_mut().unwrap();
         let statement = &client.update_transaction_log_stmt;
+        let insert_transaction_signatures_stmt = &client.insert_transaction_signatures_stmt;
+        let insert_transaction_account_writes_stmt = &client.insert_transaction_account_writes_stmt;
+        let insert_transaction_instructions_stmt = &client.insert_transaction_instructions_stmt;
+        let types = &client.types;
         let client = &mut client.client;
         let updated_on = Utc::now().naive_utc();
 
-        let transaction_info = transaction_log_info.transaction_info;
-        let result = client.query(
-            statement,
-            &[
-                &transaction_info.signature,
-                &transaction_info.is_vote,
-                &transaction_info.slot,
-                &transaction_info.message_type,
+        let transaction_info = &transaction_log_info.transaction_info;
+        let (legacy_message, v0_loaded_message, meta): (
+            &(dyn ToSql + Sync),
+            &(dyn ToSql + Sync),
+            &(dyn ToSql + Sync),
+        ) = match encoded {
+            Some(encoded) => (
+                &encoded.legacy_message,
+                &encoded.v0_loaded_message,
+                &encoded.meta,
+            ),
+            None => (
                 &transaction_info.legacy_message,
                 &transaction_info.v0_loaded_message,
-                &transaction_info.signatures,
-                &transaction_info.message_hash,
                 &transaction_info.meta,
-                &transaction_info.write_version,
-                &updated_on,
-            ],
-        );
+            ),
+        };
+        // The compressed message is that of the message_type.
+        let compressed_message = match transaction_info.message_type {
+            1 => v0_loaded_message,
+            _ => legacy_message,
+        };
+        let data_compression = compression_marker(self.transaction_columns.compression());
+        let values = self.transaction_columns.select::<&(dyn ToSql + Sync)>([
+            &transaction_info.signature,
+            &transaction_info.is_vote,
+            &transaction_info.slot,
+            &transaction_info.message_type,
+            legacy_message,
+            v0_loaded_message,
+            &transaction_info.signatures,
+            &transaction_info.message_hash,
+            meta,
+            &transaction_info.write_version,
+            &updated_on,
+            &data_compression,
+            compressed_message,
+            meta,
+        ]);
+        let result = statement.query(client, types, &values);
 
         if let Err(err) = result {
             let msg = format!(
@@ -587,51 +787,81 @@ impl SimplePostgresClient {
             return Err(GeyserPluginError::AccountsUpdateError { msg });
         }
 
+        if let Some(statement) = insert_transaction_signatures_stmt {
+            let result = statement.execute(
+                client,
+                types,
+                &[
+                    &transaction_info.signatures,
+                    &transaction_info.slot,
+                    &transaction_info.signature,
+                ],
+            );
+            if let Err(err) = result {
+                let msg = format!(
+                    "Failed to persist the transaction signatures to the PostgreSQL database. Error: {:?}",
+                    err
+                );
+                error!("{}", msg);
+                return Err(GeyserPluginError::AccountsUpdateError { msg });
+            }
+        }
+
+        if let Some(statement) = insert_transaction_account_writes_stmt {
+            Self::insert_transaction_account_writes(
+                client,
+                types,
+                statement,
+                &transaction_log_info,
+            )?;
+        }
+
+        if let (Some(statement), Some(instruction_idl)) =
+            (insert_transaction_instructions_stmt, &self.instruction_idl)
+        {
+            Self::insert_transaction_instructions(
+                client,
+                types,
+                statement,
+                instruction_idl,
+                transaction_info,
+            )?;
+        }
+
         Ok(())
     }
 }
 
-impl ParallelPostgresClient {
-    fn build_transaction_request(
+impl LogTransactionRequest {
+    /// Assemble the transaction, along with the accounts it wrote when
+    /// index_transaction_accounts is set.
+    pub fn new(
         slot: u64,
         transaction_info: &ReplicaTransactionInfo,
         transaction_write_version: u64,
-    ) -> LogTransactionRequest {
-        LogTransactionRequest {
+        index_transaction_accounts: bool,
+    ) -> Self {
+        Self {
             transaction_info: build_db_transaction(
                 slot,
                 transaction_info,
                 transaction_write_version,
             ),
+            encoded: None,
+            account_writes: index_transaction_accounts
+                .then(|| DbTransactionAccountWrites::from(transaction_info)),
         }
     }
-
-    pub fn log_transaction_info(
-        &mut self,
-        transaction_info: &ReplicaTransactionInfo,
-        slot: u64,
-    ) -> Result<(), GeyserPluginError> {
-        self.transaction_write_version
-            .fetch_add(1, Ordering::Relaxed);
-        let wrk_item = DbWorkItem::LogTransaction(Box::new(Self::build_transaction_request(
-            slot,
-            transaction_info,
-            self.transaction_write_version.load(Ordering::Relaxed),
-        )));
-
-        if let Err(err) = self.sender.send(wrk_item) {
-            return Err(GeyserPluginError::SlotStatusUpdateError {
-                msg: format!("Failed to update the transaction, error: {:?}", err),
-            });
-        }
-        Ok(())
-    }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use {
         super::*,
+        crate::postgres_client::{
+            postgres_client_compression::tests::decompress, tests::TestDatabase, PostgresClient,
+        },
+        serde_json::json,
         solana_account_decoder::parse_token::UiTokenAmount,
         solana_sdk::{
             hash::Hash,
@@ -1406,4 +1636,263 @@ pub(crate) mod tests {
         let db_transaction = build_db_transaction(slot, &transaction_info, 1);
         check_transaction(slot, &transaction_info, &db_transaction);
     }
+
+    /// Build the request logging the legacy test transaction with the signature.
+    pub(crate) fn build_test_log_transaction_request(
+        signature_byte: u8,
+        slot: u64,
+    ) -> LogTransactionRequest {
+        let signature = Signature::new(&[signature_byte; 64]);
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::from(build_test_transaction_legacy()),
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Disabled,
+        )
+        .unwrap();
+        let transaction_status_meta = build_transaction_status_meta();
+        let transaction_info = ReplicaTransactionInfo {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        LogTransactionRequest {
+            transaction_info: build_db_transaction(slot, &transaction_info, 1),
+            encoded: None,
+            account_writes: None,
+        }
+    }
+
+    #[test]
+    fn test_log_transaction() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::from(build_test_transaction_legacy()),
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Disabled,
+        )
+        .unwrap();
+        let transaction_status_meta = build_transaction_status_meta();
+        let transaction_info = ReplicaTransactionInfo {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let slot = 54;
+        client
+            .log_transaction(LogTransactionRequest {
+                transaction_info: build_db_transaction(slot, &transaction_info, 1),
+                encoded: None,
+                account_writes: None,
+            })
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT slot, message_type, meta FROM transaction WHERE signature = $1",
+                &[&signature.as_ref()],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, i64>(0), slot as i64);
+        assert_eq!(row.get::<_, i16>(1), 0);
+        let meta: DbTransactionStatusMeta = row.get(2);
+        check_transaction_status_meta(&transaction_status_meta, &meta);
+        assert_eq!(db.count("transaction_signatures"), 0);
+    }
+
+    #[test]
+    fn test_log_transaction_signatures() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "store_transaction_signatures": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::from(build_test_transaction_legacy()),
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Disabled,
+        )
+        .unwrap();
+        let transaction_status_meta = build_transaction_status_meta();
+        let transaction_info = ReplicaTransactionInfo {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let slot = 54;
+        let mut db_transaction = build_db_transaction(slot, &transaction_info, 1);
+        let cosigner_signature = Signature::new(&[2u8; 64]);
+        db_transaction
+            .signatures
+            .push(cosigner_signature.as_ref().to_vec());
+        client
+            .log_transaction(LogTransactionRequest {
+                transaction_info: db_transaction,
+                encoded: None,
+                account_writes: None,
+            })
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT transaction_signature, signature_index FROM transaction_signatures \
+                WHERE signature = $1",
+                &[&cosigner_signature.as_ref()],
+            )
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), signature.as_ref());
+        assert_eq!(row.get::<_, i16>(1), 1);
+        assert_eq!(db.count("transaction_signatures"), 2);
+    }
+
+    #[test]
+    fn test_log_transaction_columns() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "store_transaction_message": false,
+            "store_transaction_signature_array": false,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        client
+            .log_transaction(build_test_log_transaction_request(1, 54))
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one(
+                "SELECT legacy_message IS NULL, message_hash IS NULL, signatures IS NULL, \
+                meta IS NULL, message_type FROM transaction",
+                &[],
+            )
+            .unwrap();
+        assert!(row.get::<_, bool>(0));
+        assert!(row.get::<_, bool>(1));
+        assert!(row.get::<_, bool>(2));
+        assert!(!row.get::<_, bool>(3));
+        assert_eq!(row.get::<_, i16>(4), 0);
+    }
+
+    #[test]
+    fn test_log_transaction_compressed() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let statement = db
+            .client
+            .prepare("SELECT legacy_message, meta FROM transaction LIMIT 0")
+            .unwrap();
+        let (message_type, meta_type) = (
+            statement.columns()[0].type_().clone(),
+            statement.columns()[1].type_().clone(),
+        );
+        for (signature_byte, compression, marker) in [(1u8, "zstd", 1i16), (2, "lz4", 2)] {
+            let config = db.config(json!({ "compress_transaction_data": compression }));
+            let mut client = SimplePostgresClient::new(&config).unwrap();
+            let request = build_test_log_transaction_request(signature_byte, 54);
+            let expected = request.transaction_info.clone();
+            client.log_transaction(request).unwrap();
+
+            let row = db
+                .client
+                .query_one(
+                    "SELECT legacy_message IS NULL, meta IS NULL, data_compression, \
+                    compressed_message, compressed_meta FROM transaction WHERE signature = $1",
+                    &[&expected.signature],
+                )
+                .unwrap();
+            assert!(row.get::<_, bool>(0));
+            assert!(row.get::<_, bool>(1));
+            assert_eq!(row.get::<_, Option<i16>>(2), Some(marker));
+            // The blobs decode as their composite types once decompressed.
+            let message = decompress(Some(marker), &row.get::<_, Vec<u8>>(3));
+            let message = DbTransactionMessage::from_sql(&message_type, &message).unwrap();
+            assert_eq!(
+                message.account_keys,
+                expected.legacy_message.unwrap().account_keys
+            );
+            let meta = decompress(Some(marker), &row.get::<_, Vec<u8>>(4));
+            let meta = DbTransactionStatusMeta::from_sql(&meta_type, &meta).unwrap();
+            assert_eq!(meta.fee, expected.meta.fee);
+            assert_eq!(meta.log_messages, expected.meta.log_messages);
+        }
+    }
+
+    #[test]
+    fn test_log_transaction_replayed() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        db.client
+            .batch_execute("ALTER TABLE transaction DROP CONSTRAINT transaction_pk")
+            .unwrap();
+        let config = db.config(json!({}));
+        SimplePostgresClient::ensure_transaction_unique_index(&config).unwrap();
+        // The index created is found on the next load.
+        SimplePostgresClient::ensure_transaction_unique_index(&config).unwrap();
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction = SanitizedTransaction::try_create(
+            VersionedTransaction::from(build_test_transaction_legacy()),
+            Hash::new_unique(),
+            Some(false),
+            SimpleAddressLoader::Disabled,
+        )
+        .unwrap();
+        let transaction_status_meta = build_transaction_status_meta();
+        let transaction_info = ReplicaTransactionInfo {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        for write_version in 1..=2 {
+            client
+                .log_transaction(LogTransactionRequest {
+                    transaction_info: build_db_transaction(54, &transaction_info, write_version),
+                    encoded: None,
+                    account_writes: None,
+                })
+                .unwrap();
+        }
+
+        let write_version: i64 = db
+            .client
+            .query_one("SELECT write_version FROM transaction", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(write_version, 1);
+        let index_count: i64 = db
+            .client
+            .query_one(
+                "SELECT COUNT(*) FROM pg_indexes WHERE indexname = 'transaction_slot_signature'",
+                &[],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(index_count, 1);
+    }
 }
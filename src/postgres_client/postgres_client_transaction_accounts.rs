@@ -0,0 +1,218 @@
+/// Module responsible for recording the accounts written by each transaction, to relate the
+/// account updates of a slot to the transactions, and the programs they invoke, which could
+/// have modified them.
+///
+/// The plugin is not told which transaction or program modified an account. A transaction can
+/// only modify the accounts it locks as writable, a failed one only its fee payer. These are
+/// recorded into the transaction_account_write table, along with the programs invoked by the
+/// instructions of the transaction. The account_update_transaction view joins the account
+/// updates to the transactions of the same slot which wrote them, by the (slot, pubkey)
+/// primary key prefix of the table.
+use {
+    super::{
+        postgres_client_transaction::LogTransactionRequest,
+        postgres_client_unprepared::{PluginStatement, TypeRegistry},
+        SimplePostgresClient,
+    },
+    crate::geyser_plugin_postgres::GeyserPluginPostgresConfig,
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::{
+        GeyserPluginError, ReplicaTransactionInfo,
+    },
+    std::sync::Mutex,
+};
+
+/// The accounts a transaction could have written, and the programs it invoked.
+pub struct DbTransactionAccountWrites {
+    pub pubkeys: Vec<Vec<u8>>,
+    pub program_ids: Vec<Vec<u8>>,
+}
+
+impl From<&ReplicaTransactionInfo<'_>> for DbTransactionAccountWrites {
+    fn from(transaction_info: &ReplicaTransactionInfo) -> Self {
+        let message = transaction_info.transaction.message();
+        let pubkeys = if transaction_info.transaction_status_meta.status.is_err() {
+            // Only the fee is charged to the fee payer.
+            vec![message.fee_payer().as_ref().to_vec()]
+        } else {
+            message
+                .account_keys()
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| message.is_writable(*index))
+                .map(|(_, pubkey)| pubkey.as_ref().to_vec())
+                .collect()
+        };
+
+        let mut program_ids: Vec<Vec<u8>> = Vec::default();
+        for (program_id, _) in message.program_instructions_iter() {
+            let program_id = program_id.as_ref().to_vec();
+            if !program_ids.contains(&program_id) {
+                program_ids.push(program_id);
+            }
+        }
+
+        Self {
+            pubkeys,
+            program_ids,
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    pub(crate) fn build_transaction_account_writes_insert_statement(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<PluginStatement, GeyserPluginError> {
+        let stmt = format!(
+            "INSERT INTO {} (slot, pubkey, signature, program_ids) \
+        SELECT $1, pubkey, $2, $3 FROM unnest($4::BYTEA[]) AS pubkey \
+        ON CONFLICT (slot, pubkey, signature) DO NOTHING",
+            Self::table_name(&config.table_names, "transaction_account_write")
+        );
+
+        Self::prepare_query_statement(client, config, &stmt)
+    }
+
+    /// Record the accounts written by the transaction, when the request carries them.
+    pub(crate) fn insert_transaction_account_writes(
+        client: &mut Client,
+        types: &Mutex<Option<TypeRegistry>>,
+        statement: &PluginStatement,
+        transaction_log_info: &LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let account_writes = match &transaction_log_info.account_writes {
+            Some(account_writes) => account_writes,
+            None => return Ok(()),
+        };
+
+        let transaction_info = &transaction_log_info.transaction_info;
+        let result = statement.execute(
+            client,
+            types,
+            &[
+                &transaction_info.slot,
+                &transaction_info.signature,
+                &account_writes.program_ids,
+                &account_writes.pubkeys,
+            ],
+        );
+        if let Err(err) = result {
+            let msg = format!(
+                "Failed to persist the accounts written by the transaction to the PostgreSQL database. Error: {:?}",
+                err
+            );
+            error!("{}", msg);
+            return Err(GeyserPluginError::AccountsUpdateError { msg });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            postgres_client_transaction::build_db_transaction,
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::{
+            instruction::{AccountMeta, Instruction},
+            message::Message,
+            pubkey::Pubkey,
+            signature::Signature,
+            transaction::{SanitizedTransaction, Transaction, TransactionError},
+        },
+        solana_transaction_status::TransactionStatusMeta,
+    };
+
+    fn build_log_transaction_request(
+        program_id: &Pubkey,
+        payer: &Pubkey,
+        written: &Pubkey,
+        read: &Pubkey,
+        failed: bool,
+        slot: u64,
+    ) -> LogTransactionRequest {
+        let instruction = Instruction::new_with_bytes(
+            *program_id,
+            &[],
+            vec![
+                AccountMeta::new(*written, false),
+                AccountMeta::new_readonly(*read, false),
+            ],
+        );
+        let message = Message::new(&[instruction], Some(payer));
+        let transaction =
+            SanitizedTransaction::try_from_legacy_transaction(Transaction::new_unsigned(message))
+                .unwrap();
+        let transaction_status_meta = TransactionStatusMeta {
+            status: if failed {
+                Err(TransactionError::AccountInUse)
+            } else {
+                Ok(())
+            },
+            ..TransactionStatusMeta::default()
+        };
+        let signature = Signature::new_unique();
+        let transaction_info = ReplicaTransactionInfo {
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        LogTransactionRequest {
+            transaction_info: build_db_transaction(slot, &transaction_info, 1),
+            encoded: None,
+            account_writes: Some(DbTransactionAccountWrites::from(&transaction_info)),
+        }
+    }
+
+    #[test]
+    fn test_account_update_transaction() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "index_transaction_accounts": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let program_id = Pubkey::new_unique();
+        let payer = Pubkey::new_unique();
+        let written = build_account(&program_id, vec![1], 10);
+        let read = build_account(&program_id, vec![2], 10);
+        let written_pubkey = Pubkey::new(&written.pubkey);
+        let read_pubkey = Pubkey::new(&read.pubkey);
+        for failed in [false, true] {
+            client
+                .log_transaction(build_log_transaction_request(
+                    &program_id,
+                    &payer,
+                    &written_pubkey,
+                    &read_pubkey,
+                    failed,
+                    10,
+                ))
+                .unwrap();
+        }
+        // The payer, and the writable account of the successful transaction only.
+        assert_eq!(db.count("transaction_account_write"), 3);
+
+        client.update_account(written, false).unwrap();
+        client.update_account(read, false).unwrap();
+        let rows = db
+            .client
+            .query(
+                "SELECT pubkey FROM account_update_transaction \
+                WHERE slot = $1 AND $2 = ANY(program_ids)",
+                &[&10i64, &program_id.as_ref()],
+            )
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get::<_, Vec<u8>>(0), written_pubkey.as_ref());
+    }
+}
@@ -0,0 +1,892 @@
+/// Module responsible for sending the statements without preparing them on the server, when
+/// use_prepared_statements is false, for the connection poolers which do not support the
+/// prepared statements, such as PgBouncer in the transaction pool mode.
+///
+/// The statements are built by the same builders either way: each builder passes its SQL to
+/// PluginStatement::prepare, which prepares it on the server, or keeps the SQL with the number of
+/// its parameters. The call sites execute the PluginStatement with the same parameters either
+/// way. The SQL of an unprepared statement is sent as a simple query, with each $n parameter
+/// replaced by a literal of its value, rendered on the client and cast to its type:
+/// * The type of a value is the first of the candidate types its ToSql encodes it for: the
+///   built-in scalar and array types, then the composite and enum types of the schemas in the
+///   search path, read from the catalog with simple queries when first needed on the
+///   connection, and shared by all its statements.
+/// * The binary encoding of the value is rendered back into a literal of the type: the strings
+///   escaped, the byte arrays in hex, and the arrays and the composites element by element, as
+///   ARRAY[...] and ROW(...).
+///
+/// No value is spliced into the SQL as is, so the values cannot inject SQL. The statements whose
+/// rows are read are sent as a single unnamed statement without parameters instead, as the
+/// simple queries return the rows as text. The statements run once as the plugin loads, such as
+/// the schema checks, are still prepared by the driver.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{
+        DataCompression, GeyserPluginPostgresConfig, GeyserPluginPostgresError,
+    },
+    chrono::{DateTime, NaiveDateTime, Utc},
+    log::*,
+    postgres::{
+        error::SqlState,
+        types::{Field, FromSql, IsNull, Kind, ToSql, Type, WrongType},
+        GenericClient, Row, SimpleQueryMessage, Statement,
+    },
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{
+        collections::HashMap,
+        fmt::{self, Write},
+        str,
+        sync::{Arc, Mutex},
+    },
+};
+
+impl SimplePostgresClient {
+    /// Whether the statements are prepared on the server.
+    pub(crate) fn use_prepared_statements(config: &GeyserPluginPostgresConfig) -> bool {
+        !matches!(config.use_prepared_statements, Some(false))
+    }
+
+    /// Check that the features relying on the statements prepared on the server, or on the
+    /// settings of the server session, are not set along with use_prepared_statements set to
+    /// false. The connection poolers in transaction mode, for which the statements are not
+    /// prepared, do not keep a server session to the client between the transactions.
+    pub(crate) fn check_use_prepared_statements(
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        if Self::use_prepared_statements(config) {
+            return Ok(());
+        }
+        let (field, requirement) = if matches!(config.serialization_threads, Some(threads) if threads > 0)
+        {
+            (
+                "serialization_threads",
+                "the statements prepared on the server",
+            )
+        } else if matches!(
+            config.compress_transaction_data,
+            Some(DataCompression::Zstd | DataCompression::Lz4)
+        ) {
+            (
+                "compress_transaction_data",
+                "the statements prepared on the server",
+            )
+        } else if matches!(config.record_statement_checksums, Some(true)) {
+            (
+                "record_statement_checksums",
+                "the statements prepared on the server",
+            )
+        } else if config.synchronous_commit.is_some() {
+            ("synchronous_commit", "a session setting")
+        } else if matches!(config.maintain_recent_account_table, Some(true)) {
+            ("maintain_recent_account_table", "a session setting")
+        } else if Self::skip_account_audit_during_startup(config) {
+            ("store_historical_data_during_startup", "a session setting")
+        } else {
+            return Ok(());
+        };
+        let msg = format!(
+            "\"{}\" requires {}, \"use_prepared_statements\" must not be false",
+            field, requirement
+        );
+        error!("{}", msg);
+        Err(GeyserPluginError::Custom(Box::new(
+            GeyserPluginPostgresError::ConfigurationError { msg },
+        )))
+    }
+}
+
+/// A statement of the plugin, prepared on the server, or its SQL sent with the parameters
+/// rendered on the client.
+#[derive(Clone)]
+pub enum PluginStatement {
+    Prepared(Statement),
+    Unprepared(Arc<UnpreparedStatement>),
+}
+
+pub struct UnpreparedStatement {
+    sql: String,
+    param_count: usize,
+}
+
+/// The error of a statement, from the server or from rendering its parameters.
+pub enum StatementError {
+    Postgres(postgres::Error),
+    Render(String),
+}
+
+impl StatementError {
+    pub fn code(&self) -> Option<&SqlState> {
+        match self {
+            Self::Postgres(err) => err.code(),
+            Self::Render(_) => None,
+        }
+    }
+}
+
+impl From<postgres::Error> for StatementError {
+    fn from(err: postgres::Error) -> Self {
+        Self::Postgres(err)
+    }
+}
+
+impl fmt::Debug for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Postgres(err) => fmt::Debug::fmt(err, f),
+            Self::Render(msg) => write!(f, "Failed to render the parameters: {}", msg),
+        }
+    }
+}
+
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Postgres(err) => fmt::Display::fmt(err, f),
+            Self::Render(msg) => write!(f, "Failed to render the parameters: {}", msg),
+        }
+    }
+}
+
+impl PluginStatement {
+    /// Prepare the statement on the server, or keep its SQL when the statements are not
+    /// prepared.
+    pub fn prepare<C: GenericClient>(
+        client: &mut C,
+        sql: &str,
+        use_prepared_statements: bool,
+    ) -> Result<Self, postgres::Error> {
+        if use_prepared_statements {
+            return Ok(Self::Prepared(client.prepare(sql)?));
+        }
+        Ok(Self::Unprepared(Arc::new(UnpreparedStatement {
+            sql: sql.to_string(),
+            param_count: param_count(sql),
+        })))
+    }
+
+    /// The number of the parameters of the statement.
+    pub fn param_count(&self) -> usize {
+        match self {
+            Self::Prepared(statement) => statement.params().len(),
+            Self::Unprepared(statement) => statement.param_count,
+        }
+    }
+
+    /// The types of the parameters, only known of the prepared statements.
+    pub fn param_types(&self) -> Option<&[Type]> {
+        match self {
+            Self::Prepared(statement) => Some(statement.params()),
+            Self::Unprepared(_) => None,
+        }
+    }
+
+    /// Execute the statement, returning the number of the rows modified. The types are the
+    /// composite and enum types of the connection, read when first needed to render the
+    /// parameters of a statement not prepared.
+    pub fn execute<C: GenericClient>(
+        &self,
+        client: &mut C,
+        types: &Mutex<Option<TypeRegistry>>,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, StatementError> {
+        match self {
+            Self::Prepared(statement) => Ok(client.execute(statement, params)?),
+            Self::Unprepared(statement) => {
+                let sql = statement.render(client, types, params)?;
+                let rows = client
+                    .simple_query(&sql)?
+                    .iter()
+                    .map(|message| match message {
+                        SimpleQueryMessage::CommandComplete(rows) => *rows,
+                        _ => 0,
+                    })
+                    .sum();
+                Ok(rows)
+            }
+        }
+    }
+
+    /// Execute the statement, returning its rows.
+    pub fn query<C: GenericClient>(
+        &self,
+        client: &mut C,
+        types: &Mutex<Option<TypeRegistry>>,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, StatementError> {
+        match self {
+            Self::Prepared(statement) => Ok(client.query(statement, params)?),
+            Self::Unprepared(statement) => {
+                let sql = statement.render(client, types, params)?;
+                Ok(client.query_typed(&sql, &[])?)
+            }
+        }
+    }
+}
+
+impl UnpreparedStatement {
+    /// The SQL with the parameters replaced by their literals.
+    fn render<C: GenericClient>(
+        &self,
+        client: &mut C,
+        types: &Mutex<Option<TypeRegistry>>,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<String, StatementError> {
+        if params.len() != self.param_count {
+            return Err(StatementError::Render(format!(
+                "expected {} parameters but got {}",
+                self.param_count,
+                params.len()
+            )));
+        }
+        let mut literals = Vec::with_capacity(params.len());
+        for param in params {
+            let literal = match render_param(*param, &[])? {
+                Some(literal) => literal,
+                None => {
+                    let mut types = types.lock().unwrap();
+                    if types.is_none() {
+                        *types = Some(TypeRegistry::load(client)?);
+                    }
+                    let types = &types.as_ref().unwrap().types;
+                    render_param(*param, types)?.ok_or_else(|| {
+                        StatementError::Render(
+                            "a parameter is of no type known to the connection".to_string(),
+                        )
+                    })?
+                }
+            };
+            literals.push(literal);
+        }
+        Ok(interpolate(&self.sql, &literals))
+    }
+}
+
+/// The built-in types tried for the parameters, before the types of the schemas.
+const BUILTIN_TYPES: [Type; 20] = [
+    Type::BOOL,
+    Type::INT2,
+    Type::INT4,
+    Type::INT8,
+    Type::NUMERIC,
+    Type::FLOAT4,
+    Type::FLOAT8,
+    Type::TEXT,
+    Type::BYTEA,
+    Type::TIMESTAMP,
+    Type::TIMESTAMPTZ,
+    Type::BOOL_ARRAY,
+    Type::INT2_ARRAY,
+    Type::INT4_ARRAY,
+    Type::INT8_ARRAY,
+    Type::FLOAT8_ARRAY,
+    Type::TEXT_ARRAY,
+    Type::BYTEA_ARRAY,
+    Type::TIMESTAMP_ARRAY,
+    Type::NUMERIC_ARRAY,
+];
+
+/// The composite and enum types of the schemas in the search path, and their arrays.
+pub struct TypeRegistry {
+    types: Vec<Type>,
+}
+
+/// A composite or enum type as read from the catalog.
+struct CatalogType {
+    name: String,
+    schema: String,
+    array_oid: u32,
+    fields: Vec<(String, u32)>,
+    labels: Vec<String>,
+    is_enum: bool,
+}
+
+fn simple_query_rows<C: GenericClient>(
+    client: &mut C,
+    sql: &str,
+) -> Result<Vec<Vec<String>>, postgres::Error> {
+    Ok(client
+        .simple_query(sql)?
+        .iter()
+        .filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => Some(
+                (0..row.len())
+                    .map(|index| row.get(index).unwrap_or_default().to_string())
+                    .collect(),
+            ),
+            _ => None,
+        })
+        .collect())
+}
+
+impl TypeRegistry {
+    fn load<C: GenericClient>(client: &mut C) -> Result<Self, postgres::Error> {
+        let mut catalog_types: HashMap<u32, CatalogType> = HashMap::default();
+        for row in simple_query_rows(
+            client,
+            "SELECT t.oid, t.typname, n.nspname, t.typarray, t.typtype \
+            FROM pg_catalog.pg_type t JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace \
+            LEFT JOIN pg_catalog.pg_class c ON c.oid = t.typrelid \
+            WHERE n.nspname = ANY (current_schemas(false)) \
+            AND (t.typtype = 'e' OR (t.typtype = 'c' AND c.relkind = 'c'))",
+        )? {
+            catalog_types.insert(
+                row[0].parse().unwrap_or_default(),
+                CatalogType {
+                    name: row[1].clone(),
+                    schema: row[2].clone(),
+                    array_oid: row[3].parse().unwrap_or_default(),
+                    fields: Vec::new(),
+                    labels: Vec::new(),
+                    is_enum: row[4] == "e",
+                },
+            );
+        }
+        for row in simple_query_rows(
+            client,
+            "SELECT t.oid, a.attname, a.atttypid \
+            FROM pg_catalog.pg_type t JOIN pg_catalog.pg_attribute a ON a.attrelid = t.typrelid \
+            WHERE t.typtype = 'c' AND a.attnum > 0 AND NOT a.attisdropped \
+            ORDER BY t.oid, a.attnum",
+        )? {
+            if let Some(catalog_type) = catalog_types.get_mut(&row[0].parse().unwrap_or_default()) {
+                catalog_type
+                    .fields
+                    .push((row[1].clone(), row[2].parse().unwrap_or_default()));
+            }
+        }
+        for row in simple_query_rows(
+            client,
+            "SELECT enumtypid, enumlabel FROM pg_catalog.pg_enum ORDER BY enumtypid, enumsortorder",
+        )? {
+            if let Some(catalog_type) = catalog_types.get_mut(&row[0].parse().unwrap_or_default()) {
+                catalog_type.labels.push(row[1].clone());
+            }
+        }
+
+        let mut built = HashMap::default();
+        let mut oids: Vec<u32> = catalog_types.keys().copied().collect();
+        oids.sort_unstable();
+        let mut types = Vec::new();
+        for oid in oids {
+            if let Some(ty) = build_type(oid, &catalog_types, &mut built) {
+                types.push(ty);
+            }
+            if let Some(ty) = build_type(catalog_types[&oid].array_oid, &catalog_types, &mut built)
+            {
+                types.push(ty);
+            }
+        }
+        Ok(Self { types })
+    }
+}
+
+/// The type of the oid, None when it is of neither a built-in nor a catalog type.
+fn build_type(
+    oid: u32,
+    catalog_types: &HashMap<u32, CatalogType>,
+    built: &mut HashMap<u32, Type>,
+) -> Option<Type> {
+    if let Some(ty) = Type::from_oid(oid).or_else(|| built.get(&oid).cloned()) {
+        return Some(ty);
+    }
+    let ty = match catalog_types.get(&oid) {
+        Some(catalog_type) => {
+            let kind = if catalog_type.is_enum {
+                Kind::Enum(catalog_type.labels.clone())
+            } else {
+                let mut fields = Vec::with_capacity(catalog_type.fields.len());
+                for (name, field_oid) in &catalog_type.fields {
+                    let field_type = build_type(*field_oid, catalog_types, built)?;
+                    fields.push(Field::new(name.clone(), field_type));
+                }
+                Kind::Composite(fields)
+            };
+            Type::new(
+                catalog_type.name.clone(),
+                oid,
+                kind,
+                catalog_type.schema.clone(),
+            )
+        }
+        None => {
+            let (element_oid, element) = catalog_types
+                .iter()
+                .find(|(_, catalog_type)| catalog_type.array_oid == oid)?;
+            let element_type = build_type(*element_oid, catalog_types, built)?;
+            Type::new(
+                format!("_{}", element.name),
+                oid,
+                Kind::Array(element_type),
+                element.schema.clone(),
+            )
+        }
+    };
+    built.insert(oid, ty.clone());
+    Some(ty)
+}
+
+/// The number of the parameters of the SQL, the highest $n outside of the quotes.
+fn param_count(sql: &str) -> usize {
+    let mut count = 0;
+    scan_params(sql, |_, param| count = count.max(param));
+    count
+}
+
+/// Call back with the byte range and the number of each $n outside of the string literals and
+/// the quoted identifiers.
+fn scan_params(sql: &str, mut on_param: impl FnMut(std::ops::Range<usize>, usize)) {
+    let bytes = sql.as_bytes();
+    let mut quote = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match quote {
+            Some(open) if byte == open => quote = None,
+            Some(_) => {}
+            None if byte == b'\'' || byte == b'"' => quote = Some(byte),
+            None if byte == b'$' => {
+                let digits = bytes[index + 1..]
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_digit())
+                    .count();
+                if digits > 0 {
+                    let end = index + 1 + digits;
+                    if let Ok(param) = sql[index + 1..end].parse() {
+                        on_param(index..end, param);
+                    }
+                    index = end;
+                    continue;
+                }
+            }
+            None => {}
+        }
+        index += 1;
+    }
+}
+
+/// The SQL with each $n replaced by the nth literal.
+fn interpolate(sql: &str, literals: &[String]) -> String {
+    let mut rendered =
+        String::with_capacity(sql.len() + literals.iter().map(String::len).sum::<usize>());
+    let mut copied = 0;
+    scan_params(sql, |range, param| {
+        rendered.push_str(&sql[copied..range.start]);
+        rendered.push_str(&literals[param - 1]);
+        copied = range.end;
+    });
+    rendered.push_str(&sql[copied..]);
+    rendered
+}
+
+/// The literal of the parameter, as the first of the types it encodes for, None when it encodes
+/// for none of them.
+fn render_param(
+    param: &(dyn ToSql + Sync),
+    schema_types: &[Type],
+) -> Result<Option<String>, StatementError> {
+    let mut first_error = None;
+    let mut buf = bytes::BytesMut::new();
+    for ty in BUILTIN_TYPES.iter().chain(schema_types) {
+        buf.clear();
+        match param.to_sql_checked(ty, &mut buf) {
+            Ok(IsNull::Yes) => return Ok(Some(format!("NULL::{}", type_name(ty)))),
+            Ok(IsNull::No) => return render_value(ty, &buf).map(Some),
+            Err(err) if err.is::<WrongType>() => {}
+            // The value may encode for a later type, such as a write_version beyond BIGINT for
+            // NUMERIC.
+            Err(err) => {
+                first_error.get_or_insert_with(|| err.to_string());
+            }
+        }
+    }
+    match first_error {
+        Some(err) => Err(StatementError::Render(err)),
+        None => Ok(None),
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn quote_literal(text: &str) -> String {
+    format!("E'{}'", text.replace('\\', "\\\\").replace('\'', "''"))
+}
+
+/// The name of the type to cast the literals to.
+fn type_name(ty: &Type) -> String {
+    match ty.kind() {
+        Kind::Array(element) => format!("{}[]", type_name(element)),
+        _ if ty.schema() == "pg_catalog" => ty.name().to_string(),
+        _ => format!(
+            "{}.{}",
+            quote_identifier(ty.schema()),
+            quote_identifier(ty.name())
+        ),
+    }
+}
+
+fn render_error(err: impl fmt::Display) -> StatementError {
+    StatementError::Render(err.to_string())
+}
+
+/// Read a big-endian integer of N bytes from the buffer.
+fn read_be<const N: usize>(raw: &mut &[u8]) -> Result<[u8; N], StatementError> {
+    if raw.len() < N {
+        return Err(render_error("the encoded value is truncated"));
+    }
+    let (bytes, rest) = raw.split_at(N);
+    *raw = rest;
+    Ok(bytes.try_into().unwrap())
+}
+
+fn read_i32(raw: &mut &[u8]) -> Result<i32, StatementError> {
+    read_be::<4>(raw).map(i32::from_be_bytes)
+}
+
+/// Read a length-prefixed value, None for NULL.
+fn read_value<'a>(raw: &mut &'a [u8]) -> Result<Option<&'a [u8]>, StatementError> {
+    let len = read_i32(raw)?;
+    if len < 0 {
+        return Ok(None);
+    }
+    let len = len as usize;
+    if raw.len() < len {
+        return Err(render_error("the encoded value is truncated"));
+    }
+    let (value, rest) = raw.split_at(len);
+    *raw = rest;
+    Ok(Some(value))
+}
+
+fn render_nullable(ty: &Type, raw: Option<&[u8]>) -> Result<String, StatementError> {
+    match raw {
+        Some(raw) => render_value(ty, raw),
+        None => Ok(format!("NULL::{}", type_name(ty))),
+    }
+}
+
+/// The literal of the value from its binary encoding for the type.
+fn render_value(ty: &Type, raw: &[u8]) -> Result<String, StatementError> {
+    match ty.kind() {
+        Kind::Array(element) => render_array(ty, element, raw),
+        Kind::Composite(fields) => render_composite(ty, fields, raw),
+        Kind::Enum(_) => Ok(format!(
+            "{}::{}",
+            quote_literal(str::from_utf8(raw).map_err(render_error)?),
+            type_name(ty)
+        )),
+        _ => Ok(format!(
+            "{}::{}",
+            quote_literal(&render_scalar(ty, raw)?),
+            type_name(ty)
+        )),
+    }
+}
+
+/// The text of the built-in scalar value.
+fn render_scalar(ty: &Type, raw: &[u8]) -> Result<String, StatementError> {
+    fn decode<'a, T: FromSql<'a>>(ty: &Type, raw: &'a [u8]) -> Result<T, StatementError> {
+        T::from_sql(ty, raw).map_err(render_error)
+    }
+    fn float(value: f64) -> String {
+        if value.is_nan() {
+            "NaN".to_string()
+        } else if value.is_infinite() {
+            if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+        } else {
+            format!("{:?}", value)
+        }
+    }
+    Ok(match *ty {
+        Type::BOOL => decode::<bool>(ty, raw)?.to_string(),
+        Type::INT2 => decode::<i16>(ty, raw)?.to_string(),
+        Type::INT4 => decode::<i32>(ty, raw)?.to_string(),
+        Type::INT8 => decode::<i64>(ty, raw)?.to_string(),
+        Type::FLOAT4 => float(decode::<f32>(ty, raw)? as f64),
+        Type::FLOAT8 => float(decode::<f64>(ty, raw)?),
+        Type::NUMERIC => render_numeric(raw)?,
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => decode::<String>(ty, raw)?,
+        Type::BYTEA => {
+            let mut hex = String::with_capacity(2 + raw.len() * 2);
+            hex.push_str("\\x");
+            for byte in raw {
+                write!(hex, "{:02x}", byte).unwrap();
+            }
+            hex
+        }
+        Type::TIMESTAMP => decode::<NaiveDateTime>(ty, raw)?
+            .format("%Y-%m-%d %H:%M:%S%.6f")
+            .to_string(),
+        Type::TIMESTAMPTZ => decode::<DateTime<Utc>>(ty, raw)?
+            .format("%Y-%m-%d %H:%M:%S%.6f+00")
+            .to_string(),
+        _ => return Err(render_error(format!("the type {} is not supported", ty))),
+    })
+}
+
+const NUMERIC_NEGATIVE: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+
+/// The text of a NUMERIC value, from its base 10000 digits.
+fn render_numeric(mut raw: &[u8]) -> Result<String, StatementError> {
+    let raw = &mut raw;
+    let ndigits = i16::from_be_bytes(read_be::<2>(raw)?);
+    let weight = i16::from_be_bytes(read_be::<2>(raw)?) as i32;
+    let sign = u16::from_be_bytes(read_be::<2>(raw)?);
+    let dscale = u16::from_be_bytes(read_be::<2>(raw)?) as usize;
+    let mut digits = Vec::with_capacity(ndigits.max(0) as usize);
+    for _ in 0..ndigits {
+        digits.push(i16::from_be_bytes(read_be::<2>(raw)?));
+    }
+    if sign == NUMERIC_NAN {
+        return Ok("NaN".to_string());
+    }
+    let digit = |index: i32| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| digits.get(index).copied())
+            .unwrap_or(0)
+    };
+    let mut text = String::new();
+    if sign == NUMERIC_NEGATIVE {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        write!(text, "{}", digit(0)).unwrap();
+        for index in 1..=weight {
+            write!(text, "{:04}", digit(index)).unwrap();
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut index = weight + 1;
+        while fraction.len() < dscale {
+            write!(fraction, "{:04}", digit(index)).unwrap();
+            index += 1;
+        }
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Ok(text)
+}
+
+/// The ARRAY[...] literal of the array, nested by its dimensions.
+fn render_array(ty: &Type, element: &Type, mut raw: &[u8]) -> Result<String, StatementError> {
+    let raw = &mut raw;
+    let dimensions = read_i32(raw)?;
+    let _has_nulls = read_i32(raw)?;
+    let _element_oid = read_i32(raw)?;
+    let mut lengths = Vec::with_capacity(dimensions.max(0) as usize);
+    for _ in 0..dimensions {
+        lengths.push(read_i32(raw)?.max(0) as usize);
+        let _lower_bound = read_i32(raw)?;
+    }
+    if lengths.is_empty() {
+        return Ok(format!("'{{}}'::{}", type_name(ty)));
+    }
+    let mut elements = Vec::with_capacity(lengths.iter().product());
+    for _ in 0..lengths.iter().product::<usize>() {
+        elements.push(render_nullable(element, read_value(raw)?)?);
+    }
+    // Group the elements by the innermost dimension first.
+    for length in lengths.iter().skip(1).rev() {
+        elements = elements
+            .chunks(*length)
+            .map(|chunk| format!("ARRAY[{}]", chunk.join(", ")))
+            .collect();
+    }
+    Ok(format!("ARRAY[{}]::{}", elements.join(", "), type_name(ty)))
+}
+
+/// The ROW(...) literal of the composite.
+fn render_composite(ty: &Type, fields: &[Field], mut raw: &[u8]) -> Result<String, StatementError> {
+    let raw = &mut raw;
+    let count = read_i32(raw)?;
+    if count as usize != fields.len() {
+        return Err(render_error(format!(
+            "expected {} fields of {} but got {}",
+            fields.len(),
+            ty,
+            count
+        )));
+    }
+    let mut values = Vec::with_capacity(fields.len());
+    for field in fields {
+        let _oid = read_i32(raw)?;
+        values.push(render_nullable(field.type_(), read_value(raw)?)?);
+    }
+    Ok(format!("ROW({})::{}", values.join(", "), type_name(ty)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::{
+            geyser_plugin_postgres::{AccountRebuildConfig, AuditCompactionConfig},
+            postgres_client::{
+                postgres_client_block_metadata::DbBlockInfo,
+                postgres_client_transaction::tests::build_test_log_transaction_request,
+                tests::{build_account, TestDatabase},
+                PostgresClient, UpdateBlockMetadataRequest,
+            },
+        },
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn render(param: &(dyn ToSql + Sync)) -> String {
+        render_param(param, &[]).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_render_params() {
+        let sql = "SELECT $1, '$2', \"$3\", $2 WHERE x = $10";
+        assert_eq!(param_count(sql), 10);
+        let literals: Vec<String> = (1..=10).map(|n| format!("<{}>", n)).collect();
+        assert_eq!(
+            interpolate(sql, &literals),
+            "SELECT <1>, '$2', \"$3\", <2> WHERE x = <10>"
+        );
+
+        assert_eq!(render(&5i64), "E'5'::int8");
+        assert_eq!(render(&"it's a \\ test"), r"E'it''s a \\ test'::text");
+        assert_eq!(render(&vec![1u8, 0xab]), r"E'\\x01ab'::bytea");
+        assert_eq!(render(&None::<i64>), "NULL::int8");
+        assert_eq!(
+            render(&vec![1i64, 2]),
+            "ARRAY[E'1'::int8, E'2'::int8]::int8[]"
+        );
+        assert_eq!(render(&Vec::<String>::new()), "ARRAY[]::text[]");
+    }
+
+    #[test]
+    fn test_unprepared_statements() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "use_prepared_statements": false }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        let account = build_account(&owner, vec![0, b'\'', b'\\', 0xff], 3);
+        client.update_account(account.clone(), false).unwrap();
+        client
+            .update_slot_status(3, Some(2), SlotStatus::Rooted)
+            .unwrap();
+        client
+            .log_transaction(build_test_log_transaction_request(1, 3))
+            .unwrap();
+        client
+            .update_block_metadata(UpdateBlockMetadataRequest {
+                block_info: DbBlockInfo {
+                    slot: 3,
+                    blockhash: "it's".to_string(),
+                    rewards: Vec::default(),
+                    block_time: Some(7_250),
+                    block_height: None,
+                },
+            })
+            .unwrap();
+
+        let row = db
+            .client
+            .query_one("SELECT owner, data FROM account", &[])
+            .unwrap();
+        assert_eq!(row.get::<_, Vec<u8>>(0), owner.to_bytes().to_vec());
+        assert_eq!(row.get::<_, Vec<u8>>(1), account.data);
+        assert_eq!(db.count("slot"), 1);
+        assert_eq!(db.count("transaction"), 1);
+        // The composite types of the transaction are read once, for all the statements.
+        let types = &client.client.get_mut().unwrap().types;
+        assert!(types.lock().unwrap().is_some());
+        let blockhash: String = db
+            .client
+            .query_one("SELECT blockhash FROM block WHERE slot = 3", &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(blockhash, "it's");
+
+        let config = db.config(json!({
+            "use_prepared_statements": false,
+            "record_statement_checksums": true,
+        }));
+        assert!(SimplePostgresClient::check_use_prepared_statements(&config).is_err());
+        for overrides in [
+            json!({ "synchronous_commit": "off" }),
+            json!({ "maintain_recent_account_table": true }),
+            json!({
+                "store_account_historical_data": true,
+                "store_historical_data_during_startup": false,
+            }),
+        ] {
+            let mut config = overrides;
+            config["use_prepared_statements"] = json!(false);
+            let err = SimplePostgresClient::check_use_prepared_statements(&db.config(config))
+                .unwrap_err();
+            assert!(format!("{:?}", err).contains("a session setting"));
+        }
+    }
+
+    #[test]
+    fn test_unprepared_maintenance_statements() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({
+            "use_prepared_statements": false,
+            "staging_table_mode": true,
+            "staging_merge_chunk_rows": 1,
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let account = build_account(&Pubkey::new_unique(), vec![1], 3);
+        client.update_account(account.clone(), false).unwrap();
+        let merged_count = SimplePostgresClient::merge_staging_tables(
+            &mut db.client,
+            &Mutex::default(),
+            &config,
+            true,
+        )
+        .unwrap();
+        assert_eq!(merged_count, 1);
+        assert_eq!(db.count("account"), 1);
+
+        // The compaction and the rebuild run their statements with the parameters rendered.
+        let mut client = SimplePostgresClient::new(&db.config(json!({
+            "use_prepared_statements": false,
+            "store_account_historical_data": true,
+        })))
+        .unwrap();
+        for slot in [4, 5] {
+            let mut account = account.clone();
+            account.slot = slot;
+            client.update_account(account, false).unwrap();
+        }
+        let removed_count = client
+            .compact_account_audit(&AuditCompactionConfig {
+                start_slot: 0,
+                end_slot: 10,
+                batch_size: None,
+            })
+            .unwrap();
+        assert_eq!(removed_count, 1);
+        db.client.batch_execute("DELETE FROM account").unwrap();
+        let restored_count = client
+            .rebuild_account_from_audit(&AccountRebuildConfig {
+                start_slot: 0,
+                end_slot: 10,
+                batch_size: None,
+            })
+            .unwrap();
+        assert_eq!(restored_count, 1);
+        assert_eq!(db.count("account"), 1);
+    }
+}
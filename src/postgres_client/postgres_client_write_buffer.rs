@@ -0,0 +1,167 @@
+/// Module responsible for the write buffer between the notifications and the workers, the
+/// bounded queue the notifications are written into before the workers write them to the
+/// database.
+///
+/// The buffer is a fixed-size ring of write_buffer_capacity work items, preallocated and
+/// lock-free, so the notification thread only copies the item into it and the latency spikes
+/// of the database are absorbed until the ring is full. When it is full, the account updates
+/// are handled by write_buffer_full_policy: 'block' waits for the workers to free a slot,
+/// 'drop' drops the update, leaving a gap in the data. The other notifications, which the
+/// account updates are checkpointed and rooted against, always wait. The occupancy of the
+/// ring is reported as the geyser-plugin-postgres-write-buffer datapoint every
+/// WRITE_BUFFER_REPORT_INTERVAL_MS, with the number of the updates dropped.
+use {
+    super::{DbWork, DbWorkItem, MAX_ASYNC_REQUESTS},
+    crate::geyser_plugin_postgres::{GeyserPluginPostgresConfig, WriteBufferFullPolicy},
+    crossbeam_channel::{bounded, Receiver, SendError, Sender, TrySendError},
+    solana_metrics::*,
+    solana_sdk::timing::AtomicInterval,
+    std::sync::atomic::{AtomicU64, Ordering},
+};
+
+const WRITE_BUFFER_REPORT_INTERVAL_MS: u64 = 5_000;
+
+/// The outcome of writing a work item into the buffer.
+#[derive(Debug, PartialEq)]
+pub(crate) enum BufferedWrite {
+    Queued,
+    /// The buffer is full and the work item is dropped by the policy.
+    Dropped,
+}
+
+pub(crate) struct WriteBuffer {
+    capacity: usize,
+    full_policy: WriteBufferFullPolicy,
+    dropped_count: AtomicU64,
+    last_report: AtomicInterval,
+}
+
+impl WriteBuffer {
+    /// The buffer configured, and the ring of the work items shared with the workers.
+    pub fn new(config: &GeyserPluginPostgresConfig) -> (Self, Sender<DbWork>, Receiver<DbWork>) {
+        let capacity = config
+            .write_buffer_capacity
+            .unwrap_or(MAX_ASYNC_REQUESTS)
+            .max(1);
+        let (sender, receiver) = bounded(capacity);
+        let write_buffer = Self {
+            capacity,
+            full_policy: config
+                .write_buffer_full_policy
+                .unwrap_or(WriteBufferFullPolicy::Block),
+            dropped_count: AtomicU64::default(),
+            last_report: AtomicInterval::default(),
+        };
+        (write_buffer, sender, receiver)
+    }
+
+    /// Write the work item into the buffer, applying the full policy to the account updates.
+    pub fn send(
+        &self,
+        sender: &Sender<DbWork>,
+        work: DbWork,
+    ) -> Result<BufferedWrite, SendError<DbWork>> {
+        let droppable = matches!(work.item, DbWorkItem::UpdateAccount(_))
+            && self.full_policy == WriteBufferFullPolicy::Drop;
+        if !droppable {
+            return sender.send(work).map(|_| BufferedWrite::Queued);
+        }
+        match sender.try_send(work) {
+            Ok(()) => Ok(BufferedWrite::Queued),
+            Err(TrySendError::Full(_)) => {
+                self.dropped_count.fetch_add(1, Ordering::Relaxed);
+                Ok(BufferedWrite::Dropped)
+            }
+            Err(TrySendError::Disconnected(work)) => Err(SendError(work)),
+        }
+    }
+
+    /// Report the occupancy of the buffer when the interval has elapsed.
+    pub fn maybe_report(&self, sender: &Sender<DbWork>) {
+        if !self
+            .last_report
+            .should_update(WRITE_BUFFER_REPORT_INTERVAL_MS)
+        {
+            return;
+        }
+        let occupancy = sender.len();
+        datapoint_info!(
+            "geyser-plugin-postgres-write-buffer",
+            ("occupancy", occupancy as i64, i64),
+            ("capacity", self.capacity as i64, i64),
+            (
+                "occupancy-percent",
+                (occupancy * 100 / self.capacity) as i64,
+                i64
+            ),
+            (
+                "dropped-count",
+                self.dropped_count.load(Ordering::Relaxed) as i64,
+                i64
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{tests::build_account, UpdateAccountRequest, UpdateSlotRequest},
+        chrono::Utc,
+        serde_json::json,
+        solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    fn build_work(item: DbWorkItem) -> DbWork {
+        DbWork {
+            sequence: None,
+            item,
+        }
+    }
+
+    fn build_account_work() -> DbWork {
+        build_work(DbWorkItem::UpdateAccount(Box::new(UpdateAccountRequest {
+            account: build_account(&Pubkey::new_unique(), vec![], 1),
+            is_startup: false,
+        })))
+    }
+
+    #[test]
+    fn test_write_buffer_full_policy() {
+        let config = serde_json::from_value(json!({
+            "write_buffer_capacity": 1,
+            "write_buffer_full_policy": "drop",
+        }))
+        .unwrap();
+        let (write_buffer, sender, receiver) = WriteBuffer::new(&config);
+        assert_eq!(
+            write_buffer.send(&sender, build_account_work()).unwrap(),
+            BufferedWrite::Queued
+        );
+        assert_eq!(
+            write_buffer.send(&sender, build_account_work()).unwrap(),
+            BufferedWrite::Dropped
+        );
+        assert_eq!(write_buffer.dropped_count.load(Ordering::Relaxed), 1);
+        assert_eq!(receiver.len(), 1);
+
+        // The slot statuses wait for the workers to free a slot.
+        receiver.recv().unwrap();
+        let slot_work = build_work(DbWorkItem::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot: 1,
+            parent: None,
+            slot_status: SlotStatus::Rooted,
+            observed_on: Utc::now().naive_utc(),
+            transaction_tally: None,
+        })));
+        assert_eq!(
+            write_buffer.send(&sender, slot_work).unwrap(),
+            BufferedWrite::Queued
+        );
+
+        drop(receiver);
+        assert!(write_buffer.send(&sender, build_account_work()).is_err());
+    }
+}
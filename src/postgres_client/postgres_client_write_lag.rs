@@ -0,0 +1,125 @@
+/// Module responsible for the write_lag_ms column of the account tables, the freshness of the
+/// account updates, when record_write_lag is set.
+///
+/// The write lag of an account update is its updated_on, the time its row is written, minus
+/// the best-known wall clock of its slot. The block times are only notified once the slot is
+/// replayed, after its account updates, so the wall clock of the slot is estimated from the
+/// newest block time notified, as the block time of that slot plus DEFAULT_MS_PER_SLOT for
+/// each slot since. The block times are whole seconds, and the skipped slots shift the
+/// estimate, so the lag is accurate to a second or so. The write_lag_ms is NULL until a block
+/// time is notified.
+use {
+    super::{DbAccountInfo, SimplePostgresClient},
+    chrono::naive::NaiveDateTime,
+    solana_sdk::clock::DEFAULT_MS_PER_SLOT,
+    std::sync::{Arc, RwLock},
+};
+
+/// The newest block time notified, shared by the workers.
+#[derive(Default)]
+pub(crate) struct SlotClock {
+    /// The newest slot with a block time, and its block time in seconds.
+    newest_block_time: RwLock<Option<(u64, i64)>>,
+}
+
+impl SlotClock {
+    /// Record the block time of the slot, kept when it is the newest slot.
+    pub fn record_block_time(&self, slot: u64, block_time: i64) {
+        let mut newest_block_time = self.newest_block_time.write().unwrap();
+        if !matches!(*newest_block_time, Some((newest_slot, _)) if newest_slot >= slot) {
+            *newest_block_time = Some((slot, block_time));
+        }
+    }
+
+    /// The estimated wall clock of the slot in milliseconds since the epoch.
+    fn slot_time_ms(&self, slot: i64) -> Option<i64> {
+        let (newest_slot, block_time) = (*self.newest_block_time.read().unwrap())?;
+        Some(block_time * 1000 + (slot - newest_slot as i64) * DEFAULT_MS_PER_SLOT as i64)
+    }
+
+    /// The write lag in milliseconds of the account written at updated_on.
+    pub fn write_lag_ms(&self, account: &DbAccountInfo, updated_on: &NaiveDateTime) -> Option<i64> {
+        self.slot_time_ms(account.slot)
+            .map(|slot_time_ms| updated_on.and_utc().timestamp_millis() - slot_time_ms)
+    }
+}
+
+impl SimplePostgresClient {
+    /// Share the clock of the slots of the workers with the client.
+    pub(crate) fn set_slot_clock(&mut self, slot_clock: Option<Arc<SlotClock>>) {
+        self.slot_clock = slot_clock;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            DbBlockInfo, PostgresClient, UpdateBlockMetadataRequest,
+        },
+        chrono::{DateTime, Utc},
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_slot_clock() {
+        let slot_clock = SlotClock::default();
+        let account = build_account(&Pubkey::new_unique(), vec![], 105);
+        let updated_on = DateTime::from_timestamp(1_650_000_010, 0)
+            .unwrap()
+            .naive_utc();
+        assert_eq!(slot_clock.write_lag_ms(&account, &updated_on), None);
+
+        slot_clock.record_block_time(100, 1_650_000_000);
+        // The older block times do not replace the newest one.
+        slot_clock.record_block_time(90, 1_640_000_000);
+        assert_eq!(
+            slot_clock.write_lag_ms(&account, &updated_on),
+            Some(10_000 - 5 * DEFAULT_MS_PER_SLOT as i64)
+        );
+    }
+
+    #[test]
+    fn test_record_write_lag() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let config = db.config(json!({ "record_write_lag": true }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        client
+            .update_account(build_account(&owner, vec![], 1), false)
+            .unwrap();
+
+        let now = Utc::now().timestamp();
+        client
+            .update_block_metadata(UpdateBlockMetadataRequest {
+                block_info: DbBlockInfo {
+                    slot: 2,
+                    blockhash: "blockhash".to_string(),
+                    rewards: vec![],
+                    block_time: Some(now - 10),
+                    block_height: Some(2),
+                },
+            })
+            .unwrap();
+        client
+            .update_account(build_account(&owner, vec![], 2), false)
+            .unwrap();
+
+        let write_lags: Vec<Option<i64>> = db
+            .client
+            .query("SELECT write_lag_ms FROM account ORDER BY slot", &[])
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(write_lags[0], None);
+        let write_lag_ms = write_lags[1].unwrap();
+        assert!((9_000..12_000).contains(&write_lag_ms), "{}", write_lag_ms);
+    }
+}
@@ -0,0 +1,194 @@
+/// Module responsible for binding the write_version of the account updates at the width of
+/// the write_version columns.
+///
+/// The write_version of the Geyser interface is a u64. The write_version columns of the
+/// account, account_audit and account_staging tables are BIGINT by default, holding up to
+/// i64::MAX: a write_version beyond it fails the write, instead of wrapping around to a
+/// negative value which would lose the conflicts against the earlier versions. The columns
+/// altered to NUMERIC(20) hold the full u64 range. The write_version is bound in the type of
+/// the column, and write_version_type, when set, is checked against the account table.
+use {
+    super::SimplePostgresClient,
+    crate::geyser_plugin_postgres::{
+        GeyserPluginPostgresConfig, GeyserPluginPostgresError, WriteVersionType,
+    },
+    bytes::{BufMut, BytesMut},
+    log::*,
+    postgres::Client,
+    solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError,
+    std::{convert::TryFrom, error::Error},
+    tokio_postgres::types::{to_sql_checked, IsNull, ToSql, Type},
+};
+
+/// The base of the digits of the NUMERIC binary format.
+const NUMERIC_BASE: u64 = 10_000;
+const NUMERIC_POSITIVE: u16 = 0x0000;
+
+/// The write_version of an account update, bound as BIGINT or NUMERIC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DbWriteVersion(pub u64);
+
+impl DbWriteVersion {
+    /// Encode into the NUMERIC binary format: the count of the base 10000 digits, the weight
+    /// of the first digit, the sign, the display scale, then the digits, most significant
+    /// first and without the trailing zeros.
+    fn encode_numeric(&self, out: &mut BytesMut) {
+        let mut digits = Vec::new();
+        let mut value = self.0;
+        while value > 0 {
+            digits.push((value % NUMERIC_BASE) as i16);
+            value /= NUMERIC_BASE;
+        }
+        let weight = digits.len().saturating_sub(1) as i16;
+        let trailing_zeros = digits.iter().take_while(|digit| **digit == 0).count();
+        digits.drain(..trailing_zeros);
+
+        out.put_i16(digits.len() as i16);
+        out.put_i16(weight);
+        out.put_u16(NUMERIC_POSITIVE);
+        out.put_u16(0);
+        for digit in digits.iter().rev() {
+            out.put_i16(*digit);
+        }
+    }
+}
+
+impl ToSql for DbWriteVersion {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        if *ty == Type::NUMERIC {
+            self.encode_numeric(out);
+            return Ok(IsNull::No);
+        }
+        let write_version = i64::try_from(self.0).map_err(|_| {
+            format!(
+                "write_version {} is beyond the BIGINT write_version column, alter it to NUMERIC(20)",
+                self.0
+            )
+        })?;
+        write_version.to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC || <i64 as ToSql>::accepts(ty)
+    }
+
+    to_sql_checked!();
+}
+
+impl SimplePostgresClient {
+    /// Check the write_version column of the account table is of the configured type.
+    pub(crate) fn check_write_version_type(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+    ) -> Result<(), GeyserPluginError> {
+        let expected = match config.write_version_type {
+            Some(WriteVersionType::Bigint) => "bigint",
+            Some(WriteVersionType::Numeric) => "numeric",
+            None => return Ok(()),
+        };
+        let column_type = client
+            .query_opt(
+                "SELECT data_type::TEXT FROM information_schema.columns \
+                WHERE (table_schema = current_schema() AND table_name::TEXT = $1 OR table_schema || '.' || table_name = $1::TEXT) \
+                AND column_name = 'write_version'",
+                &[&Self::table_name(&config.table_names, "account")],
+            )
+            .map(|row| row.map(|row| row.get::<_, String>(0)));
+        match column_type {
+            Ok(Some(column_type)) if column_type == expected => Ok(()),
+            column_type => {
+                let msg = format!(
+                    "The write_version column of the account table is {:?}, \"write_version_type\" is {:?}",
+                    column_type, expected
+                );
+                error!("{}", msg);
+                Err(GeyserPluginError::Custom(Box::new(
+                    GeyserPluginPostgresError::DataSchemaError { msg },
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::{
+            tests::{build_account, TestDatabase},
+            PostgresClient,
+        },
+        serde_json::json,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_encode_numeric() {
+        for (write_version, expected) in [
+            (0, vec![0, 0, 0, 0, 0, 0, 0, 0]),
+            // 1_0000_0000: the trailing zero digits are dropped.
+            (100_000_000, vec![0, 1, 0, 2, 0, 0, 0, 0, 0, 1]),
+            // 1844_6744_0737_0955_1615
+            (
+                u64::MAX,
+                vec![0, 5, 0, 4, 0, 0, 0, 0, 7, 52, 26, 88, 2, 225, 3, 187, 6, 79],
+            ),
+        ] {
+            let mut out = BytesMut::new();
+            DbWriteVersion(write_version).encode_numeric(&mut out);
+            assert_eq!(out.to_vec(), expected, "{}", write_version);
+        }
+    }
+
+    #[test]
+    fn test_large_write_version() {
+        let mut db = match TestDatabase::new() {
+            Some(db) => db,
+            None => return,
+        };
+        let owner = Pubkey::new_unique();
+
+        // Beyond the BIGINT column, the write fails instead of wrapping around.
+        let mut client = SimplePostgresClient::new(&db.config(json!({}))).unwrap();
+        let mut account = build_account(&owner, vec![1], 1);
+        account.write_version = DbWriteVersion(i64::MAX as u64 + 1);
+        assert!(client.update_account(account, false).is_err());
+        assert!(SimplePostgresClient::new(&db.config(json!({
+            "write_version_type": "numeric",
+        })))
+        .is_err());
+
+        db.client
+            .batch_execute(
+                "DROP VIEW account_update_transaction; \
+                ALTER TABLE account ALTER COLUMN write_version TYPE NUMERIC(20); \
+                ALTER TABLE account_audit ALTER COLUMN write_version TYPE NUMERIC(20);",
+            )
+            .unwrap();
+        let config = db.config(json!({
+            "write_version_type": "numeric",
+            "store_account_historical_data": true,
+        }));
+        let mut client = SimplePostgresClient::new(&config).unwrap();
+        let mut account = build_account(&owner, vec![1], 1);
+        for write_version in [u64::MAX - 1, u64::MAX, u64::MAX - 2] {
+            account.write_version = DbWriteVersion(write_version);
+            client.update_account(account.clone(), false).unwrap();
+        }
+
+        let write_version: String = db
+            .client
+            .query_one(
+                "SELECT write_version::TEXT FROM account WHERE pubkey = $1",
+                &[&account.pubkey],
+            )
+            .unwrap()
+            .get(0);
+        assert_eq!(write_version, u64::MAX.to_string());
+        assert_eq!(db.count("account_audit"), 2);
+    }
+}
@@ -0,0 +1,201 @@
+/// A sink printing the selected notifications as JSON lines to stdout, or to a file, instead of
+/// writing them to the PostgreSQL database, when stdout_sink is set.
+///
+/// It is meant for developing the consumers and debugging the selection and the assembly
+/// without a database: each notification is a JSON object on its own line, with its kind in
+/// "type". The pubkeys, the signatures and the hashes are in base58 and the account data in
+/// base64. The transactions are reduced to their flat fields and the main fields of their
+/// status meta, as the Parquet files are. Each line is flushed as it is written, on the
+/// notifying thread.
+use {
+    crate::{
+        geyser_plugin_postgres::{GeyserPluginPostgresConfig, GeyserPluginPostgresError},
+        notification_sink::NotificationSink,
+        postgres_client::{DbAccountInfo, DbBlockInfo, LogTransactionRequest},
+    },
+    log::*,
+    serde_json::{json, Value},
+    solana_geyser_plugin_interface::geyser_plugin_interface::{GeyserPluginError, SlotStatus},
+    std::{
+        fs::OpenOptions,
+        io::{self, BufWriter, Write},
+    },
+};
+
+fn encode_bs58(bytes: &[u8]) -> String {
+    bs58::encode(bytes).into_string()
+}
+
+/// The sink printing the notifications as JSON lines.
+pub struct StdoutSink {
+    writer: Box<dyn Write + Send + Sync>,
+}
+
+impl StdoutSink {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let writer: Box<dyn Write + Send + Sync> = match &config.stdout_sink_file {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| {
+                        let msg = format!(
+                            "Failed to open the \"stdout_sink_file\" {:?}. Error: {:?}",
+                            path, err
+                        );
+                        error!("{}", msg);
+                        GeyserPluginError::Custom(Box::new(
+                            GeyserPluginPostgresError::ConfigurationError { msg },
+                        ))
+                    })?;
+                info!("Printing the notifications into {:?}", path);
+                Box::new(BufWriter::new(file))
+            }
+            None => {
+                info!("Printing the notifications to stdout");
+                Box::new(io::stdout())
+            }
+        };
+        Ok(Self { writer })
+    }
+
+    fn print(&mut self, value: Value) -> Result<(), GeyserPluginError> {
+        writeln!(self.writer, "{}", value)
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| GeyserPluginError::Custom(Box::new(err)))
+    }
+}
+
+impl NotificationSink for StdoutSink {
+    fn update_account(
+        &mut self,
+        account: DbAccountInfo,
+        is_startup: bool,
+    ) -> Result<(), GeyserPluginError> {
+        self.print(json!({
+            "type": "account",
+            "pubkey": encode_bs58(&account.pubkey),
+            "owner": encode_bs58(&account.owner),
+            "lamports": account.lamports,
+            "slot": account.slot,
+            "executable": account.executable,
+            "rent_epoch": account.rent_epoch,
+            "data": base64::encode(&account.data),
+            "write_version": account.write_version.0,
+            "target_table": account.target_table.as_deref(),
+            "is_startup": is_startup,
+        }))
+    }
+
+    fn update_slot_status(
+        &mut self,
+        slot: u64,
+        parent: Option<u64>,
+        status: SlotStatus,
+    ) -> Result<(), GeyserPluginError> {
+        self.print(json!({
+            "type": "slot",
+            "slot": slot,
+            "parent": parent,
+            "status": status.as_str(),
+        }))
+    }
+
+    fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
+        self.print(json!({ "type": "end_of_startup" }))
+    }
+
+    fn log_transaction(
+        &mut self,
+        transaction_log_info: LogTransactionRequest,
+    ) -> Result<(), GeyserPluginError> {
+        let transaction = &transaction_log_info.transaction_info;
+        let meta = &transaction.meta;
+        self.print(json!({
+            "type": "transaction",
+            "signature": encode_bs58(&transaction.signature),
+            "slot": transaction.slot,
+            "is_vote": transaction.is_vote,
+            "message_type": transaction.message_type,
+            "message_hash": encode_bs58(&transaction.message_hash),
+            "signatures": transaction
+                .signatures
+                .iter()
+                .map(|signature| encode_bs58(signature))
+                .collect::<Vec<_>>(),
+            "write_version": transaction.write_version,
+            "is_successful": meta.error.is_none(),
+            "error": meta.error.as_ref().map(|error| format!("{:?}", error)),
+            "fee": meta.fee,
+            "pre_balances": meta.pre_balances,
+            "post_balances": meta.post_balances,
+            "log_messages": meta.log_messages,
+        }))
+    }
+
+    fn update_block_metadata(&mut self, block_info: DbBlockInfo) -> Result<(), GeyserPluginError> {
+        self.print(json!({
+            "type": "block",
+            "slot": block_info.slot,
+            "blockhash": block_info.blockhash,
+            "block_time": block_info.block_time,
+            "block_height": block_info.block_height,
+            "reward_count": block_info.rewards.len(),
+        }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::*,
+        crate::postgres_client::tests::{build_account, build_test_log_transaction_request},
+        solana_sdk::pubkey::Pubkey,
+        std::fs,
+    };
+
+    #[test]
+    fn test_stdout_sink() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let path = output_dir.path().join("notifications.jsonl");
+        let config: GeyserPluginPostgresConfig = serde_json::from_value(json!({
+            "stdout_sink": true,
+            "stdout_sink_file": path,
+        }))
+        .unwrap();
+        let mut sink = StdoutSink::new(&config).unwrap();
+
+        let owner = Pubkey::new_unique();
+        sink.update_account(build_account(&owner, vec![1, 2, 3], 5), true)
+            .unwrap();
+        sink.notify_end_of_startup().unwrap();
+        sink.log_transaction(build_test_log_transaction_request(1, 6))
+            .unwrap();
+        sink.update_slot_status(6, Some(5), SlotStatus::Rooted)
+            .unwrap();
+
+        let lines: Vec<Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["type"], "account");
+        assert_eq!(lines[0]["owner"], owner.to_string());
+        assert_eq!(lines[0]["data"], "AQID");
+        assert_eq!(lines[0]["slot"], 5);
+        assert_eq!(lines[0]["is_startup"], true);
+        assert_eq!(lines[1]["type"], "end_of_startup");
+        assert_eq!(lines[2]["type"], "transaction");
+        assert_eq!(lines[2]["slot"], 6);
+        assert_eq!(
+            lines[2]["signature"],
+            solana_sdk::signature::Signature::new(&[1u8; 64]).to_string()
+        );
+        assert_eq!(
+            lines[3],
+            json!({ "type": "slot", "slot": 6, "parent": 5, "status": "rooted" })
+        );
+    }
+}